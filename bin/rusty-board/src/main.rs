@@ -6,55 +6,245 @@
 //! - It contains NO business logic.
 //! - It only starts the application.
 //!
-//! TODO:
-//! - Load configuration
-//! - Initialize database
-//! - Build services
-//! - Start HTTP server
-//!
 //! End of File Notes:
 //! Keep this file minimal and stable.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::Router;
+use axum::http::{header, HeaderValue};
 use tower_http::services::ServeDir;
+use tower_http::set_header::SetResponseHeaderLayer;
 
-use storage::{create_connection, init_database};
-use services::ServiceLayer;
+use config::{AppConfig, DatabaseBackend};
+use media::{Ffmpeg, LocalMediaStore, MediaLimits};
+use storage::{create_pool, init_database, migrations, Repositories};
+use services::{RateLimits, ServiceLayer};
+use api::rate_limit::RateLimiter;
 use api::routes::{create_router, AppState};
 
+/// Open the SQLite database and bring its schema up to date.
+fn open_sqlite(config: &AppConfig) -> Repositories {
+    let db = create_pool(&config.database_path, config.database_pool_size)
+        .expect("Failed to open database");
+
+    let conn = db.get().expect("Failed to open database");
+
+    let pending = migrations::pending_migrations(&conn)
+        .expect("Database schema is not supported by this binary");
+
+    for migration in &pending {
+        tracing::info!(
+            "Pending migration {:03}: {}",
+            migration.version,
+            migration.name
+        );
+    }
+
+    init_database(&conn)
+        .expect("Failed to initialize database");
+
+    drop(conn);
+    Repositories::sqlite(db)
+}
+
+/// Connect to PostgreSQL and bring its schema up to date.
+#[cfg(feature = "postgres")]
+fn open_postgres(config: &AppConfig) -> Repositories {
+    use storage::postgres::{create_pg_pool, migrations};
+
+    let db = create_pg_pool(&config.database_url, config.database_pool_size)
+        .expect("Failed to connect to PostgreSQL");
+
+    let mut conn = db.get().expect("Failed to connect to PostgreSQL");
+
+    let pending = migrations::pending_migrations(&mut conn)
+        .expect("Database schema is not supported by this binary");
+
+    for migration in &pending {
+        tracing::info!(
+            "Pending migration {:03}: {}",
+            migration.version,
+            migration.name
+        );
+    }
+
+    migrations::run_migrations(&mut conn)
+        .expect("Failed to initialize database");
+
+    drop(conn);
+    Repositories::postgres(db)
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres(_config: &AppConfig) -> Repositories {
+    panic!("DATABASE_BACKEND=postgres requires building with `--features postgres`");
+}
+
+/// Periodically delete media files no attachment references.
+async fn run_media_gc(services: Arc<ServiceLayer>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+
+        let services = services.clone();
+        match tokio::task::spawn_blocking(move || services.collect_media_garbage()).await {
+            Ok(Ok(report)) if report.removed > 0 => {
+                tracing::info!("Media GC removed {} of {} files", report.removed, report.scanned);
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::warn!("Media GC failed: {e}"),
+            Err(e) => tracing::warn!("Media GC task failed: {e}"),
+        }
+    }
+}
+
+/// Periodically delete sessions past their lifetime or idle timeout.
+async fn run_session_purge(services: Arc<ServiceLayer>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+
+        let services = services.clone();
+        match tokio::task::spawn_blocking(move || services.purge_expired_sessions()).await {
+            Ok(Ok(purged)) if purged > 0 => tracing::info!("Purged {purged} expired sessions"),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::warn!("Session purge failed: {e}"),
+            Err(e) => tracing::warn!("Session purge task failed: {e}"),
+        }
+    }
+}
+
+/// Periodically forget poster addresses past their retention period.
+async fn run_ip_purge(services: Arc<ServiceLayer>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+
+    loop {
+        interval.tick().await;
+
+        let services = services.clone();
+        match tokio::task::spawn_blocking(move || services.purge_poster_ips()).await {
+            Ok(Ok(purged)) if purged > 0 => tracing::info!("Cleared {purged} poster addresses"),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::warn!("Poster address purge failed: {e}"),
+            Err(e) => tracing::warn!("Poster address purge task failed: {e}"),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
     tracing::info!("Starting Rusty-Board Lean v1...");
 
-    // Initialize database
-    let conn = create_connection("rusty_board.db")
-        .expect("Failed to open database");
+    let config = AppConfig::from_env();
 
-    init_database(&conn)
-        .expect("Failed to initialize database");
+    // Initialize database (blocking drivers must stay off the runtime)
+    let db_config = config.clone();
+    let repos = tokio::task::spawn_blocking(move || {
+        match db_config.database_backend {
+            DatabaseBackend::Sqlite => open_sqlite(&db_config),
+            DatabaseBackend::Postgres => open_postgres(&db_config),
+        }
+    })
+    .await
+    .expect("Failed to initialize database");
+
+    // Open the media directory
+    let media = LocalMediaStore::open(&config.media_dir)
+        .expect("Failed to open media directory");
+
+    let limits = MediaLimits {
+        max_file_size: config.max_upload_size,
+        max_av_file_size: config.max_av_upload_size,
+        max_dimension: config.max_image_dimension,
+        thumbnail_size: config.thumbnail_size,
+    };
 
     // Build application state
-    let services = Arc::new(ServiceLayer);
-    let db = Arc::new(conn);
+    let mut services = ServiceLayer::new(repos)
+        .with_media(Arc::new(media), limits)
+        .with_image_ban_distance(config.image_ban_distance)
+        .with_cyclical_reply_limit(config.cyclical_reply_limit)
+        .with_registration(config.allow_registration)
+        .with_session_lifetime(
+            Duration::from_secs(config.session_max_age),
+            Duration::from_secs(config.session_idle_timeout),
+        )
+        .with_ip_retention(Duration::from_secs(config.ip_retention))
+        .with_rate_limits(RateLimits {
+            thread_cooldown: Duration::from_secs(config.thread_cooldown),
+            reply_cooldown: Duration::from_secs(config.reply_cooldown),
+            repost_cooldown: Duration::from_secs(config.repost_cooldown),
+        });
+    if config.ip_hash_key.is_empty() {
+        tracing::warn!("IP_HASH_KEY is not set; poster hashes will not match across restarts");
+    } else {
+        services = services.with_ip_hash_key(config.ip_hash_key.clone().into_bytes());
+    }
+    if !config.ffmpeg_path.is_empty() {
+        services = services.with_poster_frames(Arc::new(Ffmpeg::new(&config.ffmpeg_path)));
+    }
+    let services = Arc::new(services);
+
+    if config.media_gc_interval > 0 {
+        tokio::spawn(run_media_gc(
+            services.clone(),
+            Duration::from_secs(config.media_gc_interval),
+        ));
+    }
+
+    if config.session_purge_interval > 0 {
+        tokio::spawn(run_session_purge(
+            services.clone(),
+            Duration::from_secs(config.session_purge_interval),
+        ));
+    }
+
+    if config.ip_purge_interval > 0 {
+        tokio::spawn(run_ip_purge(
+            services.clone(),
+            Duration::from_secs(config.ip_purge_interval),
+        ));
+    }
+
+    let csrf_key = if config.csrf_key.is_empty() {
+        tracing::warn!("CSRF_KEY is not set; forms open across a restart will be refused");
+        auth::generate_key()
+    } else {
+        config.csrf_key.clone().into_bytes()
+    };
 
-    let state = AppState { services, db };
+    let state = AppState {
+        services,
+        trusted_proxies: Arc::new(config.trusted_proxies.clone()),
+        rate_limiter: (config.rate_limit_per_second > 0.0).then(|| {
+            Arc::new(RateLimiter::new(config.rate_limit_burst, config.rate_limit_per_second))
+        }),
+        csrf_key: Arc::new(csrf_key),
+    };
 
     // Create router
     let app = create_router(state)
-        .nest_service("/static", ServeDir::new("static"));
+        .nest_service("/static", ServeDir::new("static"))
+        .nest_service("/media", ServeDir::new(&config.media_dir))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ));
 
     // Start server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+    let listener = tokio::net::TcpListener::bind(&config.server_address)
         .await
         .expect("Failed to bind port");
 
-    tracing::info!("Server running on http://localhost:3000");
+    tracing::info!("Server running on http://{}", config.server_address);
 
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .expect("Server failed");
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+//! Client Addresses
+//!
+//! Developer Notes:
+//! - `ClientIp` is the address of the peer that opened the connection,
+//!   taken from axum's `ConnectInfo`. The server must be started with
+//!   `into_make_service_with_connect_info::<SocketAddr>()`.
+//! - When that peer is one of `AppState::trusted_proxies`, the address
+//!   is read from `X-Forwarded-For` instead: the rightmost entry not
+//!   itself a trusted proxy. Entries to its left were written by the
+//!   client and prove nothing.
+//!
+//! End Notes:
+//! A request without connection info is refused with 500 rather than
+//! attributed to a made-up address.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use ipnet::IpNet;
+
+use crate::routes::AppState;
+
+/// The address a request came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(addr) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(ClientIp(client_ip(addr.ip(), &parts.headers, &state.trusted_proxies)))
+    }
+}
+
+/// Walk `X-Forwarded-For` back from `peer` through trusted proxies.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+
+    let mut client = peer.to_canonical();
+    if !is_trusted(&client) {
+        return client;
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    for hop in hops.into_iter().rev() {
+        // A garbled hop ends the chain at the last proxy we believe.
+        let Ok(ip) = hop.parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !is_trusted(&client) {
+            break;
+        }
+    }
+    client
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn forwarded_for_is_believed_only_from_trusted_proxies() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", HeaderValue::from_static("6.6.6.6, 198.51.100.7"));
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.2"));
+
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &trusted), ip("198.51.100.7"));
+        assert_eq!(client_ip(ip("203.0.113.1"), &headers, &trusted), ip("203.0.113.1"));
+        assert_eq!(client_ip(ip("10.0.0.1"), &HeaderMap::new(), &trusted), ip("10.0.0.1"));
+
+        let mut garbled = HeaderMap::new();
+        garbled.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.7, junk"));
+        assert_eq!(client_ip(ip("10.0.0.1"), &garbled, &trusted), ip("10.0.0.1"));
+    }
+}
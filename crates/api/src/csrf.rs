@@ -0,0 +1,284 @@
+//! CSRF Protection
+//!
+//! Developer Notes:
+//! - Every request gets a `CsrfToken`, derived by `auth` from a secret
+//!   only the browser holds: its session token when it has one, or else
+//!   an anonymous `rb_csrf` cookie (a double-submit cookie), which
+//!   `protect` sets on first visit. The token is signed with the
+//!   server's CSRF key, so a secret planted in a browser's cookies does
+//!   not let an attacker compute the token that goes with it.
+//! - Form templates embed the token as a hidden `csrf_token` field
+//!   (`components/csrf.html`); other clients may send it in the
+//!   `X-CSRF-Token` header.
+//! - `protect` is middleware on the whole router: any request that is
+//!   not GET, HEAD or OPTIONS must carry the token, or gets 403 before
+//!   its handler runs.
+//! - To read the field, the body is buffered (within the router's body
+//!   limit) and handed on unchanged.
+//!
+//! End Notes:
+//! Logging in or out changes the secret, so forms rendered before that
+//! stop working; this is intended.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    async_trait,
+    body::{to_bytes, Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Multipart, Request, State},
+    http::{header::{CONTENT_TYPE, SET_COOKIE}, request::Parts, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Form,
+};
+
+use crate::session::{cookie_value, session_token};
+
+/// Name of the anonymous CSRF cookie.
+pub const CSRF_COOKIE: &str = "rb_csrf";
+
+/// Name of the hidden form field carrying the token.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// Header carrying the token for requests that are not forms.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// What `protect` needs to check a request.
+#[derive(Clone)]
+pub struct CsrfConfig {
+    /// Server key tokens are signed with.
+    pub key: Arc<Vec<u8>>,
+    /// Most of a body buffered to find the token.
+    pub body_limit: usize,
+}
+
+/// The token forms must submit on this request's behalf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CsrfToken>()
+            .cloned()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// `Set-Cookie` value that stores an anonymous CSRF secret.
+pub fn csrf_cookie(secret: &str) -> String {
+    format!("{CSRF_COOKIE}={secret}; Path=/; HttpOnly; Secure; SameSite=Lax")
+}
+
+/// The secret a request's CSRF token is derived from, if it has one.
+pub fn csrf_secret(headers: &HeaderMap) -> Option<String> {
+    session_token(headers).or_else(|| cookie_value(headers, CSRF_COOKIE))
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// The token submitted with a form body, if any.
+async fn submitted_field(headers: &HeaderMap, body: Bytes) -> Option<String> {
+    let mut request = Request::new(Body::from(body));
+    *request.method_mut() = Method::POST;
+    if let Some(content_type) = headers.get(CONTENT_TYPE) {
+        request.headers_mut().insert(CONTENT_TYPE, content_type.clone());
+    }
+
+    let is_multipart = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    if !is_multipart {
+        let Form(mut fields) = Form::<HashMap<String, String>>::from_request(request, &())
+            .await
+            .ok()?;
+        return fields.remove(CSRF_FIELD);
+    }
+
+    let mut multipart = Multipart::from_request(request, &()).await.ok()?;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some(CSRF_FIELD) && field.file_name().is_none() {
+            return field.text().await.ok();
+        }
+    }
+    None
+}
+
+/// Reject state-changing requests without a valid CSRF token, and give
+/// every request its `CsrfToken`.
+pub async fn protect(
+    State(config): State<CsrfConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (secret, new_secret) = match csrf_secret(request.headers()) {
+        Some(secret) => (secret, None),
+        None => {
+            let secret = auth::generate_session_token();
+            (secret.clone(), Some(secret))
+        }
+    };
+    let token = auth::derive_csrf_token(&config.key, &secret);
+
+    let mut request = if is_safe(request.method()) {
+        request
+    } else {
+        // A browser that never received a secret cannot have a form
+        // carrying a token for it.
+        if new_secret.is_some() {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        let (parts, body) = request.into_parts();
+        let body = match to_bytes(body, config.body_limit).await {
+            Ok(body) => body,
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        };
+
+        let submitted = match parts.headers.get(CSRF_HEADER) {
+            Some(value) => value.to_str().ok().map(str::to_string),
+            None => submitted_field(&parts.headers, body.clone()).await,
+        };
+        if !submitted.is_some_and(|submitted| auth::tokens_match(&submitted, &token)) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        Request::from_parts(parts, Body::from(body))
+    };
+
+    request.extensions_mut().insert(CsrfToken(token));
+    let mut response = next.run(request).await;
+
+    if let Some(secret) = new_secret
+        && let Ok(cookie) = csrf_cookie(&secret).parse()
+    {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header::COOKIE, routing::post, Router};
+    use tower::ServiceExt;
+
+    use crate::session::SESSION_COOKIE;
+
+    const KEY: &[u8] = b"test key";
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", post(|CsrfToken(token): CsrfToken| async move { token }).get(
+                |CsrfToken(token): CsrfToken| async move { token },
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                CsrfConfig { key: Arc::new(KEY.to_vec()), body_limit: 1024 * 1024 },
+                protect,
+            ))
+    }
+
+    fn form(cookie: &str, content_type: &str, body: String) -> Request {
+        Request::post("/")
+            .header(COOKIE, cookie)
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn status(request: Request) -> StatusCode {
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn first_visit_gets_a_secret_and_a_token() {
+        let response = app()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cookie = response.headers()[SET_COOKIE].to_str().unwrap().to_string();
+        assert!(cookie.starts_with("rb_csrf=") && cookie.contains("HttpOnly"));
+
+        let secret = cookie.split(';').next().unwrap().trim_start_matches("rb_csrf=");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, auth::derive_csrf_token(KEY, secret));
+    }
+
+    #[tokio::test]
+    async fn forged_posts_are_forbidden() {
+        let token = auth::derive_csrf_token(KEY, "abc");
+        let urlencoded = "application/x-www-form-urlencoded";
+
+        // No cookie at all, a missing field, a wrong token, a token for
+        // another browser's secret, and one signed with another key.
+        assert_eq!(
+            status(Request::post("/").body(Body::from(format!("csrf_token={token}"))).unwrap()).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(form("rb_csrf=abc", urlencoded, "content=hi".into())).await, StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(form("rb_csrf=abc", urlencoded, "csrf_token=0000".into())).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(form("rb_csrf=xyz", urlencoded, format!("csrf_token={token}"))).await,
+            StatusCode::FORBIDDEN
+        );
+        let unkeyed = auth::derive_csrf_token(b"", "abc");
+        assert_eq!(
+            status(form("rb_csrf=abc", urlencoded, format!("csrf_token={unkeyed}"))).await,
+            StatusCode::FORBIDDEN
+        );
+
+        assert_eq!(
+            status(form("rb_csrf=abc", urlencoded, format!("content=hi&csrf_token={token}"))).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn sessions_multipart_and_headers_are_checked() {
+        let session = format!("{SESSION_COOKIE}=s3cret; rb_csrf=abc");
+        let token = auth::derive_csrf_token(KEY, "s3cret");
+        let multipart = |token: &str| {
+            format!(
+                "--X\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{token}\r\n\
+                 --X\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\r\n\
+                 DATA\r\n--X--\r\n"
+            )
+        };
+
+        let content_type = "multipart/form-data; boundary=X";
+        assert_eq!(status(form(&session, content_type, multipart(&token))).await, StatusCode::OK);
+        let anonymous_token = auth::derive_csrf_token(KEY, "abc");
+        assert_eq!(
+            status(form(&session, content_type, multipart(&anonymous_token))).await,
+            StatusCode::FORBIDDEN
+        );
+
+        let with_header = |token: &str| {
+            Request::post("/")
+                .header(COOKIE, &session)
+                .header(CSRF_HEADER, token)
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(status(with_header(&token)).await, StatusCode::OK);
+        assert_eq!(status(with_header("nope")).await, StatusCode::FORBIDDEN);
+    }
+}
@@ -9,5 +9,10 @@
 //! End of File Notes:
 //! Keep this crate focused on request/response handling.
 
+pub mod client;
+pub mod csrf;
+pub mod rate_limit;
 pub mod routes;
-pub mod templates;
\ No newline at end of file
+pub mod session;
+pub mod templates;
+pub mod upload;
\ No newline at end of file
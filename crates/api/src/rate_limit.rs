@@ -0,0 +1,173 @@
+//! Request Rate Limiting
+//!
+//! Developer Notes:
+//! - `limit` is middleware on the whole router: every client address has
+//!   a token bucket holding up to `burst` requests, refilled at
+//!   `per_second`. A request finding the bucket empty gets 429 with a
+//!   `Retry-After` header before its handler runs.
+//! - IPv6 clients are bucketed by their /64, which one subscriber
+//!   usually holds in full.
+//! - Buckets live in memory, so limits reset on restart and are not
+//!   shared between processes. Idle buckets are dropped once enough
+//!   addresses are tracked.
+//! - Posting cooldowns are business rules and live in `services`; this
+//!   only guards the server against request floods.
+//!
+//! End Notes:
+//! Disabled when `AppState::rate_limiter` is `None`.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use askama::Template;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+
+use crate::client::ClientIp;
+use crate::routes::AppState;
+use crate::templates::SlowDownTemplate;
+
+/// Tracked addresses beyond which idle buckets are dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Least time between two sweeps for idle buckets.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Per-address token buckets.
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    state: Mutex<Buckets>,
+}
+
+struct Buckets {
+    buckets: HashMap<IpAddr, Bucket>,
+    last_pruned: Option<Instant>,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Allow bursts of `burst` requests per address, refilled at
+    /// `per_second` requests a second.
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self {
+            burst: f64::from(burst.max(1)),
+            per_second,
+            state: Mutex::new(Buckets { buckets: HashMap::new(), last_pruned: None }),
+        }
+    }
+
+    /// Take a token for `ip`, or say how long until one is available.
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.buckets.len() >= PRUNE_THRESHOLD
+            && state.last_pruned.is_none_or(|at| now.duration_since(at) >= PRUNE_INTERVAL)
+        {
+            let refill_time = self.burst / self.per_second;
+            state
+                .buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated).as_secs_f64() < refill_time);
+            state.last_pruned = Some(now);
+        }
+
+        let bucket = state
+            .buckets
+            .entry(bucket_key(ip))
+            .or_insert(Bucket { tokens: self.burst, updated: now });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second))
+        }
+    }
+}
+
+/// The address a client is counted under: itself, or its /64 for IPv6.
+fn bucket_key(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & !((1u128 << 64) - 1)).into()),
+        v4 => v4,
+    }
+}
+
+/// Refuse requests from addresses that have run out of tokens.
+pub async fn limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(limiter) = state.rate_limiter.clone() else {
+        return next.run(request).await;
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let ip = match ClientIp::from_request_parts(&mut parts, &state).await {
+        Ok(ClientIp(ip)) => ip,
+        Err(status) => return status.into_response(),
+    };
+
+    match limiter.check(ip, Instant::now()) {
+        Ok(()) => next.run(Request::from_parts(parts, body)).await,
+        Err(wait) => too_many_requests("sending more requests", wait),
+    }
+}
+
+/// A 429 page telling the client how long to wait before `action`.
+pub fn too_many_requests(action: &str, wait: Duration) -> Response {
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let secs = secs.max(1);
+    let unit = if secs == 1 { "second" } else { "seconds" };
+
+    let template = SlowDownTemplate {
+        message: format!("Please wait {secs} {unit} before {action}."),
+    };
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, secs.to_string())],
+        Html(template.render().unwrap()),
+    )
+        .into_response()
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = RateLimiter::new(2, 1.0);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check(ip, start).is_ok());
+        assert!(limiter.check(ip, start).is_ok());
+        let wait = limiter.check(ip, start).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(1));
+        assert!(limiter.check("198.51.100.8".parse().unwrap(), start).is_ok());
+        assert!(limiter.check(ip, start + Duration::from_millis(1500)).is_ok());
+
+        let a: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+        let b: IpAddr = "2001:db8:1:2:ffff::9".parse().unwrap();
+        assert!(limiter.check(a, start).is_ok() && limiter.check(b, start).is_ok());
+        assert!(limiter.check(a, start).is_err());
+    }
+}
@@ -1,32 +1,130 @@
+use askama::Template;
 use axum::{
     routing::{get, post},
-    Router,
-    extract::{State, Form, Path},
-    response::{Html, IntoResponse},
-    http::StatusCode,
+    Form, Router,
+    extract::{DefaultBodyLimit, State, Path, Query},
+    response::{Html, IntoResponse, Redirect, Response},
+    http::{header::{CACHE_CONTROL, CONTENT_TYPE, SET_COOKIE, USER_AGENT}, HeaderMap, StatusCode},
 };
+use ipnet::IpNet;
 use serde::Deserialize;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
-use services::ServiceLayer;
-use storage::{thread_repository, post_repository};
-use templates::*;
+use models::{
+    AvPolicy, Board, BoardRole, CaptchaMode, Cooldowns, DuplicatePolicy, ModActionKind, Permission,
+    PruneMode, PrunePolicy, ReportCategory, Role, ThreadFlags,
+};
+use services::{
+    ModLogQuery, NewBan, Poster, PosterBan, ServiceError, ServiceLayer, MAX_BAN_DURATION,
+    MAX_FILES_PER_POST, MOD_LOG_PAGE_LEN,
+};
+use services::views::ThreadView;
+use crate::client::ClientIp;
+use crate::csrf::{self, CsrfToken};
+use crate::rate_limit::{self, too_many_requests, RateLimiter};
+use crate::session::{clear_session_cookie, session_cookie, session_token, CurrentUser};
+use crate::templates::*;
+use crate::upload::PostForm;
+
+/// Room left in a request body for text fields and multipart framing.
+const FORM_OVERHEAD: usize = 64 * 1024;
+
+/// Refused uploads listed on the image bans page.
+const IMAGE_BAN_MATCHES_SHOWN: u32 = 50;
 
 #[derive(Clone)]
 pub struct AppState {
     pub services: Arc<ServiceLayer>,
-    pub db: Arc<rusqlite::Connection>,
+    /// Proxies whose `X-Forwarded-For` names the client (see `ClientIp`).
+    pub trusted_proxies: Arc<Vec<IpNet>>,
+    /// Per-address request limit; `None` disables it.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Server key CSRF tokens are signed with (see `csrf`).
+    pub csrf_key: Arc<Vec<u8>>,
+}
+
+impl AppState {
+    /// Run blocking service work on tokio's blocking thread pool.
+    ///
+    /// Repositories may block on database I/O; handlers must never call
+    /// them directly on the async runtime.
+    pub async fn run<F, T>(&self, f: F) -> Result<T, StatusCode>
+    where
+        F: FnOnce(&ServiceLayer) -> Result<T, ServiceError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let services = self.services.clone();
+
+        tokio::task::spawn_blocking(move || f(&services))
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map_err(error_status)
+    }
+}
+
+/// Map a service error onto an HTTP status.
+fn error_status(err: ServiceError) -> StatusCode {
+    match err {
+        ServiceError::Validation(_) => StatusCode::BAD_REQUEST,
+        ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+        ServiceError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        ServiceError::Media(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        ServiceError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+        ServiceError::Banned(_) => StatusCode::FORBIDDEN,
+        ServiceError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        ServiceError::Auth(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
 
 pub fn create_router(state: AppState) -> Router {
+    let limits = state.services.media_limits();
+    let body_limit =
+        limits.max_file_size.max(limits.max_av_file_size) * MAX_FILES_PER_POST + FORM_OVERHEAD;
+
     Router::new()
         .route("/", get(index))
         .route("/boards", get(list_boards))
-        .route("/boards/:id", get(view_board))
+        .route("/boards/:board", get(view_board))
+        .route("/boards/:board/res/:number", get(view_thread_by_number))
+        .route("/boards/:board/res/:number/flags", post(set_thread_flags))
+        .route("/boards/:board/log", get(board_log))
+        .route("/boards/:board/archive", get(board_archive))
+        .route("/boards/:board/settings", get(board_settings_page).post(edit_board_settings))
+        .route("/boards/:board/staff", get(board_staff_page).post(appoint_staff))
+        .route("/boards/:board/staff/:user_id/dismiss", post(dismiss_staff))
         .route("/threads/:id", get(view_thread))
         .route("/threads", post(create_thread))
         .route("/posts", post(create_post))
+        .route("/captcha/:id", get(captcha_image))
+        .route("/boards/:board/posts/:number/delete", post(delete_post))
+        .route("/boards/:board/posts/:number/report", post(report_post))
+        .route("/banned", get(banned_page))
+        .route("/banned/:id/appeal", post(appeal_ban))
+        .route("/mod/bans", get(mod_bans_page).post(ban_ip))
+        .route("/mod/bans/:id/lift", post(lift_ban))
+        .route("/mod/appeals/:id", post(decide_appeal))
+        .route("/mod/image-bans", get(mod_image_bans_page).post(ban_image))
+        .route("/mod/image-bans/:id/lift", post(unban_image))
+        .route("/mod/posters/:ip_hash", get(mod_poster_page))
+        .route("/mod/reports", get(mod_reports_page))
+        .route("/mod/reports/:post_id", post(handle_reports))
+        .route("/mod/log", get(mod_log_page))
+        .route("/mod/users", get(mod_users_page).post(set_user_role))
+        .route("/login", get(login_page).post(login))
+        .route("/logout", post(logout))
+        .route("/logout/all", post(logout_everywhere))
+        .route("/register", get(register_page).post(register))
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(axum::middleware::from_fn_with_state(
+            csrf::CsrfConfig { key: state.csrf_key.clone(), body_limit },
+            csrf::protect,
+        ))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit::limit))
         .with_state(state)
 }
 
@@ -38,123 +136,1924 @@ async fn index() -> impl IntoResponse {
 async fn list_boards(
     State(state): State<AppState>,
 ) -> Result<Html<String>, StatusCode> {
-    let boards = state
-        .services
-        .list_boards(&state.db)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let boards = state.run(|services| services.list_boards()).await?;
 
     let template = BoardsTemplate { boards };
 
     Ok(Html(template.render().unwrap()))
 }
 
-#[derive(Deserialize)]
-struct CreateThreadForm {
-    board_id: String,
-    title: String,
+/// Answer a thread or post submission; banned posters are sent to the
+/// ban page and hasty ones told how long to wait.
+fn posted<T>(result: Result<T, ServiceError>) -> Result<Response, StatusCode> {
+    match result {
+        Ok(_) => Ok(StatusCode::SEE_OTHER.into_response()),
+        Err(ServiceError::Banned(_)) => Ok(Redirect::to("/banned").into_response()),
+        Err(ServiceError::RateLimited { action, retry_after }) => {
+            Ok(too_many_requests(action, retry_after))
+        }
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+/// The poster behind a request: its address and `User-Agent`.
+fn poster(ip: IpAddr, headers: &HeaderMap) -> Poster {
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    Poster::new(ip).with_user_agent(user_agent)
+}
+
+/// The CAPTCHA fields of a post form.
+struct CaptchaAnswer {
+    id: Option<Uuid>,
+    answer: String,
+}
+
+impl CaptchaAnswer {
+    /// Take the fields out of `form`; either may be missing.
+    fn from_form(form: &mut PostForm) -> Self {
+        Self {
+            id: form.field("captcha_id").ok().and_then(|id| Uuid::parse_str(id).ok()),
+            answer: form.take("captcha_answer").unwrap_or_default(),
+        }
+    }
+
+    /// Whether the answer solves its challenge, using the challenge up.
+    fn solves(&self, services: &ServiceLayer) -> Result<bool, ServiceError> {
+        match self.id {
+            Some(id) => services.solve_captcha(id, &self.answer),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Answer a post whose CAPTCHA was wrong, expired or missing.
+fn captcha_failed() -> Response {
+    (StatusCode::BAD_REQUEST, Html(CaptchaFailedTemplate.render().unwrap())).into_response()
+}
+
+/// A new challenge when `required`, for a page with a post form.
+fn form_captcha(services: &ServiceLayer, required: bool) -> Result<Option<Uuid>, ServiceError> {
+    required.then(|| services.new_captcha()).transpose()
+}
+
+async fn captcha_image(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let png = state.run(move |services| services.captcha_image(id)).await?;
+
+    Ok(([(CONTENT_TYPE, "image/png"), (CACHE_CONTROL, "no-store")], png).into_response())
 }
 
 async fn create_thread(
     State(state): State<AppState>,
-    Form(form): Form<CreateThreadForm>,
-) -> Result<impl IntoResponse, StatusCode> {
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    mut form: PostForm,
+) -> Result<Response, StatusCode> {
     let board_id =
-        Uuid::parse_str(&form.board_id)
+        Uuid::parse_str(form.field("board_id")?)
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let title = form.take("title")?;
+    let captcha = CaptchaAnswer::from_form(&mut form);
+    let poster = poster(ip, &headers);
+
+    let result = state
+        .run(move |services| {
+            if services.thread_needs_captcha(board_id)? && !captcha.solves(services)? {
+                return Ok(None);
+            }
+            Ok(Some(services.create_thread(&poster, board_id, title, form.files)))
+        })
+        .await?;
+
+    match result {
+        Some(result) => posted(result),
+        None => Ok(captcha_failed()),
+    }
+}
+
+async fn create_post(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    mut form: PostForm,
+) -> Result<Response, StatusCode> {
+    let thread_id =
+        Uuid::parse_str(form.field("thread_id")?)
             .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let content = form.take("content").unwrap_or_default();
+    let options = form.take("options").unwrap_or_default();
+    let captcha = CaptchaAnswer::from_form(&mut form);
+    let poster = poster(ip, &headers);
+
+    let result = state
+        .run(move |services| {
+            if services.reply_needs_captcha(thread_id)? && !captcha.solves(services)? {
+                return Ok(None);
+            }
+            Ok(Some(services.create_post(&poster, thread_id, content, &options, form.files)))
+        })
+        .await?;
+
+    match result {
+        Some(result) => posted(result),
+        None => Ok(captcha_failed()),
+    }
+}
+
+#[derive(Deserialize)]
+struct DeleteForm {
+    /// Recorded in the moderation log; may be empty.
+    #[serde(default)]
+    reason: String,
+}
+
+/// Delete a reply as board staff, then return to its thread.
+async fn delete_post(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path((board, number)): Path<(String, i64)>,
+    Form(form): Form<DeleteForm>,
+) -> Result<Redirect, StatusCode> {
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &board)?;
+            let post = services.delete_post(&user, board.id, number, form.reason)?;
+
+            let thread = services.get_thread(post.thread_id)?;
+            Ok(format!("/boards/{}/res/{}", board.name, thread.op_number))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+/// Fields of the thread flags form; unticked boxes are not sent.
+#[derive(Deserialize)]
+struct ThreadFlagsForm {
+    /// Position among the stickies; empty to unsticky.
+    #[serde(default)]
+    sticky: String,
+    #[serde(default)]
+    locked: Option<String>,
+    #[serde(default)]
+    cyclical: Option<String>,
+    #[serde(default)]
+    autosage: Option<String>,
+    /// Recorded in the moderation log; may be empty.
+    #[serde(default)]
+    reason: String,
+}
+
+/// Change a thread's flags as board staff, then return to it.
+async fn set_thread_flags(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path((board, number)): Path<(String, i64)>,
+    Form(form): Form<ThreadFlagsForm>,
+) -> Result<Redirect, StatusCode> {
+    let sticky = match form.sticky.trim() {
+        "" => None,
+        position => Some(position.parse().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    let flags = ThreadFlags {
+        sticky,
+        locked: form.locked.is_some(),
+        cyclical: form.cyclical.is_some(),
+        autosage: form.autosage.is_some(),
+    };
+
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &board)?;
+            let thread = services.find_thread_by_number(board.id, number)?;
+            services.set_thread_flags(&user, thread.id, flags, form.reason)?;
+            Ok(format!("/boards/{}/res/{}", board.name, thread.op_number))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+/// A board's settings, for staff who may change them.
+async fn board_settings_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+    Path(key): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let board = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            services.authorize(&user, Some(board.id), Permission::EditBoard)?;
+            Ok(board)
+        })
+        .await?;
+
+    let template = BoardSettingsTemplate { csrf_token, board };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Fields of the board settings form.
+#[derive(Deserialize)]
+struct BoardSettingsForm {
+    /// `off`, `threads` or `all`.
+    captcha_mode: String,
+    /// Cooldowns in seconds; empty keeps the site default.
+    #[serde(default)]
+    thread_cooldown: String,
+    #[serde(default)]
+    reply_cooldown: String,
+    #[serde(default)]
+    repost_cooldown: String,
+    /// Empty keeps the current bump limit.
+    #[serde(default)]
+    bump_limit: String,
+    /// Empty keeps the current number of live threads.
+    #[serde(default)]
+    max_threads: String,
+    /// `archive` or `delete`; empty keeps the current mode.
+    #[serde(default)]
+    prune_mode: String,
+    /// `allow`, `thread` or `board`; empty keeps the current policy.
+    #[serde(default)]
+    duplicate_policy: String,
+    /// Longest audio or video in seconds; empty keeps the current limit.
+    #[serde(default)]
+    av_max_duration: String,
+    /// Comma-separated codec names, where an empty list refuses audio
+    /// and video; left out, the current codecs are kept.
+    av_codecs: Option<String>,
+    /// Checked to publish the anonymised log; browsers leave out an
+    /// unchecked box, so a missing field withdraws it.
+    #[serde(default)]
+    public_mod_log: bool,
+}
+
+/// Parse an optional setting; empty means none.
+fn optional<T: FromStr>(value: &str) -> Result<Option<T>, StatusCode> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value.parse().map(Some).map_err(|_| StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Change a board's settings, then return to them.
+///
+/// Only settings that differ from the board's are changed, so each
+/// change is logged once.
+async fn edit_board_settings(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(key): Path<String>,
+    Form(form): Form<BoardSettingsForm>,
+) -> Result<Redirect, StatusCode> {
+    let captcha_mode: CaptchaMode = form.captcha_mode.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let cooldowns = Cooldowns {
+        thread_secs: optional(&form.thread_cooldown)?,
+        reply_secs: optional(&form.reply_cooldown)?,
+        repost_secs: optional(&form.repost_cooldown)?,
+    };
+    let bump_limit = optional(&form.bump_limit)?;
+    let max_threads = optional(&form.max_threads)?;
+    let prune_mode: Option<PruneMode> = optional(&form.prune_mode)?;
+    let duplicate_policy: Option<DuplicatePolicy> = optional(&form.duplicate_policy)?;
+    let av_max_duration = optional(&form.av_max_duration)?;
+    let av_codecs = form.av_codecs.as_deref().map(AvPolicy::parse_codec_list);
+
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            services.authorize(&user, Some(board.id), Permission::EditBoard)?;
+            if board.captcha_mode != captcha_mode {
+                services.set_captcha_mode(&user, board.id, captcha_mode)?;
+            }
+            if board.cooldowns != cooldowns {
+                services.set_cooldowns(&user, board.id, cooldowns)?;
+            }
+            let bump_limit = bump_limit.unwrap_or(board.bump_limit);
+            if board.bump_limit != bump_limit {
+                services.set_bump_limit(&user, board.id, bump_limit)?;
+            }
+            let prune_policy = PrunePolicy {
+                max_threads: max_threads.unwrap_or(board.prune_policy.max_threads),
+                mode: prune_mode.unwrap_or(board.prune_policy.mode),
+            };
+            if board.prune_policy != prune_policy {
+                services.set_prune_policy(&user, board.id, prune_policy)?;
+            }
+            if let Some(policy) = duplicate_policy.filter(|policy| *policy != board.duplicate_policy) {
+                services.set_duplicate_policy(&user, board.id, policy)?;
+            }
+            let av_policy = AvPolicy {
+                max_duration_secs: av_max_duration.unwrap_or(board.av_policy.max_duration_secs),
+                codecs: av_codecs.unwrap_or_else(|| board.av_policy.codecs.clone()),
+            };
+            if board.av_policy != av_policy {
+                services.set_av_policy(&user, board.id, av_policy)?;
+            }
+            if board.public_mod_log != form.public_mod_log {
+                services.set_public_mod_log(&user, board.id, form.public_mod_log)?;
+            }
+            Ok(format!("/boards/{}/settings", board.name))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+async fn board_staff_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+    Path(key): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (board, staff) = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            let staff = services.staff_views(&user, board.id)?;
+            Ok((board, staff))
+        })
+        .await?;
+
+    let template = BoardStaffTemplate { csrf_token, board, staff };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+#[derive(Deserialize)]
+struct RoleForm {
+    username: String,
+    role: String,
+}
+
+async fn appoint_staff(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(key): Path<String>,
+    Form(form): Form<RoleForm>,
+) -> Result<Redirect, StatusCode> {
+    let role: BoardRole = form.role.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            // Before the lookup, so outsiders cannot probe for usernames.
+            services.authorize(&user, Some(board.id), Permission::ManageStaff)?;
+            let appointee = services.find_user(&form.username)?;
+            services.appoint_staff(&user, board.id, appointee.id, role)?;
+            Ok(format!("/boards/{}/staff", board.name))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+async fn dismiss_staff(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path((key, user_id)): Path<(String, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            services.dismiss_staff(&user, board.id, user_id)?;
+            Ok(format!("/boards/{}/staff", board.name))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+async fn mod_users_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+) -> Result<Html<String>, StatusCode> {
+    state
+        .run(move |services| services.authorize(&user, None, Permission::ManageUsers))
+        .await?;
+
+    let template = ModUsersTemplate { csrf_token };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn set_user_role(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<RoleForm>,
+) -> Result<Redirect, StatusCode> {
+    let role: Role = form.role.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
 
     state
-        .services
-        .create_thread(&state.db, board_id, form.title)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .run(move |services| {
+            services.authorize(&user, None, Permission::ManageUsers)?;
+            let target = services.find_user(&form.username)?;
+            services.set_user_role(&user, target.id, role)
+        })
+        .await?;
+
+    Ok(Redirect::to("/mod/users"))
+}
+
+/// The ban page for `ip`, with `error` from a rejected appeal.
+async fn banned_form(
+    state: &AppState,
+    ip: IpAddr,
+    csrf_token: String,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let notices = state.run(move |services| services.ban_notices(ip)).await?;
+    let template = BannedTemplate { csrf_token, notices, error };
+
+    Ok(Html(template.render().unwrap()))
+}
 
-    Ok(StatusCode::SEE_OTHER)
+async fn banned_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    ClientIp(ip): ClientIp,
+) -> Result<Html<String>, StatusCode> {
+    banned_form(&state, ip, csrf_token, None).await
 }
 
 #[derive(Deserialize)]
-struct CreatePostForm {
-    thread_id: String,
-    content: String,
+struct AppealForm {
+    message: String,
 }
 
-async fn create_post(
+async fn appeal_ban(
     State(state): State<AppState>,
-    Form(form): Form<CreatePostForm>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let thread_id =
-        Uuid::parse_str(&form.thread_id)
-            .map_err(|_| StatusCode::BAD_REQUEST)?;
+    CsrfToken(csrf_token): CsrfToken,
+    ClientIp(ip): ClientIp,
+    Path(ban_id): Path<Uuid>,
+    Form(form): Form<AppealForm>,
+) -> Result<Response, StatusCode> {
+    let result = state
+        .run(move |services| Ok(services.appeal_ban(ip, ban_id, form.message)))
+        .await?;
+
+    match result {
+        Ok(_) => Ok(Redirect::to("/banned").into_response()),
+        Err(ServiceError::Validation(message)) => {
+            let page = banned_form(&state, ip, csrf_token, Some(message)).await?;
+            Ok((StatusCode::BAD_REQUEST, page).into_response())
+        }
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+async fn mod_bans_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+) -> Result<Html<String>, StatusCode> {
+    let (boards, bans, appeals) = state
+        .run(move |services| {
+            if !services.is_staff(&user)? {
+                return Err(ServiceError::Forbidden("Staff only".into()));
+            }
+
+            let boards = services.list_boards()?;
+            let bans = services
+                .list_bans(&user)?
+                .into_iter()
+                .map(|ban| {
+                    let board = ban
+                        .board_id
+                        .and_then(|id| boards.iter().find(|b| b.id == id))
+                        .map(|b| b.name.clone());
+                    (ban, board)
+                })
+                .collect();
+            Ok((boards, bans, services.list_appeals(&user)?))
+        })
+        .await?;
+
+    let template = ModBansTemplate { csrf_token, boards, bans, appeals };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Everything posted from one address, by its hash.
+async fn mod_poster_page(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(ip_hash): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let lookup = ip_hash.clone();
+    let posts = state
+        .run(move |services| services.posts_by_poster(&user, &lookup))
+        .await?;
+
+    let template = ModPosterTemplate { ip_hash, posts };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Parse an optional whole number of hours; empty means no limit.
+///
+/// Zero, and anything past `MAX_BAN_DURATION`, is refused.
+fn duration_hours(hours: &str) -> Result<Option<Duration>, StatusCode> {
+    match hours.trim() {
+        "" => Ok(None),
+        hours => {
+            let hours: u64 = hours.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+            if hours == 0 || hours > MAX_BAN_DURATION.as_secs() / (60 * 60) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Ok(Some(Duration::from_secs(hours * 60 * 60)))
+        }
+    }
+}
+
+/// Fields of the ban form.
+#[derive(Deserialize)]
+struct BanForm {
+    /// A single address or a CIDR range.
+    ip_range: String,
+    /// Board name; empty for every board.
+    board: String,
+    reason: String,
+    #[serde(default)]
+    staff_note: String,
+    /// Whole hours; empty for a permanent ban.
+    #[serde(default)]
+    duration_hours: String,
+}
+
+async fn ban_ip(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BanForm>,
+) -> Result<Redirect, StatusCode> {
+    let range = form.ip_range.trim();
+    let ip_range = range
+        .parse::<IpNet>()
+        .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let duration = duration_hours(&form.duration_hours)?;
 
     state
-        .services
-        .create_post(&state.db, thread_id, form.content)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .run(move |services| {
+            let board_id = match form.board.trim() {
+                "" => None,
+                name => Some(services.get_board_by_name(name)?.id),
+            };
+            let ban = NewBan {
+                ip_range,
+                board_id,
+                reason: form.reason,
+                staff_note: form.staff_note,
+                duration,
+            };
+            services.ban_ip(&user, ban)
+        })
+        .await?;
 
-    Ok(StatusCode::SEE_OTHER)
+    Ok(Redirect::to("/mod/bans"))
+}
+
+async fn lift_ban(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(ban_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    state
+        .run(move |services| services.lift_ban(&user, ban_id))
+        .await?;
+
+    Ok(Redirect::to("/mod/bans"))
+}
+
+/// Banned images and the uploads they refused.
+async fn mod_image_bans_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+) -> Result<Html<String>, StatusCode> {
+    let (bans, matches) = state
+        .run(move |services| {
+            let bans = services.list_image_bans(&user)?;
+            let boards = services.list_boards()?;
+            let matches = services
+                .list_image_ban_matches(&user, IMAGE_BAN_MATCHES_SHOWN)?
+                .into_iter()
+                .map(|hit| {
+                    let board = boards
+                        .iter()
+                        .find(|b| b.id == hit.board_id)
+                        .map(|b| b.name.clone())
+                        .unwrap_or_default();
+                    (hit, board)
+                })
+                .collect();
+            Ok((bans, matches))
+        })
+        .await?;
+
+    let template = ModImageBansTemplate { csrf_token, bans, matches };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn ban_image(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    mut form: PostForm,
+) -> Result<Redirect, StatusCode> {
+    let reason = form.take("reason")?;
+    let Some(file) = form.files.pop() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    state
+        .run(move |services| services.ban_image(&user, &file.data, reason))
+        .await?;
+
+    Ok(Redirect::to("/mod/image-bans"))
+}
+
+async fn unban_image(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(ban_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    state
+        .run(move |services| services.unban_image(&user, ban_id))
+        .await?;
+
+    Ok(Redirect::to("/mod/image-bans"))
+}
+
+#[derive(Deserialize)]
+struct DecisionForm {
+    /// `accept` or `deny`.
+    decision: String,
+}
+
+async fn decide_appeal(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(appeal_id): Path<Uuid>,
+    Form(form): Form<DecisionForm>,
+) -> Result<Redirect, StatusCode> {
+    let accept = match form.decision.as_str() {
+        "accept" => true,
+        "deny" => false,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    state
+        .run(move |services| services.decide_appeal(&user, appeal_id, accept))
+        .await?;
+
+    Ok(Redirect::to("/mod/bans"))
+}
+
+#[derive(Deserialize)]
+struct ReportForm {
+    category: String,
+    #[serde(default)]
+    comment: String,
+}
+
+/// Report a reply, then return to its thread.
+async fn report_post(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Path((board, number)): Path<(String, i64)>,
+    Form(form): Form<ReportForm>,
+) -> Result<Redirect, StatusCode> {
+    let category: ReportCategory = form.category.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let poster = poster(ip, &headers);
+
+    let location = state
+        .run(move |services| {
+            let board = find_board(services, &board)?;
+            services.report_post(&poster, board.id, number, category, form.comment)?;
+
+            let post = services.find_post_by_number(board.id, number)?;
+            let thread = services.get_thread(post.thread_id)?;
+            Ok(format!("/boards/{}/res/{}#p{}", board.name, thread.op_number, number))
+        })
+        .await?;
+
+    Ok(Redirect::to(&location))
+}
+
+async fn mod_reports_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    CurrentUser(user): CurrentUser,
+) -> Result<Html<String>, StatusCode> {
+    let (posts, handled) = state
+        .run(move |services| Ok((services.report_queue(&user)?, services.handled_reports(&user)?)))
+        .await?;
+
+    let template = ModReportsTemplate { csrf_token, posts, handled };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Fields of the report queue's action forms.
+#[derive(Deserialize)]
+struct ReportActionForm {
+    /// `dismiss`, `delete` or `ban`.
+    action: String,
+    /// Ban reason; only read for `ban`.
+    #[serde(default)]
+    reason: String,
+    /// Whole hours; empty for a permanent ban.
+    #[serde(default)]
+    duration_hours: String,
+}
+
+/// Dismiss a post's reports, or delete the post and optionally ban
+/// its poster from the board.
+async fn handle_reports(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Path(post_id): Path<Uuid>,
+    Form(form): Form<ReportActionForm>,
+) -> Result<Redirect, StatusCode> {
+    let ban = match form.action.as_str() {
+        "dismiss" | "delete" => None,
+        "ban" => Some(PosterBan {
+            reason: form.reason,
+            duration: duration_hours(&form.duration_hours)?,
+        }),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    state
+        .run(move |services| match form.action.as_str() {
+            "dismiss" => services.dismiss_reports(&user, post_id),
+            _ => services.delete_reported_post(&user, post_id, ban),
+        })
+        .await?;
+
+    Ok(Redirect::to("/mod/reports"))
+}
+
+/// Filters of the moderation log page; empty fields are unset.
+#[derive(Deserialize)]
+struct ModLogParams {
+    /// Board name.
+    #[serde(default)]
+    board: String,
+    /// Username of the staff member who acted.
+    #[serde(default)]
+    actor: String,
+    #[serde(default)]
+    action: String,
+    /// Unix time in nanoseconds; only older entries are shown.
+    #[serde(default)]
+    before: String,
+}
+
+async fn mod_log_page(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(params): Query<ModLogParams>,
+) -> Result<Html<String>, StatusCode> {
+    let action = match params.action.as_str() {
+        "" => None,
+        action => Some(action.parse::<ModActionKind>().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    let before = match params.before.as_str() {
+        "" => None,
+        nanos => {
+            let nanos: i128 = nanos.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+            Some(
+                time::OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                    .map_err(|_| StatusCode::BAD_REQUEST)?,
+            )
+        }
+    };
+    let board = params.board.trim().to_string();
+    let actor = params.actor.trim().to_string();
+
+    let query_board = board.clone();
+    let query_actor = actor.clone();
+    let (boards, entries) = state
+        .run(move |services| {
+            let board_id = match query_board.as_str() {
+                "" => None,
+                name => Some(services.get_board_by_name(name)?.id),
+            };
+            let query = ModLogQuery {
+                board_id,
+                actor: Some(query_actor),
+                action,
+                before,
+            };
+            let entries = services.mod_log(&user, query)?;
+            Ok((services.list_boards()?, entries))
+        })
+        .await?;
+
+    let action = action.map(|action| action.to_string()).unwrap_or_default();
+    let older = entries
+        .last()
+        .filter(|_| entries.len() == MOD_LOG_PAGE_LEN as usize)
+        .map(|last| {
+            format!(
+                "board={board}&actor={actor}&action={action}&before={}",
+                last.action.created_at.unix_timestamp_nanos()
+            )
+        });
+    let template = ModLogTemplate { boards, board, actor, action, entries, older };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// A board's public moderation log, when it publishes one.
+async fn board_log(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (board_name, entries) = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            Ok((board.name, services.public_mod_log(board.id)?))
+        })
+        .await?;
+
+    let template = BoardLogTemplate { board_name, entries };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Threads pushed off a board into its archive.
+async fn board_archive(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (board_name, threads) = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            Ok((board.name, services.list_archived_threads(board.id)?))
+        })
+        .await?;
+
+    let template = BoardArchiveTemplate { board_name, threads };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+/// Which staff controls a visitor gets on a thread page, and the
+/// reply form's CAPTCHA.
+fn thread_template(
+    services: &ServiceLayer,
+    user: Option<CurrentUser>,
+    csrf_token: String,
+    view: ThreadView,
+) -> Result<ThreadTemplate, ServiceError> {
+    let allowed = |permission| match &user {
+        Some(CurrentUser(user)) => services.is_allowed(user, Some(view.board.id), permission),
+        None => Ok(false),
+    };
+
+    Ok(ThreadTemplate {
+        csrf_token,
+        can_delete: allowed(Permission::DeletePost)?,
+        can_flag: allowed(Permission::StickyThread)? || allowed(Permission::LockThread)?,
+        can_view_posters: allowed(Permission::BanUser)?,
+        captcha: form_captcha(services, view.board.captcha_mode.covers_replies())?,
+        view,
+    })
 }
 
 async fn view_thread(
     State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    user: Option<CurrentUser>,
     Path(id): Path<String>,
 ) -> Result<Html<String>, StatusCode> {
     let thread_id =
         Uuid::parse_str(&id)
             .map_err(|_| StatusCode::BAD_REQUEST)?;
 
-    let posts =
-        post_repository::get_posts_by_thread(&state.db, thread_id)
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let template = state
+        .run(move |services| {
+            let view = services.thread_view(thread_id)?;
+            thread_template(services, user, csrf_token, view)
+        })
+        .await?;
 
-    let thread = models::Thread {
-        id: thread_id,
-        board_id: Uuid::nil(),
-        title: "Thread".into(),
-        created_at: time::OffsetDateTime::now_utc(),
-    };
+    Ok(Html(template.render().unwrap()))
+}
 
-    let template = ThreadTemplate { thread, posts };
+/// Resolve a `:board` path segment, which may be a board id or name.
+fn find_board(services: &ServiceLayer, key: &str) -> Result<Board, ServiceError> {
+    match Uuid::parse_str(key) {
+        Ok(id) => services.get_board(id),
+        Err(_) => services.get_board_by_name(key),
+    }
+}
+
+async fn view_thread_by_number(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    user: Option<CurrentUser>,
+    Path((board, number)): Path<(String, i64)>,
+) -> Result<Html<String>, StatusCode> {
+    let template = state
+        .run(move |services| {
+            let board = find_board(services, &board)?;
+            let thread = services.find_thread_by_number(board.id, number)?;
+            let view = services.thread_view(thread.id)?;
+            thread_template(services, user, csrf_token, view)
+        })
+        .await?;
 
     Ok(Html(template.render().unwrap()))
 }
 
 async fn view_board(
     State(state): State<AppState>,
-    Path(id): Path<String>,
+    CsrfToken(csrf_token): CsrfToken,
+    Path(key): Path<String>,
 ) -> Result<Html<String>, StatusCode> {
-    let board_id =
-        Uuid::parse_str(&id)
-            .map_err(|_| StatusCode::BAD_REQUEST)?;
-
-    let threads =
-        thread_repository::get_threads_by_board(&state.db, board_id)
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let (board, threads, captcha) = state
+        .run(move |services| {
+            let board = find_board(services, &key)?;
+            let threads = services.list_threads(board.id)?;
+            let captcha = form_captcha(services, board.captcha_mode.covers_threads())?;
+            Ok((board, threads, captcha))
+        })
+        .await?;
 
     let template = BoardTemplate {
-        board_id: id,
-        board_name: "Board".into(),
+        csrf_token,
+        board_id: board.id.to_string(),
+        board_name: board.name,
         threads,
+        captcha,
+        public_mod_log: board.public_mod_log,
+        archive: board.prune_policy.mode == PruneMode::Archive,
     };
 
     Ok(Html(template.render().unwrap()))
 }
 
+/// Fields of the login and registration forms.
+#[derive(Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// Set the session cookie and go to the front page.
+fn start_session(state: &AppState, token: &str) -> Response {
+    let cookie = session_cookie(token, state.services.session_max_age());
+
+    ([(SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+fn login_form(state: &AppState, csrf_token: String, error: Option<String>) -> Html<String> {
+    let template = LoginTemplate {
+        csrf_token,
+        username: None,
+        error,
+        registration_open: state.services.registration_open(),
+    };
+
+    Html(template.render().unwrap())
+}
+
+async fn login_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    user: Option<CurrentUser>,
+) -> Html<String> {
+    let template = LoginTemplate {
+        csrf_token,
+        username: user.map(|CurrentUser(user)| user.username),
+        error: None,
+        registration_open: state.services.registration_open(),
+    };
+
+    Html(template.render().unwrap())
+}
+
+async fn login(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    headers: HeaderMap,
+    Form(form): Form<Credentials>,
+) -> Result<Response, StatusCode> {
+    // A new login always gets a new token; any session the browser
+    // already had is ended rather than carried over.
+    let previous = session_token(&headers);
+    let result = state
+        .run(move |services| {
+            let result = services.log_in(&form.username, &form.password);
+            if let (Ok(_), Some(previous)) = (&result, previous) {
+                services.log_out(&previous)?;
+            }
+            Ok(result)
+        })
+        .await?;
+
+    match result {
+        Ok(token) => Ok(start_session(&state, &token)),
+        Err(ServiceError::Unauthorized(message)) => {
+            Ok((StatusCode::UNAUTHORIZED, login_form(&state, csrf_token, Some(message))).into_response())
+        }
+        Err(err) => Err(error_status(err)),
+    }
+}
+
+async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    if let Some(token) = session_token(&headers) {
+        state.run(move |services| services.log_out(&token)).await?;
+    }
+
+    Ok(([(SET_COOKIE, clear_session_cookie())], Redirect::to("/")).into_response())
+}
+
+async fn logout_everywhere(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Response, StatusCode> {
+    state
+        .run(move |services| services.log_out_everywhere(user.id))
+        .await?;
+
+    Ok(([(SET_COOKIE, clear_session_cookie())], Redirect::to("/")).into_response())
+}
+
+async fn register_page(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+) -> Result<Html<String>, StatusCode> {
+    if !state.services.registration_open() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let template = RegisterTemplate { csrf_token, error: None };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn register(
+    State(state): State<AppState>,
+    CsrfToken(csrf_token): CsrfToken,
+    Form(form): Form<Credentials>,
+) -> Result<Response, StatusCode> {
+    if !state.services.registration_open() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let result = state
+        .run(move |services| {
+            Ok(services
+                .register_user(form.username, &form.password)
+                .and_then(|user| services.log_in(&user.username, &form.password)))
+        })
+        .await?;
+
+    match result {
+        Ok(token) => Ok(start_session(&state, &token)),
+        Err(ServiceError::Validation(message)) => {
+            let template = RegisterTemplate { csrf_token, error: Some(message) };
+            Ok((StatusCode::BAD_REQUEST, Html(template.render().unwrap())).into_response())
+        }
+        Err(err) => Err(error_status(err)),
+    }
+}
+
 
 
 
 /// TESTS:
-/// 
-/// 
-/// 
+///
+///
+///
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::{body::Body, extract::connect_info::MockConnectInfo, http::{header, Request}};
+    use std::net::SocketAddr;
+    use storage::Repositories;
+    use tower::ServiceExt;
+
+    fn test_state() -> AppState {
+        AppState {
+            services: Arc::new(ServiceLayer::new(Repositories::memory())),
+            trusted_proxies: Arc::default(),
+            rate_limiter: None,
+            csrf_key: Arc::new(CSRF_KEY.to_vec()),
+        }
+    }
+
+    /// A state with an `admin` account (see `promote_admin`).
+    fn admin_state() -> (AppState, models::User) {
+        let repos = Repositories::memory();
+        let state = AppState {
+            services: Arc::new(ServiceLayer::new(repos.clone())),
+            ..test_state()
+        };
+        let admin = promote_admin(&repos, &state.services);
+        (state, admin)
+    }
+
+    /// Register `admin`, password `hunter2hunter2`, and promote them
+    /// straight in storage, the way an operator makes the first admin.
+    fn promote_admin(repos: &Repositories, services: &ServiceLayer) -> models::User {
+        let admin = services.register_user("admin".into(), "hunter2hunter2").unwrap();
+        repos.users.set_role(admin.id, &models::Role::Admin).unwrap();
+        models::User { role: models::Role::Admin, ..admin }
+    }
+
+    const CSRF_KEY: &[u8] = b"test key";
+
+    fn csrf_token(secret: &str) -> String {
+        auth::derive_csrf_token(CSRF_KEY, secret)
+    }
+
+    /// The address every test request comes from.
+    const CLIENT: ([u8; 4], u16) = ([192, 0, 2, 1], 40000);
+
+    fn poster() -> Poster {
+        Poster::new(SocketAddr::from(CLIENT).ip())
+    }
+
+    /// The router, seeing requests as coming from `CLIENT`.
+    fn app(state: AppState) -> Router {
+        create_router(state).layer(MockConnectInfo(SocketAddr::from(CLIENT)))
+    }
 
     #[test]
     fn router_builds() {
+        let _router = create_router(test_state());
+    }
+
+    #[tokio::test]
+    async fn run_executes_off_the_runtime() {
+        let (state, admin) = admin_state();
+
+        let boards = state
+            .run(move |services| {
+                services.create_board(&admin, "b".into(), "d".into())?;
+                services.list_boards()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(boards.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_thread_is_not_found() {
+        let status = test_state()
+            .run(|services| services.get_thread(Uuid::new_v4()))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn thread_page_plays_audio_and_video() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone());
+        let admin = promote_admin(&repos, &services);
+        let board = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        let mut view = services.thread_view(thread.id).unwrap();
+
+        let file = |name: &str, content_type: &str, thumbnail_key: &str| models::Attachment {
+            id: Uuid::new_v4(),
+            thread_id: thread.id,
+            post_id: None,
+            position: 0,
+            file_name: name.into(),
+            content_type: content_type.into(),
+            size_bytes: 2048,
+            sha256: "ab".repeat(32),
+            width: 640,
+            height: 360,
+            duration_ms: Some(75_000),
+            storage_key: name.into(),
+            thumbnail_key: thumbnail_key.into(),
+            thumbnail_width: 250,
+            thumbnail_height: 141,
+            metadata_stripped: false,
+            created_at: thread.created_at,
+        };
+        view.attachments = vec![
+            file("a.webm", "video/webm", "a.jpg"),
+            file("b.mp4", "video/mp4", ""),
+            file("c.ogg", "audio/ogg", ""),
+        ];
+
+        let html = ThreadTemplate {
+            csrf_token: "t".into(),
+            can_delete: false,
+            can_flag: false,
+            can_view_posters: false,
+            captcha: None,
+            view,
+        }.render().unwrap();
+        assert!(html.contains("<video src=\"/media/src/a.webm\""));
+        assert!(html.contains("poster=\"/media/thumb/a.jpg\""));
+        assert_eq!(html.matches("poster=").count(), 1);
+        assert!(html.contains("<audio src=\"/media/src/c.ogg\""));
+        assert!(html.contains("1:15"));
+        assert!(!html.contains("<img"));
+    }
+
+    /// A urlencoded POST from a browser holding `cookie`, carrying the
+    /// CSRF token for `secret` when one is given.
+    fn form_post(path: &str, cookie: &str, secret: Option<&str>, body: &str) -> Request<Body> {
+        let body = match secret {
+            Some(secret) => format!("{body}&csrf_token={}", csrf_token(secret)),
+            None => body.to_string(),
+        };
+
+        Request::post(path)
+            .header(header::COOKIE, cookie)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    /// A multipart POST uploading `image` as `file`, with the CSRF token
+    /// for `secret`.
+    fn upload_post(path: &str, cookie: &str, secret: &str, reason: &str, image: &[u8]) -> Request<Body> {
+        let mut body = format!(
+            "--X\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{}\r\n\
+             --X\r\nContent-Disposition: form-data; name=\"reason\"\r\n\r\n{reason}\r\n\
+             --X\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\r\n",
+            csrf_token(secret)
+        )
+        .into_bytes();
+        body.extend_from_slice(image);
+        body.extend_from_slice(b"\r\n--X--\r\n");
+
+        Request::post(path)
+            .header(header::COOKIE, cookie)
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn page(app: &Router, path: &str, cookie: &str) -> String {
+        let request = Request::get(path).header(header::COOKIE, cookie).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let html = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8_lossy(&html).into_owned()
+    }
+
+    #[tokio::test]
+    async fn login_sets_a_session_cookie_that_logout_clears() {
+        let state = test_state();
+        state.services.register_user("alice".into(), "hunter2hunter2").unwrap();
+        let app = app(state);
+        let anonymous = "rb_csrf=anon";
+
+        let refused = app
+            .clone()
+            .oneshot(form_post("/login", anonymous, Some("anon"), "username=alice&password=nope"))
+            .await
+            .unwrap();
+        assert_eq!(refused.status(), StatusCode::UNAUTHORIZED);
+        assert!(refused.headers().get(header::SET_COOKIE).is_none());
+
+        let response = app
+            .clone()
+            .oneshot(form_post("/login", anonymous, Some("anon"), "username=alice&password=hunter2hunter2"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        let pair = cookie.split(';').next().unwrap().to_string();
+        let token = pair.trim_start_matches("rb_session=").to_string();
+        let logged_in = format!("{pair}; {anonymous}");
+
+        let html = page(&app, "/login", &logged_in).await;
+        assert!(html.contains("logged in as <strong>alice</strong>"));
+        assert!(html.contains(&csrf_token(&token)));
+
+        // Once logged in, the anonymous token no longer works.
+        let forged = app.clone().oneshot(form_post("/logout", &logged_in, Some("anon"), "")).await.unwrap();
+        assert_eq!(forged.status(), StatusCode::FORBIDDEN);
+
+        let logout = app.clone().oneshot(form_post("/logout", &logged_in, Some(&token), "")).await.unwrap();
+        assert!(logout.headers()[header::SET_COOKIE].to_str().unwrap().contains("Max-Age=0"));
+
+        assert!(!page(&app, "/login", &logged_in).await.contains("logged in as"));
+    }
+
+    #[tokio::test]
+    async fn forged_posts_are_rejected_before_any_change() {
+        let (state, admin) = admin_state();
+        let board = state.services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let app = app(state.clone());
+        let body = format!("board_id={}&title=hello", board.id);
+
+        let html = page(&app, "/boards/b", "rb_csrf=anon").await;
+        assert!(html.contains(&format!("name=\"csrf_token\" value=\"{}\"", csrf_token("anon"))));
+
+        for forged in [
+            form_post("/threads", "", None, &body),
+            form_post("/threads", "rb_csrf=anon", None, &body),
+            form_post("/threads", "rb_csrf=anon", Some("other"), &body),
+            // A token for the right secret, but not signed with the server's key.
+            form_post(
+                "/threads",
+                "rb_csrf=anon",
+                None,
+                &format!("{body}&csrf_token={}", auth::derive_csrf_token(b"", "anon")),
+            ),
+            form_post("/register", "rb_csrf=anon", None, "username=mallory&password=hunter2hunter2"),
+        ] {
+            let response = app.clone().oneshot(forged).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(state.services.list_threads(board.id).unwrap().is_empty());
+        assert!(state.services.log_in("mallory", "hunter2hunter2").is_err());
+
+        let genuine = app
+            .oneshot(form_post("/threads", "rb_csrf=anon", Some("anon"), &body))
+            .await
+            .unwrap();
+        assert_eq!(genuine.status(), StatusCode::SEE_OTHER);
+        assert_eq!(state.services.list_threads(board.id).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_board_staff_may_delete_posts() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let other = services.create_board(&admin, "c".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        let reply = services.create_post(&poster(), thread.id, "spam".into(), "", vec![]).unwrap();
+
+        let account = |name: &str| {
+            let user = services.register_user(name.into(), "hunter2hunter2").unwrap();
+            (user, services.log_in(name, "hunter2hunter2").unwrap())
+        };
+        let (janitor, _) = account("janitor");
+        let (visitor, _) = account("visitor");
+        services.appoint_staff(&admin, board.id, janitor.id, models::BoardRole::Janitor).unwrap();
+        services.appoint_staff(&admin, other.id, visitor.id, models::BoardRole::Moderator).unwrap();
+        // Appointments end earlier sessions; log in again.
+        let janitor_token = services.log_in("janitor", "hunter2hunter2").unwrap();
+        let visitor_token = services.log_in("visitor", "hunter2hunter2").unwrap();
+
+        let app = app(state);
+        let path = format!("/boards/b/posts/{}/delete", reply.post_number);
+        let thread_page = format!("/boards/b/res/{}", thread.op_number);
+        let cookie = |token: &str| format!("rb_session={token}");
+        let delete = |token: &str| form_post(&path, &cookie(token), Some(token), "");
+
+        assert!(!page(&app, &thread_page, &cookie(&visitor_token)).await.contains(&path));
+        assert!(page(&app, &thread_page, &cookie(&janitor_token)).await.contains(&path));
+
+        let anonymous = app.clone().oneshot(form_post(&path, "rb_csrf=anon", Some("anon"), "")).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        let refused = app.clone().oneshot(delete(&visitor_token)).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        assert_eq!(services.list_posts(thread.id).unwrap().len(), 1);
+
+        let deleted = app.oneshot(delete(&janitor_token)).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::SEE_OTHER);
+        assert_eq!(deleted.headers()[header::LOCATION], thread_page.as_str());
+        assert!(services.list_posts(thread.id).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reported_posts_reach_the_queue_and_can_be_deleted_with_a_ban() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        let reply = services.create_post(&poster(), thread.id, "buy now".into(), "", vec![]).unwrap();
+        let token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let staff = format!("rb_session={token}");
+        let app = app(state);
+
+        let thread_page = format!("/boards/b/res/{}", thread.op_number);
+        let path = format!("/boards/b/posts/{}/report", reply.post_number);
+        assert!(page(&app, &thread_page, "rb_csrf=anon").await.contains(&path));
+        let report = |body: &str| form_post(&path, "rb_csrf=anon", Some("anon"), body);
+        assert_eq!(app.clone().oneshot(report("category=bogus")).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let reported = app.clone().oneshot(report("category=spam&comment=advert")).await.unwrap();
+        assert_eq!(reported.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            reported.headers()[header::LOCATION],
+            format!("{thread_page}#p{}", reply.post_number).as_str()
+        );
+
+        let queue = page(&app, "/mod/reports", &staff).await;
+        assert!(queue.contains("1 report") && queue.contains("Spam or advertising: advert"));
+        assert!(queue.contains("buy now") && queue.contains("Delete and ban"));
+
+        let action = form_post(
+            &format!("/mod/reports/{}", reply.id),
+            &staff,
+            Some(&token),
+            "action=ban&reason=spam&duration_hours=24",
+        );
+        assert_eq!(app.clone().oneshot(action).await.unwrap().status(), StatusCode::SEE_OTHER);
+        assert!(services.list_posts(thread.id).unwrap().is_empty());
+        assert!(services.create_post(&poster(), thread.id, "again".into(), "", vec![]).is_err());
+
+        let queue = page(&app, "/mod/reports", &staff).await;
+        assert!(queue.contains("No open reports") && queue.contains("banned") && queue.contains("by admin"));
+        let anonymous = Request::get("/mod/reports").body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(anonymous).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn moderation_is_logged_for_staff_and_on_public_board_logs() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        let reply = services.create_post(&poster(), thread.id, "spam".into(), "", vec![]).unwrap();
+        let token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let staff = format!("rb_session={token}");
+        services.register_user("visitor".into(), "hunter2hunter2").unwrap();
+        let visitor = format!("rb_session={}", services.log_in("visitor", "hunter2hunter2").unwrap());
+        let app = app(state);
+
+        let path = format!("/boards/b/posts/{}/delete", reply.post_number);
+        let deleted = app.clone().oneshot(form_post(&path, &staff, Some(&token), "reason=advertising")).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::SEE_OTHER);
+        let ban = "ip_range=198.51.100.7&board=b&reason=flooding&staff_note=bot";
+        let banned = app.clone().oneshot(form_post("/mod/bans", &staff, Some(&token), ban)).await.unwrap();
+        assert_eq!(banned.status(), StatusCode::SEE_OTHER);
+
+        let log = page(&app, "/mod/log", &staff).await;
+        assert!(log.contains("Deleted post") && log.contains("advertising") && log.contains("198.51.100.7"));
+        assert!(log.contains("Changed role"));
+        let filtered = page(&app, "/mod/log?board=b&actor=admin&action=delete_post", &staff).await;
+        assert!(filtered.contains("advertising") && !filtered.contains("198.51.100.7"));
+        let bogus = Request::get("/mod/log?action=bogus").header(header::COOKIE, &staff).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(bogus).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let refused = Request::get("/mod/log").header(header::COOKIE, &visitor).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let public = || Request::get("/boards/b/log").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(public()).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let settings = page(&app, "/boards/b/settings", &staff).await;
+        assert!(settings.contains(r#"name="public_mod_log" value="true">"#));
+        let published = form_post("/boards/b/settings", &staff, Some(&token), "captcha_mode=off&public_mod_log=true");
+        assert_eq!(app.clone().oneshot(published).await.unwrap().status(), StatusCode::SEE_OTHER);
+        assert!(page(&app, "/boards/b/settings", &staff).await.contains(r#"name="public_mod_log" value="true" checked>"#));
+        assert!(page(&app, "/boards/b", "rb_csrf=anon").await.contains("/boards/b/log"));
+        let public = page(&app, "/boards/b/log", "rb_csrf=anon").await;
+        assert!(public.contains("Deleted post") && public.contains("advertising") && public.contains("flooding"));
+        assert!(!public.contains("198.51.100.7") && !public.contains("admin") && !public.contains("bot"));
+
+        let withdrawn = form_post("/boards/b/settings", &staff, Some(&token), "captcha_mode=off");
+        assert_eq!(app.clone().oneshot(withdrawn).await.unwrap().status(), StatusCode::SEE_OTHER);
+        let gone = Request::get("/boards/b/log").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(gone).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn staff_set_thread_flags_from_the_thread_page() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let rules = services.create_thread(&poster(), board.id, "rules".into(), vec![]).unwrap();
+        services.create_thread(&poster(), board.id, "chat".into(), vec![]).unwrap();
+        let token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let staff = format!("rb_session={token}");
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, board.id, janitor.id, models::BoardRole::Janitor).unwrap();
+        let janitor_token = services.log_in("janitor", "hunter2hunter2").unwrap();
+        let app = app(state);
+
+        let path = "/boards/b/res/1/flags";
+        assert!(page(&app, "/boards/b/res/1", &staff).await.contains(path));
+        assert!(!page(&app, "/boards/b/res/1", &format!("rb_session={janitor_token}")).await.contains(path));
+        let cookie = format!("rb_session={janitor_token}");
+        let refused = app.clone().oneshot(form_post(path, &cookie, Some(&janitor_token), "locked=on")).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        let bogus = app.clone().oneshot(form_post(path, &staff, Some(&token), "sticky=first")).await.unwrap();
+        assert_eq!(bogus.status(), StatusCode::BAD_REQUEST);
+
+        let flagged = app.clone().oneshot(form_post(path, &staff, Some(&token), "sticky=0&locked=on&reason=pinned")).await.unwrap();
+        assert_eq!(flagged.status(), StatusCode::SEE_OTHER);
+        let thread = services.get_thread(rules.id).unwrap();
+        assert_eq!((thread.flags.sticky, thread.flags.locked, thread.flags.cyclical), (Some(0), true, false));
+
+        let index = page(&app, "/boards/b", "rb_csrf=anon").await;
+        assert!(index.find("rules").unwrap() < index.find("chat").unwrap());
+        assert!(index.contains("title=\"Sticky\"") && index.contains("title=\"Locked\""));
+        let locked = page(&app, "/boards/b/res/1", "rb_csrf=anon").await;
+        assert!(locked.contains("This thread is locked") && !locked.contains("Post Reply"));
+    }
+
+    #[tokio::test]
+    async fn saged_replies_leave_the_board_order_alone() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let older = services.create_thread(&poster(), board.id, "older".into(), vec![]).unwrap();
+        services.create_thread(&poster(), board.id, "newer".into(), vec![]).unwrap();
+        let app = app(state);
+        let order = |html: String| html.find("<h3>older").unwrap() < html.find("<h3>newer").unwrap();
+
+        assert!(page(&app, "/boards/b/res/1", "rb_csrf=anon").await.contains("name=\"options\""));
+        let saged = format!("thread_id={}&content=hi&options=sage", older.id);
+        let reply = app.clone().oneshot(form_post("/posts", "rb_csrf=anon", Some("anon"), &saged)).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::SEE_OTHER);
+        assert!(!order(page(&app, "/boards/b", "rb_csrf=anon").await));
+
+        let bumping = format!("thread_id={}&content=hi", older.id);
+        let reply = app.clone().oneshot(form_post("/posts", "rb_csrf=anon", Some("anon"), &bumping)).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::SEE_OTHER);
+        assert!(order(page(&app, "/boards/b", "rb_csrf=anon").await));
+    }
+
+    #[tokio::test]
+    async fn pruned_threads_are_read_only_in_the_archive() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let policy = models::PrunePolicy { max_threads: 1, mode: PruneMode::Archive };
+        services.set_prune_policy(&admin, board.id, policy).unwrap();
+        let old = services.create_thread(&poster(), board.id, "old".into(), vec![]).unwrap();
+        services.create_thread(&poster(), board.id, "new".into(), vec![]).unwrap();
+        let app = app(state);
+
+        let listing = page(&app, "/boards/b", "rb_csrf=anon").await;
+        assert!(listing.contains("/boards/b/archive") && !listing.contains("<h3>old"));
+        assert!(page(&app, "/boards/b/archive", "rb_csrf=anon").await.contains("<h3>old"));
+        let archived = page(&app, "/boards/b/res/1", "rb_csrf=anon").await;
+        assert!(archived.contains("This thread is archived") && !archived.contains("Post Reply"));
+
+        let reply = format!("thread_id={}&content=hi", old.id);
+        let reply = app.clone().oneshot(form_post("/posts", "rb_csrf=anon", Some("anon"), &reply)).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn banned_posters_see_the_ban_and_can_appeal_once() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let app = app(state);
+        let anonymous = "rb_csrf=anon";
+
+        let token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let staff = format!("rb_session={token}");
+        for hours in ["0", "18446744073709551615", "999999999"] {
+            let body = format!("ip_range=192.0.2.0%2F24&board=&reason=flooding&duration_hours={hours}");
+            let refused = app.clone().oneshot(form_post("/mod/bans", &staff, Some(&token), &body)).await.unwrap();
+            assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(services.list_bans(&admin).unwrap().is_empty());
+        let ban = form_post(
+            "/mod/bans",
+            &staff,
+            Some(&token),
+            "ip_range=192.0.2.0%2F24&board=&reason=flooding&staff_note=bot&duration_hours=24",
+        );
+        assert_eq!(app.clone().oneshot(ban).await.unwrap().status(), StatusCode::SEE_OTHER);
+        let staff_page = page(&app, "/mod/bans", &staff).await;
+        assert!(staff_page.contains("192.0.2.0/24") && staff_page.contains("bot"));
+
+        let body = format!("board_id={}&title=hello", board.id);
+        let refused = app.clone().oneshot(form_post("/threads", anonymous, Some("anon"), &body)).await.unwrap();
+        assert_eq!(refused.headers()[header::LOCATION], "/banned");
+        assert!(services.list_threads(board.id).unwrap().is_empty());
+
+        let notice = page(&app, "/banned", anonymous).await;
+        assert!(notice.contains("You are banned") && notice.contains("flooding"));
+        assert!(!notice.contains("bot"));
+
+        let ban_id = services.list_bans(&admin).unwrap()[0].id;
+        let appeal = |message: &str| {
+            form_post(&format!("/banned/{ban_id}/appeal"), anonymous, Some("anon"), message)
+        };
+        assert_eq!(app.clone().oneshot(appeal("message=")).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app.clone().oneshot(appeal("message=sorry")).await.unwrap().status(), StatusCode::SEE_OTHER);
+        assert_eq!(app.clone().oneshot(appeal("message=again")).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert!(page(&app, "/banned", anonymous).await.contains("Your appeal is pending"));
+
+        let visitor = services.register_user("visitor".into(), "hunter2hunter2").unwrap();
+        let visitor_token = services.log_in(&visitor.username, "hunter2hunter2").unwrap();
+        let visitor_page = Request::get("/mod/bans")
+            .header(header::COOKIE, format!("rb_session={visitor_token}"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(visitor_page).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let appeal_id = services.list_appeals(&admin).unwrap()[0].appeal.id;
+        let accept = form_post(&format!("/mod/appeals/{appeal_id}"), &staff, Some(&token), "decision=accept");
+        assert_eq!(app.clone().oneshot(accept).await.unwrap().status(), StatusCode::SEE_OTHER);
+        assert!(page(&app, "/banned", anonymous).await.contains("You are not banned"));
+
+        let accepted = app.oneshot(form_post("/threads", anonymous, Some("anon"), &body)).await.unwrap();
+        assert_eq!(accepted.status(), StatusCode::SEE_OTHER);
+        assert_eq!(services.list_threads(board.id).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn image_bans_are_managed_from_their_own_page() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let staff = format!("rb_session={token}");
+        services.register_user("visitor".into(), "hunter2hunter2").unwrap();
+        let visitor_token = services.log_in("visitor", "hunter2hunter2").unwrap();
+        let visitor = format!("rb_session={visitor_token}");
+        let app = app(state);
+        let image: &[u8] = include_bytes!("../../media/testdata/base.png");
+
+        let refused = Request::get("/mod/image-bans").header(header::COOKIE, &visitor).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let refused = upload_post("/mod/image-bans", &visitor, &visitor_token, "gore", image);
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert!(services.list_image_bans(&admin).unwrap().is_empty());
+
+        let not_an_image = upload_post("/mod/image-bans", &staff, &token, "gore", b"hello");
+        assert_eq!(app.clone().oneshot(not_an_image).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        let banned = app.clone().oneshot(upload_post("/mod/image-bans", &staff, &token, "gore", image)).await.unwrap();
+        assert_eq!(banned.status(), StatusCode::SEE_OTHER);
+        let ban = services.list_image_bans(&admin).unwrap().remove(0);
+        let lift = format!("/mod/image-bans/{}/lift", ban.id);
+        let html = page(&app, "/mod/image-bans", &staff).await;
+        assert!(html.contains("Reason: gore") && html.contains(&lift));
+
+        let refused = app.clone().oneshot(form_post(&lift, &visitor, Some(&visitor_token), "")).await.unwrap();
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        let lifted = app.clone().oneshot(form_post(&lift, &staff, Some(&token), "")).await.unwrap();
+        assert_eq!(lifted.status(), StatusCode::SEE_OTHER);
+        assert!(services.list_image_bans(&admin).unwrap().is_empty());
+
+        let log = page(&app, "/mod/log", &staff).await;
+        assert!(log.contains("Banned image") && log.contains("Lifted image ban"));
+    }
+
+    #[tokio::test]
+    async fn board_owners_change_settings_on_the_settings_page() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let owner = services.register_user("owner".into(), "hunter2hunter2").unwrap();
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, board.id, owner.id, models::BoardRole::BoardOwner).unwrap();
+        services.appoint_staff(&admin, board.id, janitor.id, models::BoardRole::Janitor).unwrap();
+        let owner_token = services.log_in("owner", "hunter2hunter2").unwrap();
+        let owner_cookie = format!("rb_session={owner_token}");
+        let janitor_token = services.log_in("janitor", "hunter2hunter2").unwrap();
+        let janitor_cookie = format!("rb_session={janitor_token}");
+        let app = app(state);
+        let path = "/boards/b/settings";
+        let settings = "captcha_mode=threads&thread_cooldown=300&reply_cooldown=0&repost_cooldown=\
+                        &bump_limit=500&max_threads=20&prune_mode=delete&duplicate_policy=board\
+                        &av_max_duration=60&av_codecs=vp9%2C+opus";
+
+        let refused = Request::get(path).header(header::COOKIE, &janitor_cookie).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let refused = form_post(path, &janitor_cookie, Some(&janitor_token), settings);
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let anonymous = form_post(path, "rb_csrf=anon", Some("anon"), settings);
+        assert_eq!(app.clone().oneshot(anonymous).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let html = page(&app, path, &owner_cookie).await;
+        assert!(html.contains(r#"<option value="off" selected>"#));
+        for bogus in [
+            "captcha_mode=sometimes",
+            "captcha_mode=off&reply_cooldown=-1",
+            "captcha_mode=off&thread_cooldown=soon",
+            "captcha_mode=off&bump_limit=many",
+            "captcha_mode=off&prune_mode=shred",
+            "captcha_mode=off&max_threads=0",
+            "captcha_mode=off&duplicate_policy=never",
+            "captcha_mode=off&av_max_duration=1m",
+            "captcha_mode=off&av_max_duration=0",
+            "captcha_mode=off&av_codecs=vp9,theora",
+        ] {
+            let bogus = form_post(path, &owner_cookie, Some(&owner_token), bogus);
+            assert_eq!(app.clone().oneshot(bogus).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        }
+        let saved = app.clone().oneshot(form_post(path, &owner_cookie, Some(&owner_token), settings)).await.unwrap();
+        assert_eq!(saved.status(), StatusCode::SEE_OTHER);
+        let saved = services.get_board(board.id).unwrap();
+        assert_eq!(saved.captcha_mode, models::CaptchaMode::Threads);
+        assert_eq!(saved.cooldowns, Cooldowns { thread_secs: Some(300), reply_secs: Some(0), repost_secs: None });
+        assert_eq!(saved.bump_limit, 500);
+        assert_eq!(saved.prune_policy, PrunePolicy { max_threads: 20, mode: PruneMode::Delete });
+        assert_eq!(saved.duplicate_policy, DuplicatePolicy::Board);
+        assert_eq!(saved.av_policy, AvPolicy { max_duration_secs: 60, codecs: vec!["vp9".into(), "opus".into()] });
+        let html = page(&app, path, &owner_cookie).await;
+        assert!(html.contains(r#"<option value="threads" selected>"#) && html.contains(r#"value="300""#));
+        assert!(html.contains(r#"<option value="delete" selected>"#) && html.contains(r#"value="500""#));
+        assert!(html.contains(r#"<option value="board" selected>"#) && html.contains(r#"value="vp9,opus""#));
+
+        // Saving again without changes logs nothing more.
+        app.clone().oneshot(form_post(path, &owner_cookie, Some(&owner_token), settings)).await.unwrap();
+        let edits = ModLogQuery { action: Some(ModActionKind::EditBoard), ..ModLogQuery::default() };
+        let edits = services.mod_log(&admin, edits).unwrap();
+        assert_eq!(edits.len(), 6);
+        assert!(edits.iter().all(|edit| edit.action.actor_name == "owner"));
+    }
+
+    #[tokio::test]
+    async fn board_owners_appoint_and_dismiss_staff_on_the_staff_page() {
+        let (state, admin) = admin_state();
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let owner = services.register_user("owner".into(), "hunter2hunter2").unwrap();
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        let helper = services.register_user("helper".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, board.id, owner.id, BoardRole::BoardOwner).unwrap();
+        services.appoint_staff(&admin, board.id, janitor.id, BoardRole::Janitor).unwrap();
+        let owner_token = services.log_in("owner", "hunter2hunter2").unwrap();
+        let owner_cookie = format!("rb_session={owner_token}");
+        let janitor_token = services.log_in("janitor", "hunter2hunter2").unwrap();
+        let janitor_cookie = format!("rb_session={janitor_token}");
+        let admin_token = services.log_in("admin", "hunter2hunter2").unwrap();
+        let admin_cookie = format!("rb_session={admin_token}");
+        let app = app(state);
+        let path = "/boards/b/staff";
+        let appoint = "username=helper&role=moderator";
+
+        let refused = Request::get(path).header(header::COOKIE, &janitor_cookie).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let refused = form_post(path, &janitor_cookie, Some(&janitor_token), appoint);
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let anonymous = form_post(path, "rb_csrf=anon", Some("anon"), appoint);
+        assert_eq!(app.clone().oneshot(anonymous).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        for (body, status) in [
+            ("username=helper&role=boss", StatusCode::BAD_REQUEST),
+            ("username=nobody&role=janitor", StatusCode::NOT_FOUND),
+            ("username=helper&role=board_owner", StatusCode::FORBIDDEN),
+            (appoint, StatusCode::SEE_OTHER),
+        ] {
+            let response = app.clone().oneshot(form_post(path, &owner_cookie, Some(&owner_token), body)).await.unwrap();
+            assert_eq!(response.status(), status, "{body}");
+        }
+        let staff = services.list_staff(board.id).unwrap();
+        assert!(staff.iter().any(|s| s.user_id == helper.id && s.role == BoardRole::Moderator));
+        let html = page(&app, path, &owner_cookie).await;
+        assert!(html.contains("<strong>helper</strong>, moderator"));
+
+        let dismiss = format!("/boards/b/staff/{}/dismiss", helper.id);
+        let dismissed = app.clone().oneshot(form_post(&dismiss, &owner_cookie, Some(&owner_token), "")).await.unwrap();
+        assert_eq!(dismissed.status(), StatusCode::SEE_OTHER);
+        assert!(services.list_staff(board.id).unwrap().iter().all(|s| s.user_id != helper.id));
+
+        let refused = Request::get("/mod/users").header(header::COOKIE, &owner_cookie).body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let promote = "username=helper&role=global_moderator";
+        let refused = form_post("/mod/users", &owner_cookie, Some(&owner_token), promote);
+        assert_eq!(app.clone().oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert!(page(&app, "/mod/users", &admin_cookie).await.contains("Set role"));
+        let promoted = app.clone().oneshot(form_post("/mod/users", &admin_cookie, Some(&admin_token), promote)).await.unwrap();
+        assert_eq!(promoted.status(), StatusCode::SEE_OTHER);
+        assert_eq!(services.find_user("helper").unwrap().role, models::Role::GlobalModerator);
+    }
+
+    #[tokio::test]
+    async fn staff_trace_posts_to_the_forwarded_client() {
+        let (state, admin) = admin_state();
         let state = AppState {
-            services: Arc::new(ServiceLayer),
-            db: Arc::new(rusqlite::Connection::open_in_memory().unwrap()),
+            trusted_proxies: Arc::new(vec!["192.0.2.0/24".parse().unwrap()]),
+            ..state
         };
+        let services = state.services.clone();
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let admin_cookie = format!("rb_session={}", services.log_in("admin", "hunter2hunter2").unwrap());
+        services.register_user("visitor".into(), "hunter2hunter2").unwrap();
+        let visitor_cookie = format!("rb_session={}", services.log_in("visitor", "hunter2hunter2").unwrap());
+        let app = app(state);
+
+        let mut request = form_post("/threads", "rb_csrf=anon", Some("anon"), &format!("board_id={}&title=hello", board.id));
+        let headers = request.headers_mut();
+        headers.insert("x-forwarded-for", "203.0.113.50, 198.51.100.7".parse().unwrap());
+        headers.insert(header::USER_AGENT, "curl/8".parse().unwrap());
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::SEE_OTHER);
+
+        let thread = services.list_threads(board.id).unwrap().remove(0);
+        let poster = thread.poster.clone().unwrap();
+        assert_eq!(poster.ip, Some("198.51.100.7".parse().unwrap()));
+        assert!(poster.user_agent_hash.is_some());
 
-        let _router = create_router(state);
+        let lookup = format!("/mod/posters/{}", poster.ip_hash);
+        let thread_page = format!("/boards/b/res/{}", thread.op_number);
+        assert!(page(&app, &thread_page, &admin_cookie).await.contains(&lookup));
+        assert!(!page(&app, &thread_page, &visitor_cookie).await.contains(&lookup));
+
+        let listing = page(&app, &lookup, &admin_cookie).await;
+        assert!(listing.contains("198.51.100.7") && listing.contains("hello"));
+        let refused = Request::get(&lookup).header(header::COOKIE, &visitor_cookie).body(Body::empty()).unwrap();
+        assert_eq!(app.oneshot(refused).await.unwrap().status(), StatusCode::FORBIDDEN);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn hasty_clients_are_told_how_long_to_wait() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone()).with_rate_limits(services::RateLimits {
+            thread_cooldown: Duration::from_secs(90),
+            ..Default::default()
+        });
+        let state = AppState {
+            services: Arc::new(services),
+            rate_limiter: Some(Arc::new(RateLimiter::new(3, 0.001))),
+            ..test_state()
+        };
+        let admin = promote_admin(&repos, &state.services);
+        let board = state.services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let app = app(state);
+        let body = format!("board_id={}&title=hello", board.id);
+
+        let first = app.clone().oneshot(form_post("/threads", "rb_csrf=anon", Some("anon"), &body)).await.unwrap();
+        assert_eq!(first.status(), StatusCode::SEE_OTHER);
+
+        let second = app.clone().oneshot(form_post("/threads", "rb_csrf=anon", Some("anon"), &body)).await.unwrap();
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+        let wait: u64 = second.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!((89..=90).contains(&wait));
+        let page = String::from_utf8(axum::body::to_bytes(second.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(page.contains(&format!("Please wait {wait} seconds before starting another thread.")));
+
+        let boards = || Request::get("/boards").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(boards()).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(app.oneshot(boards()).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn boards_can_require_a_captcha_before_posting() {
+        let repos = Repositories::memory();
+        let state = AppState {
+            services: Arc::new(ServiceLayer::new(repos.clone())),
+            ..test_state()
+        };
+        let services = state.services.clone();
+        let admin = promote_admin(&repos, &services);
+        let board = services.create_board(&admin, "b".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        services.set_captcha_mode(&admin, board.id, models::CaptchaMode::Threads).unwrap();
+        let app = app(state);
+        let anonymous = "rb_csrf=anon";
+        let submit = |path: &str, body: String| app.clone().oneshot(form_post(path, anonymous, Some("anon"), &body));
+
+        let thread_page = format!("/boards/b/res/{}", thread.op_number);
+        assert!(!page(&app, &thread_page, anonymous).await.contains("captcha_id"));
+        let reply = submit("/posts", format!("thread_id={}&content=hi", thread.id)).await.unwrap();
+        assert_eq!(reply.status(), StatusCode::SEE_OTHER);
+
+        let challenge = || async {
+            let html = page(&app, "/boards/b", anonymous).await;
+            let id = html.split("name=\"captcha_id\" value=\"").nth(1).unwrap()[..36].to_string();
+            id.parse::<Uuid>().unwrap()
+        };
+        let id = challenge().await;
+        let image = app
+            .clone()
+            .oneshot(Request::get(format!("/captcha/{id}")).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(image.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(image.headers()[header::CACHE_CONTROL], "no-store");
+
+        let thread_form = |id: Uuid, answer: &str| {
+            format!("board_id={}&title=hello&captcha_id={id}&captcha_answer={answer}", board.id)
+        };
+        let missing = submit("/threads", format!("board_id={}&title=hello", board.id)).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+        let answer = repos.captchas.get(id).unwrap().unwrap().answer;
+        let wrong = submit("/threads", thread_form(id, "WRONG")).await.unwrap();
+        assert_eq!(wrong.status(), StatusCode::BAD_REQUEST);
+        let reused = submit("/threads", thread_form(id, &answer)).await.unwrap();
+        assert_eq!(reused.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(services.list_threads(board.id).unwrap().len(), 1);
+
+        let id = challenge().await;
+        let answer = repos.captchas.get(id).unwrap().unwrap().answer.to_lowercase();
+        let solved = submit("/threads", thread_form(id, &answer)).await.unwrap();
+        assert_eq!(solved.status(), StatusCode::SEE_OTHER);
+        assert_eq!(services.list_threads(board.id).unwrap().len(), 2);
+
+        services.set_captcha_mode(&admin, board.id, models::CaptchaMode::All).unwrap();
+        assert!(page(&app, &thread_page, anonymous).await.contains("captcha_id"));
+        let unsolved = submit("/posts", format!("thread_id={}&content=hi", thread.id)).await.unwrap();
+        assert_eq!(unsolved.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(services.list_posts(thread.id).unwrap().len(), 1);
+    }
+}
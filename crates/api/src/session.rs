@@ -0,0 +1,122 @@
+//! Session Cookies
+//!
+//! Developer Notes:
+//! - A login is carried in the `rb_session` cookie, holding the session
+//!   token from `services::ServiceLayer::log_in`.
+//! - The cookie is `HttpOnly`, `Secure` and `SameSite=Lax`; browsers
+//!   still send `Secure` cookies to `http://localhost` during development.
+//! - The cookie's `Max-Age` is the session's absolute lifetime; idle
+//!   expiry is enforced by services alone.
+//! - `CurrentUser` resolves the cookie to a `models::User`. Handlers that
+//!   need a login take `CurrentUser` (401 without one); handlers that
+//!   only adapt to it take `Option<CurrentUser>`.
+//!
+//! End Notes:
+//! Token checks belong to services; this module only moves the token
+//! between requests and responses.
+
+use std::time::Duration;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::COOKIE, request::Parts, HeaderMap, StatusCode},
+};
+
+use models::User;
+use crate::routes::AppState;
+
+/// Name of the session cookie.
+pub const SESSION_COOKIE: &str = "rb_session";
+
+/// `Set-Cookie` value that stores `token` in the browser for `max_age`.
+pub fn session_cookie(token: &str, max_age: Duration) -> String {
+    format!(
+        "{SESSION_COOKIE}={token}; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        max_age.as_secs()
+    )
+}
+
+/// `Set-Cookie` value that removes the session cookie.
+pub fn clear_session_cookie() -> String {
+    format!("{SESSION_COOKIE}=; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age=0")
+}
+
+/// The value of cookie `name` sent with a request, if set and not empty.
+pub fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(cookie, _)| *cookie == name)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The session token sent with a request, if any.
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    cookie_value(headers, SESSION_COOKIE)
+}
+
+/// The logged-in user making the request.
+#[derive(Debug, Clone)]
+pub struct CurrentUser(pub User);
+
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = session_token(&parts.headers).ok_or(StatusCode::UNAUTHORIZED)?;
+
+        state
+            .run(move |services| services.session_user(&token))
+            .await?
+            .map(CurrentUser)
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn finds_the_token_among_other_cookies() {
+        let token = auth::generate_session_token();
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(
+            COOKIE,
+            HeaderValue::from_str(&format!("a=1; {SESSION_COOKIE}={token}; b=2")).unwrap(),
+        );
+
+        assert_eq!(session_token(&headers), Some(token));
+        assert_eq!(session_token(&HeaderMap::new()), None);
+
+        let mut cleared = HeaderMap::new();
+        cleared.insert(COOKIE, HeaderValue::from_static("rb_session="));
+        assert_eq!(session_token(&cleared), None);
+    }
+
+    #[test]
+    fn cookies_are_locked_down() {
+        let cookie = session_cookie("abc", Duration::from_secs(3600));
+        assert!(cookie.contains("Max-Age=3600"));
+        for attribute in ["HttpOnly", "Secure", "SameSite=Lax", "Path=/"] {
+            assert!(cookie.contains(attribute));
+            assert!(clear_session_cookie().contains(attribute));
+        }
+        assert!(clear_session_cookie().contains("Max-Age=0"));
+    }
+}
@@ -1,5 +1,10 @@
 use askama::Template;
-use models::{Board, Thread, Post};
+use models::{Ban, BannedImageHash, BannedImageMatch, Board, Thread};
+use services::views::{
+    AppealView, BanNotice, HandledReportView, ModActionView, PosterPostView, PublicModActionView,
+    ReportedPostView, StaffView, ThreadView,
+};
+use uuid::Uuid;
 
 #[derive(Template)]
 #[template(path = "index.html")]
@@ -14,20 +19,159 @@ pub struct BoardsTemplate {
 #[derive(Template)]
 #[template(path = "board.html")]
 pub struct BoardTemplate {
+    pub csrf_token: String,
     pub board_id: String,
     pub board_name: String,
     pub threads: Vec<Thread>,
+    /// Challenge for the new thread form, when the board wants one.
+    pub captcha: Option<Uuid>,
+    /// Link to the board's public moderation log.
+    pub public_mod_log: bool,
+    /// Link to the board's archive.
+    pub archive: bool,
 }
 
 #[derive(Template)]
 #[template(path = "thread.html")]
 pub struct ThreadTemplate {
-    pub thread: Thread,
-    pub posts: Vec<Post>,
+    pub csrf_token: String,
+    /// Show staff controls for deleting replies.
+    pub can_delete: bool,
+    /// Show staff controls for the thread's flags.
+    pub can_flag: bool,
+    /// Show staff links to everything else from each poster.
+    pub can_view_posters: bool,
+    /// Challenge for the reply form, when the board wants one.
+    pub captcha: Option<Uuid>,
+    pub view: ThreadView,
+}
+
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginTemplate {
+    pub csrf_token: String,
+    /// Set when already logged in.
+    pub username: Option<String>,
+    pub error: Option<String>,
+    pub registration_open: bool,
+}
+
+#[derive(Template)]
+#[template(path = "register.html")]
+pub struct RegisterTemplate {
+    pub csrf_token: String,
+    pub error: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "catalog.html")]
 pub struct CatalogTemplate {
     pub threads: Vec<Thread>,
-}
\ No newline at end of file
+}
+
+#[derive(Template)]
+#[template(path = "banned.html")]
+pub struct BannedTemplate {
+    pub csrf_token: String,
+    pub notices: Vec<BanNotice>,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "mod_bans.html")]
+pub struct ModBansTemplate {
+    pub csrf_token: String,
+    /// Boards a new ban can be scoped to.
+    pub boards: Vec<Board>,
+    /// Active bans with the name of the board each applies to, or
+    /// `None` for every board.
+    pub bans: Vec<(Ban, Option<String>)>,
+    pub appeals: Vec<AppealView>,
+}
+
+#[derive(Template)]
+#[template(path = "board_settings.html")]
+pub struct BoardSettingsTemplate {
+    pub csrf_token: String,
+    pub board: Board,
+}
+
+#[derive(Template)]
+#[template(path = "board_staff.html")]
+pub struct BoardStaffTemplate {
+    pub csrf_token: String,
+    pub board: Board,
+    pub staff: Vec<StaffView>,
+}
+
+#[derive(Template)]
+#[template(path = "mod_users.html")]
+pub struct ModUsersTemplate {
+    pub csrf_token: String,
+}
+
+#[derive(Template)]
+#[template(path = "mod_image_bans.html")]
+pub struct ModImageBansTemplate {
+    pub csrf_token: String,
+    /// Image bans in force.
+    pub bans: Vec<BannedImageHash>,
+    /// Recently refused uploads with the name of the board each was
+    /// posted to.
+    pub matches: Vec<(BannedImageMatch, String)>,
+}
+
+#[derive(Template)]
+#[template(path = "mod_poster.html")]
+pub struct ModPosterTemplate {
+    pub ip_hash: String,
+    pub posts: Vec<PosterPostView>,
+}
+
+#[derive(Template)]
+#[template(path = "slow_down.html")]
+pub struct SlowDownTemplate {
+    pub message: String,
+}
+
+#[derive(Template)]
+#[template(path = "captcha_failed.html")]
+pub struct CaptchaFailedTemplate;
+
+#[derive(Template)]
+#[template(path = "mod_reports.html")]
+pub struct ModReportsTemplate {
+    pub csrf_token: String,
+    /// Open reports grouped by post, most reported first.
+    pub posts: Vec<ReportedPostView>,
+    pub handled: Vec<HandledReportView>,
+}
+
+#[derive(Template)]
+#[template(path = "mod_log.html")]
+pub struct ModLogTemplate {
+    /// Boards the log can be filtered by.
+    pub boards: Vec<Board>,
+    /// Current filters, empty when unset.
+    pub board: String,
+    pub actor: String,
+    pub action: String,
+    pub entries: Vec<ModActionView>,
+    /// Query string of the next, older page, when there may be one.
+    pub older: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "board_log.html")]
+pub struct BoardLogTemplate {
+    pub board_name: String,
+    pub entries: Vec<PublicModActionView>,
+}
+
+#[derive(Template)]
+#[template(path = "board_archive.html")]
+pub struct BoardArchiveTemplate {
+    pub board_name: String,
+    /// Most recently archived first.
+    pub threads: Vec<Thread>,
+}
@@ -0,0 +1,144 @@
+//! Post Form Extraction
+//!
+//! Developer Notes:
+//! - `PostForm` accepts both `application/x-www-form-urlencoded` and
+//!   `multipart/form-data`, so forms without a file input keep working.
+//! - Multipart parts with a file name become `services::Upload`s; empty
+//!   file parts (a file input left blank) are dropped.
+//! - Request size is capped by the router's `DefaultBodyLimit`.
+//!
+//! End Notes:
+//! No validation of file contents here; that belongs to services.
+
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Multipart, Request},
+    http::{header::CONTENT_TYPE, StatusCode},
+    Form,
+};
+
+use services::Upload;
+
+/// Text fields and files of a submitted form.
+#[derive(Debug, Default)]
+pub struct PostForm {
+    pub fields: HashMap<String, String>,
+    pub files: Vec<Upload>,
+}
+
+impl PostForm {
+    /// A required text field, or `400 Bad Request`.
+    pub fn field(&self, name: &str) -> Result<&str, StatusCode> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .ok_or(StatusCode::BAD_REQUEST)
+    }
+
+    /// Take a required text field out of the form, or `400 Bad Request`.
+    pub fn take(&mut self, name: &str) -> Result<String, StatusCode> {
+        self.fields.remove(name).ok_or(StatusCode::BAD_REQUEST)
+    }
+}
+
+fn is_multipart(req: &Request) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"))
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for PostForm
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_multipart(&req) {
+            let Form(fields) = Form::<HashMap<String, String>>::from_request(req, state)
+                .await
+                .map_err(|rejection| rejection.status())?;
+            return Ok(Self { fields, files: Vec::new() });
+        }
+
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|rejection| rejection.status())?;
+
+        let mut form = Self::default();
+        while let Some(field) = multipart.next_field().await.map_err(|e| e.status())? {
+            let name = field.name().unwrap_or_default().to_string();
+
+            match field.file_name().map(str::to_string) {
+                Some(file_name) => {
+                    let data = field.bytes().await.map_err(|e| e.status())?;
+                    if !data.is_empty() {
+                        form.files.push(Upload { file_name, data: data.to_vec() });
+                    }
+                }
+                None => {
+                    let value = field.text().await.map_err(|e| e.status())?;
+                    form.fields.insert(name, value);
+                }
+            }
+        }
+
+        Ok(form)
+    }
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request(content_type: &str, body: &'static str) -> Request {
+        Request::builder()
+            .method("POST")
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_urlencoded_forms() {
+        let req = request("application/x-www-form-urlencoded", "thread_id=abc&content=hi+there");
+        let form = PostForm::from_request(req, &()).await.unwrap();
+
+        assert_eq!(form.field("content"), Ok("hi there"));
+        assert!(form.files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reads_multipart_fields_and_files() {
+        let body = "--X\r\n\
+            Content-Disposition: form-data; name=\"content\"\r\n\r\n\
+            hello\r\n\
+            --X\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"cat.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\
+            PNGDATA\r\n\
+            --X\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n\
+            \r\n\
+            --X--\r\n";
+        let form = PostForm::from_request(request("multipart/form-data; boundary=X", body), &())
+            .await
+            .unwrap();
+
+        assert_eq!(form.field("content"), Ok("hello"));
+        assert_eq!(form.field("thread_id"), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(form.files.len(), 1);
+        assert_eq!(form.files[0].file_name, "cat.png");
+        assert_eq!(form.files[0].data, b"PNGDATA");
+    }
+}
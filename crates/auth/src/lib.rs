@@ -2,7 +2,21 @@
 //!
 //! Developer Notes:
 //! - Provides password hashing and verification.
-//! - Generates secure session tokens.
+//! - Generates secure session tokens: 256 random bits, hex-encoded.
+//!   Only `hash_session_token` of a token is ever stored, so a leaked
+//!   sessions table cannot be replayed as cookies.
+//! - Derives CSRF tokens from a per-browser secret with HMAC-SHA-256
+//!   under a server key (`derive_csrf_token`), so a token cannot be
+//!   computed by anyone who learns or plants the secret, and compares
+//!   them in constant time.
+//! - Hashes poster addresses and user agents with HMAC-SHA-256 under a
+//!   server key, so stored hashes link posts by the same poster but
+//!   cannot be reversed by hashing every possible address.
+//! - Picks CAPTCHA answers from the OS random source, uniformly over
+//!   the alphabet given (`generate_captcha_answer`).
+//! - `authorize` is the one permission check: a user's site `Role`, or
+//!   their `BoardRole` on the board concerned, must grant the
+//!   `Permission`. Callers look up the board appointment themselves.
 //! - Contains NO database logic.
 //! - Contains NO HTTP logic.
 //!
@@ -14,15 +28,18 @@
 //! End of File Notes:
 //! Keep this crate focused and security-oriented.
 
+use std::net::IpAddr;
+
 use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
-use rand_core::OsRng;
-use uuid::Uuid;
+use hmac::{Hmac, KeyInit, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
-use models::Role;
+use models::{BoardRole, BoardStaff, Permission, Role, User};
 
 #[derive(Debug, Error)]
 pub enum AuthError {
@@ -31,6 +48,9 @@ pub enum AuthError {
 
     #[error("Password verification failed")]
     VerificationFailed,
+
+    #[error("You are not allowed to {0}")]
+    Forbidden(Permission),
 }
 
 /// Hash a password using Argon2.
@@ -62,9 +82,91 @@ pub fn verify_password(
         .is_ok())
 }
 
+/// Random bytes in a session token.
+pub const SESSION_TOKEN_BYTES: usize = 32;
+
 /// Generate a new secure session token.
-pub fn generate_session_token() -> Uuid {
-    Uuid::new_v4()
+pub fn generate_session_token() -> String {
+    let mut bytes = [0u8; SESSION_TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+/// The form of a session token kept in storage.
+pub fn hash_session_token(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// The CSRF token that forms must echo back for a browser holding
+/// `secret` (its session token, or an anonymous CSRF cookie), signed
+/// with the server's `key`.
+pub fn derive_csrf_token(key: &[u8], secret: &str) -> String {
+    keyed_hash(key, "csrf", secret.as_bytes())
+}
+
+/// A fresh random key for `hash_ip`, `hash_user_agent` and
+/// `derive_csrf_token`.
+pub fn generate_key() -> Vec<u8> {
+    let mut key = vec![0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// A random CAPTCHA answer of `len` characters from `alphabet`.
+///
+/// Draws are rejected rather than reduced modulo the alphabet size, so
+/// every character is equally likely.
+pub fn generate_captcha_answer(alphabet: &str, len: usize) -> String {
+    let chars: Vec<char> = alphabet.chars().collect();
+    assert!(!chars.is_empty() && chars.len() <= 256, "alphabet must have 1 to 256 characters");
+    let limit = 256 - 256 % chars.len();
+
+    (0..len)
+        .map(|_| loop {
+            let mut byte = [0u8; 1];
+            OsRng.fill_bytes(&mut byte);
+            if usize::from(byte[0]) < limit {
+                break chars[usize::from(byte[0]) % chars.len()];
+            }
+        })
+        .collect()
+}
+
+/// Keyed hash of a poster's address, as stored with their posts.
+///
+/// IPv4-mapped IPv6 addresses hash like their IPv4 address.
+pub fn hash_ip(key: &[u8], ip: IpAddr) -> String {
+    keyed_hash(key, "ip", ip.to_canonical().to_string().as_bytes())
+}
+
+/// Keyed hash of a poster's `User-Agent`, as stored with their posts.
+pub fn hash_user_agent(key: &[u8], user_agent: &str) -> String {
+    keyed_hash(key, "user-agent", user_agent.as_bytes())
+}
+
+/// HMAC-SHA-256 of `value` under `key`, hex-encoded; `purpose` keeps
+/// hashes of different kinds of value apart.
+fn keyed_hash(key: &[u8], purpose: &str, value: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.update(b":");
+    mac.update(value);
+    hex(&mac.finalize().into_bytes())
+}
+
+/// Compare a submitted token with the expected one without leaking,
+/// through timing, how much of it matched.
+pub fn tokens_match(submitted: &str, expected: &str) -> bool {
+    submitted.len() == expected.len()
+        && submitted
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
 /// Check if role is admin.
@@ -72,11 +174,57 @@ pub fn is_admin(role: &Role) -> bool {
     matches!(role, Role::Admin)
 }
 
+/// Whether a site role grants `permission` on every board.
+fn role_allows(role: Role, permission: Permission) -> bool {
+    match role {
+        Role::Admin => true,
+        Role::GlobalModerator => {
+            permission == Permission::ManageImageBans
+                || board_role_allows(BoardRole::Moderator, permission)
+        }
+        Role::User => false,
+    }
+}
 
+/// Whether a board role grants `permission` on its board.
+fn board_role_allows(role: BoardRole, permission: Permission) -> bool {
+    use Permission::*;
 
+    match role {
+        BoardRole::BoardOwner => !permission.is_site_wide(),
+        BoardRole::Moderator => matches!(
+            permission,
+            DeletePost | HandleReports | BanUser | StickyThread | LockThread | ViewModLog
+        ),
+        BoardRole::Janitor => matches!(permission, DeletePost | HandleReports),
+    }
+}
 
+/// Check that `user` may exercise `permission`.
+///
+/// `board` is the user's staff appointment on the board the action
+/// concerns, if they have one; it never grants site-wide permissions.
+pub fn authorize(
+    user: &User,
+    board: Option<&BoardStaff>,
+    permission: Permission,
+) -> Result<(), AuthError> {
+    let by_board = board
+        .filter(|staff| staff.user_id == user.id && !permission.is_site_wide())
+        .is_some_and(|staff| board_role_allows(staff.role, permission));
+
+    if by_board || role_allows(user.role, permission) {
+        Ok(())
+    } else {
+        Err(AuthError::Forbidden(permission))
+    }
+}
 
-//// TESTS:
+
+
+
+
+/// TESTS:
 /// 
 /// 
 #[cfg(test)]
@@ -98,5 +246,92 @@ mod tests {
         let t1 = generate_session_token();
         let t2 = generate_session_token();
         assert_ne!(t1, t2);
+        assert_eq!(t1.len(), SESSION_TOKEN_BYTES * 2);
+    }
+
+    #[test]
+    fn session_token_hash_is_stable() {
+        let token = generate_session_token();
+
+        assert_eq!(hash_session_token(&token), hash_session_token(&token));
+        assert_ne!(hash_session_token(&token), token);
+        assert_eq!(
+            hash_session_token(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn permissions_follow_site_and_board_roles() {
+        let user = |role| User {
+            id: uuid::Uuid::new_v4(),
+            username: "u".into(),
+            password_hash: String::new(),
+            role,
+        };
+        let staff = |user: &User, role| BoardStaff {
+            board_id: uuid::Uuid::new_v4(),
+            user_id: user.id,
+            role,
+            created_at: time::OffsetDateTime::now_utc(),
+        };
+        let allowed = |user: &User, board: Option<&BoardStaff>, permission| {
+            authorize(user, board, permission).is_ok()
+        };
+
+        let admin = user(Role::Admin);
+        let global = user(Role::GlobalModerator);
+        let nobody = user(Role::User);
+        assert!(allowed(&admin, None, Permission::ManageUsers));
+        assert!(allowed(&global, None, Permission::BanUser));
+        assert!(allowed(&global, None, Permission::ManageImageBans));
+        assert!(!allowed(&global, None, Permission::EditBoard));
+        assert!(!allowed(&nobody, None, Permission::DeletePost));
+
+        let owner = staff(&nobody, BoardRole::BoardOwner);
+        assert!(allowed(&nobody, Some(&owner), Permission::ManageStaff));
+        assert!(!allowed(&nobody, Some(&owner), Permission::CreateBoard));
+
+        let janitor = staff(&nobody, BoardRole::Janitor);
+        assert!(allowed(&nobody, Some(&janitor), Permission::DeletePost));
+        assert!(!allowed(&nobody, Some(&janitor), Permission::BanUser));
+        assert!(allowed(&nobody, Some(&staff(&nobody, BoardRole::Moderator)), Permission::BanUser));
+
+        // Someone else's appointment grants nothing.
+        assert!(matches!(
+            authorize(&global, Some(&owner), Permission::ManageStaff),
+            Err(AuthError::Forbidden(Permission::ManageStaff))
+        ));
+    }
+
+    #[test]
+    fn csrf_tokens_are_bound_to_their_secret_and_key() {
+        let token = derive_csrf_token(b"key", "secret");
+
+        assert!(tokens_match(&token, &derive_csrf_token(b"key", "secret")));
+        assert!(!tokens_match(&token, &derive_csrf_token(b"key", "other")));
+        assert!(!tokens_match(&token, &derive_csrf_token(b"other key", "secret")));
+        assert!(!tokens_match(&token, &hash_session_token("secret")));
+        assert!(!tokens_match("", &token));
+    }
+
+    #[test]
+    fn poster_hashes_need_the_key() {
+        let ip: IpAddr = "203.0.113.9".parse().unwrap();
+        let mapped: IpAddr = "::ffff:203.0.113.9".parse().unwrap();
+
+        assert_eq!(hash_ip(b"key", ip), hash_ip(b"key", mapped));
+        assert_ne!(hash_ip(b"key", ip), hash_ip(b"other key", ip));
+        assert_ne!(hash_ip(b"key", ip), hash_user_agent(b"key", "203.0.113.9"));
+        assert_eq!(hash_user_agent(b"key", "curl/8").len(), 64);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn captcha_answers_use_the_alphabet() {
+        let answer = generate_captcha_answer("AB7", 32);
+
+        assert_eq!(answer.len(), 32);
+        assert!(answer.chars().all(|c| "AB7".contains(c)));
+        assert_ne!(answer, generate_captcha_answer("AB7", 32));
+    }
+}
@@ -15,36 +15,243 @@
 //! End of File Notes:
 //! Keep configuration simple and explicit.
 
+use ipnet::IpNet;
 use serde::Deserialize;
 use std::env;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Which storage backend the application runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    /// Embedded SQLite file at `database_path`.
+    Sqlite,
+    /// PostgreSQL server at `database_url` (needs the `postgres` feature).
+    Postgres,
+}
+
+impl FromStr for DatabaseBackend {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqlite" => Ok(DatabaseBackend::Sqlite),
+            "postgres" | "postgresql" => Ok(DatabaseBackend::Postgres),
+            _ => Err(()),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
+    pub database_backend: DatabaseBackend,
     pub database_path: String,
+    pub database_url: String,
     pub server_address: String,
+    pub database_pool_size: u32,
+    /// Directory holding uploaded files and their thumbnails.
+    pub media_dir: String,
+    /// Largest accepted image upload, in bytes.
+    pub max_upload_size: usize,
+    /// Largest accepted audio or video upload, in bytes.
+    pub max_av_upload_size: usize,
+    /// Largest accepted image width or height, in pixels.
+    pub max_image_dimension: u32,
+    /// Thumbnails fit in a square of this many pixels.
+    pub thumbnail_size: u32,
+    /// ffmpeg binary used for video poster frames; empty disables them.
+    pub ffmpeg_path: String,
+    /// Seconds between sweeps for unreferenced media files; 0 disables.
+    pub media_gc_interval: u64,
+    /// Largest Hamming distance at which an upload matches a banned image.
+    pub image_ban_distance: u32,
+    /// Replies a cyclical thread keeps before dropping the oldest.
+    pub cyclical_reply_limit: usize,
+    /// Whether visitors may create accounts at `/register`.
+    pub allow_registration: bool,
+    /// Seconds a session lasts after login.
+    pub session_max_age: u64,
+    /// Seconds a session lasts without being used.
+    pub session_idle_timeout: u64,
+    /// Seconds between sweeps for expired sessions; 0 disables.
+    pub session_purge_interval: u64,
+    /// Reverse proxies whose `X-Forwarded-For` is believed; empty means
+    /// posters are identified by the connecting address.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Key for poster address and user agent hashes; empty picks a
+    /// random key at startup, so hashes change across restarts.
+    pub ip_hash_key: String,
+    /// Key CSRF tokens are signed with; empty picks a random key at
+    /// startup, so forms open across a restart are refused.
+    pub csrf_key: String,
+    /// Seconds a poster's raw address is kept with their post.
+    pub ip_retention: u64,
+    /// Seconds between sweeps for raw addresses past retention; 0 disables.
+    pub ip_purge_interval: u64,
+    /// Seconds an address must wait between new threads on a board.
+    pub thread_cooldown: u64,
+    /// Seconds an address must wait between replies on a board.
+    pub reply_cooldown: u64,
+    /// Seconds before an address may post the same text on a board again.
+    pub repost_cooldown: u64,
+    /// Requests an address may make in a burst.
+    pub rate_limit_burst: u32,
+    /// Requests per second an address regains; 0 disables the limit.
+    pub rate_limit_per_second: f64,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            database_backend: DatabaseBackend::Sqlite,
             database_path: "rusty_board.db".into(),
+            database_url: "postgres://localhost/rusty_board".into(),
             server_address: "0.0.0.0:3000".into(),
+            database_pool_size: 8,
+            media_dir: "media".into(),
+            max_upload_size: 4 * 1024 * 1024,
+            max_av_upload_size: 16 * 1024 * 1024,
+            max_image_dimension: 10_000,
+            thumbnail_size: 250,
+            ffmpeg_path: "ffmpeg".into(),
+            media_gc_interval: 60 * 60,
+            image_ban_distance: 8,
+            cyclical_reply_limit: 250,
+            allow_registration: true,
+            session_max_age: 30 * 24 * 60 * 60,
+            session_idle_timeout: 7 * 24 * 60 * 60,
+            session_purge_interval: 60 * 60,
+            trusted_proxies: Vec::new(),
+            ip_hash_key: String::new(),
+            csrf_key: String::new(),
+            ip_retention: 30 * 24 * 60 * 60,
+            ip_purge_interval: 60 * 60,
+            thread_cooldown: 60,
+            reply_cooldown: 10,
+            repost_cooldown: 2 * 60,
+            rate_limit_burst: 60,
+            rate_limit_per_second: 5.0,
         }
     }
 }
 
+/// Read and parse an environment variable, ignoring unset or invalid values.
+fn env_parse<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok()?.parse().ok()
+}
+
+/// Parse a comma-separated list of addresses and CIDR ranges; a bare
+/// address is a range of one.
+fn parse_networks(list: &str) -> Option<Vec<IpNet>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse::<IpNet>()
+                .ok()
+                .or_else(|| item.parse::<IpAddr>().ok().map(IpNet::from))
+        })
+        .collect()
+}
+
 impl AppConfig {
     /// Load configuration from environment variables.
     ///
+    /// Unset or unparsable variables keep their default value.
+    ///
     /// Environment Variables:
+    /// - DATABASE_BACKEND (`sqlite` or `postgres`)
     /// - DATABASE_PATH
+    /// - DATABASE_URL
     /// - SERVER_ADDRESS
+    /// - DATABASE_POOL_SIZE
+    /// - MEDIA_DIR
+    /// - MAX_UPLOAD_SIZE
+    /// - MAX_AV_UPLOAD_SIZE
+    /// - MAX_IMAGE_DIMENSION
+    /// - THUMBNAIL_SIZE
+    /// - FFMPEG_PATH (empty disables video posters)
+    /// - MEDIA_GC_INTERVAL (seconds, `0` disables)
+    /// - IMAGE_BAN_DISTANCE (bits, 0-64)
+    /// - CYCLICAL_REPLY_LIMIT (replies)
+    /// - ALLOW_REGISTRATION (`true` or `false`)
+    /// - SESSION_MAX_AGE (seconds)
+    /// - SESSION_IDLE_TIMEOUT (seconds)
+    /// - SESSION_PURGE_INTERVAL (seconds, `0` disables)
+    /// - TRUSTED_PROXIES (comma-separated addresses or CIDR ranges)
+    /// - IP_HASH_KEY
+    /// - CSRF_KEY
+    /// - IP_RETENTION (seconds)
+    /// - IP_PURGE_INTERVAL (seconds, `0` disables)
+    /// - THREAD_COOLDOWN (seconds, `0` disables)
+    /// - REPLY_COOLDOWN (seconds, `0` disables)
+    /// - REPOST_COOLDOWN (seconds, `0` disables)
+    /// - RATE_LIMIT_BURST (requests)
+    /// - RATE_LIMIT_PER_SECOND (requests, `0` disables)
     pub fn from_env() -> Self {
+        let defaults = Self::default();
+
         Self {
-            database_path: env::var("DATABASE_PATH")
-                .unwrap_or_else(|_| "rusty_board.db".into()),
-            server_address: env::var("SERVER_ADDRESS")
-                .unwrap_or_else(|_| "0.0.0.0:3000".into()),
+            database_backend: env_parse("DATABASE_BACKEND")
+                .unwrap_or(defaults.database_backend),
+            database_path: env_parse("DATABASE_PATH")
+                .unwrap_or(defaults.database_path),
+            database_url: env_parse("DATABASE_URL")
+                .unwrap_or(defaults.database_url),
+            server_address: env_parse("SERVER_ADDRESS")
+                .unwrap_or(defaults.server_address),
+            database_pool_size: env_parse("DATABASE_POOL_SIZE")
+                .unwrap_or(defaults.database_pool_size),
+            media_dir: env_parse("MEDIA_DIR")
+                .unwrap_or(defaults.media_dir),
+            max_upload_size: env_parse("MAX_UPLOAD_SIZE")
+                .unwrap_or(defaults.max_upload_size),
+            max_av_upload_size: env_parse("MAX_AV_UPLOAD_SIZE")
+                .unwrap_or(defaults.max_av_upload_size),
+            max_image_dimension: env_parse("MAX_IMAGE_DIMENSION")
+                .unwrap_or(defaults.max_image_dimension),
+            thumbnail_size: env_parse("THUMBNAIL_SIZE")
+                .unwrap_or(defaults.thumbnail_size),
+            ffmpeg_path: env::var("FFMPEG_PATH")
+                .unwrap_or(defaults.ffmpeg_path),
+            media_gc_interval: env_parse("MEDIA_GC_INTERVAL")
+                .unwrap_or(defaults.media_gc_interval),
+            image_ban_distance: env_parse("IMAGE_BAN_DISTANCE")
+                .unwrap_or(defaults.image_ban_distance),
+            cyclical_reply_limit: env_parse("CYCLICAL_REPLY_LIMIT")
+                .unwrap_or(defaults.cyclical_reply_limit),
+            allow_registration: env_parse("ALLOW_REGISTRATION")
+                .unwrap_or(defaults.allow_registration),
+            session_max_age: env_parse("SESSION_MAX_AGE")
+                .unwrap_or(defaults.session_max_age),
+            session_idle_timeout: env_parse("SESSION_IDLE_TIMEOUT")
+                .unwrap_or(defaults.session_idle_timeout),
+            session_purge_interval: env_parse("SESSION_PURGE_INTERVAL")
+                .unwrap_or(defaults.session_purge_interval),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .ok()
+                .and_then(|list| parse_networks(&list))
+                .unwrap_or(defaults.trusted_proxies),
+            ip_hash_key: env::var("IP_HASH_KEY")
+                .unwrap_or(defaults.ip_hash_key),
+            csrf_key: env::var("CSRF_KEY")
+                .unwrap_or(defaults.csrf_key),
+            ip_retention: env_parse("IP_RETENTION")
+                .unwrap_or(defaults.ip_retention),
+            ip_purge_interval: env_parse("IP_PURGE_INTERVAL")
+                .unwrap_or(defaults.ip_purge_interval),
+            thread_cooldown: env_parse("THREAD_COOLDOWN")
+                .unwrap_or(defaults.thread_cooldown),
+            reply_cooldown: env_parse("REPLY_COOLDOWN")
+                .unwrap_or(defaults.reply_cooldown),
+            repost_cooldown: env_parse("REPOST_COOLDOWN")
+                .unwrap_or(defaults.repost_cooldown),
+            rate_limit_burst: env_parse("RATE_LIMIT_BURST")
+                .unwrap_or(defaults.rate_limit_burst),
+            rate_limit_per_second: env_parse("RATE_LIMIT_PER_SECOND")
+                .filter(|rate: &f64| rate.is_finite() && *rate >= 0.0)
+                .unwrap_or(defaults.rate_limit_per_second),
         }
     }
 }
@@ -64,5 +271,23 @@ mod tests {
         let config = AppConfig::default();
         assert!(!config.database_path.is_empty());
         assert!(!config.server_address.is_empty());
+        assert_eq!(config.database_backend, DatabaseBackend::Sqlite);
+        assert!(config.thumbnail_size <= config.max_image_dimension);
+        assert!(config.session_idle_timeout <= config.session_max_age);
+    }
+
+    #[test]
+    fn backend_parses() {
+        assert_eq!("postgres".parse(), Ok(DatabaseBackend::Postgres));
+        assert!("mysql".parse::<DatabaseBackend>().is_err());
+    }
+
+    #[test]
+    fn trusted_proxies_parse() {
+        let proxies = parse_networks("10.0.0.0/8, 127.0.0.1,::1").unwrap();
+        assert_eq!(proxies.len(), 3);
+        assert_eq!(proxies[1].to_string(), "127.0.0.1/32");
+        assert_eq!(parse_networks(""), Some(vec![]));
+        assert!(parse_networks("10.0.0.0/8,proxy").is_none());
     }
 }
\ No newline at end of file
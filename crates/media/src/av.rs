@@ -0,0 +1,263 @@
+//! Audio and Video Uploads
+//!
+//! Developer Notes:
+//! - Validates an audio or video upload: size, container (via `probe`),
+//!   duration and codecs against the board's `AvLimits`, and picture
+//!   size against `MediaLimits`.
+//! - Poster frames for video come from a `PosterFrames` implementation;
+//!   `Ffmpeg` runs a local ffmpeg binary on the already validated file.
+//! - A poster is best-effort: a video without one is still accepted and
+//!   played without a preview image.
+//! - Files are stored as uploaded; container metadata is not stripped.
+//!
+//! End Notes:
+//! ffmpeg only ever sees files that passed `probe`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
+
+use crate::thumbnail::make_thumbnail;
+use crate::{probe, sniff, AvInfo, MediaClass, MediaError, MediaKind, MediaLimits, Thumbnail};
+
+/// Per-board limits on audio and video uploads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvLimits {
+    /// Longest accepted duration.
+    pub max_duration: Duration,
+    /// Accepted codec names, from `KNOWN_CODECS`; empty refuses all
+    /// audio and video.
+    pub codecs: Vec<String>,
+}
+
+/// A validated audio or video upload.
+#[derive(Debug, Clone)]
+pub struct ProcessedAv {
+    pub info: AvInfo,
+    /// The file as uploaded.
+    pub data: Vec<u8>,
+    /// Poster frame thumbnail, for video when one could be made.
+    pub poster: Option<Thumbnail>,
+}
+
+/// Extracts a still frame from a video.
+pub trait PosterFrames: Send + Sync {
+    /// An early frame of `data`, a video of the given kind.
+    fn poster_frame(&self, data: &[u8], kind: MediaKind) -> Result<DynamicImage, MediaError>;
+}
+
+/// Validate `data` as audio or video within `limits` and `av`.
+///
+/// `posters` makes the poster frame for video; without it, or if it
+/// fails, the video has no poster.
+pub fn process_av(
+    data: &[u8],
+    limits: &MediaLimits,
+    av: &AvLimits,
+    posters: Option<&dyn PosterFrames>,
+) -> Result<ProcessedAv, MediaError> {
+    if data.len() > limits.max_av_file_size {
+        return Err(MediaError::TooLarge {
+            size: data.len(),
+            max: limits.max_av_file_size,
+        });
+    }
+
+    let kind = sniff(data).ok_or(MediaError::UnsupportedType)?;
+    if kind.class() == MediaClass::Image {
+        return Err(MediaError::UnsupportedType);
+    }
+
+    let info = probe(data, kind)?;
+    if let Some(codec) = info.codecs().find(|codec| !av.codecs.iter().any(|c| c == codec)) {
+        return Err(MediaError::CodecNotAllowed(codec.to_string()));
+    }
+    if info.duration > av.max_duration {
+        return Err(MediaError::TooLong {
+            seconds: info.duration.as_secs(),
+            max: av.max_duration.as_secs(),
+        });
+    }
+    if info.width > limits.max_dimension || info.height > limits.max_dimension {
+        return Err(MediaError::DimensionsTooLarge {
+            width: info.width,
+            height: info.height,
+            max: limits.max_dimension,
+        });
+    }
+
+    let poster = match (kind.class(), posters) {
+        (MediaClass::Video, Some(posters)) => posters
+            .poster_frame(data, kind)
+            .and_then(|frame| make_thumbnail(&frame, limits.thumbnail_size))
+            .ok(),
+        _ => None,
+    };
+
+    Ok(ProcessedAv {
+        info,
+        data: data.to_vec(),
+        poster,
+    })
+}
+
+/// Poster frames from a local ffmpeg binary.
+#[derive(Debug, Clone)]
+pub struct Ffmpeg {
+    program: PathBuf,
+    timeout: Duration,
+}
+
+impl Ffmpeg {
+    /// How long one extraction may run before ffmpeg is killed.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Use the ffmpeg at `program`, a path or a name looked up in `PATH`.
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn run(&self, input: &Path, output: &Path) -> Result<(), MediaError> {
+        let mut child = Command::new(&self.program)
+            .args(["-nostdin", "-v", "error", "-y", "-i"])
+            .arg(input)
+            .args(["-frames:v", "1", "-f", "image2", "-c:v", "png"])
+            .arg(output)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return if status.success() {
+                    Ok(())
+                } else {
+                    Err(MediaError::Decode(format!("ffmpeg exited with {status}")))
+                };
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(MediaError::Decode("ffmpeg timed out".into()));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl PosterFrames for Ffmpeg {
+    fn poster_frame(&self, data: &[u8], kind: MediaKind) -> Result<DynamicImage, MediaError> {
+        static NEXT_JOB: AtomicU64 = AtomicU64::new(0);
+
+        let job = format!(
+            "rusty-board-poster-{}-{}",
+            std::process::id(),
+            NEXT_JOB.fetch_add(1, Ordering::Relaxed)
+        );
+        let input = std::env::temp_dir().join(format!("{job}.{}", kind.extension()));
+        let output = std::env::temp_dir().join(format!("{job}.png"));
+
+        let result = fs::write(&input, data)
+            .map_err(MediaError::from)
+            .and_then(|()| self.run(&input, &output))
+            .and_then(|()| {
+                image::open(&output).map_err(|e| MediaError::Decode(e.to_string()))
+            });
+
+        let _ = fs::remove_file(&input);
+        let _ = fs::remove_file(&output);
+        result
+    }
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn corpus(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/testdata/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    fn all_codecs() -> AvLimits {
+        AvLimits {
+            max_duration: Duration::from_secs(60),
+            codecs: crate::KNOWN_CODECS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// A stand-in for ffmpeg that returns a fixed frame.
+    struct Still;
+
+    impl PosterFrames for Still {
+        fn poster_frame(&self, _: &[u8], _: MediaKind) -> Result<DynamicImage, MediaError> {
+            Ok(DynamicImage::ImageRgb8(RgbImage::from_pixel(640, 360, Rgb([9, 9, 9]))))
+        }
+    }
+
+    #[test]
+    fn videos_get_a_poster_and_audio_does_not() {
+        let limits = MediaLimits::default();
+
+        let video = process_av(&corpus("clip.webm"), &limits, &all_codecs(), Some(&Still)).unwrap();
+        let poster = video.poster.unwrap();
+        assert_eq!((poster.width, poster.height), (250, 141));
+
+        let audio = process_av(&corpus("tone.ogg"), &limits, &all_codecs(), Some(&Still)).unwrap();
+        assert!(audio.poster.is_none());
+        assert_eq!(audio.data, corpus("tone.ogg"));
+
+        let missing = Ffmpeg::new("/nonexistent/ffmpeg");
+        let posterless = process_av(&corpus("clip.mp4"), &limits, &all_codecs(), Some(&missing)).unwrap();
+        assert!(posterless.poster.is_none());
+    }
+
+    #[test]
+    fn board_limits_are_enforced() {
+        let limits = MediaLimits::default();
+        let short = AvLimits { max_duration: Duration::from_secs(10), ..all_codecs() };
+        let no_vp9 = AvLimits { codecs: vec!["opus".into()], ..all_codecs() };
+
+        assert!(matches!(
+            process_av(&corpus("clip.webm"), &limits, &short, None),
+            Err(MediaError::TooLong { seconds: 12, max: 10 })
+        ));
+        assert!(matches!(
+            process_av(&corpus("clip.webm"), &limits, &no_vp9, None),
+            Err(MediaError::CodecNotAllowed(codec)) if codec == "vp9"
+        ));
+        assert!(process_av(&corpus("tone.ogg"), &limits, &no_vp9, None).is_ok());
+
+        let small = MediaLimits { max_av_file_size: 1024, max_dimension: 320, ..limits };
+        assert!(matches!(
+            process_av(&corpus("tone.mp3"), &small, &all_codecs(), None),
+            Err(MediaError::TooLarge { .. })
+        ));
+        assert!(matches!(
+            process_av(&corpus("clip.webm"), &small, &all_codecs(), None),
+            Err(MediaError::DimensionsTooLarge { width: 640, .. })
+        ));
+        assert!(matches!(
+            process_av(&corpus("base.png"), &limits, &all_codecs(), None),
+            Err(MediaError::UnsupportedType)
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+//! CAPTCHA Images
+//!
+//! Developer Notes:
+//! - Renders a challenge text as a distorted PNG, entirely in process:
+//!   no fonts, no external services.
+//! - Glyphs come from a built-in 5x7 bitmap font covering
+//!   `CAPTCHA_ALPHABET` only, which leaves out look-alikes (0/O, 1/I,
+//!   2/Z, 5/S, 8/B, G/6).
+//! - Each glyph is scaled, rotated and sheared on its own, the whole
+//!   image is warped by two sine waves, and crossing lines and speckles
+//!   are drawn over it.
+//! - Output is a pure function of the text and the seed, so a stored
+//!   challenge can be re-rendered instead of keeping its image.
+//!
+//! End Notes:
+//! This stops unattended scripts, not determined OCR.
+
+use std::f32::consts::TAU;
+use std::io::Cursor;
+
+use image::{GrayImage, ImageFormat, Luma};
+
+use crate::MediaError;
+
+/// Characters a challenge may use.
+pub const CAPTCHA_ALPHABET: &str = "ACDEFHJKLMNPRTUVWXY34679";
+
+/// Width of a challenge image, in pixels.
+pub const CAPTCHA_WIDTH: u32 = 240;
+
+/// Height of a challenge image, in pixels.
+pub const CAPTCHA_HEIGHT: u32 = 70;
+
+const PAPER: u8 = 236;
+const INK: u8 = 40;
+
+/// Rows of a glyph, top first; bit 4 is the leftmost column.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        _ => return None,
+    })
+}
+
+/// SplitMix64: small, fast and good enough for picking distortions.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[low, high)`.
+    fn range(&mut self, low: f32, high: f32) -> f32 {
+        low + (self.next() >> 40) as f32 / (1u64 << 24) as f32 * (high - low)
+    }
+}
+
+/// Where one glyph sits and how it is bent.
+struct Placement {
+    rows: [u8; 7],
+    x: f32,
+    y: f32,
+    scale: f32,
+    cos: f32,
+    sin: f32,
+    shear: f32,
+}
+
+impl Placement {
+    /// Whether the glyph covers the image point `(x, y)`.
+    fn covers(&self, x: f32, y: f32) -> bool {
+        let (dx, dy) = (x - self.x, y - self.y);
+        let u = dx * self.cos + dy * self.sin;
+        let v = -dx * self.sin + dy * self.cos;
+        let u = u - self.shear * v;
+
+        let gx = (u / self.scale + 2.5).floor();
+        let gy = (v / self.scale + 3.5).floor();
+        (0.0..5.0).contains(&gx)
+            && (0.0..7.0).contains(&gy)
+            && self.rows[gy as usize] & (0b10000 >> gx as u32) != 0
+    }
+}
+
+/// Distance from `p` to the segment `a`-`b`.
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (ab_x, ab_y) = (b.0 - a.0, b.1 - a.1);
+    let t = (((p.0 - a.0) * ab_x + (p.1 - a.1) * ab_y) / (ab_x * ab_x + ab_y * ab_y)).clamp(0.0, 1.0);
+    ((a.0 + t * ab_x - p.0).powi(2) + (a.1 + t * ab_y - p.1).powi(2)).sqrt()
+}
+
+/// Render `text` as a distorted PNG, varied by `seed`.
+///
+/// Fails on characters outside `CAPTCHA_ALPHABET`.
+pub fn render_captcha(text: &str, seed: u64) -> Result<Vec<u8>, MediaError> {
+    let rows: Vec<[u8; 7]> = text
+        .chars()
+        .map(|c| glyph(c).ok_or(MediaError::UnsupportedType))
+        .collect::<Result<_, _>>()?;
+
+    let mut rng = Rng(seed);
+    let (width, height) = (CAPTCHA_WIDTH as f32, CAPTCHA_HEIGHT as f32);
+    let step = (width - 40.0) / rows.len().max(1) as f32;
+
+    let glyphs: Vec<Placement> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, rows)| {
+            let angle = rng.range(-0.3, 0.3);
+            Placement {
+                rows,
+                x: 20.0 + step * (i as f32 + 0.5) + rng.range(-3.0, 3.0),
+                y: height / 2.0 + rng.range(-7.0, 7.0),
+                scale: rng.range(4.2, 5.0),
+                cos: angle.cos(),
+                sin: angle.sin(),
+                shear: rng.range(-0.25, 0.25),
+            }
+        })
+        .collect();
+
+    let waves = (
+        rng.range(2.0, 4.0),
+        rng.range(0.0, TAU),
+        rng.range(2.0, 4.0),
+        rng.range(0.0, TAU),
+    );
+    let lines: Vec<((f32, f32), (f32, f32))> = (0..2)
+        .map(|_| {
+            (
+                (rng.range(0.0, width * 0.3), rng.range(0.0, height)),
+                (rng.range(width * 0.7, width), rng.range(0.0, height)),
+            )
+        })
+        .collect();
+
+    let mut image = GrayImage::from_pixel(CAPTCHA_WIDTH, CAPTCHA_HEIGHT, Luma([PAPER]));
+    for (px, py, pixel) in image.enumerate_pixels_mut() {
+        let (x, y) = (px as f32, py as f32);
+        let wx = x + waves.0 * (y / 9.0 + waves.1).sin();
+        let wy = y + waves.2 * (x / 13.0 + waves.3).sin();
+
+        let inked = glyphs.iter().any(|glyph| glyph.covers(wx, wy))
+            ^ lines.iter().any(|&(a, b)| segment_distance((x, y), a, b) < 0.9);
+        if inked {
+            *pixel = Luma([INK]);
+        }
+    }
+
+    for _ in 0..(CAPTCHA_WIDTH * CAPTCHA_HEIGHT / 25) {
+        let x = rng.next() % u64::from(CAPTCHA_WIDTH);
+        let y = rng.next() % u64::from(CAPTCHA_HEIGHT);
+        let shade = (rng.next() % 256) as u8;
+        image.put_pixel(x as u32, y as u32, Luma([shade]));
+    }
+
+    let mut data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .map_err(|e| MediaError::Decode(e.to_string()))?;
+    Ok(data)
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_alphabet_character_has_a_glyph() {
+        assert!(CAPTCHA_ALPHABET.chars().all(|c| glyph(c).is_some()));
+        assert!(glyph('O').is_none() && glyph('0').is_none());
+    }
+
+    #[test]
+    fn renders_a_png_that_depends_on_text_and_seed() {
+        let png = render_captcha("AC3DE7", 1).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!((image.width(), image.height()), (CAPTCHA_WIDTH, CAPTCHA_HEIGHT));
+
+        assert_eq!(png, render_captcha("AC3DE7", 1).unwrap());
+        assert_ne!(png, render_captcha("AC3DE7", 2).unwrap());
+        assert_ne!(png, render_captcha("AC3DE9", 1).unwrap());
+        assert!(render_captcha("HELLO", 1).is_err());
+    }
+}
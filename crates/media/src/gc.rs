@@ -0,0 +1,136 @@
+//! Media Garbage Collection
+//!
+//! Developer Notes:
+//! - Removes stored files that no attachment references any more:
+//!   leftovers of deleted posts, failed uploads and crashed writes.
+//! - The caller supplies the referenced keys; this module never looks
+//!   at the database.
+//! - Files younger than the grace period are kept, since an upload
+//!   writes (or re-saves) its files before inserting the rows that
+//!   reference them.
+//! - `remove_if_stale` applies the same rule to a single file, for
+//!   releasing the files of a deleted post right away.
+//!
+//! End Notes:
+//! Safe to run while the server is accepting uploads.
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use crate::store::{Bucket, MediaStore};
+use crate::MediaError;
+
+/// Keys still referenced by attachments, per bucket.
+#[derive(Debug, Clone, Default)]
+pub struct ReferencedKeys {
+    pub originals: HashSet<String>,
+    pub thumbnails: HashSet<String>,
+}
+
+impl ReferencedKeys {
+    fn contains(&self, bucket: Bucket, key: &str) -> bool {
+        match bucket {
+            Bucket::Originals => self.originals.contains(key),
+            Bucket::Thumbnails => self.thumbnails.contains(key),
+        }
+    }
+}
+
+/// What one collection pass did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Files looked at, in both buckets.
+    pub scanned: usize,
+    /// Files deleted.
+    pub removed: usize,
+}
+
+fn cutoff(grace: Duration) -> SystemTime {
+    SystemTime::now()
+        .checked_sub(grace)
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Delete `key` if it was last modified more than `grace` ago.
+///
+/// The caller has established that nothing references it. Returns
+/// whether the file was removed.
+pub fn remove_if_stale(
+    store: &dyn MediaStore,
+    bucket: Bucket,
+    key: &str,
+    grace: Duration,
+) -> Result<bool, MediaError> {
+    match store.modified(bucket, key)? {
+        Some(modified) if modified < cutoff(grace) => {
+            store.delete(bucket, key)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Delete every unreferenced file last modified more than `grace` ago.
+pub fn collect_garbage(
+    store: &dyn MediaStore,
+    referenced: &ReferencedKeys,
+    grace: Duration,
+) -> Result<GcReport, MediaError> {
+    let cutoff = cutoff(grace);
+    let mut report = GcReport::default();
+
+    for bucket in [Bucket::Originals, Bucket::Thumbnails] {
+        for blob in store.list(bucket)? {
+            report.scanned += 1;
+            if blob.modified < cutoff && !referenced.contains(bucket, &blob.key) {
+                store.delete(bucket, &blob.key)?;
+                report.removed += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::LocalMediaStore;
+
+    #[test]
+    fn removes_only_old_unreferenced_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::open(dir.path()).unwrap();
+        for key in ["kept.png", "orphan.png"] {
+            store.save(Bucket::Originals, key, b"x").unwrap();
+            store.save(Bucket::Thumbnails, key, b"x").unwrap();
+        }
+
+        let mut referenced = ReferencedKeys::default();
+        referenced.originals.insert("kept.png".into());
+        referenced.thumbnails.insert("kept.png".into());
+
+        let fresh = collect_garbage(&store, &referenced, Duration::from_secs(3600)).unwrap();
+        assert_eq!(fresh, GcReport { scanned: 4, removed: 0 });
+
+        let report = collect_garbage(&store, &referenced, Duration::ZERO).unwrap();
+        assert_eq!(report.removed, 2);
+        let left: Vec<_> = store.list(Bucket::Thumbnails).unwrap().into_iter().map(|b| b.key).collect();
+        assert_eq!(left, ["kept.png"]);
+    }
+
+    #[test]
+    fn single_files_respect_the_grace_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::open(dir.path()).unwrap();
+        store.save(Bucket::Originals, "a.png", b"x").unwrap();
+
+        assert!(!remove_if_stale(&store, Bucket::Originals, "a.png", Duration::from_secs(60)).unwrap());
+        assert!(remove_if_stale(&store, Bucket::Originals, "a.png", Duration::ZERO).unwrap());
+        assert!(!remove_if_stale(&store, Bucket::Originals, "a.png", Duration::ZERO).unwrap());
+    }
+}
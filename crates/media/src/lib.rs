@@ -0,0 +1,112 @@
+//! Media Crate
+//!
+//! Developer Notes:
+//! - Handles uploaded files: type sniffing, validation, thumbnails and
+//!   content-addressed storage.
+//! - Images are decoded and thumbnailed here; audio and video are only
+//!   probed, with poster frames delegated to ffmpeg.
+//! - File types are decided by magic bytes, never by file name or the
+//!   client's Content-Type.
+//! - Uploads are stored only after their metadata has been stripped.
+//! - Also renders CAPTCHA challenge images (`captcha`).
+//! - Contains NO database logic.
+//! - Contains NO HTTP logic.
+//!
+//! End of File Notes:
+//! Everything here runs on blocking threads; never call it from async code.
+
+pub mod sniff;
+pub mod probe;
+pub mod sanitize;
+pub mod thumbnail;
+pub mod av;
+pub mod upload;
+pub mod phash;
+pub mod store;
+pub mod gc;
+pub mod captcha;
+
+use thiserror::Error;
+
+pub use sniff::{sniff, MediaClass, MediaKind};
+pub use probe::{probe, AvInfo, KNOWN_CODECS};
+pub use sanitize::strip_metadata;
+pub use thumbnail::{process_image, ProcessedImage, Thumbnail};
+pub use av::{process_av, AvLimits, Ffmpeg, PosterFrames, ProcessedAv};
+pub use upload::{process_upload, ProcessedUpload};
+pub use phash::{hamming, ImageHashes};
+pub use store::{content_key, sha256_hex, Bucket, LocalMediaStore, MediaStore, StoredBlob};
+pub use gc::{collect_garbage, remove_if_stale, GcReport, ReferencedKeys};
+pub use captcha::{render_captcha, CAPTCHA_ALPHABET, CAPTCHA_HEIGHT, CAPTCHA_WIDTH};
+
+/// Errors returned while validating, processing or storing media.
+#[derive(Debug, Error)]
+pub enum MediaError {
+    /// The upload is larger than `MediaLimits::max_file_size`, or
+    /// `max_av_file_size` for audio and video.
+    #[error("File is {size} bytes, the limit is {max}")]
+    TooLarge { size: usize, max: usize },
+
+    /// The bytes are not a supported format.
+    #[error("Unsupported file type")]
+    UnsupportedType,
+
+    /// The image is wider or taller than `MediaLimits::max_dimension`.
+    #[error("Image is {width}x{height}, the limit is {max}x{max}")]
+    DimensionsTooLarge { width: u32, height: u32, max: u32 },
+
+    /// The file claims a supported format but could not be decoded.
+    #[error("File could not be decoded: {0}")]
+    Decode(String),
+
+    /// An audio or video track uses a codec we do not recognise.
+    #[error("Unsupported codec `{0}`")]
+    UnsupportedCodec(String),
+
+    /// The codec is known but the board does not accept it.
+    #[error("Codec `{0}` is not allowed on this board")]
+    CodecNotAllowed(String),
+
+    /// Audio or video longer than the board allows.
+    #[error("File is {seconds} seconds long, the limit is {max}")]
+    TooLong { seconds: u64, max: u64 },
+
+    /// A media key that would escape the media directory.
+    #[error("Invalid media key: {0}")]
+    InvalidKey(String),
+
+    /// Reading or writing the media directory failed.
+    #[error("Media I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl MediaError {
+    /// Whether the error is the uploader's fault rather than the server's.
+    pub fn is_rejection(&self) -> bool {
+        !matches!(self, MediaError::Io(_) | MediaError::InvalidKey(_))
+    }
+}
+
+/// Size and dimension limits applied to every upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaLimits {
+    /// Largest accepted image, in bytes.
+    pub max_file_size: usize,
+    /// Largest accepted audio or video file, in bytes.
+    pub max_av_file_size: usize,
+    /// Largest accepted width or height, in pixels.
+    pub max_dimension: u32,
+    /// Thumbnails fit in a square of this many pixels.
+    pub thumbnail_size: u32,
+}
+
+impl Default for MediaLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 4 * 1024 * 1024,
+            max_av_file_size: 16 * 1024 * 1024,
+            max_dimension: 10_000,
+            thumbnail_size: 250,
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Perceptual Hashing
+//!
+//! Developer Notes:
+//! - Two 64-bit fingerprints that survive re-encoding, resizing and
+//!   small edits, used to recognise banned images:
+//!   - pHash: sign of the low-frequency DCT coefficients of a 32x32
+//!     greyscale copy, against their median.
+//!   - dHash: brightness gradient between neighbouring pixels of a
+//!     9x8 greyscale copy.
+//! - Two images are compared by Hamming distance; `ImageHashes::distance`
+//!   takes the worse of the two, so both fingerprints must agree.
+//!
+//! End Notes:
+//! Hashes are computed from the oriented image, after metadata stripping.
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage};
+
+/// Side of the greyscale copy the DCT runs on.
+const PHASH_SIZE: usize = 32;
+
+/// Side of the low-frequency block the pHash bits come from.
+const PHASH_BLOCK: usize = 8;
+
+/// Perceptual fingerprints of one image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageHashes {
+    pub phash: u64,
+    pub dhash: u64,
+}
+
+impl ImageHashes {
+    /// Fingerprint `image`.
+    pub fn of(image: &DynamicImage) -> Self {
+        let gray = image.to_luma8();
+        Self {
+            phash: phash(&gray),
+            dhash: dhash(&gray),
+        }
+    }
+
+    /// Differing bits of the less similar fingerprint (0-64).
+    pub fn distance(&self, other: &ImageHashes) -> u32 {
+        hamming(self.phash, other.phash).max(hamming(self.dhash, other.dhash))
+    }
+}
+
+/// Number of differing bits.
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn dhash(gray: &GrayImage) -> u64 {
+    let small = imageops::resize(gray, 9, 8, FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x + 1, y)[0] > small.get_pixel(x, y)[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+fn phash(gray: &GrayImage) -> u64 {
+    let n = PHASH_SIZE;
+    let small = imageops::resize(gray, n as u32, n as u32, FilterType::Triangle);
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    // cos((2x + 1) * u * pi / 2n), for the low frequencies only.
+    let cos: Vec<f64> = (0..PHASH_BLOCK)
+        .flat_map(|u| {
+            (0..n).map(move |x| {
+                ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * n) as f64).cos()
+            })
+        })
+        .collect();
+
+    // Separable 2D DCT-II: rows first, then columns of the block.
+    let mut rows = vec![0.0; n * PHASH_BLOCK];
+    for y in 0..n {
+        for u in 0..PHASH_BLOCK {
+            rows[y * PHASH_BLOCK + u] = (0..n).map(|x| pixels[y * n + x] * cos[u * n + x]).sum();
+        }
+    }
+
+    let mut block = [0.0; PHASH_BLOCK * PHASH_BLOCK];
+    for v in 0..PHASH_BLOCK {
+        for u in 0..PHASH_BLOCK {
+            block[v * PHASH_BLOCK + u] =
+                (0..n).map(|y| rows[y * PHASH_BLOCK + u] * cos[v * n + y]).sum();
+        }
+    }
+
+    // The DC term is overall brightness; leave it out of the median.
+    let mut ac = block[1..].to_vec();
+    ac.sort_by(f64::total_cmp);
+    let median = (ac[ac.len() / 2 - 1] + ac[ac.len() / 2]) / 2.0;
+
+    block
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A 256x192 picture with enough structure to fingerprint.
+    fn picture(seed: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(256, 192, |x, y| {
+            let wave = ((x as f64 / (12.0 + seed as f64)).sin() * 60.0) as i32;
+            let v = ((x * 3 + y * (2 + seed)) % 256) as i32;
+            let c = (v + wave).clamp(0, 255) as u8;
+            Rgb([c, c / 2, 255 - c])
+        }))
+    }
+
+    fn reencoded(image: &DynamicImage) -> DynamicImage {
+        let mut data = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 40)
+            .encode_image(&image.to_rgb8())
+            .unwrap();
+        image::load_from_memory(&data).unwrap()
+    }
+
+    #[test]
+    fn survives_resizing_and_reencoding() {
+        let original = ImageHashes::of(&picture(1));
+        let altered = ImageHashes::of(&reencoded(&picture(1).resize_exact(300, 150, FilterType::Lanczos3)));
+
+        assert!(original.distance(&altered) <= 8, "distance {}", original.distance(&altered));
+        assert_eq!(original.distance(&original), 0);
+    }
+
+    #[test]
+    fn different_images_are_far_apart() {
+        let a = ImageHashes::of(&picture(1));
+        let b = ImageHashes::of(&picture(7).fliph());
+
+        assert!(a.distance(&b) > 16, "distance {}", a.distance(&b));
+        assert_eq!(hamming(0b1011, 0b0110), 3);
+    }
+}
@@ -0,0 +1,601 @@
+//! Audio and Video Probing
+//!
+//! Developer Notes:
+//! - Walks the container of an audio or video upload to validate it and
+//!   read its duration, codecs and picture size.
+//! - Pure Rust and read-only: no media data is decoded and no external
+//!   tool runs on untrusted input.
+//! - Containers: WebM (EBML), MP4 (ISO BMFF), MP3 (MPEG audio frames),
+//!   Ogg (Opus, Vorbis or FLAC streams) and native FLAC.
+//! - Codec identifiers are mapped onto the short names in
+//!   `KNOWN_CODECS`; anything else is refused, so boards can allow
+//!   codecs by name.
+//!
+//! End Notes:
+//! Every read is bounds-checked; a structure that does not add up is a
+//! `Decode` error, never a panic.
+
+use std::time::Duration;
+
+use crate::{MediaClass, MediaError, MediaKind};
+
+/// Codec names a probe can report.
+pub const KNOWN_CODECS: &[&str] = &[
+    "vp8", "vp9", "av1", "h264", "hevc", "opus", "vorbis", "aac", "mp3", "flac",
+];
+
+/// What probing an audio or video file found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvInfo {
+    pub kind: MediaKind,
+    pub duration: Duration,
+    /// Codec of the first video track, if any.
+    pub video_codec: Option<&'static str>,
+    /// Codec of the first audio track, if any.
+    pub audio_codec: Option<&'static str>,
+    /// Picture size of the video track; 0 for audio.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AvInfo {
+    /// The codecs in use, video first.
+    pub fn codecs(&self) -> impl Iterator<Item = &'static str> {
+        self.video_codec.into_iter().chain(self.audio_codec)
+    }
+}
+
+/// What a container parser found, before codec ids are checked.
+#[derive(Debug, Default)]
+struct Raw {
+    duration: Option<Duration>,
+    video: Option<String>,
+    audio: Option<String>,
+    width: u32,
+    height: u32,
+}
+
+/// Map a container's codec identifier onto a `KNOWN_CODECS` name.
+fn codec_name(id: &str) -> Option<&'static str> {
+    Some(match id {
+        "V_VP8" => "vp8",
+        "V_VP9" | "vp09" => "vp9",
+        "V_AV1" | "av01" => "av1",
+        "V_MPEG4/ISO/AVC" | "avc1" | "avc3" => "h264",
+        "V_MPEGH/ISO/HEVC" | "hvc1" | "hev1" => "hevc",
+        "A_OPUS" | "Opus" | "opus" => "opus",
+        "A_VORBIS" | "vorbis" => "vorbis",
+        "A_AAC" | "mp4a" => "aac",
+        "A_MPEG/L3" | ".mp3" | "mp3" => "mp3",
+        "A_FLAC" | "fLaC" | "flac" => "flac",
+        _ => return None,
+    })
+}
+
+fn malformed(kind: MediaKind) -> MediaError {
+    MediaError::Decode(format!("malformed {} structure", kind.extension()))
+}
+
+/// Validate an audio or video file and describe it.
+pub fn probe(data: &[u8], kind: MediaKind) -> Result<AvInfo, MediaError> {
+    let raw = match kind {
+        MediaKind::WebM => webm(data)?,
+        MediaKind::Mp4 => mp4(data),
+        MediaKind::Mp3 => mp3(data),
+        MediaKind::Ogg => ogg(data),
+        MediaKind::Flac => flac(data),
+        _ => return Err(MediaError::UnsupportedType),
+    }
+    .ok_or_else(|| malformed(kind))?;
+
+    let name = |id: Option<String>| {
+        id.map(|id| codec_name(&id).ok_or(MediaError::UnsupportedCodec(id)))
+            .transpose()
+    };
+    let video_codec = name(raw.video)?;
+    let audio_codec = name(raw.audio)?;
+
+    let complete = match kind.class() {
+        MediaClass::Video => video_codec.is_some(),
+        _ => audio_codec.is_some(),
+    };
+    if !complete {
+        return Err(MediaError::Decode(format!("no playable track in {} file", kind.extension())));
+    }
+
+    Ok(AvInfo {
+        kind,
+        duration: raw
+            .duration
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| MediaError::Decode("unknown duration".into()))?,
+        video_codec,
+        audio_codec,
+        width: raw.width,
+        height: raw.height,
+    })
+}
+
+/// `count` ticks of a clock running at `rate` per second.
+fn ticks(count: u64, rate: u64) -> Option<Duration> {
+    if rate == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(count / rate) + Duration::from_nanos((count % rate) * 1_000_000_000 / rate))
+}
+
+fn be_uint(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() > 8 {
+        return None;
+    }
+    Some(bytes.iter().fold(0, |v, &b| (v << 8) | b as u64))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+// =========================
+// WebM
+// =========================
+
+const EBML_HEADER: u64 = 0x1A45DFA3;
+const EBML_DOC_TYPE: u64 = 0x4282;
+const SEGMENT: u64 = 0x18538067;
+const INFO: u64 = 0x1549A966;
+const TIMECODE_SCALE: u64 = 0x2AD7B1;
+const DURATION: u64 = 0x4489;
+const TRACKS: u64 = 0x1654AE6B;
+const TRACK_ENTRY: u64 = 0xAE;
+const TRACK_TYPE: u64 = 0x83;
+const CODEC_ID: u64 = 0x86;
+const VIDEO: u64 = 0xE0;
+const PIXEL_WIDTH: u64 = 0xB0;
+const PIXEL_HEIGHT: u64 = 0xBA;
+
+/// Read an EBML variable-length integer at `at`: `(value, length)`.
+///
+/// Element ids keep their length marker; sizes drop it. A size with
+/// every value bit set means "unknown", returned as `None` in the value.
+fn vint(data: &[u8], at: usize, is_id: bool) -> Option<(Option<u64>, usize)> {
+    let first = *data.get(at)?;
+    if first == 0 {
+        return None;
+    }
+    let len = first.leading_zeros() as usize + 1;
+    let rest = be_uint(data.get(at + 1..at + len)?)?;
+    let marker = if is_id { first as u64 } else { first as u64 & (0xFF >> len) };
+    let value = (marker << (8 * (len - 1))) | rest;
+
+    let unknown = !is_id && value == (1u64 << (7 * len)) - 1;
+    Some(((!unknown).then_some(value), len))
+}
+
+/// The child elements of an EBML master element, as `(id, payload)`.
+///
+/// An unknown size runs to the end of the parent, as live streams write
+/// their Segment.
+fn ebml_children(data: &[u8]) -> Option<Vec<(u64, &[u8])>> {
+    let mut children = Vec::new();
+    let mut at = 0;
+
+    while at < data.len() {
+        let (id, id_len) = vint(data, at, true)?;
+        let (size, size_len) = vint(data, at + id_len, false)?;
+        let start = at + id_len + size_len;
+        let end = match size {
+            Some(size) => start.checked_add(usize::try_from(size).ok()?)?,
+            None => data.len(),
+        };
+        children.push((id?, data.get(start..end)?));
+        at = end;
+    }
+
+    Some(children)
+}
+
+fn ebml_child<'a>(children: &[(u64, &'a [u8])], id: u64) -> Option<&'a [u8]> {
+    children.iter().find(|(child, _)| *child == id).map(|(_, payload)| *payload)
+}
+
+fn webm(data: &[u8]) -> Result<Option<Raw>, MediaError> {
+    let Some(top) = ebml_children(data) else {
+        return Ok(None);
+    };
+    let doc_type = top
+        .first()
+        .filter(|(id, _)| *id == EBML_HEADER)
+        .and_then(|(_, header)| ebml_child(&ebml_children(header)?, EBML_DOC_TYPE));
+    match doc_type {
+        Some(b"webm") => {}
+        Some(_) => return Err(MediaError::UnsupportedType),
+        None => return Ok(None),
+    }
+
+    Ok(webm_segment(&top))
+}
+
+fn webm_segment(top: &[(u64, &[u8])]) -> Option<Raw> {
+    let segment = ebml_children(ebml_child(top, SEGMENT)?)?;
+    let info = ebml_children(ebml_child(&segment, INFO)?)?;
+    let tracks = ebml_children(ebml_child(&segment, TRACKS)?)?;
+    let mut raw = Raw::default();
+
+    let scale = match ebml_child(&info, TIMECODE_SCALE) {
+        Some(scale) => be_uint(scale)?,
+        None => 1_000_000,
+    };
+    raw.duration = match ebml_child(&info, DURATION) {
+        Some(&[a, b, c, d]) => Some(f32::from_be_bytes([a, b, c, d]) as f64),
+        Some(bytes) => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+        None => None,
+    }
+    .map(|units| Duration::try_from_secs_f64(units * scale as f64 / 1e9))
+    .transpose()
+    .ok()?;
+
+    for (id, entry) in tracks {
+        if id != TRACK_ENTRY {
+            continue;
+        }
+        let entry = ebml_children(entry)?;
+        let codec = String::from_utf8_lossy(ebml_child(&entry, CODEC_ID)?).into_owned();
+
+        match be_uint(ebml_child(&entry, TRACK_TYPE)?)? {
+            1 if raw.video.is_none() => {
+                let video = ebml_children(ebml_child(&entry, VIDEO)?)?;
+                raw.width = be_uint(ebml_child(&video, PIXEL_WIDTH)?)?.try_into().ok()?;
+                raw.height = be_uint(ebml_child(&video, PIXEL_HEIGHT)?)?.try_into().ok()?;
+                raw.video = Some(codec);
+            }
+            2 if raw.audio.is_none() => raw.audio = Some(codec),
+            _ => {}
+        }
+    }
+
+    Some(raw)
+}
+
+// =========================
+// MP4
+// =========================
+
+/// The boxes inside `data`, as `(type, payload)`.
+fn mp4_boxes(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    let mut boxes = Vec::new();
+    let mut at = 0;
+
+    while at < data.len() {
+        let kind: [u8; 4] = data.get(at + 4..at + 8)?.try_into().ok()?;
+        let (header, size) = match be32(data, at)? {
+            0 => (8, data.len() - at),
+            1 => (16, usize::try_from(be64(data, at + 8)?).ok()?),
+            size => (8, size as usize),
+        };
+        if size < header {
+            return None;
+        }
+        boxes.push((kind, data.get(at + header..at.checked_add(size)?)?));
+        at += size;
+    }
+
+    Some(boxes)
+}
+
+fn mp4_child<'a>(boxes: &[([u8; 4], &'a [u8])], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes.iter().find(|(child, _)| child == kind).map(|(_, payload)| *payload)
+}
+
+/// Follow a path of nested boxes.
+fn mp4_path<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter()
+        .try_fold(data, |payload, kind| mp4_child(&mp4_boxes(payload)?, kind))
+}
+
+fn mp4(data: &[u8]) -> Option<Raw> {
+    let top = mp4_boxes(data)?;
+    let moov = mp4_boxes(mp4_child(&top, b"moov")?)?;
+    let mut raw = Raw::default();
+
+    let mvhd = mp4_child(&moov, b"mvhd")?;
+    let (timescale, duration) = match mvhd.first()? {
+        0 => (be32(mvhd, 12)?, be32(mvhd, 16)? as u64),
+        1 => (be32(mvhd, 20)?, be64(mvhd, 24)?),
+        _ => return None,
+    };
+    raw.duration = ticks(duration, timescale as u64);
+
+    for (kind, trak) in &moov {
+        if kind != b"trak" {
+            continue;
+        }
+        let handler = mp4_path(trak, &[b"mdia", b"hdlr"])?.get(8..12)?;
+        let stsd = mp4_path(trak, &[b"mdia", b"minf", b"stbl", b"stsd"])?;
+        let codec = String::from_utf8_lossy(stsd.get(12..16)?).into_owned();
+
+        match handler {
+            b"vide" if raw.video.is_none() => {
+                let tkhd = mp4_child(&mp4_boxes(trak)?, b"tkhd")?;
+                let at = if tkhd.first()? == &1 { 88 } else { 76 };
+                raw.width = be32(tkhd, at)? >> 16;
+                raw.height = be32(tkhd, at + 4)? >> 16;
+                raw.video = Some(codec);
+            }
+            b"soun" if raw.audio.is_none() => raw.audio = Some(codec),
+            _ => {}
+        }
+    }
+
+    Some(raw)
+}
+
+// =========================
+// MP3
+// =========================
+
+/// Layer III bitrates in kbit/s, by bitrate index.
+const MP3_BITRATES_V1: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MP3_BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// A decoded MPEG audio layer III frame header.
+struct Mp3Frame {
+    length: usize,
+    samples: u64,
+    sample_rate: u32,
+    /// Offset of the Xing/Info tag, if this frame carries one.
+    tag_at: usize,
+}
+
+fn mp3_frame(header: &[u8]) -> Option<Mp3Frame> {
+    let &[0xFF, b1, b2, b3] = header.get(..4)? else {
+        return None;
+    };
+    if b1 & 0xE0 != 0xE0 || b1 & 0x06 != 0x02 {
+        return None;
+    }
+    let version = (b1 >> 3) & 0x03;
+    let bitrate_index = (b2 >> 4) as usize;
+    let rate_index = ((b2 >> 2) & 0x03) as usize;
+    if version == 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let bitrate = if mpeg1 { MP3_BITRATES_V1 } else { MP3_BITRATES_V2 }[bitrate_index] * 1000;
+    let sample_rate = match version {
+        3 => [44100, 48000, 32000][rate_index],
+        2 => [22050, 24000, 16000][rate_index],
+        _ => [11025, 12000, 8000][rate_index],
+    };
+    let padding = ((b2 >> 1) & 1) as usize;
+    let mono = b3 >> 6 == 3;
+
+    let (samples, factor, side_info) = match (mpeg1, mono) {
+        (true, false) => (1152, 144, 32),
+        (true, true) => (1152, 144, 17),
+        (false, false) => (576, 72, 17),
+        (false, true) => (576, 72, 9),
+    };
+
+    Some(Mp3Frame {
+        length: (factor * bitrate / sample_rate) as usize + padding,
+        samples,
+        sample_rate,
+        tag_at: 4 + side_info,
+    })
+}
+
+fn mp3(data: &[u8]) -> Option<Raw> {
+    let mut at = 0;
+    if data.starts_with(b"ID3") {
+        let size = data.get(6..10)?.iter().fold(0usize, |v, &b| (v << 7) | (b & 0x7F) as usize);
+        let footer = if data.get(5)? & 0x10 != 0 { 10 } else { 0 };
+        at = 10 + size + footer;
+    }
+
+    let mut samples = 0;
+    let mut sample_rate = 0;
+    let mut frames = 0;
+
+    while let Some(frame) = data.get(at..).and_then(mp3_frame) {
+        let body = data.get(at..at + frame.length)?;
+        // A Xing/Info frame describes the stream and holds no audio.
+        let is_tag = matches!(body.get(frame.tag_at..frame.tag_at + 4), Some(b"Xing" | b"Info"));
+        if frames > 0 && frame.sample_rate != sample_rate {
+            return None;
+        }
+
+        sample_rate = frame.sample_rate;
+        if !is_tag {
+            samples += frame.samples;
+        }
+        frames += 1;
+        at += frame.length;
+    }
+
+    // After the last frame only tags may follow.
+    let rest = data.get(at..)?;
+    if frames == 0 || !(rest.is_empty() || rest.starts_with(b"TAG") || rest.starts_with(b"APETAGEX")) {
+        return None;
+    }
+
+    Some(Raw {
+        duration: ticks(samples, sample_rate as u64),
+        audio: Some("mp3".into()),
+        ..Raw::default()
+    })
+}
+
+// =========================
+// Ogg
+// =========================
+
+/// One Ogg page: its granule position, stream serial and body.
+struct OggPage<'a> {
+    flags: u8,
+    granule: i64,
+    serial: u32,
+    body: &'a [u8],
+}
+
+fn ogg_pages(data: &[u8]) -> Option<Vec<OggPage<'_>>> {
+    let mut pages = Vec::new();
+    let mut at = 0;
+
+    while at < data.len() {
+        let header = data.get(at..at + 27)?;
+        if &header[..4] != b"OggS" || header[4] != 0 {
+            return None;
+        }
+        let segments = header[26] as usize;
+        let lacing = data.get(at + 27..at + 27 + segments)?;
+        let start = at + 27 + segments;
+        let length: usize = lacing.iter().map(|&l| l as usize).sum();
+
+        pages.push(OggPage {
+            flags: header[5],
+            granule: i64::from_le_bytes(header[6..14].try_into().ok()?),
+            serial: le32(header, 14)?,
+            body: data.get(start..start + length)?,
+        });
+        at = start + length;
+    }
+
+    Some(pages)
+}
+
+fn ogg(data: &[u8]) -> Option<Raw> {
+    let pages = ogg_pages(data)?;
+    // The first beginning-of-stream page names the first stream's codec.
+    let first = pages.first().filter(|page| page.flags & 0x02 != 0)?;
+    let head = first.body;
+
+    let (codec, rate, pre_skip) = if head.starts_with(b"OpusHead") {
+        ("opus", 48_000, le16(head, 10)? as u64)
+    } else if head.starts_with(b"\x01vorbis") {
+        ("vorbis", le32(head, 12)? as u64, 0)
+    } else if head.starts_with(b"\x7FFLAC") {
+        ("flac", be64(head, 27)? >> 44, 0)
+    } else if head.starts_with(b"\x80theora") {
+        ("theora", 0, 0)
+    } else {
+        ("unknown", 0, 0)
+    };
+
+    let granule = pages
+        .iter()
+        .filter(|page| page.serial == first.serial && page.granule >= 0)
+        .map(|page| page.granule as u64)
+        .max()?;
+
+    Some(Raw {
+        duration: ticks(granule.saturating_sub(pre_skip), rate),
+        audio: Some(codec.into()),
+        ..Raw::default()
+    })
+}
+
+// =========================
+// FLAC
+// =========================
+
+fn flac(data: &[u8]) -> Option<Raw> {
+    // fLaC, then the STREAMINFO block, which must come first.
+    let block = data.get(4..8)?;
+    if block[0] & 0x7F != 0 || be_uint(&block[1..])? < 34 {
+        return None;
+    }
+    let packed = be64(data, 8 + 10)?;
+    let rate = packed >> 44;
+    let samples = packed & ((1 << 36) - 1);
+
+    Some(Raw {
+        duration: ticks(samples, rate),
+        audio: Some("flac".into()),
+        ..Raw::default()
+    })
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn corpus(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/testdata/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+    }
+
+    #[test]
+    fn probes_every_container() {
+        let cases = [
+            ("clip.webm", MediaKind::WebM, 12_500, Some("vp9"), Some("opus"), (640, 360)),
+            ("clip.mp4", MediaKind::Mp4, 3_000, Some("h264"), Some("aac"), (320, 240)),
+            ("tone.mp3", MediaKind::Mp3, 2_612, None, Some("mp3"), (0, 0)),
+            ("tone.ogg", MediaKind::Ogg, 2_000, None, Some("opus"), (0, 0)),
+            ("tone.flac", MediaKind::Flac, 1_500, None, Some("flac"), (0, 0)),
+        ];
+
+        for (name, kind, millis, video, audio, size) in cases {
+            let info = probe(&corpus(name), kind).unwrap();
+            assert_eq!(info.duration.as_millis(), millis, "{name}");
+            assert_eq!((info.video_codec, info.audio_codec), (video, audio), "{name}");
+            assert_eq!((info.width, info.height), size, "{name}");
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_vouch_for() {
+        assert!(matches!(
+            probe(&corpus("theora.ogg"), MediaKind::Ogg),
+            Err(MediaError::UnsupportedCodec(codec)) if codec == "theora"
+        ));
+        assert!(matches!(probe(&corpus("live.webm"), MediaKind::WebM), Err(MediaError::Decode(_))));
+
+        let clip = corpus("clip.mp4");
+        assert!(matches!(probe(&clip[..clip.len() - 9], MediaKind::Mp4), Err(MediaError::Decode(_))));
+        let mut tone = corpus("tone.mp3");
+        tone.truncate(tone.len() - 300);
+        assert!(probe(&tone, MediaKind::Mp3).is_err());
+        assert!(probe(b"fLaC\x80\0\0", MediaKind::Flac).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn damaged_files_never_panic(
+            file in 0..5usize,
+            damage in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+        ) {
+            let (name, kind) = [
+                ("clip.webm", MediaKind::WebM),
+                ("clip.mp4", MediaKind::Mp4),
+                ("tone.mp3", MediaKind::Mp3),
+                ("tone.ogg", MediaKind::Ogg),
+                ("tone.flac", MediaKind::Flac),
+            ][file];
+            let mut data = corpus(name);
+            for (at, byte) in damage {
+                let at = at.index(data.len());
+                data[at] = byte;
+            }
+
+            let _ = probe(&data, kind);
+            let _ = probe(&data[..data.len() / 2], kind);
+        }
+    }
+}
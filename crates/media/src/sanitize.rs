@@ -0,0 +1,407 @@
+//! Metadata Stripping
+//!
+//! Developer Notes:
+//! - Removes EXIF, XMP, IPTC, comments and embedded thumbnails from
+//!   uploads without re-encoding: only container blocks are dropped,
+//!   compressed image data is copied byte for byte.
+//! - Every format is filtered by allow-list; unknown blocks are dropped.
+//! - Bytes after the end of the image (appended archives, trackers) are
+//!   dropped too.
+//! - JPEG keeps a minimal EXIF block holding only the orientation tag,
+//!   so phone photos are not shown sideways.
+//!
+//! End Notes:
+//! Structural damage is reported as `MediaError::Decode`.
+
+use crate::{MediaError, MediaKind};
+
+/// EXIF orientation tag id.
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// PNG chunks kept in the output; everything else is metadata or unknown.
+const PNG_KEEP: &[&[u8; 4]] = &[
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"iCCP",
+    b"sBIT", b"pHYs", b"bKGD", b"hIST", b"acTL", b"fcTL", b"fdAT",
+];
+
+/// WebP chunks kept in the output.
+const WEBP_KEEP: &[&[u8; 4]] = &[b"VP8X", b"VP8 ", b"VP8L", b"ALPH", b"ANIM", b"ANMF", b"ICCP"];
+
+/// VP8X flag bits announcing EXIF and XMP chunks.
+const WEBP_EXIF_FLAG: u8 = 0x08;
+const WEBP_XMP_FLAG: u8 = 0x04;
+
+/// GIF application extensions kept (animation looping).
+const GIF_KEEP_APPS: &[&[u8; 11]] = &[b"NETSCAPE2.0", b"ANIMEXTS1.0"];
+
+fn malformed(kind: MediaKind) -> MediaError {
+    MediaError::Decode(format!("malformed {} structure", kind.extension()))
+}
+
+/// Return `data` with all metadata removed.
+pub fn strip_metadata(data: &[u8], kind: MediaKind) -> Result<Vec<u8>, MediaError> {
+    let stripped = match kind {
+        MediaKind::Jpeg => strip_jpeg(data),
+        MediaKind::Png => strip_png(data),
+        MediaKind::WebP => strip_webp(data),
+        MediaKind::Gif => strip_gif(data),
+        _ => return Err(MediaError::UnsupportedType),
+    };
+    stripped.ok_or_else(|| malformed(kind))
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+// =========================
+// JPEG
+// =========================
+
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    let mut i = 2;
+
+    loop {
+        // Markers may be preceded by any number of 0xFF fill bytes.
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        while *data.get(i + 1)? == 0xFF {
+            i += 1;
+        }
+        let marker = data[i + 1];
+
+        match marker {
+            0xD9 => {
+                out.extend_from_slice(&[0xFF, 0xD9]);
+                return Some(out);
+            }
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&[0xFF, marker]);
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let len = be16(data, i + 2)? as usize;
+        let end = i + 2 + len;
+        if len < 2 || end > data.len() {
+            return None;
+        }
+        let payload = &data[i + 4..end];
+
+        match marker {
+            0xE0 if payload.starts_with(b"JFIF\0") && payload.len() >= 14 => {
+                // Rewrite without the optional JFIF thumbnail.
+                let mut jfif = payload[..12].to_vec();
+                jfif.extend_from_slice(&[0, 0]);
+                push_segment(&mut out, 0xE0, &jfif);
+            }
+            0xE1 if payload.starts_with(b"Exif\0\0") => {
+                if let Some(orientation) = exif_orientation(&payload[6..]).filter(|&o| o != 1) {
+                    push_segment(&mut out, 0xE1, &orientation_exif(orientation));
+                }
+            }
+            0xE2 if payload.starts_with(b"ICC_PROFILE\0") => out.extend_from_slice(&data[i..end]),
+            0xEE if payload.starts_with(b"Adobe") => out.extend_from_slice(&data[i..end]),
+            0xE0..=0xEF | 0xFE => {}
+            0xDA => {
+                out.extend_from_slice(&data[i..end]);
+                let scan_end = entropy_end(data, end)?;
+                out.extend_from_slice(&data[end..scan_end]);
+                i = scan_end;
+                continue;
+            }
+            _ => out.extend_from_slice(&data[i..end]),
+        }
+
+        i = end;
+    }
+}
+
+fn push_segment(out: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Offset of the first marker after entropy-coded scan data at `from`.
+fn entropy_end(data: &[u8], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i + 1 < data.len() {
+        if data[i] == 0xFF {
+            match data[i + 1] {
+                0x00 | 0xD0..=0xD7 | 0xFF => {}
+                _ => return Some(i),
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Orientation (1-8) from a TIFF-structured EXIF block, if present.
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let little = match tiff.get(..4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+
+    (0..count)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG) && u16_at(entry + 2) == Some(3))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|o| (1..=8).contains(o))
+}
+
+/// A minimal big-endian EXIF block holding only the orientation tag.
+fn orientation_exif(orientation: u16) -> Vec<u8> {
+    let mut exif = b"Exif\0\0MM\0*".to_vec();
+    exif.extend_from_slice(&8u32.to_be_bytes());
+    exif.extend_from_slice(&1u16.to_be_bytes());
+    exif.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    exif.extend_from_slice(&3u16.to_be_bytes());
+    exif.extend_from_slice(&1u32.to_be_bytes());
+    exif.extend_from_slice(&orientation.to_be_bytes());
+    exif.extend_from_slice(&[0, 0]);
+    exif.extend_from_slice(&0u32.to_be_bytes());
+    exif
+}
+
+// =========================
+// PNG
+// =========================
+
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    let mut i = SIGNATURE.len();
+
+    loop {
+        let len = be32(data, i)? as usize;
+        let kind: &[u8; 4] = data.get(i + 4..i + 8)?.try_into().ok()?;
+        let end = i.checked_add(12)?.checked_add(len)?;
+        if end > data.len() {
+            return None;
+        }
+
+        if PNG_KEEP.contains(&kind) {
+            out.extend_from_slice(&data[i..end]);
+        }
+        if kind == b"IEND" {
+            return Some(out);
+        }
+        i = end;
+    }
+}
+
+// =========================
+// WebP
+// =========================
+
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let riff_end = (le32(data, 4)? as usize).checked_add(8)?;
+    let body = data.get(12..riff_end)?;
+
+    let mut chunks = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        let fourcc: &[u8; 4] = body.get(i..i + 4)?.try_into().ok()?;
+        let len = le32(body, i + 4)? as usize;
+        let chunk = body.get(i..(i + 8).checked_add(len)?)?;
+
+        if WEBP_KEEP.contains(&fourcc) {
+            let start = chunks.len();
+            chunks.extend_from_slice(chunk);
+            // Chunks are padded to even length; the last pad may be missing.
+            if len % 2 == 1 {
+                chunks.push(0);
+            }
+            if fourcc == b"VP8X" {
+                *chunks.get_mut(start + 8)? &= !(WEBP_EXIF_FLAG | WEBP_XMP_FLAG);
+            }
+        }
+        i += chunk.len() + len % 2;
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&((chunks.len() + 4) as u32).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend_from_slice(&chunks);
+    Some(out)
+}
+
+// =========================
+// GIF
+// =========================
+
+/// End offset of a run of GIF data sub-blocks starting at `at`.
+fn gif_sub_blocks_end(data: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(at)? as usize;
+        at += 1 + len;
+        if len == 0 {
+            return (at <= data.len()).then_some(at);
+        }
+    }
+}
+
+fn gif_color_table_len(packed: u8) -> usize {
+    if packed & 0x80 != 0 {
+        3 * (1 << ((packed & 0x07) + 1))
+    } else {
+        0
+    }
+}
+
+fn strip_gif(data: &[u8]) -> Option<Vec<u8>> {
+    let header_end = 13 + gif_color_table_len(*data.get(10)?);
+    let mut out = data.get(..header_end)?.to_vec();
+    let mut i = header_end;
+
+    loop {
+        match *data.get(i)? {
+            0x3B => {
+                out.push(0x3B);
+                return Some(out);
+            }
+            0x2C => {
+                let table = gif_color_table_len(*data.get(i + 9)?);
+                // Descriptor, local color table, LZW minimum code size.
+                let end = gif_sub_blocks_end(data, i + 10 + table + 1)?;
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            0x21 => {
+                let label = *data.get(i + 1)?;
+                let end = gif_sub_blocks_end(data, i + 2)?;
+                let keep = match label {
+                    0xF9 | 0x01 => true,
+                    0xFF => data
+                        .get(i + 3..i + 14)
+                        .is_some_and(|id| GIF_KEEP_APPS.iter().any(|keep| keep.as_slice() == id)),
+                    _ => false,
+                };
+                if keep {
+                    out.extend_from_slice(&data[i..end]);
+                }
+                i = end;
+            }
+            _ => return None,
+        }
+    }
+}
+
+
+/// TESTS
+///
+/// The corpus in `testdata/` (see `make_corpus.py`) tags every metadata
+/// block with a `SECRET-` string.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{process_image, sniff, MediaLimits};
+
+    const CORPUS: &[(&str, &[u8])] = &[
+        ("metadata.jpg", include_bytes!("../testdata/metadata.jpg")),
+        ("metadata.png", include_bytes!("../testdata/metadata.png")),
+        ("metadata.webp", include_bytes!("../testdata/metadata.webp")),
+        ("metadata.gif", include_bytes!("../testdata/metadata.gif")),
+    ];
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn corpus_metadata_is_removed() {
+        for (name, data) in CORPUS {
+            let kind = sniff(data).unwrap();
+            assert!(contains(data, b"SECRET-"), "{name} has no metadata to strip");
+
+            let stripped = strip_metadata(data, kind).unwrap();
+            assert!(!contains(&stripped, b"SECRET-"), "{name} still leaks metadata");
+            assert!(!contains(&stripped, b"zTXt") && !contains(&stripped, b"tIME"), "{name}");
+
+            let processed = process_image(&stripped, &MediaLimits::default()).unwrap();
+            assert!(processed.width > 0, "{name} no longer decodes");
+            assert_eq!(strip_metadata(&stripped, kind).unwrap(), stripped, "{name} not idempotent");
+        }
+    }
+
+    #[test]
+    fn clean_images_pass_through_unchanged() {
+        let png = include_bytes!("../testdata/base.png");
+        let gif = include_bytes!("../testdata/base.gif");
+
+        assert_eq!(strip_metadata(png, MediaKind::Png).unwrap(), png);
+        assert_eq!(strip_metadata(gif, MediaKind::Gif).unwrap(), gif);
+    }
+
+    #[test]
+    fn jpeg_orientation_survives() {
+        let stripped = strip_metadata(CORPUS[0].1, MediaKind::Jpeg).unwrap();
+        let exif_at = stripped.windows(6).position(|w| w == b"Exif\0\0").unwrap();
+
+        assert_eq!(exif_orientation(&stripped[exif_at + 6..]), Some(6));
+
+        // Orientation 6 rotates the 16x8 source a quarter turn.
+        let processed = process_image(&stripped, &MediaLimits::default()).unwrap();
+        assert_eq!((processed.width, processed.height), (8, 16));
+    }
+
+    #[test]
+    fn webp_flags_are_cleared() {
+        let stripped = strip_metadata(CORPUS[2].1, MediaKind::WebP).unwrap();
+
+        assert_eq!(&stripped[12..16], b"VP8X");
+        assert_eq!(stripped[20] & (WEBP_EXIF_FLAG | WEBP_XMP_FLAG), 0);
+        assert_eq!(le32(&stripped, 4).unwrap() as usize, stripped.len() - 8);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        for (name, data) in CORPUS {
+            let kind = sniff(data).unwrap();
+            let cut = &data[..data.len() / 2];
+            assert!(
+                matches!(strip_metadata(cut, kind), Err(MediaError::Decode(_))),
+                "{name} truncated was accepted"
+            );
+        }
+    }
+}
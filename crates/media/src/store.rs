@@ -0,0 +1,247 @@
+//! Media Store
+//!
+//! Developer Notes:
+//! - `MediaStore` is the backend-neutral interface for uploaded files;
+//!   `LocalMediaStore` keeps them in a directory on local disk.
+//! - Files are content-addressed: keys are the SHA-256 of the stored
+//!   bytes (see `content_key`), so identical uploads share one blob.
+//! - Originals and thumbnails live in separate buckets, served under
+//!   `/media/src/` and `/media/thumb/`.
+//! - Keys are flat file names; anything that could escape the directory
+//!   is refused.
+//! - Files are written to a temporary name and renamed into place, so a
+//!   crash never leaves a half-written file under a served name.
+//!
+//! End Notes:
+//! No knowledge of posts or the database here; which blobs are still
+//! referenced is decided by the caller (see `gc`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::{MediaError, MediaKind};
+
+/// The two kinds of file a store holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bucket {
+    /// Uploaded files, as stored after sanitizing.
+    Originals,
+    /// Generated thumbnails.
+    Thumbnails,
+}
+
+impl Bucket {
+    /// Directory name, which is also the URL segment under `/media/`.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Bucket::Originals => "src",
+            Bucket::Thumbnails => "thumb",
+        }
+    }
+}
+
+/// A file found in a store.
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub key: String,
+    pub modified: SystemTime,
+}
+
+/// Storage for uploaded files and thumbnails.
+pub trait MediaStore: Send + Sync {
+    /// Store `data` under `key`.
+    ///
+    /// Keys are content hashes, so an existing key is kept as is and
+    /// only has its modification time refreshed.
+    fn save(&self, bucket: Bucket, key: &str, data: &[u8]) -> Result<(), MediaError>;
+
+    /// Remove `key`; a missing key is not an error.
+    fn delete(&self, bucket: Bucket, key: &str) -> Result<(), MediaError>;
+
+    /// Every file in a bucket.
+    fn list(&self, bucket: Bucket) -> Result<Vec<StoredBlob>, MediaError>;
+
+    /// When `key` was last written or saved again; `None` if missing.
+    fn modified(&self, bucket: Bucket, key: &str) -> Result<Option<SystemTime>, MediaError>;
+}
+
+/// Hex-encoded SHA-256 of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Content-addressed key for a file of type `kind`.
+pub fn content_key(sha256: &str, kind: MediaKind) -> String {
+    format!("{sha256}.{}", kind.extension())
+}
+
+/// A media directory on local disk.
+#[derive(Debug, Clone)]
+pub struct LocalMediaStore {
+    root: PathBuf,
+}
+
+impl LocalMediaStore {
+    /// Open (creating if needed) the media directory at `root`.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, MediaError> {
+        let root = root.into();
+        fs::create_dir_all(root.join(Bucket::Originals.dir_name()))?;
+        fs::create_dir_all(root.join(Bucket::Thumbnails.dir_name()))?;
+        Ok(Self { root })
+    }
+
+    /// The media directory itself.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Path of a stored file.
+    pub fn path(&self, bucket: Bucket, key: &str) -> Result<PathBuf, MediaError> {
+        Ok(self.root.join(bucket.dir_name()).join(check_key(key)?))
+    }
+}
+
+impl MediaStore for LocalMediaStore {
+    fn save(&self, bucket: Bucket, key: &str, data: &[u8]) -> Result<(), MediaError> {
+        let path = self.path(bucket, key)?;
+        if path.exists() {
+            // Keep the garbage collector's grace period from expiring
+            // between this upload and its database row.
+            fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(SystemTime::now())?;
+            return Ok(());
+        }
+        write_atomic(&path, data)
+    }
+
+    fn delete(&self, bucket: Bucket, key: &str) -> Result<(), MediaError> {
+        match fs::remove_file(self.path(bucket, key)?) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    fn list(&self, bucket: Bucket) -> Result<Vec<StoredBlob>, MediaError> {
+        let mut blobs = Vec::new();
+
+        for entry in fs::read_dir(self.root.join(bucket.dir_name()))? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let Some(key) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if metadata.is_file() && check_key(&key).is_ok() {
+                blobs.push(StoredBlob { key, modified: metadata.modified()? });
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    fn modified(&self, bucket: Bucket, key: &str) -> Result<Option<SystemTime>, MediaError> {
+        match fs::metadata(self.path(bucket, key)?) {
+            Ok(metadata) => Ok(Some(metadata.modified()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn check_key(key: &str) -> Result<&str, MediaError> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'.' | b'-' | b'_'));
+
+    if valid {
+        Ok(key)
+    } else {
+        Err(MediaError::InvalidKey(key.to_string()))
+    }
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), MediaError> {
+    // Unique per write, so concurrent uploads of one file cannot clash.
+    static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_originals_and_thumbnails() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::open(dir.path().join("media")).unwrap();
+
+        store.save(Bucket::Originals, "a.png", b"original").unwrap();
+        store.save(Bucket::Thumbnails, "a.jpg", b"thumb").unwrap();
+
+        assert_eq!(fs::read(store.path(Bucket::Originals, "a.png").unwrap()).unwrap(), b"original");
+        assert_eq!(fs::read(dir.path().join("media/thumb/a.jpg")).unwrap(), b"thumb");
+
+        let listed = store.list(Bucket::Originals).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].key, "a.png");
+
+        assert!(store.modified(Bucket::Originals, "a.png").unwrap().is_some());
+        store.delete(Bucket::Originals, "a.png").unwrap();
+        store.delete(Bucket::Originals, "a.png").unwrap();
+        assert!(store.list(Bucket::Originals).unwrap().is_empty());
+        assert!(store.modified(Bucket::Originals, "a.png").unwrap().is_none());
+    }
+
+    #[test]
+    fn identical_content_shares_a_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::open(dir.path()).unwrap();
+        let key = content_key(&sha256_hex(b"same"), MediaKind::Png);
+
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(key, content_key(&sha256_hex(b"same"), MediaKind::Png));
+        assert_ne!(key, content_key(&sha256_hex(b"other"), MediaKind::Png));
+
+        store.save(Bucket::Originals, &key, b"same").unwrap();
+        store.save(Bucket::Originals, &key, b"same").unwrap();
+        assert_eq!(store.list(Bucket::Originals).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn keys_cannot_escape_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::open(dir.path()).unwrap();
+
+        for key in ["../x", "a/b", "", ".hidden", "..", "a\\b"] {
+            assert!(matches!(
+                store.save(Bucket::Originals, key, b""),
+                Err(MediaError::InvalidKey(_))
+            ));
+            assert!(store.delete(Bucket::Thumbnails, key).is_err());
+        }
+    }
+}
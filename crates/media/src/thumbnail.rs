@@ -0,0 +1,210 @@
+//! Image Processing
+//!
+//! Developer Notes:
+//! - Validates an upload against `MediaLimits` and builds its thumbnail.
+//! - Metadata is stripped first; everything after works on (and
+//!   returns) the sanitized file.
+//! - Dimensions are read from the header before anything is decoded, so
+//!   oversized images are rejected without allocating their pixels.
+//! - Thumbnails are JPEG, or PNG when the source has transparency.
+//! - Animated GIFs are thumbnailed from their first frame.
+//! - EXIF orientation is applied, so reported dimensions and the
+//!   thumbnail match what a browser displays.
+//! - Perceptual hashes are taken from the same decoded image.
+//!
+//! End Notes:
+//! Thumbnails never upscale.
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+
+use crate::{sniff, strip_metadata, ImageHashes, MediaError, MediaKind, MediaLimits};
+
+const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+/// An encoded thumbnail.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub kind: MediaKind,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// A validated image upload.
+#[derive(Debug, Clone)]
+pub struct ProcessedImage {
+    pub kind: MediaKind,
+    /// Width as displayed, after orientation.
+    pub width: u32,
+    /// Height as displayed, after orientation.
+    pub height: u32,
+    /// The file with its metadata stripped; this is what gets stored.
+    pub data: Vec<u8>,
+    /// Perceptual fingerprints, for matching banned images.
+    pub hashes: ImageHashes,
+    pub thumbnail: Thumbnail,
+}
+
+fn reader(data: &[u8], format: ImageFormat) -> ImageReader<Cursor<&[u8]>> {
+    ImageReader::with_format(Cursor::new(data), format)
+}
+
+/// Validate `data` as an image within `limits` and thumbnail it.
+pub fn process_image(data: &[u8], limits: &MediaLimits) -> Result<ProcessedImage, MediaError> {
+    if data.len() > limits.max_file_size {
+        return Err(MediaError::TooLarge {
+            size: data.len(),
+            max: limits.max_file_size,
+        });
+    }
+
+    let kind = sniff(data).ok_or(MediaError::UnsupportedType)?;
+    let format = kind.image_format().ok_or(MediaError::UnsupportedType)?;
+    let data = strip_metadata(data, kind)?;
+
+    let (width, height) = reader(&data, format)
+        .into_dimensions()
+        .map_err(|e| MediaError::Decode(e.to_string()))?;
+    if width > limits.max_dimension || height > limits.max_dimension {
+        return Err(MediaError::DimensionsTooLarge {
+            width,
+            height,
+            max: limits.max_dimension,
+        });
+    }
+
+    let mut decode_limits = Limits::default();
+    decode_limits.max_image_width = Some(limits.max_dimension);
+    decode_limits.max_image_height = Some(limits.max_dimension);
+
+    let mut reader = reader(&data, format);
+    reader.limits(decode_limits);
+    let image = decode_oriented(reader).map_err(|e| MediaError::Decode(e.to_string()))?;
+
+    Ok(ProcessedImage {
+        kind,
+        width: image.width(),
+        height: image.height(),
+        thumbnail: make_thumbnail(&image, limits.thumbnail_size)?,
+        hashes: ImageHashes::of(&image),
+        data,
+    })
+}
+
+fn decode_oriented(reader: ImageReader<Cursor<&[u8]>>) -> image::ImageResult<DynamicImage> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+pub(crate) fn make_thumbnail(image: &DynamicImage, size: u32) -> Result<Thumbnail, MediaError> {
+    let thumb = if image.width() <= size && image.height() <= size {
+        image.clone()
+    } else {
+        image.thumbnail(size, size)
+    };
+
+    let mut data = Vec::new();
+    let kind = if thumb.color().has_alpha() {
+        DynamicImage::ImageRgba8(thumb.to_rgba8())
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .map_err(|e| MediaError::Decode(e.to_string()))?;
+        MediaKind::Png
+    } else {
+        JpegEncoder::new_with_quality(&mut data, THUMBNAIL_JPEG_QUALITY)
+            .encode_image(&thumb.to_rgb8())
+            .map_err(|e| MediaError::Decode(e.to_string()))?;
+        MediaKind::Jpeg
+    };
+
+    Ok(Thumbnail {
+        kind,
+        width: thumb.width(),
+        height: thumb.height(),
+        data,
+    })
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    /// Encode a solid `width`x`height` image in `format`.
+    fn encoded(width: u32, height: u32, format: ImageFormat, alpha: u8) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([200, 30, 30, alpha]));
+        let image = if format == ImageFormat::Jpeg {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8())
+        } else {
+            DynamicImage::ImageRgba8(image)
+        };
+
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    #[test]
+    fn thumbnails_every_supported_format() {
+        let limits = MediaLimits::default();
+
+        for (format, kind) in [
+            (ImageFormat::Jpeg, MediaKind::Jpeg),
+            (ImageFormat::Png, MediaKind::Png),
+            (ImageFormat::Gif, MediaKind::Gif),
+            (ImageFormat::WebP, MediaKind::WebP),
+        ] {
+            let processed = process_image(&encoded(1000, 500, format, 255), &limits).unwrap();
+
+            assert_eq!(processed.kind, kind);
+            assert_eq!((processed.width, processed.height), (1000, 500));
+            assert_eq!((processed.thumbnail.width, processed.thumbnail.height), (250, 125));
+            assert_eq!(sniff(&processed.thumbnail.data), Some(processed.thumbnail.kind));
+        }
+    }
+
+    #[test]
+    fn small_and_transparent_images() {
+        let processed =
+            process_image(&encoded(40, 30, ImageFormat::Png, 0), &MediaLimits::default()).unwrap();
+
+        assert_eq!((processed.thumbnail.width, processed.thumbnail.height), (40, 30));
+        assert_eq!(processed.thumbnail.kind, MediaKind::Png);
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let limits = MediaLimits {
+            max_file_size: 64 * 1024,
+            max_dimension: 100,
+            thumbnail_size: 50,
+            ..MediaLimits::default()
+        };
+
+        assert!(matches!(
+            process_image(&encoded(101, 10, ImageFormat::Png, 255), &limits),
+            Err(MediaError::DimensionsTooLarge { width: 101, .. })
+        ));
+        assert!(matches!(
+            process_image(&vec![0xFF; 64 * 1024 + 1], &limits),
+            Err(MediaError::TooLarge { .. })
+        ));
+        assert!(matches!(
+            process_image(b"not an image", &limits),
+            Err(MediaError::UnsupportedType)
+        ));
+        assert!(matches!(
+            process_image(b"\x89PNG\r\n\x1a\ntruncated", &limits),
+            Err(MediaError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+//! Upload Dispatch
+//!
+//! Developer Notes:
+//! - One entry point for every upload: sniffs the file and hands it to
+//!   image or audio/video processing.
+//! - `ProcessedUpload` exposes what storage needs without the caller
+//!   caring which kind of file it was.
+//!
+//! End Notes:
+//! Limits are checked by the processor that owns them.
+
+use std::time::Duration;
+
+use crate::{
+    process_av, process_image, sniff, AvLimits, ImageHashes, MediaClass, MediaError, MediaKind,
+    MediaLimits, PosterFrames, ProcessedAv, ProcessedImage, Thumbnail,
+};
+
+/// A validated upload of any supported kind.
+#[derive(Debug, Clone)]
+pub enum ProcessedUpload {
+    Image(ProcessedImage),
+    Av(ProcessedAv),
+}
+
+impl ProcessedUpload {
+    pub fn kind(&self) -> MediaKind {
+        match self {
+            ProcessedUpload::Image(image) => image.kind,
+            ProcessedUpload::Av(av) => av.info.kind,
+        }
+    }
+
+    /// The bytes to store.
+    pub fn data(&self) -> &[u8] {
+        match self {
+            ProcessedUpload::Image(image) => &image.data,
+            ProcessedUpload::Av(av) => &av.data,
+        }
+    }
+
+    /// Displayed size; 0x0 for audio.
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            ProcessedUpload::Image(image) => (image.width, image.height),
+            ProcessedUpload::Av(av) => (av.info.width, av.info.height),
+        }
+    }
+
+    /// The thumbnail or video poster, if there is one.
+    pub fn thumbnail(&self) -> Option<&Thumbnail> {
+        match self {
+            ProcessedUpload::Image(image) => Some(&image.thumbnail),
+            ProcessedUpload::Av(av) => av.poster.as_ref(),
+        }
+    }
+
+    /// Perceptual fingerprints, for images.
+    pub fn hashes(&self) -> Option<ImageHashes> {
+        match self {
+            ProcessedUpload::Image(image) => Some(image.hashes),
+            ProcessedUpload::Av(_) => None,
+        }
+    }
+
+    /// Playing time, for audio and video.
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            ProcessedUpload::Image(_) => None,
+            ProcessedUpload::Av(av) => Some(av.info.duration),
+        }
+    }
+
+    /// Whether the stored bytes have had their metadata removed.
+    pub fn metadata_stripped(&self) -> bool {
+        matches!(self, ProcessedUpload::Image(_))
+    }
+}
+
+/// Validate and process an upload of any supported kind.
+pub fn process_upload(
+    data: &[u8],
+    limits: &MediaLimits,
+    av: &AvLimits,
+    posters: Option<&dyn PosterFrames>,
+) -> Result<ProcessedUpload, MediaError> {
+    match sniff(data).map(MediaKind::class) {
+        Some(MediaClass::Image) => process_image(data, limits).map(ProcessedUpload::Image),
+        Some(_) => process_av(data, limits, av, posters).map(ProcessedUpload::Av),
+        None => Err(MediaError::UnsupportedType),
+    }
+}
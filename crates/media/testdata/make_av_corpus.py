@@ -0,0 +1,144 @@
+#!/usr/bin/env python3
+"""Build the audio/video probing corpus.
+
+The files are structurally valid containers whose codec payloads are
+filler bytes: enough for the prober, which never decodes media data,
+but not playable. Each file's expected duration, codecs and size are
+listed next to the tests in src/probe.rs.
+
+Run from this directory: python3 make_av_corpus.py
+"""
+
+import struct
+
+
+def write(name, data):
+    with open(name, "wb") as f:
+        f.write(data)
+
+
+# --- WebM (EBML) --------------------------------------------------------
+
+def ebml_id(value):
+    return value.to_bytes((value.bit_length() + 7) // 8, "big")
+
+
+def ebml(value, payload, unknown_size=False):
+    size = b"\x01\xff\xff\xff\xff\xff\xff\xff" if unknown_size else (
+        b"\x01" + len(payload).to_bytes(7, "big")
+    )
+    return ebml_id(value) + size + payload
+
+
+def uint(value):
+    return value.to_bytes(max(1, (value.bit_length() + 7) // 8), "big")
+
+
+def webm(duration_ms, tracks, unknown_size=False):
+    header = ebml(0x1A45DFA3, ebml(0x4286, uint(1)) + ebml(0x4282, b"webm"))
+    info = ebml(0x2AD7B1, uint(1_000_000))
+    if duration_ms is not None:
+        info += ebml(0x4489, struct.pack(">d", duration_ms))
+    entries = b""
+    for number, (kind, codec, size) in enumerate(tracks, 1):
+        entry = ebml(0xD7, uint(number)) + ebml(0x83, uint(kind)) + ebml(0x86, codec)
+        if size:
+            entry += ebml(0xE0, ebml(0xB0, uint(size[0])) + ebml(0xBA, uint(size[1])))
+        entries += ebml(0xAE, entry)
+    cluster = ebml(0x1F43B675, ebml(0xE7, uint(0)) + ebml(0xA3, b"\x81\x00\x00\x80" + bytes(64)))
+    segment = ebml(0x1549A966, info) + ebml(0x1654AE6B, entries) + cluster
+    return header + ebml(0x18538067, segment, unknown_size)
+
+
+# --- MP4 (ISO BMFF) -----------------------------------------------------
+
+def box(kind, payload):
+    return struct.pack(">I", 8 + len(payload)) + kind + payload
+
+
+def full_box(kind, version, payload):
+    return box(kind, struct.pack(">I", version << 24) + payload)
+
+
+def trak(handler, fourcc, width, height, duration):
+    matrix = struct.pack(">9I", 0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x40000000)
+    tkhd = full_box(
+        b"tkhd", 0,
+        struct.pack(">IIIII", 0, 0, 1, 0, duration) + bytes(8)
+        + struct.pack(">hhhH", 0, 0, 0, 0) + matrix
+        + struct.pack(">II", width << 16, height << 16),
+    )
+    hdlr = full_box(b"hdlr", 0, bytes(4) + handler + bytes(12) + b"\x00")
+    entry = box(fourcc, bytes(6) + struct.pack(">H", 1) + bytes(70))
+    stsd = full_box(b"stsd", 0, struct.pack(">I", 1) + entry)
+    minf = box(b"minf", box(b"stbl", stsd))
+    return box(b"trak", tkhd + box(b"mdia", hdlr + minf))
+
+
+def mp4(seconds, timescale=1000):
+    duration = seconds * timescale
+    mvhd = full_box(b"mvhd", 0, struct.pack(">IIII", 0, 0, timescale, duration) + bytes(80))
+    moov = box(
+        b"moov",
+        mvhd
+        + trak(b"vide", b"avc1", 320, 240, duration)
+        + trak(b"soun", b"mp4a", 0, 0, duration),
+    )
+    ftyp = box(b"ftyp", b"isom" + struct.pack(">I", 512) + b"isomiso2avc1mp41")
+    # moov after mdat, as written by encoders without faststart.
+    return ftyp + box(b"mdat", bytes(256)) + moov
+
+
+# --- MP3 ----------------------------------------------------------------
+
+def mp3(frames):
+    # MPEG-1 layer III, 128 kbit/s, 44.1 kHz, joint stereo: 417-byte frames.
+    frame = b"\xff\xfb\x90\x64" + bytes(413)
+    id3 = b"ID3\x04\x00\x00" + bytes([0, 0, 0, 20]) + b"TIT2" + struct.pack(">I", 10) + b"\x00\x00\x03" + b"SECRET-ID"
+    return id3 + frame * frames + b"TAG" + bytes(125)
+
+
+# --- Ogg ----------------------------------------------------------------
+
+def ogg_page(serial, sequence, granule, packet, header_type=0):
+    lacing = bytes([255] * (len(packet) // 255) + [len(packet) % 255])
+    return (
+        b"OggS\x00" + bytes([header_type]) + struct.pack("<qII", granule, serial, sequence)
+        + bytes(4) + bytes([len(lacing)]) + lacing + packet
+    )
+
+
+def ogg(head, granule):
+    serial = 0x1234
+    return (
+        ogg_page(serial, 0, 0, head, header_type=0x02)
+        + ogg_page(serial, 1, 0, b"OpusTags" + bytes(8))
+        + ogg_page(serial, 2, granule // 2, bytes(100))
+        + ogg_page(serial, 3, granule, bytes(100), header_type=0x04)
+    )
+
+
+def opus_head(pre_skip):
+    return b"OpusHead\x01\x02" + struct.pack("<HIhB", pre_skip, 48000, 0, 0)
+
+
+# --- FLAC ---------------------------------------------------------------
+
+def streaminfo(rate, samples):
+    packed = (rate << 44) | (1 << 41) | (15 << 36) | samples
+    return struct.pack(">HH", 4096, 4096) + bytes(6) + struct.pack(">Q", packed) + bytes(16)
+
+
+def flac(rate, samples):
+    info = streaminfo(rate, samples)
+    return b"fLaC" + bytes([0x80]) + len(info).to_bytes(3, "big") + info + b"\xff\xf8" + bytes(32)
+
+
+if __name__ == "__main__":
+    write("clip.webm", webm(12500.0, [(1, b"V_VP9", (640, 360)), (2, b"A_OPUS", None)]))
+    write("live.webm", webm(None, [(1, b"V_VP8", (64, 64))], unknown_size=True))
+    write("clip.mp4", mp4(3))
+    write("tone.mp3", mp3(100))
+    write("tone.ogg", ogg(opus_head(312), 48000 * 2 + 312))
+    write("theora.ogg", ogg(b"\x80theora" + bytes(34), 100))
+    write("tone.flac", flac(44100, 44100 * 3 // 2))
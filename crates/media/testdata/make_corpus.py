@@ -0,0 +1,205 @@
+#!/usr/bin/env python3
+"""Build the metadata test corpus from the plain base images.
+
+Every metadata block carries a string starting with SECRET-, so a test
+can prove removal by searching the sanitised bytes for it. Compressed
+blocks (PNG zTXt) are checked by chunk type instead.
+
+Run from this directory: python3 make_corpus.py
+"""
+
+import struct
+import zlib
+
+
+def read(name):
+    with open(name, "rb") as f:
+        return f.read()
+
+
+def write(name, data):
+    with open(name, "wb") as f:
+        f.write(data)
+
+
+# --- EXIF (TIFF) --------------------------------------------------------
+
+def tiff(entries_ifd0, gps_entries, thumbnail):
+    """Big-endian TIFF with IFD0, a GPS IFD and IFD1 holding a JPEG thumbnail."""
+    out = bytearray(b"MM\x00\x2a" + struct.pack(">I", 8))
+
+    def ifd_size(n):
+        return 2 + 12 * n + 4
+
+    ifd0_at = 8
+    gps_at = ifd0_at + ifd_size(len(entries_ifd0) + 1)
+    ifd1_at = gps_at + ifd_size(len(gps_entries))
+    data_at = ifd1_at + ifd_size(2)
+
+    def entry(tag, typ, count, value, data_offset):
+        # value is bytes; stored inline when <= 4 bytes
+        if len(value) <= 4:
+            return struct.pack(">HHI", tag, typ, count) + value.ljust(4, b"\x00"), b""
+        return struct.pack(">HHII", tag, typ, count, data_offset), value
+
+    def build_ifd(entries, next_ifd, data_offset):
+        body = struct.pack(">H", len(entries))
+        extra = b""
+        for tag, typ, count, value in entries:
+            e, blob = entry(tag, typ, count, value, data_offset + len(extra))
+            body += e
+            extra += blob
+        body += struct.pack(">I", next_ifd)
+        return body, extra
+
+    ifd0_entries = entries_ifd0 + [(0x8825, 4, 1, struct.pack(">I", gps_at))]
+    ifd0, extra0 = build_ifd(ifd0_entries, ifd1_at, data_at)
+    gps, extra_gps = build_ifd(gps_entries, 0, data_at + len(extra0))
+    thumb_at = data_at + len(extra0) + len(extra_gps)
+    ifd1, extra1 = build_ifd(
+        [
+            (0x0201, 4, 1, struct.pack(">I", thumb_at)),
+            (0x0202, 4, 1, struct.pack(">I", len(thumbnail))),
+        ],
+        0,
+        thumb_at + len(thumbnail),
+    )
+
+    out += ifd0 + gps + ifd1 + extra0 + extra_gps + thumbnail + extra1
+    return bytes(out)
+
+
+def ascii_entry(tag, text):
+    value = text.encode() + b"\x00"
+    return (tag, 2, len(value), value)
+
+
+def exif_tiff(thumbnail):
+    return tiff(
+        [
+            ascii_entry(0x010E, "SECRET-DESCRIPTION"),
+            (0x0112, 3, 1, struct.pack(">H", 6)),
+            ascii_entry(0x013B, "SECRET-ARTIST"),
+        ],
+        [
+            (0x0001, 2, 2, b"N\x00"),
+            (0x0002, 5, 3, struct.pack(">IIIIII", 48, 1, 51, 1, 2976, 100)),
+            (0x001B, 7, 18, b"ASCII\x00\x00\x00SECRET-GPS"),
+        ],
+        thumbnail,
+    )
+
+
+# --- JPEG ---------------------------------------------------------------
+
+def segment(marker, payload):
+    return bytes([0xFF, marker]) + struct.pack(">H", len(payload) + 2) + payload
+
+
+def make_jpeg():
+    base = read("base.jpg")
+    assert base[:2] == b"\xff\xd8"
+    # The embedded EXIF thumbnail is itself a JPEG with a secret comment.
+    thumbnail = base[:2] + segment(0xFE, b"SECRET-THUMB") + base[2:]
+
+    app1_exif = segment(0xE1, b"Exif\x00\x00" + exif_tiff(thumbnail))
+    app1_xmp = segment(
+        0xE1,
+        b"http://ns.adobe.com/xap/1.0/\x00"
+        b"<x:xmpmeta xmlns:x='adobe:ns:meta/'>SECRET-XMP</x:xmpmeta>",
+    )
+    iptc = b"\x1c\x02\x78" + struct.pack(">H", 11) + b"SECRET-IPTC"
+    app13 = segment(
+        0xED,
+        b"Photoshop 3.0\x00" + b"8BIM" + struct.pack(">H", 0x0404) + b"\x00\x00"
+        + struct.pack(">I", len(iptc)) + iptc + b"\x00",
+    )
+    jfxx = segment(0xE0, b"JFXX\x00\x13\x01\x01" + b"SEC")
+    com = segment(0xFE, b"SECRET-COMMENT")
+    mpf = segment(0xE2, b"MPF\x00SECRET-MPF")
+
+    data = base[:2] + app1_exif + app1_xmp + app13 + jfxx + com + mpf + base[2:]
+    # Bytes smuggled after the end of the image.
+    write("metadata.jpg", data + b"SECRET-TRAILER")
+
+
+# --- PNG ----------------------------------------------------------------
+
+def chunk(kind, data):
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(
+        ">I", zlib.crc32(kind + data) & 0xFFFFFFFF
+    )
+
+
+def make_png():
+    base = read("base.png")
+    iend = base.rindex(b"IEND") - 4
+    extra = (
+        chunk(b"tEXt", b"Comment\x00SECRET-TEXT")
+        + chunk(b"zTXt", b"Author\x00\x00" + zlib.compress(b"SECRET-ZTXT"))
+        + chunk(b"iTXt", b"XML:com.adobe.xmp\x00\x00\x00\x00\x00<x:xmpmeta>SECRET-XMP</x:xmpmeta>")
+        + chunk(b"eXIf", exif_tiff(b""))
+        + chunk(b"tIME", struct.pack(">HBBBBB", 2024, 1, 2, 3, 4, 5))
+    )
+    write("metadata.png", base[:iend] + extra + base[iend:] + b"SECRET-TRAILER")
+
+
+# --- WebP ---------------------------------------------------------------
+
+def riff_chunk(fourcc, data):
+    out = fourcc + struct.pack("<I", len(data)) + data
+    if len(data) % 2:
+        out += b"\x00"
+    return out
+
+
+def make_webp():
+    base = read("base.webp")
+    assert base[:4] == b"RIFF" and base[8:12] == b"WEBP"
+    image = base[12:]
+    width, height = 16, 8
+    flags = 0x08 | 0x04  # EXIF | XMP
+    vp8x = riff_chunk(
+        b"VP8X",
+        bytes([flags, 0, 0, 0])
+        + (width - 1).to_bytes(3, "little")
+        + (height - 1).to_bytes(3, "little"),
+    )
+    body = (
+        b"WEBP"
+        + vp8x
+        + image
+        + riff_chunk(b"EXIF", exif_tiff(b""))
+        + riff_chunk(b"XMP ", b"<x:xmpmeta>SECRET-XMP</x:xmpmeta>")
+    )
+    write("metadata.webp", b"RIFF" + struct.pack("<I", len(body)) + body + b"SECRET-TRAILER")
+
+
+# --- GIF ----------------------------------------------------------------
+
+def sub_blocks(data):
+    out = b""
+    for i in range(0, len(data), 255):
+        part = data[i:i + 255]
+        out += bytes([len(part)]) + part
+    return out + b"\x00"
+
+
+def make_gif():
+    base = read("base.gif")
+    packed = base[10]
+    table = 3 * (1 << ((packed & 0x07) + 1)) if packed & 0x80 else 0
+    at = 13 + table
+    extra = (
+        b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00"
+        + b"\x21\xFE" + sub_blocks(b"SECRET-COMMENT")
+        + b"\x21\xFF\x0BXMP DataXMP" + sub_blocks(b"<x:xmpmeta>SECRET-XMP</x:xmpmeta>")
+    )
+    write("metadata.gif", base[:at] + extra + base[at:] + b"SECRET-TRAILER")
+
+
+if __name__ == "__main__":
+    make_jpeg()
+    make_png()
+    make_webp()
+    make_gif()
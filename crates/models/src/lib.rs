@@ -16,6 +16,8 @@
 
 use std::str::FromStr;
 use std::fmt;
+use std::net::IpAddr;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use time::OffsetDateTime;
@@ -26,16 +28,248 @@ pub struct Board {
     pub id: Uuid,
     pub name: String,
     pub description: String,
+    /// Whether uploads identical to an earlier file are refused.
+    pub duplicate_policy: DuplicatePolicy,
+    /// Duration and codec limits for audio and video uploads.
+    pub av_policy: AvPolicy,
+    /// Posting cooldowns that replace the site defaults on this board.
+    pub cooldowns: Cooldowns,
+    /// Which posts must solve a CAPTCHA first.
+    pub captcha_mode: CaptchaMode,
+    /// Whether anyone may read an anonymised log of moderation here.
+    pub public_mod_log: bool,
+    /// Replies after which a thread stops being bumped.
+    pub bump_limit: u32,
+    /// How many live threads the board keeps, and what becomes of the rest.
+    pub prune_policy: PrunePolicy,
     pub created_at: OffsetDateTime,
 }
 
+/// Bump limit of a new board.
+pub const DEFAULT_BUMP_LIMIT: u32 = 300;
+
+/// How a board treats an upload whose exact bytes were posted before.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicatePolicy {
+    /// Duplicates are accepted.
+    #[default]
+    Allow,
+    /// Refused if the same file is already in the thread.
+    Thread,
+    /// Refused if the same file is anywhere on the board.
+    Board,
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplicatePolicy::Allow => write!(f, "allow"),
+            DuplicatePolicy::Thread => write!(f, "thread"),
+            DuplicatePolicy::Board => write!(f, "board"),
+        }
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(DuplicatePolicy::Allow),
+            "thread" => Ok(DuplicatePolicy::Thread),
+            "board" => Ok(DuplicatePolicy::Board),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A board's own posting cooldowns, in seconds; `None` keeps the site
+/// default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cooldowns {
+    /// Between two new threads from one address.
+    pub thread_secs: Option<u32>,
+    /// Between two replies from one address.
+    pub reply_secs: Option<u32>,
+    /// Before one address may post the same text again.
+    pub repost_secs: Option<u32>,
+}
+
+/// Which posts on a board must solve a CAPTCHA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptchaMode {
+    /// No CAPTCHA.
+    #[default]
+    Off,
+    /// Only new threads.
+    Threads,
+    /// New threads and replies.
+    All,
+}
+
+impl CaptchaMode {
+    /// Whether opening a thread needs a solved CAPTCHA.
+    pub fn covers_threads(self) -> bool {
+        self != CaptchaMode::Off
+    }
+
+    /// Whether replying needs a solved CAPTCHA.
+    pub fn covers_replies(self) -> bool {
+        self == CaptchaMode::All
+    }
+}
+
+impl fmt::Display for CaptchaMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptchaMode::Off => write!(f, "off"),
+            CaptchaMode::Threads => write!(f, "threads"),
+            CaptchaMode::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for CaptchaMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(CaptchaMode::Off),
+            "threads" => Ok(CaptchaMode::Threads),
+            "all" => Ok(CaptchaMode::All),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Codecs a new board accepts.
+pub const DEFAULT_AV_CODECS: &[&str] = &[
+    "vp8", "vp9", "av1", "h264", "opus", "vorbis", "aac", "mp3", "flac",
+];
+
+/// Limits a board places on audio and video uploads.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AvPolicy {
+    /// Longest accepted playing time, in seconds.
+    pub max_duration_secs: u32,
+    /// Accepted codec names (`vp9`, `opus`, ...); empty refuses all
+    /// audio and video.
+    pub codecs: Vec<String>,
+}
+
+impl Default for AvPolicy {
+    fn default() -> Self {
+        Self {
+            max_duration_secs: 300,
+            codecs: DEFAULT_AV_CODECS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl AvPolicy {
+    /// The codecs as one comma-separated string, as stored.
+    pub fn codec_list(&self) -> String {
+        self.codecs.join(",")
+    }
+
+    /// Parse a comma-separated codec list.
+    pub fn parse_codec_list(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Live threads a new board keeps.
+pub const DEFAULT_MAX_THREADS: u32 = 150;
+
+/// How many live threads a board keeps before the least recently bumped
+/// ones are pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrunePolicy {
+    /// Live threads kept, stickies included; at least one.
+    pub max_threads: u32,
+    pub mode: PruneMode,
+}
+
+impl Default for PrunePolicy {
+    fn default() -> Self {
+        Self {
+            max_threads: DEFAULT_MAX_THREADS,
+            mode: PruneMode::default(),
+        }
+    }
+}
+
+/// What happens to a thread pushed off its board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PruneMode {
+    /// Kept read-only in the board's archive.
+    #[default]
+    Archive,
+    /// Deleted with its replies and files.
+    Delete,
+}
+
+impl fmt::Display for PruneMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PruneMode::Archive => write!(f, "archive"),
+            PruneMode::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+impl FromStr for PruneMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archive" => Ok(PruneMode::Archive),
+            "delete" => Ok(PruneMode::Delete),
+            _ => Err(()),
+        }
+    }
+}
+
 /// Represents a discussion thread inside a board.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thread {
     pub id: Uuid,
     pub board_id: Uuid,
+    /// Board-wide sequential number of the opening post ("No. 1234").
+    ///
+    /// Allocated by storage on insert; `0` until then.
+    pub op_number: i64,
     pub title: String,
     pub created_at: OffsetDateTime,
+    /// When the thread last rose to the top of its board: its creation,
+    /// or the last reply that bumped it.
+    pub bumped_at: OffsetDateTime,
+    /// Who opened the thread; `None` for threads from before posters
+    /// were recorded.
+    pub poster: Option<PosterInfo>,
+    /// Moderator-controlled state; all off for a new thread.
+    pub flags: ThreadFlags,
+    /// When the thread was pushed off its board into the archive; an
+    /// archived thread takes no replies.
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+/// How staff have set a thread apart from the rest of its board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadFlags {
+    /// Pinned above every other thread; stickies are listed by this
+    /// position, lowest first.
+    pub sticky: Option<u32>,
+    /// Closed to new replies.
+    pub locked: bool,
+    /// Keeps a fixed number of replies, dropping the oldest.
+    pub cyclical: bool,
+    /// Replies never bump the thread.
+    pub autosage: bool,
 }
 
 /// Represents a post inside a thread.
@@ -43,15 +277,152 @@ pub struct Thread {
 pub struct Post {
     pub id: Uuid,
     pub thread_id: Uuid,
+    /// Board-wide sequential post number, shared with thread OP numbers.
+    ///
+    /// Allocated by storage on insert; `0` until then.
+    pub post_number: i64,
     pub content: String,
+    /// `content` rendered to HTML by the markup engine at creation time.
+    ///
+    /// Empty for rows written before rendering was cached.
+    pub content_html: String,
+    pub created_at: OffsetDateTime,
+    /// Who made the post; `None` for posts from before posters were
+    /// recorded.
+    pub poster: Option<PosterInfo>,
+}
+
+/// What is recorded about whoever made a thread or post. Staff only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PosterInfo {
+    /// Keyed hash of the poster's address; kept as long as the post.
+    pub ip_hash: String,
+    /// The address itself, until the retention period ends.
+    pub ip: Option<IpAddr>,
+    /// Keyed hash of the poster's `User-Agent`, if they sent one.
+    pub user_agent_hash: Option<String>,
+}
+
+/// A file attached to a thread's opening post or to a reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub thread_id: Uuid,
+    /// The reply it belongs to; `None` for the thread's opening post.
+    pub post_id: Option<Uuid>,
+    /// Order among the files of one post, starting at 0.
+    pub position: u32,
+    /// File name as uploaded, for display only.
+    pub file_name: String,
+    /// MIME type detected from the file's magic bytes.
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// Hex SHA-256 of the stored file; empty for rows written before
+    /// content addressing.
+    pub sha256: String,
+    /// Displayed size; 0x0 for audio.
+    pub width: u32,
+    pub height: u32,
+    /// Playing time of audio and video, in milliseconds.
+    pub duration_ms: Option<u32>,
+    /// Key of the original in the media store, shared by identical files.
+    pub storage_key: String,
+    /// Key of the thumbnail or video poster in the media store; empty
+    /// when there is none.
+    pub thumbnail_key: String,
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+    /// Whether EXIF and other metadata were removed before storing.
+    pub metadata_stripped: bool,
+    pub created_at: OffsetDateTime,
+}
+
+impl Attachment {
+    pub fn is_video(&self) -> bool {
+        self.content_type.starts_with("video/")
+    }
+
+    pub fn is_audio(&self) -> bool {
+        self.content_type.starts_with("audio/")
+    }
+
+    pub fn has_thumbnail(&self) -> bool {
+        !self.thumbnail_key.is_empty()
+    }
+
+    /// Playing time as `m:ss`, or empty for images.
+    pub fn duration_label(&self) -> String {
+        match self.duration_ms {
+            Some(ms) => {
+                let secs = ms.div_ceil(1000);
+                format!("{}:{:02}", secs / 60, secs % 60)
+            }
+            None => String::new(),
+        }
+    }
+}
+
+/// Perceptual fingerprints of an image moderators have banned.
+///
+/// Uploads within the configured Hamming distance are refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedImageHash {
+    pub id: Uuid,
+    pub phash: u64,
+    pub dhash: u64,
+    /// Shown to moderators only.
+    pub reason: String,
+    pub created_at: OffsetDateTime,
+    /// When the ban was lifted; a lifted ban refuses nothing, but the
+    /// uploads it refused stay on record.
+    pub lifted_at: Option<OffsetDateTime>,
+}
+
+/// An upload that was refused because it matched a banned image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannedImageMatch {
+    pub id: Uuid,
+    pub ban_id: Uuid,
+    pub board_id: Uuid,
+    /// The thread replied to; `None` when the upload opened a thread.
+    pub thread_id: Option<Uuid>,
+    /// File name as uploaded.
+    pub file_name: String,
+    /// SHA-256 of the refused file, after metadata stripping.
+    pub sha256: String,
+    /// Hamming distance to the banned hashes.
+    pub distance: u32,
     pub created_at: OffsetDateTime,
 }
 
+/// The post (or thread OP) a quote resolved to when the quoting post
+/// was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteTarget {
+    pub board_id: Uuid,
+    pub number: i64,
+    pub thread_id: Uuid,
+}
+
+/// One edge of the reply graph: `from` quoted `to` with `>>N`
+/// or `>>>/board/N`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub from_post_id: Uuid,
+    pub from_thread_id: Uuid,
+    pub from_board: String,
+    pub from_number: i64,
+    pub to_thread_id: Uuid,
+    pub to_board: String,
+    pub to_number: i64,
+}
+
 
 impl fmt::Display for Role {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Role::User => write!(f, "user"),
+            Role::GlobalModerator => write!(f, "global_moderator"),
             Role::Admin => write!(f, "admin"),
         }
     }
@@ -63,19 +434,127 @@ impl FromStr for Role {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "user" => Ok(Role::User),
+            "global_moderator" => Ok(Role::GlobalModerator),
             "admin" => Ok(Role::Admin),
             _ => Err(()),
         }
     }
 }
 
-/// User roles for access control.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Site-wide user roles for access control.
+///
+/// Staff of a single board have a `BoardRole` instead.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Role {
     User,
+    /// Moderates every board.
+    GlobalModerator,
+    /// May do anything.
     Admin,
 }
 
+/// A user's role on one board, from a `board_staff` row.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BoardRole {
+    /// Runs the board: its settings and its staff.
+    BoardOwner,
+    /// Moderates the board: deletions, bans and thread flags.
+    Moderator,
+    /// Cleans up the board: deletions and reports.
+    Janitor,
+}
+
+impl fmt::Display for BoardRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardRole::BoardOwner => write!(f, "board_owner"),
+            BoardRole::Moderator => write!(f, "moderator"),
+            BoardRole::Janitor => write!(f, "janitor"),
+        }
+    }
+}
+
+impl FromStr for BoardRole {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "board_owner" => Ok(BoardRole::BoardOwner),
+            "moderator" => Ok(BoardRole::Moderator),
+            "janitor" => Ok(BoardRole::Janitor),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A user appointed to a board's staff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardStaff {
+    pub board_id: Uuid,
+    pub user_id: Uuid,
+    pub role: BoardRole,
+    pub created_at: OffsetDateTime,
+}
+
+/// Something a user may or may not be allowed to do.
+///
+/// Board permissions are checked against a particular board; site
+/// permissions only against the user's `Role`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Permission {
+    /// Delete posts and threads, and their files.
+    DeletePost,
+    /// Work through the report queue.
+    HandleReports,
+    /// Ban posters.
+    BanUser,
+    /// Pin threads to the top of the board.
+    StickyThread,
+    /// Close threads to new replies, make them cyclical or autosage.
+    LockThread,
+    /// Read the moderation log.
+    ViewModLog,
+    /// Change board settings.
+    EditBoard,
+    /// Appoint and remove board staff.
+    ManageStaff,
+    /// Ban images by perceptual hash (site).
+    ManageImageBans,
+    /// Create boards (site).
+    CreateBoard,
+    /// Change users' site roles (site).
+    ManageUsers,
+}
+
+impl Permission {
+    /// Whether the permission concerns the whole site rather than a board.
+    pub fn is_site_wide(self) -> bool {
+        matches!(
+            self,
+            Permission::ManageImageBans | Permission::CreateBoard | Permission::ManageUsers
+        )
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Permission::DeletePost => "delete posts",
+            Permission::HandleReports => "handle reports",
+            Permission::BanUser => "ban users",
+            Permission::StickyThread => "sticky threads",
+            Permission::LockThread => "lock threads",
+            Permission::ViewModLog => "view the moderation log",
+            Permission::EditBoard => "edit boards",
+            Permission::ManageStaff => "manage board staff",
+            Permission::ManageImageBans => "manage image bans",
+            Permission::CreateBoard => "create boards",
+            Permission::ManageUsers => "manage users",
+        };
+        f.write_str(action)
+    }
+}
+
 /// Represents an application user.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -88,16 +567,351 @@ pub struct User {
 /// Represents a login session.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
-    pub token: Uuid,
+    /// SHA-256 of the session token, hex-encoded; the token itself is
+    /// only known to the client.
+    pub token_hash: String,
     pub user_id: Uuid,
     pub created_at: OffsetDateTime,
+    /// Last request made with the session, updated at most once a minute.
+    pub last_seen_at: OffsetDateTime,
+}
+
+/// An issued CAPTCHA challenge. The answer never leaves the server;
+/// the client only sees the id and the rendered image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Captcha {
+    pub id: Uuid,
+    pub answer: String,
+    pub expires_at: OffsetDateTime,
+}
+
+/// A ban on posting from an address or address range.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub id: Uuid,
+    /// A single address is a /32 (IPv4) or /128 (IPv6) range.
+    pub ip_range: IpNet,
+    /// The board the ban applies to; `None` for every board.
+    pub board_id: Option<Uuid>,
+    /// Shown to the banned poster.
+    pub reason: String,
+    /// Shown to staff only.
+    pub staff_note: String,
+    /// The staff member who issued the ban, while their account exists.
+    pub created_by: Option<Uuid>,
+    pub created_at: OffsetDateTime,
+    /// `None` for a permanent ban.
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl Ban {
+    /// Whether the ban is still in force at `now`.
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    /// Whether the ban covers `ip`. IPv4-mapped IPv6 addresses count as
+    /// their IPv4 address.
+    pub fn covers(&self, ip: IpAddr) -> bool {
+        self.ip_range.contains(&ip.to_canonical())
+    }
+}
+
+/// Where an appeal against a ban stands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AppealStatus {
+    #[default]
+    Pending,
+    /// The ban was lifted.
+    Accepted,
+    Denied,
+}
+
+impl fmt::Display for AppealStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppealStatus::Pending => write!(f, "pending"),
+            AppealStatus::Accepted => write!(f, "accepted"),
+            AppealStatus::Denied => write!(f, "denied"),
+        }
+    }
+}
+
+impl FromStr for AppealStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(AppealStatus::Pending),
+            "accepted" => Ok(AppealStatus::Accepted),
+            "denied" => Ok(AppealStatus::Denied),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A banned poster's request to lift a ban. Each ban takes one appeal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanAppeal {
+    pub id: Uuid,
+    pub ban_id: Uuid,
+    pub message: String,
+    pub status: AppealStatus,
+    pub created_at: OffsetDateTime,
+    /// The staff member who decided the appeal, while their account exists.
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<OffsetDateTime>,
+}
+
+
+/// Why a post was reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportCategory {
+    Spam,
+    /// Content that is illegal to host.
+    Illegal,
+    /// Breaks the board's topic rules.
+    OffTopic,
+    Other,
+}
+
+impl ReportCategory {
+    /// Every category, in the order a report form lists them.
+    pub const ALL: [ReportCategory; 4] = [
+        ReportCategory::Spam,
+        ReportCategory::Illegal,
+        ReportCategory::OffTopic,
+        ReportCategory::Other,
+    ];
+
+    /// Name shown to reporters and staff.
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportCategory::Spam => "Spam or advertising",
+            ReportCategory::Illegal => "Illegal content",
+            ReportCategory::OffTopic => "Off-topic",
+            ReportCategory::Other => "Other rule violation",
+        }
+    }
+}
+
+impl fmt::Display for ReportCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportCategory::Spam => write!(f, "spam"),
+            ReportCategory::Illegal => write!(f, "illegal"),
+            ReportCategory::OffTopic => write!(f, "off_topic"),
+            ReportCategory::Other => write!(f, "other"),
+        }
+    }
+}
+
+impl FromStr for ReportCategory {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spam" => Ok(ReportCategory::Spam),
+            "illegal" => Ok(ReportCategory::Illegal),
+            "off_topic" => Ok(ReportCategory::OffTopic),
+            "other" => Ok(ReportCategory::Other),
+            _ => Err(()),
+        }
+    }
 }
 
+/// Where a report stands, and what staff did about it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportStatus {
+    #[default]
+    Open,
+    /// Staff looked and left the post up.
+    Dismissed,
+    /// The post was deleted.
+    Deleted,
+    /// The post was deleted and its poster banned.
+    Banned,
+}
+
+impl fmt::Display for ReportStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportStatus::Open => write!(f, "open"),
+            ReportStatus::Dismissed => write!(f, "dismissed"),
+            ReportStatus::Deleted => write!(f, "deleted"),
+            ReportStatus::Banned => write!(f, "banned"),
+        }
+    }
+}
+
+impl FromStr for ReportStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(ReportStatus::Open),
+            "dismissed" => Ok(ReportStatus::Dismissed),
+            "deleted" => Ok(ReportStatus::Deleted),
+            "banned" => Ok(ReportStatus::Banned),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A visitor's report of a reply that breaks the rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: Uuid,
+    /// The reported reply; `None` once it has been deleted.
+    pub post_id: Option<Uuid>,
+    pub board_id: Uuid,
+    /// Number of the reported reply, kept after it is deleted.
+    pub post_number: i64,
+    pub category: ReportCategory,
+    pub comment: String,
+    /// Keyed hash of the reporter's address.
+    pub reporter_ip_hash: String,
+    pub created_at: OffsetDateTime,
+    pub status: ReportStatus,
+    /// The staff member who handled the report, while their account exists.
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<OffsetDateTime>,
+}
+
+/// A kind of privileged operation recorded in the moderation log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModActionKind {
+    DeletePost,
+    /// Reports on a post were closed without acting on it.
+    DismissReports,
+    BanIp,
+    LiftBan,
+    DecideAppeal,
+    AppointStaff,
+    DismissStaff,
+    /// A user's site role changed.
+    ChangeRole,
+    /// A board setting changed.
+    EditBoard,
+    /// A thread was stickied, locked, made cyclical or autosaged, or
+    /// had one of those undone.
+    EditThread,
+    /// An image was banned by perceptual hash.
+    BanImage,
+    /// An image ban was lifted.
+    UnbanImage,
+}
+
+impl ModActionKind {
+    /// Every kind, in the order the log's filter lists them.
+    pub const ALL: [ModActionKind; 12] = [
+        ModActionKind::DeletePost,
+        ModActionKind::DismissReports,
+        ModActionKind::BanIp,
+        ModActionKind::LiftBan,
+        ModActionKind::DecideAppeal,
+        ModActionKind::AppointStaff,
+        ModActionKind::DismissStaff,
+        ModActionKind::ChangeRole,
+        ModActionKind::EditBoard,
+        ModActionKind::EditThread,
+        ModActionKind::BanImage,
+        ModActionKind::UnbanImage,
+    ];
+
+    /// Name shown in the log.
+    pub fn label(self) -> &'static str {
+        match self {
+            ModActionKind::DeletePost => "Deleted post",
+            ModActionKind::DismissReports => "Dismissed reports",
+            ModActionKind::BanIp => "Banned",
+            ModActionKind::LiftBan => "Lifted ban",
+            ModActionKind::DecideAppeal => "Decided appeal",
+            ModActionKind::AppointStaff => "Appointed staff",
+            ModActionKind::DismissStaff => "Dismissed staff",
+            ModActionKind::ChangeRole => "Changed role",
+            ModActionKind::EditBoard => "Edited board",
+            ModActionKind::EditThread => "Changed thread flags",
+            ModActionKind::BanImage => "Banned image",
+            ModActionKind::UnbanImage => "Lifted image ban",
+        }
+    }
+
+    /// Whether the board's public log lists this kind; its target must
+    /// then name nothing but a post.
+    pub fn is_public(self) -> bool {
+        matches!(
+            self,
+            ModActionKind::DeletePost | ModActionKind::BanIp | ModActionKind::EditThread
+        )
+    }
+}
+
+impl fmt::Display for ModActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModActionKind::DeletePost => write!(f, "delete_post"),
+            ModActionKind::DismissReports => write!(f, "dismiss_reports"),
+            ModActionKind::BanIp => write!(f, "ban_ip"),
+            ModActionKind::LiftBan => write!(f, "lift_ban"),
+            ModActionKind::DecideAppeal => write!(f, "decide_appeal"),
+            ModActionKind::AppointStaff => write!(f, "appoint_staff"),
+            ModActionKind::DismissStaff => write!(f, "dismiss_staff"),
+            ModActionKind::ChangeRole => write!(f, "change_role"),
+            ModActionKind::EditBoard => write!(f, "edit_board"),
+            ModActionKind::EditThread => write!(f, "edit_thread"),
+            ModActionKind::BanImage => write!(f, "ban_image"),
+            ModActionKind::UnbanImage => write!(f, "unban_image"),
+        }
+    }
+}
+
+impl FromStr for ModActionKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete_post" => Ok(ModActionKind::DeletePost),
+            "dismiss_reports" => Ok(ModActionKind::DismissReports),
+            "ban_ip" => Ok(ModActionKind::BanIp),
+            "lift_ban" => Ok(ModActionKind::LiftBan),
+            "decide_appeal" => Ok(ModActionKind::DecideAppeal),
+            "appoint_staff" => Ok(ModActionKind::AppointStaff),
+            "dismiss_staff" => Ok(ModActionKind::DismissStaff),
+            "change_role" => Ok(ModActionKind::ChangeRole),
+            "edit_board" => Ok(ModActionKind::EditBoard),
+            "edit_thread" => Ok(ModActionKind::EditThread),
+            "ban_image" => Ok(ModActionKind::BanImage),
+            "unban_image" => Ok(ModActionKind::UnbanImage),
+            _ => Err(()),
+        }
+    }
+}
+
+/// One entry in the append-only moderation log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModAction {
+    pub id: Uuid,
+    /// `None` for operator actions outside the web interface.
+    pub actor_id: Option<Uuid>,
+    /// The actor's username when acting, or `system`.
+    pub actor_name: String,
+    pub action: ModActionKind,
+    /// `None` for site-wide actions.
+    pub board_id: Option<Uuid>,
+    /// What was acted on, e.g. `>>42` or `203.0.113.0/24`.
+    pub target: String,
+    pub reason: String,
+    /// JSON snapshot of the target before the action.
+    pub before: Option<String>,
+    /// JSON snapshot of the target after the action.
+    pub after: Option<String>,
+    pub created_at: OffsetDateTime,
+}
 
 
 /// TESTS:
 /// 
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,15 +922,91 @@ mod tests {
         assert_ne!(Role::User, Role::Admin);
     }
 
+    #[test]
+    fn roles_round_trip_through_text() {
+        for role in [Role::User, Role::GlobalModerator, Role::Admin] {
+            assert_eq!(role.to_string().parse(), Ok(role));
+        }
+        for role in [BoardRole::BoardOwner, BoardRole::Moderator, BoardRole::Janitor] {
+            assert_eq!(role.to_string().parse(), Ok(role));
+        }
+        assert!("owner".parse::<BoardRole>().is_err());
+        for status in [AppealStatus::Pending, AppealStatus::Accepted, AppealStatus::Denied] {
+            assert_eq!(status.to_string().parse(), Ok(status));
+        }
+        for category in ReportCategory::ALL {
+            assert_eq!(category.to_string().parse(), Ok(category));
+        }
+        for status in [ReportStatus::Open, ReportStatus::Dismissed, ReportStatus::Deleted, ReportStatus::Banned] {
+            assert_eq!(status.to_string().parse(), Ok(status));
+        }
+        for kind in ModActionKind::ALL {
+            assert_eq!(kind.to_string().parse(), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn bans_cover_their_range_until_they_expire() {
+        let now = OffsetDateTime::now_utc();
+        let ban = Ban {
+            id: Uuid::new_v4(),
+            ip_range: "203.0.113.0/24".parse().unwrap(),
+            board_id: None,
+            reason: "spam".into(),
+            staff_note: String::new(),
+            created_by: None,
+            created_at: now,
+            expires_at: Some(now + time::Duration::hours(1)),
+        };
+
+        assert!(ban.covers("203.0.113.77".parse().unwrap()));
+        assert!(ban.covers("::ffff:203.0.113.77".parse().unwrap()));
+        assert!(!ban.covers("203.0.114.1".parse().unwrap()));
+        assert!(ban.is_active(now));
+        assert!(!ban.is_active(now + time::Duration::hours(2)));
+        assert!(Ban { expires_at: None, ..ban }.is_active(now + time::Duration::days(9999)));
+    }
+
     #[test]
     fn board_struct_can_be_created() {
         let board = Board {
             id: Uuid::new_v4(),
             name: "test".into(),
             description: "desc".into(),
+            duplicate_policy: DuplicatePolicy::default(),
+            av_policy: AvPolicy::default(),
+            cooldowns: Cooldowns::default(),
+            captcha_mode: CaptchaMode::default(),
+            public_mod_log: false,
+            bump_limit: DEFAULT_BUMP_LIMIT,
+            prune_policy: PrunePolicy::default(),
             created_at: OffsetDateTime::now_utc(),
         };
 
         assert_eq!(board.name, "test");
     }
+
+    #[test]
+    fn codec_lists_round_trip() {
+        let policy = AvPolicy::default();
+
+        assert_eq!(AvPolicy::parse_codec_list(&policy.codec_list()), policy.codecs);
+        assert_eq!(AvPolicy::parse_codec_list(" vp9, ,opus "), ["vp9", "opus"]);
+        assert!(AvPolicy::parse_codec_list("").is_empty());
+    }
+
+    #[test]
+    fn duplicate_policy_round_trips() {
+        for policy in [DuplicatePolicy::Allow, DuplicatePolicy::Thread, DuplicatePolicy::Board] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+        assert!("never".parse::<DuplicatePolicy>().is_err());
+        for mode in [CaptchaMode::Off, CaptchaMode::Threads, CaptchaMode::All] {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+        assert!(CaptchaMode::Threads.covers_threads() && !CaptchaMode::Threads.covers_replies());
+        for mode in [PruneMode::Archive, PruneMode::Delete] {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
 }
\ No newline at end of file
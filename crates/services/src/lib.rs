@@ -5,26 +5,284 @@
 //! - All validation rules live here.
 //! - All UUID generation happens here.
 //! - All timestamps are created here.
-//! - This crate calls the storage layer only through Storage types.
+//! - This crate calls the storage layer only through repository traits.
 //! - This crate MUST NOT contain SQL or DB implementation details.
+//! - Uploads are validated and thumbnailed through the `media` crate
+//!   before anything is written; audio and video are checked against
+//!   the board's `AvPolicy`.
+//! - Uploads are compared with banned images by perceptual hash; every
+//!   refused upload is recorded for moderators, and stays on record
+//!   when the ban is lifted. Banning and lifting need `ManageImageBans`.
+//! - Stored files are shared by identical uploads; a file is deleted
+//!   when the last attachment using it goes (`release_files`) or by the
+//!   periodic garbage collector.
+//! - Post bodies are rendered by `markup` once, when the post is
+//!   created, and the HTML is cached on the post row.
+//! - Accounts: passwords are hashed by `auth`; a login creates a
+//!   `Session` whose token the HTTP layer keeps in a cookie. Only the
+//!   token's hash is stored. Sessions end after an absolute lifetime or
+//!   an idle timeout, whichever comes first, and are revoked when the
+//!   user's site role or board roles change.
+//! - Staff: moderation is gated by `authorize`, which looks up the
+//!   user's appointment on the board concerned and defers the decision
+//!   to `auth::authorize`.
+//! - Bans: every thread and post is checked against the IP bans
+//!   covering the poster's address before anything else is done with
+//!   it. A banned poster may appeal each ban once; accepting the appeal
+//!   lifts the ban.
+//! - Posters: every thread and post records a keyed hash of the
+//!   poster's address and user agent, kept for good, and the address
+//!   itself, cleared by `purge_poster_ips` after the retention period.
+//!   Only staff who may ban see either.
+//! - Flood control: an address must wait out a cooldown between threads,
+//!   between replies, and before posting the same text again, counted
+//!   per board from the stored address hashes. Boards may replace each
+//!   site-wide cooldown (`Board::cooldowns`).
+//! - CAPTCHA: a board may require one for new threads or for every
+//!   post (`Board::captcha_mode`). Challenges are rendered by `media`,
+//!   their answers stay in storage, and each one expires after
+//!   `CAPTCHA_LIFETIME` or at the first attempt to solve it, right or
+//!   wrong. The HTTP layer checks the answer before posting.
+//! - Reports: anyone may report a reply; staff with `HandleReports`
+//!   work through the open reports grouped by post, and dismissing,
+//!   deleting or deleting-and-banning closes them all with the action
+//!   taken, who took it and when.
+//! - Moderation log: every privileged operation appends a `ModAction`
+//!   through `record`, with JSON snapshots of what it changed. Staff with
+//!   `ViewModLog` read it; a board may also publish an anonymised copy
+//!   of its deletions, bans and thread flag changes. Board settings
+//!   (`EditBoard`) and site roles (`ManageUsers`) are authorized and
+//!   logged like any other action.
+//! - Bumping: a reply moves its thread to the top of the board (after
+//!   the stickies) unless it was posted with `sage` in its options, the
+//!   thread is autosaged, or the thread is past the board's bump limit.
+//! - Pruning: a board keeps at most `PrunePolicy::max_threads` live
+//!   threads. A new thread pushes the least recently bumped non-sticky
+//!   threads off, in the transaction that inserts it; depending on the
+//!   board they are archived (read-only, listed on the board's archive
+//!   page) or deleted with their replies and files.
+//! - Thread flags: staff with `StickyThread` pin threads to the top of
+//!   their board; staff with `LockThread` lock them (refused by
+//!   `create_post`), make them cyclical (replies past the limit are
+//!   deleted oldest first, files and all) or autosage them.
 //!
 //! End of File Notes:
 //! Keep this layer as the system's rule authority.
 
+pub mod markup;
+pub mod quotes;
+pub mod views;
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use ipnet::IpNet;
+use serde_json::json;
 use uuid::Uuid;
+use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
-use models::{Board, Thread, Post};
-use storage::{
-    board_repository,
-    thread_repository,
-    post_repository,
-    user_repository,
-    session_repository,
-    StorageError,
-    DbConnection,
+use models::{
+    AppealStatus, Attachment, AvPolicy, Ban, BanAppeal, BannedImageHash, BannedImageMatch, Board,
+    BoardRole, BoardStaff, Captcha, CaptchaMode, Cooldowns, DuplicatePolicy, ModAction,
+    ModActionKind, Permission, Thread, ThreadFlags, Post, PosterInfo, PrunePolicy, Quote,
+    QuoteTarget, Report, ReportCategory, ReportStatus, Role, Session, User, DEFAULT_BUMP_LIMIT,
+};
+use auth::AuthError;
+use media::{
+    AvLimits, Bucket, GcReport, ImageHashes, MediaError, MediaLimits, MediaStore, PosterFrames,
+    ProcessedUpload, ReferencedKeys,
+};
+use storage::{ModActionFilter, Repositories, StorageError};
+
+use quotes::{QuoteRef, post_href};
+use views::{
+    AppealView, BanNotice, HandledReportView, ModActionView, PostView, PosterPostView,
+    PublicModActionView, ReplyLink, ReportedPostView, StaffView, ThreadView,
 };
 
+/// Upper bound on distinct quotes resolved per post.
+///
+/// Each quote costs a lookup; anything beyond this is left unlinked.
+pub const MAX_QUOTES_PER_POST: usize = 50;
+
+/// Upper bound on files attached to one thread or post.
+pub const MAX_FILES_PER_POST: usize = 4;
+
+/// Longest original file name kept for display.
+const MAX_FILE_NAME_LEN: usize = 128;
+
+/// How long a stored file is protected from deletion after it was last
+/// saved, covering the gap between writing a file and inserting the
+/// attachment row that references it.
+pub const MEDIA_GRACE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+/// Default largest Hamming distance at which an upload matches a
+/// banned image.
+pub const DEFAULT_IMAGE_BAN_DISTANCE: u32 = 8;
+
+/// Default number of replies a cyclical thread keeps.
+pub const DEFAULT_CYCLICAL_REPLY_LIMIT: usize = 250;
+
+/// Longest accepted username.
+pub const MAX_USERNAME_LEN: usize = 32;
+
+/// Shortest accepted password.
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Longest accepted password; bounds the hashing work per login.
+pub const MAX_PASSWORD_LEN: usize = 256;
+
+/// Default longest a session lasts after login.
+pub const DEFAULT_SESSION_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default longest a session lasts without being used.
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How stale a session's `last_seen_at` may get before a request
+/// writes it again.
+const SESSION_TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Longest ban reason shown to the banned poster.
+pub const MAX_BAN_REASON_LEN: usize = 500;
+
+/// Longest private note staff may keep on a ban.
+pub const MAX_BAN_NOTE_LEN: usize = 2000;
+
+/// Longest temporary ban; anything longer should be permanent.
+pub const MAX_BAN_DURATION: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// Longest ban appeal.
+pub const MAX_APPEAL_LEN: usize = 2000;
+
+/// Default time a poster's raw address is kept with their post.
+pub const DEFAULT_IP_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Characters of a thread title or post body shown in poster lookups.
+const POSTER_EXCERPT_LEN: usize = 120;
+
+/// How long a CAPTCHA can be solved after it is issued.
+pub const CAPTCHA_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Characters in a CAPTCHA answer.
+pub const CAPTCHA_LENGTH: usize = 6;
+
+/// Longest comment a report may carry.
+pub const MAX_REPORT_COMMENT_LEN: usize = 500;
+
+/// How many handled reports are listed under the queue.
+const REPORT_HISTORY_LEN: u32 = 50;
+
+/// Wait reported to a poster whose cooldown ends past the last
+/// representable time.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Entries per page of the moderation log.
+pub const MOD_LOG_PAGE_LEN: u32 = 100;
+
+/// Name logged for operator actions taken outside the web interface.
+const SYSTEM_ACTOR: &str = "system";
+
+/// Who is making a post, as far as the HTTP layer can tell.
+#[derive(Debug, Clone)]
+pub struct Poster {
+    pub ip: IpAddr,
+    /// The `User-Agent` header, if one was sent.
+    pub user_agent: Option<String>,
+}
+
+impl Poster {
+    pub fn new(ip: IpAddr) -> Self {
+        Self { ip, user_agent: None }
+    }
+
+    pub fn with_user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.user_agent = user_agent.filter(|ua| !ua.is_empty());
+        self
+    }
+}
+
+/// Site-wide posting cooldowns per address; each board may replace any
+/// of them. A zero cooldown is off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Between two new threads on a board.
+    pub thread_cooldown: Duration,
+    /// Between two replies on a board.
+    pub reply_cooldown: Duration,
+    /// Before the same title or body may be posted on a board again.
+    pub repost_cooldown: Duration,
+}
+
+/// Which cooldown a new post is held to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PostKind {
+    Thread,
+    Reply,
+}
+
+/// A ban about to be issued.
+#[derive(Debug, Clone)]
+pub struct NewBan {
+    /// A single address is a /32 or /128 range.
+    pub ip_range: IpNet,
+    /// `None` bans from every board.
+    pub board_id: Option<Uuid>,
+    /// Shown to the banned poster.
+    pub reason: String,
+    /// Shown to staff only.
+    pub staff_note: String,
+    /// `None` for a permanent ban.
+    pub duration: Option<Duration>,
+}
+
+/// A ban issued on the poster of a reported post as it is deleted.
+#[derive(Debug, Clone)]
+pub struct PosterBan {
+    /// Shown to the banned poster.
+    pub reason: String,
+    /// `None` for a permanent ban.
+    pub duration: Option<Duration>,
+}
+
+/// Which moderation log entries to show; unset fields match all.
+#[derive(Debug, Clone, Default)]
+pub struct ModLogQuery {
+    pub board_id: Option<Uuid>,
+    /// Username of the staff member who acted.
+    pub actor: Option<String>,
+    pub action: Option<ModActionKind>,
+    /// Only entries older than this, for paging.
+    pub before: Option<OffsetDateTime>,
+}
+
+/// A privileged operation about to be logged.
+struct LoggedAction {
+    action: ModActionKind,
+    board_id: Option<Uuid>,
+    target: String,
+    reason: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// A file received with a new thread or post.
+#[derive(Debug, Clone)]
+pub struct Upload {
+    /// File name as sent by the client, for display only.
+    pub file_name: String,
+    pub data: Vec<u8>,
+}
+
+/// An upload that passed validation, ready to be stored.
+struct PreparedFile {
+    file_name: String,
+    upload: ProcessedUpload,
+    sha256: String,
+}
+
 /// Errors returned from service operations.
 #[derive(Debug, thiserror::Error)]
 pub enum ServiceError {
@@ -32,29 +290,174 @@ pub enum ServiceError {
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// The requested entity does not exist.
+    #[error("Not found: {0}")]
+    NotFound(String),
+
     /// A storage layer error occurred.
     #[error("Storage error: {0}")]
     Storage(#[from] StorageError),
+
+    /// Writing to the media directory failed.
+    #[error("Media error: {0}")]
+    Media(#[from] MediaError),
+
+    /// The credentials or session presented are not valid.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// The user is known but lacks the permission required.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// The poster's address is banned; carries the public reason.
+    #[error("Banned: {0}")]
+    Banned(String),
+
+    /// The poster is posting too fast and must wait `retry_after`.
+    #[error("Rate limited: wait {}s before {action}", .retry_after.as_secs())]
+    RateLimited {
+        action: &'static str,
+        retry_after: Duration,
+    },
+
+    /// Password hashing failed.
+    #[error("Auth error: {0}")]
+    Auth(#[from] AuthError),
 }
 
 /// Core service facade.
 ///
 /// This struct groups business logic into a single type.
-/// Actual methods are stateless and could be free functions;
-/// grouping them makes mocking and abstraction easier.
-pub struct ServiceLayer;
+/// It owns the repositories as trait objects, so the same rules run
+/// against SQLite in production and in-memory storage in tests.
+pub struct ServiceLayer {
+    repos: Repositories,
+    media: Option<Arc<dyn MediaStore>>,
+    media_limits: MediaLimits,
+    posters: Option<Arc<dyn PosterFrames>>,
+    image_ban_distance: u32,
+    cyclical_reply_limit: usize,
+    registration_open: bool,
+    session_max_age: Duration,
+    session_idle_timeout: Duration,
+    ip_hash_key: Vec<u8>,
+    ip_retention: Duration,
+    rate_limits: RateLimits,
+}
 
 impl ServiceLayer {
+    /// Build the service layer on top of a set of repositories.
+    ///
+    /// File uploads are refused until a media store is attached with
+    /// `with_media`.
+    pub fn new(repos: Repositories) -> Self {
+        Self {
+            repos,
+            media: None,
+            media_limits: MediaLimits::default(),
+            posters: None,
+            image_ban_distance: DEFAULT_IMAGE_BAN_DISTANCE,
+            cyclical_reply_limit: DEFAULT_CYCLICAL_REPLY_LIMIT,
+            registration_open: true,
+            session_max_age: DEFAULT_SESSION_MAX_AGE,
+            session_idle_timeout: DEFAULT_SESSION_IDLE_TIMEOUT,
+            ip_hash_key: auth::generate_key(),
+            ip_retention: DEFAULT_IP_RETENTION,
+            rate_limits: RateLimits::default(),
+        }
+    }
+
+    /// Accept file uploads, stored in `store` and checked against `limits`.
+    pub fn with_media(mut self, store: Arc<dyn MediaStore>, limits: MediaLimits) -> Self {
+        self.media = Some(store);
+        self.media_limits = limits;
+        self
+    }
+
+    /// Make video poster frames with `posters`; without it videos are
+    /// accepted without one.
+    pub fn with_poster_frames(mut self, posters: Arc<dyn PosterFrames>) -> Self {
+        self.posters = Some(posters);
+        self
+    }
+
+    /// Refuse uploads within `distance` bits of a banned image.
+    pub fn with_image_ban_distance(mut self, distance: u32) -> Self {
+        self.image_ban_distance = distance;
+        self
+    }
+
+    /// Keep the newest `limit` replies of a cyclical thread.
+    pub fn with_cyclical_reply_limit(mut self, limit: usize) -> Self {
+        self.cyclical_reply_limit = limit;
+        self
+    }
+
+    /// Allow or refuse new accounts through `register_user`.
+    pub fn with_registration(mut self, open: bool) -> Self {
+        self.registration_open = open;
+        self
+    }
+
+    /// End sessions `max_age` after login or `idle_timeout` after their
+    /// last use.
+    pub fn with_session_lifetime(mut self, max_age: Duration, idle_timeout: Duration) -> Self {
+        self.session_max_age = max_age;
+        self.session_idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Hash poster addresses and user agents under `key`.
+    ///
+    /// Without one a random key is used, so hashes stop matching those
+    /// from before a restart.
+    pub fn with_ip_hash_key(mut self, key: Vec<u8>) -> Self {
+        self.ip_hash_key = key;
+        self
+    }
+
+    /// Keep a poster's raw address with their post for `retention`.
+    pub fn with_ip_retention(mut self, retention: Duration) -> Self {
+        self.ip_retention = retention;
+        self
+    }
+
+    /// Hold posters to `limits`; without it nothing is rate limited.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.rate_limits = limits;
+        self
+    }
+
+    /// Longest a session can last.
+    pub fn session_max_age(&self) -> Duration {
+        self.session_max_age
+    }
+
+    /// Whether anyone may create an account.
+    pub fn registration_open(&self) -> bool {
+        self.registration_open
+    }
+
+    /// Limits applied to every uploaded file.
+    pub fn media_limits(&self) -> MediaLimits {
+        self.media_limits
+    }
+
     // =========================
     // Board Logic
     // =========================
 
     /// Create a new board with validation.
+    ///
+    /// Needs `CreateBoard`.
     pub fn create_board(
-        conn: &DbConnection,
+        &self,
+        actor: &User,
         name: String,
         description: String,
     ) -> Result<Board, ServiceError> {
+        self.authorize(actor, None, Permission::CreateBoard)?;
         if name.trim().is_empty() {
             return Err(ServiceError::Validation(
                 "Board name cannot be empty".into(),
@@ -65,29 +468,180 @@ impl ServiceLayer {
             id: Uuid::new_v4(),
             name,
             description,
+            duplicate_policy: DuplicatePolicy::default(),
+            av_policy: AvPolicy::default(),
+            cooldowns: Cooldowns::default(),
+            captcha_mode: CaptchaMode::default(),
+            public_mod_log: false,
+            bump_limit: DEFAULT_BUMP_LIMIT,
+            prune_policy: PrunePolicy::default(),
             created_at: OffsetDateTime::now_utc(),
         };
 
-        board_repository::insert_board(conn, &board)?;
+        self.repos.boards.insert_board(&board)?;
         Ok(board)
     }
 
+    /// Choose whether a board refuses uploads that were posted before.
+    pub fn set_duplicate_policy(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        policy: DuplicatePolicy,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "duplicate_policy", || {
+            Ok(self.repos.boards.set_duplicate_policy(board_id, policy)?)
+        })
+    }
+
+    /// Set a board's duration and codec limits for audio and video.
+    ///
+    /// An empty codec list refuses all audio and video on the board.
+    pub fn set_av_policy(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        policy: AvPolicy,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "av_policy", || {
+            if policy.max_duration_secs == 0 {
+                return Err(ServiceError::Validation(
+                    "Maximum duration must be at least one second".into(),
+                ));
+            }
+            if let Some(codec) = policy.codecs.iter().find(|c| !media::KNOWN_CODECS.contains(&c.as_str())) {
+                return Err(ServiceError::Validation(format!("Unknown codec `{codec}`")));
+            }
+
+            Ok(self.repos.boards.set_av_policy(board_id, &policy)?)
+        })
+    }
+
+    /// Replace some or all of the site's posting cooldowns on a board.
+    pub fn set_cooldowns(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        cooldowns: Cooldowns,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "cooldowns", || {
+            Ok(self.repos.boards.set_cooldowns(board_id, &cooldowns)?)
+        })
+    }
+
+    /// Choose which posts on a board must solve a CAPTCHA.
+    pub fn set_captcha_mode(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        mode: CaptchaMode,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "captcha_mode", || {
+            Ok(self.repos.boards.set_captcha_mode(board_id, mode)?)
+        })
+    }
+
+    /// Publish or withdraw a board's anonymised moderation log.
+    pub fn set_public_mod_log(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        public: bool,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "public_mod_log", || {
+            Ok(self.repos.boards.set_public_mod_log(board_id, public)?)
+        })
+    }
+
+    /// Stop bumping threads once they have more than `limit` replies.
+    pub fn set_bump_limit(&self, actor: &User, board_id: Uuid, limit: u32) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "bump_limit", || {
+            Ok(self.repos.boards.set_bump_limit(board_id, limit)?)
+        })
+    }
+
+    /// Choose how many live threads a board keeps and whether the threads
+    /// pushed off it are archived or deleted.
+    ///
+    /// A lower limit takes effect when the next thread is opened.
+    pub fn set_prune_policy(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        policy: PrunePolicy,
+    ) -> Result<(), ServiceError> {
+        self.edit_board(actor, board_id, "prune_policy", || {
+            if policy.max_threads == 0 {
+                return Err(ServiceError::Validation(
+                    "A board must keep at least one thread".into(),
+                ));
+            }
+
+            Ok(self.repos.boards.set_prune_policy(board_id, &policy)?)
+        })
+    }
+
+    /// Change a board setting and log the change.
+    ///
+    /// Needs `EditBoard` on the board; `apply` validates and stores the
+    /// new setting.
+    fn edit_board(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        setting: &str,
+        apply: impl FnOnce() -> Result<(), ServiceError>,
+    ) -> Result<(), ServiceError> {
+        self.authorize(actor, Some(board_id), Permission::EditBoard)?;
+        let before = self.get_board(board_id)?;
+        apply()?;
+        let after = self.get_board(board_id)?;
+
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::EditBoard,
+                board_id: Some(board_id),
+                target: format!("/{}/ {setting}", before.name),
+                reason: String::new(),
+                before: Some(board_snapshot(&before)),
+                after: Some(board_snapshot(&after)),
+            },
+        )
+    }
+
     /// List all boards.
-    pub fn list_boards(
-        conn: &DbConnection,
-    ) -> Result<Vec<Board>, ServiceError> {
-        Ok(board_repository::get_all(conn)?)
+    pub fn list_boards(&self) -> Result<Vec<Board>, ServiceError> {
+        Ok(self.repos.boards.get_all()?)
+    }
+
+    /// Fetch a board by its unique name.
+    pub fn get_board_by_name(&self, name: &str) -> Result<Board, ServiceError> {
+        self.repos
+            .boards
+            .get_board_by_name(name)?
+            .ok_or_else(|| ServiceError::NotFound("board".into()))
+    }
+
+    /// Fetch a single board.
+    pub fn get_board(&self, board_id: Uuid) -> Result<Board, ServiceError> {
+        self.repos
+            .boards
+            .get_board(board_id)?
+            .ok_or_else(|| ServiceError::NotFound("board".into()))
     }
 
     // =========================
     // Thread Logic
     // =========================
 
-    /// Create a thread inside a board.
+    /// Create a thread inside a board, with files on its opening post.
     pub fn create_thread(
-        conn: &DbConnection,
+        &self,
+        poster: &Poster,
         board_id: Uuid,
         title: String,
+        uploads: Vec<Upload>,
     ) -> Result<Thread, ServiceError> {
         if title.trim().is_empty() {
             return Err(ServiceError::Validation(
@@ -95,65 +649,3041 @@ impl ServiceLayer {
             ));
         }
 
-        let thread = Thread {
+        let board = self.get_board(board_id)?;
+        self.check_ban(poster, board.id)?;
+        self.check_rate_limit(poster, &board, PostKind::Thread, &title)?;
+        let files = self.prepare_uploads(&board, uploads)?;
+        self.check_banned_images(&board, None, &files)?;
+        self.check_duplicates(&board, None, &files)?;
+
+        let now = OffsetDateTime::now_utc();
+        let mut thread = Thread {
             id: Uuid::new_v4(),
             board_id,
+            op_number: 0,
             title,
-            created_at: OffsetDateTime::now_utc(),
+            created_at: now,
+            bumped_at: now,
+            poster: Some(self.poster_info(poster)),
+            flags: ThreadFlags::default(),
+            archived_at: None,
         };
 
-        thread_repository::insert_thread(conn, &thread)?;
+        let mut stored = self.store_files(files)?;
+        for attachment in &mut stored {
+            attachment.thread_id = thread.id;
+        }
+        let (op_number, pruned) =
+            self.repos.threads.insert_thread_and_prune(&thread, &stored, &board.prune_policy)?;
+        thread.op_number = op_number;
+        // After the new attachments, so files they share are kept.
+        self.release_files(&pruned.attachments)?;
         Ok(thread)
     }
 
+    /// Fetch a single thread.
+    pub fn get_thread(&self, thread_id: Uuid) -> Result<Thread, ServiceError> {
+        self.repos
+            .threads
+            .get_thread(thread_id)?
+            .ok_or_else(|| ServiceError::NotFound("thread".into()))
+    }
+
+    /// Resolve a board-wide post number ("No. 1234") to its thread.
+    ///
+    /// The number may be the thread's OP number or any reply in it.
+    pub fn find_thread_by_number(
+        &self,
+        board_id: Uuid,
+        number: i64,
+    ) -> Result<Thread, ServiceError> {
+        if let Some(thread) = self.repos.threads.get_thread_by_number(board_id, number)? {
+            return Ok(thread);
+        }
+
+        match self.repos.posts.get_post_by_number(board_id, number)? {
+            Some(post) => self.get_thread(post.thread_id),
+            None => Err(ServiceError::NotFound("post".into())),
+        }
+    }
+
+    /// List the live threads of a board, stickies first.
+    pub fn list_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, ServiceError> {
+        Ok(self.repos.threads.get_threads_by_board(board_id)?)
+    }
+
+    /// List the archived threads of a board, most recently archived first.
+    pub fn list_archived_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, ServiceError> {
+        Ok(self.repos.threads.get_archived_threads(board_id)?)
+    }
+
+    /// Change a thread's flags as board staff; returns the updated thread.
+    ///
+    /// Needs `StickyThread` or `LockThread` on the board; changing
+    /// `sticky` needs `StickyThread`, changing any other flag `LockThread`.
+    /// Archived threads keep their flags. Setting the flags a thread
+    /// already has does nothing.
+    pub fn set_thread_flags(
+        &self,
+        actor: &User,
+        thread_id: Uuid,
+        flags: ThreadFlags,
+        reason: String,
+    ) -> Result<Thread, ServiceError> {
+        let thread = self.get_thread(thread_id)?;
+        let before = thread.flags;
+
+        if !self.is_allowed(actor, Some(thread.board_id), Permission::StickyThread)? {
+            self.authorize(actor, Some(thread.board_id), Permission::LockThread)?;
+        }
+        if thread.archived_at.is_some() {
+            return Err(ServiceError::Forbidden("This thread is archived".into()));
+        }
+        if flags.sticky != before.sticky {
+            self.authorize(actor, Some(thread.board_id), Permission::StickyThread)?;
+        }
+        let others_changed = ThreadFlags { sticky: before.sticky, ..flags } != before;
+        if others_changed {
+            self.authorize(actor, Some(thread.board_id), Permission::LockThread)?;
+        }
+        if flags == before {
+            return Ok(thread);
+        }
+
+        self.repos.threads.set_flags(thread.id, &flags)?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::EditThread,
+                board_id: Some(thread.board_id),
+                target: format!(">>{}", thread.op_number),
+                reason,
+                before: Some(flags_snapshot(&before)),
+                after: Some(flags_snapshot(&flags)),
+            },
+        )?;
+
+        if flags.cyclical && !before.cyclical {
+            self.cycle_replies(thread.id)?;
+        }
+        Ok(Thread { flags, ..thread })
+    }
+
+    /// Delete the oldest replies of a cyclical thread beyond the limit,
+    /// with the files only they were using.
+    fn cycle_replies(&self, thread_id: Uuid) -> Result<(), ServiceError> {
+        let posts = self.list_posts(thread_id)?;
+        let excess = posts.len().saturating_sub(self.cyclical_reply_limit);
+
+        for post in &posts[..excess] {
+            let attachments = self.repos.attachments.get_attachments_by_post(post.id)?;
+            if self.repos.posts.delete_post(post.id)? {
+                self.release_files(&attachments)?;
+            }
+        }
+        Ok(())
+    }
+
     // =========================
     // Post Logic
     // =========================
 
     /// Create a post inside a thread.
+    ///
+    /// The body may be empty when at least one file is attached.
+    /// `options` is the poster's options field; `sage` there keeps the
+    /// reply from bumping the thread.
     pub fn create_post(
-        conn: &DbConnection,
+        &self,
+        poster: &Poster,
         thread_id: Uuid,
         content: String,
+        options: &str,
+        uploads: Vec<Upload>,
     ) -> Result<Post, ServiceError> {
-        if content.trim().is_empty() {
+        if content.trim().is_empty() && uploads.is_empty() {
             return Err(ServiceError::Validation(
                 "Post content cannot be empty".into(),
             ));
         }
 
-        let post = Post {
+        let thread = self.get_thread(thread_id)?;
+        if thread.flags.locked {
+            return Err(ServiceError::Forbidden("This thread is locked".into()));
+        }
+        if thread.archived_at.is_some() {
+            return Err(ServiceError::Forbidden("This thread is archived".into()));
+        }
+        let board = self.get_board(thread.board_id)?;
+        self.check_ban(poster, board.id)?;
+        self.check_rate_limit(poster, &board, PostKind::Reply, &content)?;
+        let files = self.prepare_uploads(&board, uploads)?;
+        self.check_banned_images(&board, Some(thread_id), &files)?;
+        self.check_duplicates(&board, Some(thread_id), &files)?;
+        let resolved = self.resolve_quotes(board.id, &content)?;
+
+        let content_html = markup::render(&content, |quote: &QuoteRef| {
+            resolved
+                .iter()
+                .find(|(q, _)| q == quote)
+                .map(|(q, target)| {
+                    let to_board = q.board.as_deref().unwrap_or(&board.name);
+                    post_href(to_board, target.number, target.thread_id == thread_id)
+                })
+        });
+
+        let mut post = Post {
             id: Uuid::new_v4(),
             thread_id,
+            post_number: 0,
             content,
+            content_html,
             created_at: OffsetDateTime::now_utc(),
+            poster: Some(self.poster_info(poster)),
         };
 
-        post_repository::insert_post(conn, &post)?;
+        let stored = self.store_files(files)?;
+        post.post_number = self.repos.posts.insert_post(&post)?;
+        self.insert_attachments(thread_id, Some(post.id), stored)?;
+
+        let mut targets: Vec<QuoteTarget> = Vec::new();
+        for (_, target) in resolved {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+
+        if !targets.is_empty() {
+            self.repos.quotes.insert_quotes(post.id, &targets)?;
+        }
+
+        if thread.flags.cyclical {
+            self.cycle_replies(thread_id)?;
+        }
+
+        let replies = self.repos.posts.count_posts_by_thread(thread_id)?;
+        if !is_sage(options) && !thread.flags.autosage && replies <= u64::from(board.bump_limit) {
+            self.repos.threads.bump_thread(thread_id, post.created_at)?;
+        }
+
         Ok(post)
     }
-}
 
+    /// Find a reply by its board-wide number.
+    pub fn find_post_by_number(&self, board_id: Uuid, number: i64) -> Result<Post, ServiceError> {
+        self.repos
+            .posts
+            .get_post_by_number(board_id, number)?
+            .ok_or_else(|| ServiceError::NotFound("post".into()))
+    }
 
-/// TESTS:
-/// 
-/// 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use storage::{connection::create_connection, schema::initialize_schema};
+    /// Delete a reply as board staff and release the files only it was
+    /// using; returns the deleted post.
+    pub fn delete_post(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        number: i64,
+        reason: String,
+    ) -> Result<Post, ServiceError> {
+        self.authorize(actor, Some(board_id), Permission::DeletePost)?;
+        let post = self.find_post_by_number(board_id, number)?;
 
-    #[test]
-    fn board_validation_works() {
-        let conn = create_connection(":memory:").unwrap();
-        initialize_schema(&conn).unwrap();
+        self.remove_post(actor, board_id, &post, reason)?;
+        Ok(post)
+    }
 
-        let result = ServiceLayer::create_board(
-            &conn,
-            "".into(),
-            "desc".into(),
-        );
+    /// Delete `post`, log it, and release its files.
+    fn remove_post(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        post: &Post,
+        reason: String,
+    ) -> Result<(), ServiceError> {
+        let attachments = self.repos.attachments.get_attachments_by_post(post.id)?;
 
-        assert!(result.is_err());
+        if !self.repos.posts.delete_post(post.id)? {
+            return Err(ServiceError::NotFound("post".into()));
+        }
+
+        let files: Vec<&str> = attachments.iter().map(|a| a.file_name.as_str()).collect();
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::DeletePost,
+                board_id: Some(board_id),
+                target: format!(">>{}", post.post_number),
+                reason,
+                before: Some(
+                    json!({
+                        "thread_id": post.thread_id,
+                        "post_number": post.post_number,
+                        "content": post.content,
+                        "files": files,
+                    })
+                    .to_string(),
+                ),
+                after: None,
+            },
+        )?;
+
+        self.release_files(&attachments)
+    }
+
+    /// Resolve the quotes in a post body to existing posts or thread OPs.
+    ///
+    /// Quotes that point at nothing are dropped.
+    fn resolve_quotes(
+        &self,
+        board_id: Uuid,
+        content: &str,
+    ) -> Result<Vec<(QuoteRef, QuoteTarget)>, ServiceError> {
+        let mut targets = Vec::new();
+
+        for quote in quotes::parse_quotes(content)
+            .into_iter()
+            .take(MAX_QUOTES_PER_POST)
+        {
+            let target_board = match &quote.board {
+                None => board_id,
+                Some(name) => match self.repos.boards.get_board_by_name(name)? {
+                    Some(board) => board.id,
+                    None => continue,
+                },
+            };
+
+            let thread_id = match self.repos.threads.get_thread_by_number(target_board, quote.number)? {
+                Some(thread) => Some(thread.id),
+                None => self
+                    .repos
+                    .posts
+                    .get_post_by_number(target_board, quote.number)?
+                    .map(|post| post.thread_id),
+            };
+
+            if let Some(thread_id) = thread_id {
+                let target = QuoteTarget {
+                    board_id: target_board,
+                    number: quote.number,
+                    thread_id,
+                };
+                targets.push((quote, target));
+            }
+        }
+
+        Ok(targets)
+    }
+
+    // =========================
+    // Upload Logic
+    // =========================
+
+    /// Validate and thumbnail uploads before anything is written.
+    fn prepare_uploads(
+        &self,
+        board: &Board,
+        uploads: Vec<Upload>,
+    ) -> Result<Vec<PreparedFile>, ServiceError> {
+        if uploads.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.media.is_none() {
+            return Err(ServiceError::Validation("File uploads are disabled".into()));
+        }
+        if uploads.len() > MAX_FILES_PER_POST {
+            return Err(ServiceError::Validation(format!(
+                "At most {MAX_FILES_PER_POST} files per post"
+            )));
+        }
+
+        let av = AvLimits {
+            max_duration: Duration::from_secs(board.av_policy.max_duration_secs.into()),
+            codecs: board.av_policy.codecs.clone(),
+        };
+
+        let posters = self.posters.as_deref();
+
+        uploads
+            .into_iter()
+            .map(|upload| match media::process_upload(&upload.data, &self.media_limits, &av, posters) {
+                Ok(processed) => Ok(PreparedFile {
+                    file_name: display_file_name(&upload.file_name, processed.kind().extension()),
+                    sha256: media::sha256_hex(processed.data()),
+                    upload: processed,
+                }),
+                Err(err) if err.is_rejection() => Err(ServiceError::Validation(format!(
+                    "{}: {err}",
+                    display_file_name(&upload.file_name, "file")
+                ))),
+                Err(err) => Err(err.into()),
+            })
+            .collect()
+    }
+
+    /// Refuse images close to a banned image, recording the match.
+    ///
+    /// `thread_id` is `None` for a new thread. Audio and video are not
+    /// fingerprinted.
+    fn check_banned_images(
+        &self,
+        board: &Board,
+        thread_id: Option<Uuid>,
+        files: &[PreparedFile],
+    ) -> Result<(), ServiceError> {
+        if files.is_empty() {
+            return Ok(());
+        }
+        let bans = self.repos.image_bans.get_bans()?;
+
+        for file in files {
+            let Some(hashes) = file.upload.hashes() else {
+                continue;
+            };
+            let closest = bans
+                .iter()
+                .map(|ban| {
+                    let banned = ImageHashes { phash: ban.phash, dhash: ban.dhash };
+                    (ban, hashes.distance(&banned))
+                })
+                .filter(|(_, distance)| *distance <= self.image_ban_distance)
+                .min_by_key(|(_, distance)| *distance);
+
+            if let Some((ban, distance)) = closest {
+                self.repos.image_bans.insert_match(&BannedImageMatch {
+                    id: Uuid::new_v4(),
+                    ban_id: ban.id,
+                    board_id: board.id,
+                    thread_id,
+                    file_name: file.file_name.clone(),
+                    sha256: file.sha256.clone(),
+                    distance,
+                    created_at: OffsetDateTime::now_utc(),
+                })?;
+
+                return Err(ServiceError::Validation(format!(
+                    "{}: This image is not allowed",
+                    file.file_name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Refuse files the board has seen before, as its policy demands.
+    ///
+    /// `thread_id` is `None` for a new thread. Identical files within
+    /// one post count as duplicates too.
+    fn check_duplicates(
+        &self,
+        board: &Board,
+        thread_id: Option<Uuid>,
+        files: &[PreparedFile],
+    ) -> Result<(), ServiceError> {
+        let scope = match board.duplicate_policy {
+            DuplicatePolicy::Allow => return Ok(()),
+            DuplicatePolicy::Thread => "in this thread",
+            DuplicatePolicy::Board => "on this board",
+        };
+
+        for (i, file) in files.iter().enumerate() {
+            let repeated = files[..i].iter().any(|f| f.sha256 == file.sha256);
+            let posted = self
+                .repos
+                .attachments
+                .find_by_hash(board.id, &file.sha256)?
+                .iter()
+                .any(|a| board.duplicate_policy == DuplicatePolicy::Board || Some(a.thread_id) == thread_id);
+
+            if repeated || posted {
+                return Err(ServiceError::Validation(format!(
+                    "{}: This file has already been posted {scope}",
+                    file.file_name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write validated uploads to the media store.
+    ///
+    /// Returns attachments that still need their thread and post ids.
+    fn store_files(&self, files: Vec<PreparedFile>) -> Result<Vec<Attachment>, ServiceError> {
+        let Some(store) = &self.media else {
+            return Ok(Vec::new());
+        };
+
+        let created_at = OffsetDateTime::now_utc();
+        let mut attachments = Vec::with_capacity(files.len());
+
+        for (position, file) in files.into_iter().enumerate() {
+            let PreparedFile { file_name, upload, sha256 } = file;
+            let storage_key = media::content_key(&sha256, upload.kind());
+            store.save(Bucket::Originals, &storage_key, upload.data())?;
+
+            let (thumbnail_key, thumbnail_width, thumbnail_height) = match upload.thumbnail() {
+                Some(thumbnail) => {
+                    let key = media::content_key(&sha256, thumbnail.kind);
+                    store.save(Bucket::Thumbnails, &key, &thumbnail.data)?;
+                    (key, thumbnail.width, thumbnail.height)
+                }
+                None => (String::new(), 0, 0),
+            };
+            let (width, height) = upload.dimensions();
+
+            attachments.push(Attachment {
+                id: Uuid::new_v4(),
+                thread_id: Uuid::nil(),
+                post_id: None,
+                position: position as u32,
+                file_name,
+                content_type: upload.kind().mime().to_string(),
+                size_bytes: upload.data().len() as i64,
+                sha256,
+                width,
+                height,
+                duration_ms: upload.duration().map(|d| d.as_millis().min(u32::MAX as u128) as u32),
+                storage_key,
+                thumbnail_key,
+                thumbnail_width,
+                thumbnail_height,
+                metadata_stripped: upload.metadata_stripped(),
+                created_at,
+            });
+        }
+
+        Ok(attachments)
+    }
+
+    fn insert_attachments(
+        &self,
+        thread_id: Uuid,
+        post_id: Option<Uuid>,
+        mut attachments: Vec<Attachment>,
+    ) -> Result<(), ServiceError> {
+        if attachments.is_empty() {
+            return Ok(());
+        }
+
+        for attachment in &mut attachments {
+            attachment.thread_id = thread_id;
+            attachment.post_id = post_id;
+        }
+
+        self.repos.attachments.insert_attachments(&attachments)?;
+        Ok(())
+    }
+
+    /// Delete the stored files of removed attachments that nothing else
+    /// references any more.
+    ///
+    /// Files saved again within `MEDIA_GRACE_PERIOD` are left for the
+    /// garbage collector, as a concurrent upload may be about to use them.
+    fn release_files(&self, removed: &[Attachment]) -> Result<(), ServiceError> {
+        let Some(store) = &self.media else {
+            return Ok(());
+        };
+
+        let mut seen = HashSet::new();
+        for a in removed {
+            if !seen.insert(&a.storage_key)
+                || self.repos.attachments.count_by_storage_key(&a.storage_key)? > 0
+            {
+                continue;
+            }
+            media::remove_if_stale(store.as_ref(), Bucket::Originals, &a.storage_key, MEDIA_GRACE_PERIOD)?;
+            if a.has_thumbnail() {
+                media::remove_if_stale(store.as_ref(), Bucket::Thumbnails, &a.thumbnail_key, MEDIA_GRACE_PERIOD)?;
+            }
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Delete stored files no attachment references.
+    ///
+    /// Meant to run periodically; files younger than `MEDIA_GRACE_PERIOD`
+    /// are kept.
+    pub fn collect_media_garbage(&self) -> Result<GcReport, ServiceError> {
+        let Some(store) = &self.media else {
+            return Ok(GcReport::default());
+        };
+
+        let mut referenced = ReferencedKeys::default();
+        for (original, thumbnail) in self.repos.attachments.get_media_keys()? {
+            referenced.originals.insert(original);
+            if !thumbnail.is_empty() {
+                referenced.thumbnails.insert(thumbnail);
+            }
+        }
+
+        Ok(media::collect_garbage(store.as_ref(), &referenced, MEDIA_GRACE_PERIOD)?)
+    }
+
+    /// List the posts of a thread.
+    pub fn list_posts(&self, thread_id: Uuid) -> Result<Vec<Post>, ServiceError> {
+        Ok(self.repos.posts.get_posts_by_thread(thread_id)?)
+    }
+
+    // =========================
+    // Image Bans
+    // =========================
+
+    /// Ban an image, given the file itself.
+    ///
+    /// Needs `ManageImageBans`.
+    pub fn ban_image(
+        &self,
+        actor: &User,
+        data: &[u8],
+        reason: String,
+    ) -> Result<BannedImageHash, ServiceError> {
+        self.authorize(actor, None, Permission::ManageImageBans)?;
+        let image = media::process_image(data, &self.media_limits).map_err(|err| {
+            if err.is_rejection() {
+                ServiceError::Validation(err.to_string())
+            } else {
+                err.into()
+            }
+        })?;
+
+        self.ban_image_hash(actor, image.hashes, reason)
+    }
+
+    /// Ban an image by its perceptual hashes.
+    ///
+    /// Needs `ManageImageBans`.
+    pub fn ban_image_hash(
+        &self,
+        actor: &User,
+        hashes: ImageHashes,
+        reason: String,
+    ) -> Result<BannedImageHash, ServiceError> {
+        self.authorize(actor, None, Permission::ManageImageBans)?;
+        let ban = BannedImageHash {
+            id: Uuid::new_v4(),
+            phash: hashes.phash,
+            dhash: hashes.dhash,
+            reason,
+            created_at: OffsetDateTime::now_utc(),
+            lifted_at: None,
+        };
+
+        self.repos.image_bans.insert_ban(&ban)?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::BanImage,
+                board_id: None,
+                target: image_ban_target(&ban),
+                reason: ban.reason.clone(),
+                before: None,
+                after: Some(image_ban_snapshot(&ban)),
+            },
+        )?;
+        Ok(ban)
+    }
+
+    /// Image bans in force, oldest first.
+    ///
+    /// Needs `ManageImageBans`.
+    pub fn list_image_bans(&self, actor: &User) -> Result<Vec<BannedImageHash>, ServiceError> {
+        self.authorize(actor, None, Permission::ManageImageBans)?;
+        Ok(self.repos.image_bans.get_bans()?)
+    }
+
+    /// Lift an image ban; the uploads it refused stay on record.
+    ///
+    /// Needs `ManageImageBans`.
+    pub fn unban_image(&self, actor: &User, ban_id: Uuid) -> Result<(), ServiceError> {
+        self.authorize(actor, None, Permission::ManageImageBans)?;
+        let ban = self
+            .repos
+            .image_bans
+            .get_ban(ban_id)?
+            .filter(|ban| ban.lifted_at.is_none())
+            .ok_or_else(|| ServiceError::NotFound("image ban".into()))?;
+
+        let now = OffsetDateTime::now_utc();
+        if !self.repos.image_bans.lift_ban(ban.id, now)? {
+            return Err(ServiceError::NotFound("image ban".into()));
+        }
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::UnbanImage,
+                board_id: None,
+                target: image_ban_target(&ban),
+                reason: String::new(),
+                before: Some(image_ban_snapshot(&ban)),
+                after: Some(image_ban_snapshot(&BannedImageHash { lifted_at: Some(now), ..ban.clone() })),
+            },
+        )
+    }
+
+    /// The most recent uploads refused for matching a banned image,
+    /// including those refused by bans since lifted.
+    ///
+    /// Needs `ManageImageBans`.
+    pub fn list_image_ban_matches(
+        &self,
+        actor: &User,
+        limit: u32,
+    ) -> Result<Vec<BannedImageMatch>, ServiceError> {
+        self.authorize(actor, None, Permission::ManageImageBans)?;
+        Ok(self.repos.image_bans.get_matches(limit)?)
+    }
+
+    // =========================
+    // Accounts
+    // =========================
+
+    /// Create an account with the `User` role.
+    pub fn register_user(&self, username: String, password: &str) -> Result<User, ServiceError> {
+        if !self.registration_open {
+            return Err(ServiceError::Validation("Registration is closed".into()));
+        }
+
+        let username = username.trim().to_string();
+        if username.is_empty() || username.chars().count() > MAX_USERNAME_LEN {
+            return Err(ServiceError::Validation(format!(
+                "Username must be 1 to {MAX_USERNAME_LEN} characters"
+            )));
+        }
+        if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(ServiceError::Validation(
+                "Username may only contain letters, digits, `_` and `-`".into(),
+            ));
+        }
+        let length = password.chars().count();
+        if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&length) {
+            return Err(ServiceError::Validation(format!(
+                "Password must be {MIN_PASSWORD_LEN} to {MAX_PASSWORD_LEN} characters"
+            )));
+        }
+        if self.repos.users.find_by_username(&username)?.is_some() {
+            return Err(ServiceError::Validation("Username is taken".into()));
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            username,
+            password_hash: auth::hash_password(password)?,
+            role: Role::User,
+        };
+
+        self.repos.users.create(&user)?;
+        Ok(user)
+    }
+
+    /// Check a username and password and open a new session, returning
+    /// its token.
+    ///
+    /// Unknown users and wrong passwords fail the same way, and take
+    /// about as long.
+    pub fn log_in(&self, username: &str, password: &str) -> Result<String, ServiceError> {
+        let invalid = || ServiceError::Unauthorized("Invalid username or password".into());
+        if password.chars().count() > MAX_PASSWORD_LEN {
+            return Err(invalid());
+        }
+
+        let Some(user) = self.repos.users.find_by_username(username.trim())? else {
+            auth::verify_password(decoy_hash()?, password)?;
+            return Err(invalid());
+        };
+        if !auth::verify_password(&user.password_hash, password)? {
+            return Err(invalid());
+        }
+
+        let token = auth::generate_session_token();
+        let now = OffsetDateTime::now_utc();
+        let session = Session {
+            token_hash: auth::hash_session_token(&token),
+            user_id: user.id,
+            created_at: now,
+            last_seen_at: now,
+        };
+
+        self.repos.sessions.create(&session)?;
+        Ok(token)
+    }
+
+    /// End a session. Unknown tokens are ignored.
+    pub fn log_out(&self, token: &str) -> Result<(), ServiceError> {
+        self.repos.sessions.delete(&auth::hash_session_token(token))?;
+        Ok(())
+    }
+
+    /// End every session of a user; returns how many there were.
+    pub fn log_out_everywhere(&self, user_id: Uuid) -> Result<u64, ServiceError> {
+        Ok(self.repos.sessions.delete_by_user(user_id)?)
+    }
+
+    /// The user a session token belongs to, if the session is live.
+    ///
+    /// Expired sessions are deleted on sight; live ones have their
+    /// `last_seen_at` refreshed.
+    pub fn session_user(&self, token: &str) -> Result<Option<User>, ServiceError> {
+        let token_hash = auth::hash_session_token(token);
+        let Some(session) = self.repos.sessions.find(&token_hash)? else {
+            return Ok(None);
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let ended = |since, limit| offset_after(since, limit).is_some_and(|end| end <= now);
+        if ended(session.created_at, self.session_max_age)
+            || ended(session.last_seen_at, self.session_idle_timeout)
+        {
+            self.repos.sessions.delete(&token_hash)?;
+            return Ok(None);
+        }
+        if session.last_seen_at + SESSION_TOUCH_INTERVAL <= now {
+            self.repos.sessions.touch(&token_hash, now)?;
+        }
+
+        Ok(self.repos.users.find_by_id(session.user_id)?)
+    }
+
+    /// Change a user's role, ending all their sessions so that no token
+    /// issued under the old role outlives it.
+    ///
+    /// Needs `ManageUsers`.
+    pub fn set_user_role(&self, actor: &User, user_id: Uuid, role: Role) -> Result<(), ServiceError> {
+        self.authorize(actor, None, Permission::ManageUsers)?;
+        let Some(user) = self.repos.users.find_by_id(user_id)? else {
+            return Err(ServiceError::NotFound("user".into()));
+        };
+        if !self.repos.users.set_role(user_id, &role)? {
+            return Err(ServiceError::NotFound("user".into()));
+        }
+
+        self.repos.sessions.delete_by_user(user_id)?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::ChangeRole,
+                board_id: None,
+                target: user.username,
+                reason: String::new(),
+                before: Some(json!({ "role": user.role.to_string() }).to_string()),
+                after: Some(json!({ "role": role.to_string() }).to_string()),
+            },
+        )
+    }
+
+    /// Delete sessions past their lifetime or idle timeout; returns how
+    /// many there were.
+    pub fn purge_expired_sessions(&self) -> Result<u64, ServiceError> {
+        let now = OffsetDateTime::now_utc();
+        // A limit reaching back before any session existed purges nothing.
+        let cutoff = |limit| offset_before(now, limit).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        Ok(self.repos.sessions.delete_expired(
+            cutoff(self.session_max_age),
+            cutoff(self.session_idle_timeout),
+        )?)
+    }
+
+    // =========================
+    // Staff & Permissions
+    // =========================
+
+    /// Whether `user` may exercise `permission`, on `board_id` when the
+    /// action concerns a board.
+    pub fn is_allowed(
+        &self,
+        user: &User,
+        board_id: Option<Uuid>,
+        permission: Permission,
+    ) -> Result<bool, ServiceError> {
+        let staff = match board_id {
+            Some(board_id) => self.repos.staff.find(board_id, user.id)?,
+            None => None,
+        };
+
+        Ok(auth::authorize(user, staff.as_ref(), permission).is_ok())
+    }
+
+    /// Fail with `Forbidden` unless `user` may exercise `permission`.
+    ///
+    /// Every moderation action calls this before doing anything.
+    pub fn authorize(
+        &self,
+        user: &User,
+        board_id: Option<Uuid>,
+        permission: Permission,
+    ) -> Result<(), ServiceError> {
+        if self.is_allowed(user, board_id, permission)? {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden(AuthError::Forbidden(permission).to_string()))
+        }
+    }
+
+    /// Appoint a user to a board's staff, or change their role there,
+    /// ending the user's sessions when their role changes.
+    ///
+    /// Needs `ManageStaff` on the board; appointing or replacing an
+    /// owner needs `ManageUsers`.
+    pub fn appoint_staff(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        user_id: Uuid,
+        role: BoardRole,
+    ) -> Result<BoardStaff, ServiceError> {
+        self.authorize(actor, Some(board_id), Permission::ManageStaff)?;
+        self.get_board(board_id)?;
+        let Some(user) = self.repos.users.find_by_id(user_id)? else {
+            return Err(ServiceError::NotFound("user".into()));
+        };
+
+        let current = self.repos.staff.find(board_id, user_id)?;
+        if role == BoardRole::BoardOwner
+            || current.as_ref().is_some_and(|s| s.role == BoardRole::BoardOwner)
+        {
+            self.authorize(actor, None, Permission::ManageUsers)?;
+        }
+
+        let staff = BoardStaff {
+            board_id,
+            user_id,
+            role,
+            created_at: current.as_ref().map_or_else(OffsetDateTime::now_utc, |s| s.created_at),
+        };
+
+        self.repos.staff.upsert(&staff)?;
+        if current.as_ref().map(|s| s.role) != Some(role) {
+            self.repos.sessions.delete_by_user(user_id)?;
+        }
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::AppointStaff,
+                board_id: Some(board_id),
+                target: user.username,
+                reason: String::new(),
+                before: current.map(|s| json!({ "role": s.role.to_string() }).to_string()),
+                after: Some(json!({ "role": role.to_string() }).to_string()),
+            },
+        )?;
+        Ok(staff)
+    }
+
+    /// Remove a user from a board's staff, under the same rules as
+    /// `appoint_staff`, ending the user's sessions.
+    pub fn dismiss_staff(
+        &self,
+        actor: &User,
+        board_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<(), ServiceError> {
+        self.authorize(actor, Some(board_id), Permission::ManageStaff)?;
+
+        let Some(current) = self.repos.staff.find(board_id, user_id)? else {
+            return Err(ServiceError::NotFound("staff member".into()));
+        };
+        if current.role == BoardRole::BoardOwner {
+            self.authorize(actor, None, Permission::ManageUsers)?;
+        }
+
+        self.repos.staff.delete(board_id, user_id)?;
+        self.repos.sessions.delete_by_user(user_id)?;
+        let username = self.repos.users.find_by_id(user_id)?.map(|u| u.username);
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::DismissStaff,
+                board_id: Some(board_id),
+                target: username.unwrap_or_else(|| user_id.to_string()),
+                reason: String::new(),
+                before: Some(json!({ "role": current.role.to_string() }).to_string()),
+                after: None,
+            },
+        )
+    }
+
+    /// A board's staff, longest-serving first.
+    pub fn list_staff(&self, board_id: Uuid) -> Result<Vec<BoardStaff>, ServiceError> {
+        Ok(self.repos.staff.get_by_board(board_id)?)
+    }
+
+    /// A board's staff with their usernames, longest-serving first.
+    ///
+    /// Needs `ManageStaff` on the board.
+    pub fn staff_views(&self, actor: &User, board_id: Uuid) -> Result<Vec<StaffView>, ServiceError> {
+        self.authorize(actor, Some(board_id), Permission::ManageStaff)?;
+
+        let mut views = Vec::new();
+        for staff in self.list_staff(board_id)? {
+            if let Some(user) = self.repos.users.find_by_id(staff.user_id)? {
+                views.push(StaffView { staff, username: user.username });
+            }
+        }
+        Ok(views)
+    }
+
+    /// Find a user by username.
+    pub fn find_user(&self, username: &str) -> Result<User, ServiceError> {
+        self.repos
+            .users
+            .find_by_username(username.trim())?
+            .ok_or_else(|| ServiceError::NotFound("user".into()))
+    }
+
+    /// Whether `user` holds a site-wide staff role or any board role.
+    pub fn is_staff(&self, user: &User) -> Result<bool, ServiceError> {
+        Ok(user.role != Role::User || !self.repos.staff.get_by_user(user.id)?.is_empty())
+    }
+
+    // =========================
+    // Bans & Appeals
+    // =========================
+
+    /// Fail with `Banned` if a ban in force covers the poster on `board_id`.
+    fn check_ban(&self, poster: &Poster, board_id: Uuid) -> Result<(), ServiceError> {
+        let now = OffsetDateTime::now_utc();
+        let bans = self.repos.bans.find_covering(poster.ip, now)?;
+
+        match bans.into_iter().find(|ban| ban.board_id.is_none_or(|id| id == board_id)) {
+            Some(ban) => Err(ServiceError::Banned(ban.reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Ban an address or range from one board or, with `board_id`
+    /// unset, from all of them.
+    pub fn ban_ip(&self, actor: &User, ban: NewBan) -> Result<Ban, ServiceError> {
+        self.authorize(actor, ban.board_id, Permission::BanUser)?;
+        if let Some(board_id) = ban.board_id {
+            self.get_board(board_id)?;
+        }
+
+        let reason = ban.reason.trim().to_string();
+        if reason.is_empty() || reason.chars().count() > MAX_BAN_REASON_LEN {
+            return Err(ServiceError::Validation(format!(
+                "Reason must be 1 to {MAX_BAN_REASON_LEN} characters"
+            )));
+        }
+        let staff_note = ban.staff_note.trim().to_string();
+        if staff_note.chars().count() > MAX_BAN_NOTE_LEN {
+            return Err(ServiceError::Validation(format!(
+                "Staff note must be at most {MAX_BAN_NOTE_LEN} characters"
+            )));
+        }
+        if ban.duration.is_some_and(|duration| duration.is_zero()) {
+            return Err(ServiceError::Validation("Ban duration cannot be zero".into()));
+        }
+        if ban.duration.is_some_and(|duration| duration > MAX_BAN_DURATION) {
+            return Err(ServiceError::Validation(
+                "Ban duration is too long; leave it empty for a permanent ban".into(),
+            ));
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let expires_at = match ban.duration {
+            Some(duration) => Some(
+                time::Duration::try_from(duration)
+                    .ok()
+                    .and_then(|duration| now.checked_add(duration))
+                    .ok_or_else(|| ServiceError::Validation("Ban duration is too long".into()))?,
+            ),
+            None => None,
+        };
+        let ban = Ban {
+            id: Uuid::new_v4(),
+            ip_range: ban.ip_range.trunc(),
+            board_id: ban.board_id,
+            reason,
+            staff_note,
+            created_by: Some(actor.id),
+            created_at: now,
+            expires_at,
+        };
+
+        self.repos.bans.insert_ban(&ban)?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::BanIp,
+                board_id: ban.board_id,
+                target: ban.ip_range.to_string(),
+                reason: ban.reason.clone(),
+                before: None,
+                after: Some(ban_snapshot(&ban)),
+            },
+        )?;
+        Ok(ban)
+    }
+
+    /// End a ban now.
+    pub fn lift_ban(&self, actor: &User, ban_id: Uuid) -> Result<(), ServiceError> {
+        let ban = self
+            .repos
+            .bans
+            .get_ban(ban_id)?
+            .ok_or_else(|| ServiceError::NotFound("ban".into()))?;
+        self.authorize(actor, ban.board_id, Permission::BanUser)?;
+
+        let now = OffsetDateTime::now_utc();
+        self.repos.bans.set_expiry(ban.id, Some(now))?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::LiftBan,
+                board_id: ban.board_id,
+                target: ban.ip_range.to_string(),
+                reason: String::new(),
+                before: Some(ban_snapshot(&ban)),
+                after: Some(ban_snapshot(&Ban { expires_at: Some(now), ..ban.clone() })),
+            },
+        )
+    }
+
+    /// Bans in force that `actor` may lift, newest first.
+    pub fn list_bans(&self, actor: &User) -> Result<Vec<Ban>, ServiceError> {
+        let mut bans = Vec::new();
+        for ban in self.repos.bans.get_active(OffsetDateTime::now_utc())? {
+            if self.is_allowed(actor, ban.board_id, Permission::BanUser)? {
+                bans.push(ban);
+            }
+        }
+        Ok(bans)
+    }
+
+    /// The bans in force on `ip`, as shown to the poster.
+    pub fn ban_notices(&self, ip: IpAddr) -> Result<Vec<BanNotice>, ServiceError> {
+        let bans = self.repos.bans.find_covering(ip, OffsetDateTime::now_utc())?;
+
+        let mut notices = Vec::with_capacity(bans.len());
+        for ban in bans {
+            let board = match ban.board_id {
+                Some(board_id) => Some(self.get_board(board_id)?.name),
+                None => None,
+            };
+            let appeal = self.repos.bans.get_appeal_by_ban(ban.id)?;
+
+            notices.push(BanNotice {
+                ban_id: ban.id,
+                board,
+                reason: ban.reason,
+                created_at: ban.created_at,
+                expires_at: ban.expires_at,
+                appeal: appeal.map(|appeal| appeal.status),
+            });
+        }
+        Ok(notices)
+    }
+
+    /// Appeal a ban in force on `ip`. Each ban takes one appeal.
+    pub fn appeal_ban(
+        &self,
+        ip: IpAddr,
+        ban_id: Uuid,
+        message: String,
+    ) -> Result<BanAppeal, ServiceError> {
+        let now = OffsetDateTime::now_utc();
+        // Bans on other addresses are as good as missing.
+        let ban = self
+            .repos
+            .bans
+            .find_covering(ip, now)?
+            .into_iter()
+            .find(|ban| ban.id == ban_id)
+            .ok_or_else(|| ServiceError::NotFound("ban".into()))?;
+
+        let message = message.trim().to_string();
+        if message.is_empty() || message.chars().count() > MAX_APPEAL_LEN {
+            return Err(ServiceError::Validation(format!(
+                "Appeal must be 1 to {MAX_APPEAL_LEN} characters"
+            )));
+        }
+        if self.repos.bans.get_appeal_by_ban(ban.id)?.is_some() {
+            return Err(ServiceError::Validation("This ban has already been appealed".into()));
+        }
+
+        let appeal = BanAppeal {
+            id: Uuid::new_v4(),
+            ban_id: ban.id,
+            message,
+            status: AppealStatus::Pending,
+            created_at: now,
+            decided_by: None,
+            decided_at: None,
+        };
+
+        self.repos.bans.insert_appeal(&appeal)?;
+        Ok(appeal)
+    }
+
+    /// Pending appeals against bans `actor` may lift, oldest first.
+    pub fn list_appeals(&self, actor: &User) -> Result<Vec<AppealView>, ServiceError> {
+        let mut views = Vec::new();
+        for appeal in self.repos.bans.get_pending_appeals()? {
+            let Some(ban) = self.repos.bans.get_ban(appeal.ban_id)? else {
+                continue;
+            };
+            if self.is_allowed(actor, ban.board_id, Permission::BanUser)? {
+                views.push(AppealView { appeal, ban });
+            }
+        }
+        Ok(views)
+    }
+
+    /// Accept or deny a pending appeal; accepting lifts the ban.
+    pub fn decide_appeal(
+        &self,
+        actor: &User,
+        appeal_id: Uuid,
+        accept: bool,
+    ) -> Result<(), ServiceError> {
+        let appeal = self
+            .repos
+            .bans
+            .get_appeal(appeal_id)?
+            .ok_or_else(|| ServiceError::NotFound("appeal".into()))?;
+        let ban = self
+            .repos
+            .bans
+            .get_ban(appeal.ban_id)?
+            .ok_or_else(|| ServiceError::NotFound("ban".into()))?;
+        self.authorize(actor, ban.board_id, Permission::BanUser)?;
+
+        let now = OffsetDateTime::now_utc();
+        let status = if accept { AppealStatus::Accepted } else { AppealStatus::Denied };
+        if !self.repos.bans.decide_appeal(appeal.id, status, actor.id, now)? {
+            return Err(ServiceError::Validation("This appeal was already decided".into()));
+        }
+        let lifted = accept && ban.is_active(now);
+        if lifted {
+            self.repos.bans.set_expiry(ban.id, Some(now))?;
+        }
+
+        let after = if lifted { Ban { expires_at: Some(now), ..ban.clone() } } else { ban.clone() };
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::DecideAppeal,
+                board_id: ban.board_id,
+                target: ban.ip_range.to_string(),
+                reason: appeal.message,
+                before: Some(
+                    json!({ "appeal": appeal.status.to_string(), "ban": ban_value(&ban) })
+                        .to_string(),
+                ),
+                after: Some(
+                    json!({ "appeal": status.to_string(), "ban": ban_value(&after) }).to_string(),
+                ),
+            },
+        )
+    }
+
+    // =========================
+    // Reports
+    // =========================
+
+    /// Report a reply on `board_id`.
+    ///
+    /// A second report on the same post from the same address is
+    /// accepted but not stored while the first is still open.
+    pub fn report_post(
+        &self,
+        poster: &Poster,
+        board_id: Uuid,
+        number: i64,
+        category: ReportCategory,
+        comment: String,
+    ) -> Result<(), ServiceError> {
+        let post = self.find_post_by_number(board_id, number)?;
+
+        let comment = comment.trim().to_string();
+        if comment.chars().count() > MAX_REPORT_COMMENT_LEN {
+            return Err(ServiceError::Validation(format!(
+                "Comment must be at most {MAX_REPORT_COMMENT_LEN} characters"
+            )));
+        }
+
+        let reporter_ip_hash = auth::hash_ip(&self.ip_hash_key, poster.ip);
+        let open = self.repos.reports.get_open_by_post(post.id)?;
+        if open.iter().any(|report| report.reporter_ip_hash == reporter_ip_hash) {
+            return Ok(());
+        }
+
+        self.repos.reports.insert_report(&Report {
+            id: Uuid::new_v4(),
+            post_id: Some(post.id),
+            board_id,
+            post_number: post.post_number,
+            category,
+            comment,
+            reporter_ip_hash,
+            created_at: OffsetDateTime::now_utc(),
+            status: ReportStatus::Open,
+            resolved_by: None,
+            resolved_at: None,
+        })?;
+        Ok(())
+    }
+
+    /// Open reports on boards where `actor` handles reports, grouped by
+    /// post; the most reported posts come first, then the oldest.
+    pub fn report_queue(&self, actor: &User) -> Result<Vec<ReportedPostView>, ServiceError> {
+        if !self.is_staff(actor)? {
+            return Err(ServiceError::Forbidden(
+                AuthError::Forbidden(Permission::HandleReports).to_string(),
+            ));
+        }
+
+        // Board name and whether `actor` may ban there, for boards where
+        // they handle reports.
+        let mut boards: HashMap<Uuid, Option<(String, bool)>> = HashMap::new();
+        let mut visible_board = |board_id: Uuid| -> Result<Option<(String, bool)>, ServiceError> {
+            if let Some(board) = boards.get(&board_id) {
+                return Ok(board.clone());
+            }
+            let board = if self.is_allowed(actor, Some(board_id), Permission::HandleReports)? {
+                let can_ban = self.is_allowed(actor, Some(board_id), Permission::BanUser)?;
+                Some((self.get_board(board_id)?.name, can_ban))
+            } else {
+                None
+            };
+            boards.insert(board_id, board.clone());
+            Ok(board)
+        };
+
+        let mut views: Vec<ReportedPostView> = Vec::new();
+        let mut index: HashMap<Uuid, usize> = HashMap::new();
+        for report in self.repos.reports.get_open_reports()? {
+            let Some(post_id) = report.post_id else {
+                continue;
+            };
+            if let Some(&i) = index.get(&post_id) {
+                views[i].reports.push(report);
+                continue;
+            }
+            let Some((board, can_ban)) = visible_board(report.board_id)? else {
+                continue;
+            };
+
+            let post = self.find_post_by_number(report.board_id, report.post_number)?;
+            index.insert(post_id, views.len());
+            views.push(ReportedPostView {
+                post_id,
+                href: post_href(&board, post.post_number, false),
+                board,
+                number: post.post_number,
+                excerpt: excerpt(&post.content),
+                bannable: can_ban && post.poster.is_some_and(|p| p.ip.is_some()),
+                reports: vec![report],
+            });
+        }
+
+        // Stable, so posts with as many reports keep oldest-first order.
+        views.sort_by_key(|view| std::cmp::Reverse(view.reports.len()));
+        Ok(views)
+    }
+
+    /// The most recently handled reports on boards where `actor`
+    /// handles reports, newest first.
+    pub fn handled_reports(&self, actor: &User) -> Result<Vec<HandledReportView>, ServiceError> {
+        let mut views = Vec::new();
+        for report in self.repos.reports.get_resolved_reports(REPORT_HISTORY_LEN)? {
+            if !self.is_allowed(actor, Some(report.board_id), Permission::HandleReports)? {
+                continue;
+            }
+            let board = self.get_board(report.board_id)?.name;
+            let resolved_by = match report.resolved_by {
+                Some(user_id) => self.repos.users.find_by_id(user_id)?.map(|user| user.username),
+                None => None,
+            };
+            views.push(HandledReportView { report, board, resolved_by });
+        }
+        Ok(views)
+    }
+
+    /// Close every open report on a post without acting on it.
+    pub fn dismiss_reports(&self, actor: &User, post_id: Uuid) -> Result<(), ServiceError> {
+        let reports = self.open_reports(post_id)?;
+        let report = &reports[0];
+        self.authorize(actor, Some(report.board_id), Permission::HandleReports)?;
+
+        self.repos.reports.resolve_reports(
+            post_id,
+            ReportStatus::Dismissed,
+            actor.id,
+            OffsetDateTime::now_utc(),
+        )?;
+        self.record(
+            Some(actor),
+            LoggedAction {
+                action: ModActionKind::DismissReports,
+                board_id: Some(report.board_id),
+                target: format!(">>{}", report.post_number),
+                reason: String::new(),
+                before: Some(reports_snapshot(&reports)),
+                after: None,
+            },
+        )
+    }
+
+    /// Delete a reported post and close its reports, first banning its
+    /// poster from the board when `ban` is given.
+    pub fn delete_reported_post(
+        &self,
+        actor: &User,
+        post_id: Uuid,
+        ban: Option<PosterBan>,
+    ) -> Result<(), ServiceError> {
+        let reports = self.open_reports(post_id)?;
+        let report = &reports[0];
+        self.authorize(actor, Some(report.board_id), Permission::HandleReports)?;
+        self.authorize(actor, Some(report.board_id), Permission::DeletePost)?;
+        let post = self.find_post_by_number(report.board_id, report.post_number)?;
+
+        let status = match ban {
+            Some(ban) => {
+                let Some(ip) = post.poster.as_ref().and_then(|p| p.ip) else {
+                    return Err(ServiceError::Validation(
+                        "The poster's address is no longer kept, so they cannot be banned".into(),
+                    ));
+                };
+                self.ban_ip(
+                    actor,
+                    NewBan {
+                        ip_range: IpNet::from(ip),
+                        board_id: Some(report.board_id),
+                        reason: ban.reason,
+                        staff_note: format!("Reported post No. {}", post.post_number),
+                        duration: ban.duration,
+                    },
+                )?;
+                ReportStatus::Banned
+            }
+            None => ReportStatus::Deleted,
+        };
+
+        self.repos.reports.resolve_reports(post_id, status, actor.id, OffsetDateTime::now_utc())?;
+        let reason = format!("Reported: {}", report.category.label());
+        self.remove_post(actor, report.board_id, &post, reason)
+    }
+
+    /// The open reports on a post, oldest first, or `NotFound` if there
+    /// are none.
+    fn open_reports(&self, post_id: Uuid) -> Result<Vec<Report>, ServiceError> {
+        let reports = self.repos.reports.get_open_by_post(post_id)?;
+        if reports.is_empty() {
+            return Err(ServiceError::NotFound("report".into()));
+        }
+        Ok(reports)
+    }
+
+    // =========================
+    // Moderation Log
+    // =========================
+
+    /// Append a privileged operation to the moderation log; `actor` is
+    /// `None` for operator actions.
+    fn record(&self, actor: Option<&User>, entry: LoggedAction) -> Result<(), ServiceError> {
+        self.repos.mod_actions.insert_action(&ModAction {
+            id: Uuid::new_v4(),
+            actor_id: actor.map(|user| user.id),
+            actor_name: actor.map_or_else(|| SYSTEM_ACTOR.to_string(), |user| user.username.clone()),
+            action: entry.action,
+            board_id: entry.board_id,
+            target: entry.target,
+            reason: entry.reason,
+            before: entry.before,
+            after: entry.after,
+            created_at: OffsetDateTime::now_utc(),
+        })?;
+        Ok(())
+    }
+
+    /// A page of the moderation log, newest first.
+    ///
+    /// Needs `ViewModLog` on `query.board_id` when it is set; otherwise
+    /// entries are limited to the boards (and, for site-wide entries,
+    /// the site) where `actor` has it.
+    pub fn mod_log(
+        &self,
+        actor: &User,
+        query: ModLogQuery,
+    ) -> Result<Vec<ModActionView>, ServiceError> {
+        match query.board_id {
+            Some(board_id) => self.authorize(actor, Some(board_id), Permission::ViewModLog)?,
+            None if !self.is_staff(actor)? => {
+                return Err(ServiceError::Forbidden(
+                    AuthError::Forbidden(Permission::ViewModLog).to_string(),
+                ));
+            }
+            None => {}
+        }
+
+        let actor_id = match query.actor.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => match self.repos.users.find_by_username(name)? {
+                Some(user) => Some(user.id),
+                None => return Ok(Vec::new()),
+            },
+            _ => None,
+        };
+        // Staff without `ViewModLog` site-wide only read their own boards,
+        // filtered in storage so every page is full.
+        let board_ids = if query.board_id.is_some() || self.is_allowed(actor, None, Permission::ViewModLog)? {
+            None
+        } else {
+            let mut board_ids = Vec::new();
+            for staff in self.repos.staff.get_by_user(actor.id)? {
+                if self.is_allowed(actor, Some(staff.board_id), Permission::ViewModLog)? {
+                    board_ids.push(staff.board_id);
+                }
+            }
+            Some(board_ids)
+        };
+        let filter = ModActionFilter {
+            board_id: query.board_id,
+            board_ids,
+            actor_id,
+            actions: query.action.into_iter().collect(),
+            before: query.before,
+            limit: MOD_LOG_PAGE_LEN,
+        };
+
+        // Board name (`None` for site-wide entries) where `actor` may read
+        // the log.
+        let mut boards: HashMap<Option<Uuid>, Option<Option<String>>> = HashMap::new();
+        let mut visible_board = |board_id: Option<Uuid>| -> Result<_, ServiceError> {
+            if let Some(board) = boards.get(&board_id) {
+                return Ok(board.clone());
+            }
+            let board = if self.is_allowed(actor, board_id, Permission::ViewModLog)? {
+                match board_id {
+                    Some(board_id) => Some(Some(self.get_board(board_id)?.name)),
+                    None => Some(None),
+                }
+            } else {
+                None
+            };
+            boards.insert(board_id, board.clone());
+            Ok(board)
+        };
+
+        let mut views = Vec::new();
+        for action in self.repos.mod_actions.get_actions(&filter)? {
+            if let Some(board) = visible_board(action.board_id)? {
+                views.push(ModActionView { board, action });
+            }
+        }
+        Ok(views)
+    }
+
+    /// The anonymised log of a board that publishes one: deletions, bans
+    /// and thread flag changes with their reasons, without who acted,
+    /// snapshots, or the addresses banned.
+    pub fn public_mod_log(&self, board_id: Uuid) -> Result<Vec<PublicModActionView>, ServiceError> {
+        let board = self.get_board(board_id)?;
+        if !board.public_mod_log {
+            return Err(ServiceError::NotFound("moderation log".into()));
+        }
+
+        let filter = ModActionFilter {
+            board_id: Some(board.id),
+            actions: ModActionKind::ALL.into_iter().filter(|kind| kind.is_public()).collect(),
+            limit: MOD_LOG_PAGE_LEN,
+            ..ModActionFilter::default()
+        };
+        Ok(self
+            .repos
+            .mod_actions
+            .get_actions(&filter)?
+            .into_iter()
+            .map(|action| PublicModActionView {
+                target: matches!(action.action, ModActionKind::DeletePost | ModActionKind::EditThread)
+                    .then_some(action.target),
+                action: action.action,
+                reason: action.reason,
+                created_at: action.created_at,
+            })
+            .collect())
+    }
+
+    // =========================
+    // Flood Control
+    // =========================
+
+    /// Fail with `RateLimited` while the poster is cooling down on
+    /// `board`, or has posted `text` there too recently.
+    fn check_rate_limit(
+        &self,
+        poster: &Poster,
+        board: &Board,
+        kind: PostKind,
+        text: &str,
+    ) -> Result<(), ServiceError> {
+        let cooldown = |own: Option<u32>, site: Duration| {
+            own.map_or(site, |secs| Duration::from_secs(secs.into()))
+        };
+        let (between, action) = match kind {
+            PostKind::Thread => (
+                cooldown(board.cooldowns.thread_secs, self.rate_limits.thread_cooldown),
+                "starting another thread",
+            ),
+            PostKind::Reply => (
+                cooldown(board.cooldowns.reply_secs, self.rate_limits.reply_cooldown),
+                "replying again",
+            ),
+        };
+        let text = text.trim();
+        let repost = if text.is_empty() {
+            Duration::ZERO
+        } else {
+            cooldown(board.cooldowns.repost_secs, self.rate_limits.repost_cooldown)
+        };
+        if between.is_zero() && repost.is_zero() {
+            return Ok(());
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let since = offset_before(now, between.max(repost)).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        let ip_hash = auth::hash_ip(&self.ip_hash_key, poster.ip);
+        let recent: Vec<(OffsetDateTime, String)> = match kind {
+            PostKind::Thread => self
+                .repos
+                .threads
+                .get_recent_by_ip_hash(board.id, &ip_hash, since)?
+                .into_iter()
+                .map(|thread| (thread.created_at, thread.title))
+                .collect(),
+            PostKind::Reply => self
+                .repos
+                .posts
+                .get_recent_by_ip_hash(board.id, &ip_hash, since)?
+                .into_iter()
+                .map(|post| (post.created_at, post.content))
+                .collect(),
+        };
+
+        // Whole seconds left until `cooldown` has passed since `at`.
+        let remaining = |at: OffsetDateTime, cooldown: Duration| match offset_after(at, cooldown) {
+            Some(until) => {
+                let left = (until - now).as_seconds_f64();
+                (left > 0.0).then(|| Duration::from_secs(left.ceil() as u64))
+            }
+            None => Some(MAX_RETRY_AFTER),
+        };
+
+        if let Some((at, _)) = recent.first()
+            && let Some(retry_after) = remaining(*at, between)
+        {
+            return Err(ServiceError::RateLimited { action, retry_after });
+        }
+        if let Some((at, _)) = recent.iter().find(|(_, earlier)| earlier.trim() == text)
+            && let Some(retry_after) = remaining(*at, repost)
+        {
+            return Err(ServiceError::RateLimited {
+                action: "posting the same text again",
+                retry_after,
+            });
+        }
+        Ok(())
+    }
+
+    // =========================
+    // CAPTCHA
+    // =========================
+
+    /// Whether opening a thread on `board_id` needs a solved CAPTCHA.
+    pub fn thread_needs_captcha(&self, board_id: Uuid) -> Result<bool, ServiceError> {
+        Ok(self.get_board(board_id)?.captcha_mode.covers_threads())
+    }
+
+    /// Whether replying to `thread_id` needs a solved CAPTCHA.
+    pub fn reply_needs_captcha(&self, thread_id: Uuid) -> Result<bool, ServiceError> {
+        let thread = self.get_thread(thread_id)?;
+        Ok(self.get_board(thread.board_id)?.captcha_mode.covers_replies())
+    }
+
+    /// Issue a new challenge; returns the id its image is fetched by.
+    ///
+    /// Challenges that expired unsolved are cleared out first.
+    pub fn new_captcha(&self) -> Result<Uuid, ServiceError> {
+        let now = OffsetDateTime::now_utc();
+        self.repos.captchas.delete_expired(now)?;
+
+        let captcha = Captcha {
+            id: Uuid::new_v4(),
+            answer: auth::generate_captcha_answer(media::CAPTCHA_ALPHABET, CAPTCHA_LENGTH),
+            expires_at: now + CAPTCHA_LIFETIME,
+        };
+        self.repos.captchas.insert(&captcha)?;
+        Ok(captcha.id)
+    }
+
+    /// The PNG image of a challenge that is still open.
+    ///
+    /// The same challenge always renders the same image, so reloading
+    /// it gives nothing away.
+    pub fn captcha_image(&self, id: Uuid) -> Result<Vec<u8>, ServiceError> {
+        let captcha = self
+            .repos
+            .captchas
+            .get(id)?
+            .filter(|captcha| captcha.expires_at > OffsetDateTime::now_utc())
+            .ok_or_else(|| ServiceError::NotFound("captcha".into()))?;
+
+        let (high, low) = captcha.id.as_u64_pair();
+        Ok(media::render_captcha(&captcha.answer, high ^ low)?)
+    }
+
+    /// Check an answer, using the challenge up whatever the outcome.
+    ///
+    /// Case and surrounding spaces are ignored. Unknown and expired
+    /// challenges are never solved.
+    pub fn solve_captcha(&self, id: Uuid, answer: &str) -> Result<bool, ServiceError> {
+        let Some(captcha) = self.repos.captchas.take(id)? else {
+            return Ok(false);
+        };
+
+        Ok(captcha.expires_at > OffsetDateTime::now_utc()
+            && auth::tokens_match(&answer.trim().to_ascii_uppercase(), &captcha.answer))
+    }
+
+    // =========================
+    // Poster Records
+    // =========================
+
+    /// What is stored about `poster` with their thread or post.
+    fn poster_info(&self, poster: &Poster) -> PosterInfo {
+        PosterInfo {
+            ip_hash: auth::hash_ip(&self.ip_hash_key, poster.ip),
+            ip: Some(poster.ip),
+            user_agent_hash: poster
+                .user_agent
+                .as_deref()
+                .map(|ua| auth::hash_user_agent(&self.ip_hash_key, ua)),
+        }
+    }
+
+    /// Forget the raw addresses of posts older than the retention
+    /// period; returns how many threads and posts were cleared.
+    ///
+    /// A retention period reaching back before any post keeps everything.
+    pub fn purge_poster_ips(&self) -> Result<u64, ServiceError> {
+        let Some(cutoff) = offset_before(OffsetDateTime::now_utc(), self.ip_retention) else {
+            return Ok(0);
+        };
+
+        Ok(self.repos.threads.clear_ip_addresses(cutoff)?
+            + self.repos.posts.clear_ip_addresses(cutoff)?)
+    }
+
+    /// Every thread and post made from the address with `ip_hash` on
+    /// boards where `actor` may ban, newest first.
+    pub fn posts_by_poster(
+        &self,
+        actor: &User,
+        ip_hash: &str,
+    ) -> Result<Vec<PosterPostView>, ServiceError> {
+        if !self.is_staff(actor)? {
+            return Err(ServiceError::Forbidden(
+                AuthError::Forbidden(Permission::BanUser).to_string(),
+            ));
+        }
+
+        let mut boards: HashMap<Uuid, Option<String>> = HashMap::new();
+        let mut visible_board = |board_id: Uuid| -> Result<Option<String>, ServiceError> {
+            if let Some(name) = boards.get(&board_id) {
+                return Ok(name.clone());
+            }
+            let name = if self.is_allowed(actor, Some(board_id), Permission::BanUser)? {
+                Some(self.get_board(board_id)?.name)
+            } else {
+                None
+            };
+            boards.insert(board_id, name.clone());
+            Ok(name)
+        };
+
+        let mut views = Vec::new();
+        for thread in self.repos.threads.get_threads_by_ip_hash(ip_hash)? {
+            let Some(board) = visible_board(thread.board_id)? else {
+                continue;
+            };
+            views.push(PosterPostView {
+                href: post_href(&board, thread.op_number, false),
+                board,
+                number: thread.op_number,
+                excerpt: excerpt(&thread.title),
+                ip: thread.poster.and_then(|p| p.ip),
+                created_at: thread.created_at,
+            });
+        }
+        for post in self.repos.posts.get_posts_by_ip_hash(ip_hash)? {
+            let thread = self.get_thread(post.thread_id)?;
+            let Some(board) = visible_board(thread.board_id)? else {
+                continue;
+            };
+            views.push(PosterPostView {
+                href: post_href(&board, post.post_number, false),
+                board,
+                number: post.post_number,
+                excerpt: excerpt(&post.content),
+                ip: post.poster.and_then(|p| p.ip),
+                created_at: post.created_at,
+            });
+        }
+
+        views.sort_by_key(|view| std::cmp::Reverse(view.created_at));
+        Ok(views)
+    }
+
+    // =========================
+    // Page Views
+    // =========================
+
+    /// Assemble a thread page: rendered bodies, quote links and backlinks.
+    ///
+    /// Posts stored before rendering was cached are rendered here from
+    /// the stored quote edges, and the result is written back.
+    pub fn thread_view(&self, thread_id: Uuid) -> Result<ThreadView, ServiceError> {
+        let thread = self.get_thread(thread_id)?;
+        let board = self.get_board(thread.board_id)?;
+        let posts = self.list_posts(thread_id)?;
+        let edges = self.repos.quotes.get_quotes_by_thread(thread_id)?;
+        let mut attachments = self.repos.attachments.get_attachments_by_thread(thread_id)?;
+
+        let backlinks = |number: i64| -> Vec<ReplyLink> {
+            edges
+                .iter()
+                .filter(|q| q.to_thread_id == thread.id && q.to_board == board.name && q.to_number == number)
+                .map(|q| reply_link(q, &board.name, thread.id))
+                .collect()
+        };
+
+        let mut views = Vec::with_capacity(posts.len());
+        for post in posts {
+            let body_html = if post.content_html.is_empty() {
+                let outgoing: Vec<&Quote> =
+                    edges.iter().filter(|q| q.from_post_id == post.id).collect();
+
+                let html = markup::render(&post.content, |quote: &QuoteRef| {
+                    let to_board = quote.board.as_deref().unwrap_or(&board.name);
+                    outgoing
+                        .iter()
+                        .find(|q| q.to_board == to_board && q.to_number == quote.number)
+                        .map(|q| post_href(&q.to_board, q.to_number, q.to_thread_id == thread.id))
+                });
+                self.repos.posts.set_content_html(post.id, &html)?;
+                html
+            } else {
+                post.content_html.clone()
+            };
+
+            let (files, rest) = attachments
+                .into_iter()
+                .partition(|a| a.post_id == Some(post.id));
+            attachments = rest;
+
+            views.push(PostView {
+                replies: backlinks(post.post_number),
+                body_html,
+                attachments: files,
+                post,
+            });
+        }
+
+        attachments.retain(|a| a.post_id.is_none());
+
+        Ok(ThreadView {
+            replies: backlinks(thread.op_number),
+            attachments,
+            board,
+            thread,
+            posts: views,
+        })
+    }
+}
+
+
+/// A password hash checked against when a login names an unknown user,
+/// so that the response does not reveal which usernames exist.
+fn decoy_hash() -> Result<&'static str, AuthError> {
+    static DECOY: OnceLock<String> = OnceLock::new();
+
+    if let Some(hash) = DECOY.get() {
+        return Ok(hash);
+    }
+    let hash = auth::hash_password("decoy password")?;
+    Ok(DECOY.get_or_init(|| hash))
+}
+
+/// Base name of an uploaded file, trimmed to a displayable length.
+///
+/// Falls back to `file.{extension}` when the client sent no usable name.
+fn display_file_name(raw: &str, extension: &str) -> String {
+    let name = raw.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FILE_NAME_LEN)
+        .collect();
+
+    if name.is_empty() {
+        format!("file.{extension}")
+    } else {
+        name
+    }
+}
+
+/// A ban as logged: the range, scope, reasons and expiry.
+fn ban_value(ban: &Ban) -> serde_json::Value {
+    json!({
+        "ip_range": ban.ip_range.to_string(),
+        "board_id": ban.board_id,
+        "reason": ban.reason,
+        "staff_note": ban.staff_note,
+        "expires_at": ban.expires_at.map(rfc3339),
+    })
+}
+
+fn ban_snapshot(ban: &Ban) -> String {
+    ban_value(ban).to_string()
+}
+
+/// How the log names an image ban: its perceptual hash.
+fn image_ban_target(ban: &BannedImageHash) -> String {
+    format!("image {:016x}", ban.phash)
+}
+
+/// An image ban as logged.
+fn image_ban_snapshot(ban: &BannedImageHash) -> String {
+    json!({
+        "phash": format!("{:016x}", ban.phash),
+        "dhash": format!("{:016x}", ban.dhash),
+        "reason": ban.reason,
+        "lifted_at": ban.lifted_at.map(rfc3339),
+    })
+    .to_string()
+}
+
+/// A board's settings as logged.
+fn board_snapshot(board: &Board) -> String {
+    json!({
+        "description": board.description,
+        "duplicate_policy": board.duplicate_policy.to_string(),
+        "av_policy": board.av_policy,
+        "cooldowns": board.cooldowns,
+        "captcha_mode": board.captcha_mode.to_string(),
+        "public_mod_log": board.public_mod_log,
+        "bump_limit": board.bump_limit,
+        "prune_policy": {
+            "max_threads": board.prune_policy.max_threads,
+            "mode": board.prune_policy.mode.to_string(),
+        },
+    })
+    .to_string()
+}
+
+/// `at + duration`, or `None` when that is past the last representable
+/// time.
+fn offset_after(at: OffsetDateTime, duration: Duration) -> Option<OffsetDateTime> {
+    time::Duration::try_from(duration).ok().and_then(|duration| at.checked_add(duration))
+}
+
+/// `at - duration`, or `None` when that is before the first
+/// representable time.
+fn offset_before(at: OffsetDateTime, duration: Duration) -> Option<OffsetDateTime> {
+    time::Duration::try_from(duration).ok().and_then(|duration| at.checked_sub(duration))
+}
+
+/// Whether a post's options field holds `sage`, in any case, among
+/// other options separated by spaces or commas.
+fn is_sage(options: &str) -> bool {
+    options
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .any(|option| option.eq_ignore_ascii_case("sage"))
+}
+
+/// A thread's flags as logged.
+fn flags_snapshot(flags: &ThreadFlags) -> String {
+    json!(flags).to_string()
+}
+
+/// Open reports as logged when they are dismissed.
+fn reports_snapshot(reports: &[Report]) -> String {
+    let reports: Vec<_> = reports
+        .iter()
+        .map(|r| json!({ "category": r.category.to_string(), "comment": r.comment }))
+        .collect();
+    json!({ "reports": reports }).to_string()
+}
+
+fn rfc3339(at: OffsetDateTime) -> String {
+    at.format(&Rfc3339).expect("RFC 3339 formatting cannot fail for UTC times")
+}
+
+/// First line of a title or body, cut to `POSTER_EXCERPT_LEN` characters.
+fn excerpt(text: &str) -> String {
+    let line = text.lines().find(|line| !line.trim().is_empty()).unwrap_or_default().trim();
+    let mut excerpt: String = line.chars().take(POSTER_EXCERPT_LEN).collect();
+    if line.chars().count() > POSTER_EXCERPT_LEN {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+
+/// Backlink from the quoting post, labelled as seen from `board`.
+fn reply_link(quote: &Quote, board: &str, thread_id: Uuid) -> ReplyLink {
+    let label = if quote.from_board == board {
+        format!(">>{}", quote.from_number)
+    } else {
+        format!(">>>/{}/{}", quote.from_board, quote.from_number)
+    };
+
+    ReplyLink {
+        href: post_href(&quote.from_board, quote.from_number, quote.from_thread_id == thread_id),
+        label,
+    }
+}
+
+
+/// TESTS:
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use media::LocalMediaStore;
+    use models::PruneMode;
+
+    fn services() -> ServiceLayer {
+        ServiceLayer::new(Repositories::memory())
+    }
+
+    fn poster() -> Poster {
+        Poster::new(std::net::Ipv4Addr::LOCALHOST.into())
+    }
+
+    /// The site administrator, registered on first use and promoted
+    /// straight in storage, the way an operator makes the first admin.
+    fn admin(services: &ServiceLayer) -> User {
+        if let Some(user) = services.repos.users.find_by_username("admin").unwrap() {
+            return user;
+        }
+        let user = services.register_user("admin".into(), "hunter2hunter2").unwrap();
+        services.repos.users.set_role(user.id, &Role::Admin).unwrap();
+        User { role: Role::Admin, ..user }
+    }
+
+    /// A valid 2x1 RGB PNG.
+    const TINY_PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x02\x00\x00\x00\x01\x08\x02\
+        \x00\x00\x00\x7b\x40\xe8\xdd\x00\x00\x00\x0dIDAT\x78\x9c\x63\xf8\xcf\xc0\x00\x44\x00\x08\xfe\x01\
+        \xff\xc6\x9e\x79\xf7\x00\x00\x00\x00IEND\xae\x42\x60\x82";
+
+    fn upload(file_name: &str, data: &[u8]) -> Upload {
+        Upload { file_name: file_name.into(), data: data.to_vec() }
+    }
+
+    #[test]
+    fn board_validation_works() {
+        let services = services();
+        let result = services.create_board(&admin(&services), "".into(), "desc".into());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn only_admins_create_boards_and_only_owners_edit_them() {
+        let services = services();
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let account = |name: &str| services.register_user(name.into(), "hunter2hunter2").unwrap();
+        let (owner, janitor, user) = (account("owner"), account("janitor"), account("user"));
+        services.appoint_staff(&admin, g.id, owner.id, BoardRole::BoardOwner).unwrap();
+        services.appoint_staff(&admin, g.id, janitor.id, BoardRole::Janitor).unwrap();
+
+        for refused in [&owner, &janitor, &user] {
+            assert!(matches!(
+                services.create_board(refused, "b".into(), "".into()),
+                Err(ServiceError::Forbidden(_))
+            ));
+            assert!(matches!(
+                services.list_image_bans(refused),
+                Err(ServiceError::Forbidden(_))
+            ));
+        }
+        for refused in [&janitor, &user] {
+            assert!(matches!(
+                services.set_captcha_mode(refused, g.id, CaptchaMode::All),
+                Err(ServiceError::Forbidden(_))
+            ));
+        }
+        assert!(matches!(
+            services.set_captcha_mode(&owner, v.id, CaptchaMode::All),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert_eq!(services.list_boards().unwrap().len(), 2);
+        assert_eq!(services.get_board(g.id).unwrap().captcha_mode, CaptchaMode::Off);
+
+        services.set_captcha_mode(&owner, g.id, CaptchaMode::All).unwrap();
+        assert_eq!(services.get_board(g.id).unwrap().captcha_mode, CaptchaMode::All);
+    }
+
+    #[test]
+    fn post_requires_existing_thread() {
+        let result = services().create_post(&poster(), Uuid::new_v4(), "hi".into(), "", vec![]);
+
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[test]
+    fn post_numbers_are_sequential_per_board() {
+        let services = services();
+        let admin = admin(&services);
+        let a = services.create_board(&admin, "a".into(), "".into()).unwrap();
+        let b = services.create_board(&admin, "b".into(), "".into()).unwrap();
+
+        let thread = services.create_thread(&poster(), a.id, "first".into(), vec![]).unwrap();
+        let reply = services.create_post(&poster(), thread.id, "reply".into(), "", vec![]).unwrap();
+        let other = services.create_thread(&poster(), b.id, "other".into(), vec![]).unwrap();
+
+        assert_eq!((thread.op_number, reply.post_number), (1, 2));
+        assert_eq!(other.op_number, 1);
+        assert_eq!(services.find_thread_by_number(a.id, 2).unwrap().id, thread.id);
+        assert!(services.find_thread_by_number(b.id, 2).is_err());
+    }
+
+    #[test]
+    fn quotes_resolve_and_backlink() {
+        let services = services();
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "".into()).unwrap();
+
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        let first = services.create_post(&poster(), thread.id, ">>1 nice".into(), "", vec![]).unwrap();
+        let elsewhere = services.create_thread(&poster(), v.id, "other".into(), vec![]).unwrap();
+        services
+            .create_post(&poster(), elsewhere.id, format!(">>>/g/{} >>99", first.post_number), "", vec![])
+            .unwrap();
+
+        let view = services.thread_view(thread.id).unwrap();
+        assert_eq!(view.replies.len(), 1);
+        assert_eq!(view.replies[0].label, ">>2");
+        assert!(view.posts[0].body_html.contains("href=\"#p1\""));
+        assert_eq!(view.posts[0].replies[0].label, ">>>/v/2");
+
+        let other = services.thread_view(elsewhere.id).unwrap();
+        assert!(other.posts[0].body_html.contains("href=\"/boards/g/res/2#p2\""));
+        assert!(other.posts[0].body_html.contains("quote-dead"));
+    }
+
+    #[test]
+    fn markup_is_rendered_once_and_cached() {
+        let services = services();
+        let g = services.create_board(&admin(&services), "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+
+        let post = services
+            .create_post(&poster(), thread.id, ">>1 <script>\n>green **bold**".into(), "", vec![])
+            .unwrap();
+        assert_eq!(
+            post.content_html,
+            "<a class=\"quote-link\" href=\"#p1\">&gt;&gt;1</a> &lt;script&gt;\n\
+             <span class=\"greentext\">&gt;green <strong>bold</strong></span>"
+        );
+
+        services.repos.posts.set_content_html(post.id, "").unwrap();
+        let view = services.thread_view(thread.id).unwrap();
+        assert_eq!(view.posts[0].body_html, post.content_html);
+        assert_eq!(services.list_posts(thread.id).unwrap()[0].content_html, post.content_html);
+    }
+
+    /// A service layer storing uploads in `dir`, plus a handle on the store.
+    fn with_media(dir: &tempfile::TempDir) -> (ServiceLayer, LocalMediaStore) {
+        let store = LocalMediaStore::open(dir.path()).unwrap();
+        let services = services().with_media(Arc::new(store.clone()), MediaLimits::default());
+        (services, store)
+    }
+
+    /// Age every stored file past the grace period.
+    fn backdate(store: &LocalMediaStore) {
+        let old = std::time::SystemTime::now() - MEDIA_GRACE_PERIOD * 2;
+        for bucket in [Bucket::Originals, Bucket::Thumbnails] {
+            for blob in store.list(bucket).unwrap() {
+                let path = store.path(bucket, &blob.key).unwrap();
+                let file = std::fs::File::options().write(true).open(path).unwrap();
+                file.set_modified(old).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn uploads_are_stored_and_attached() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let g = services.create_board(&admin(&services), "g".into(), "".into()).unwrap();
+
+        let thread = services
+            .create_thread(&poster(), g.id, "op".into(), vec![upload("C:\\pics\\cat.png", TINY_PNG)])
+            .unwrap();
+        let post = services
+            .create_post(&poster(), thread.id, "".into(), "", vec![upload("a.gif", TINY_PNG)])
+            .unwrap();
+
+        let view = services.thread_view(thread.id).unwrap();
+        let op_file = &view.attachments[0];
+        assert_eq!(op_file.file_name, "cat.png");
+        assert_eq!((op_file.content_type.as_str(), op_file.width, op_file.height), ("image/png", 2, 1));
+        assert_eq!(op_file.storage_key, format!("{}.png", op_file.sha256));
+        assert!(store.path(Bucket::Originals, &op_file.storage_key).unwrap().exists());
+        assert!(store.path(Bucket::Thumbnails, &op_file.thumbnail_key).unwrap().exists());
+
+        assert_eq!(view.posts[0].post.id, post.id);
+        assert_eq!(view.posts[0].attachments.len(), 1);
+        assert_eq!(view.posts[0].attachments[0].storage_key.rsplit('.').next(), Some("png"));
+    }
+
+    #[test]
+    fn stored_uploads_have_no_metadata() {
+        let photo: &[u8] = include_bytes!("../../media/testdata/metadata.jpg");
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let g = services.create_board(&admin(&services), "g".into(), "".into()).unwrap();
+
+        let thread = services
+            .create_thread(&poster(), g.id, "op".into(), vec![upload("photo.jpg", photo)])
+            .unwrap();
+
+        let file = &services.thread_view(thread.id).unwrap().attachments[0];
+        let stored = std::fs::read(store.path(Bucket::Originals, &file.storage_key).unwrap()).unwrap();
+        assert!(file.metadata_stripped);
+        assert_eq!(file.size_bytes as usize, stored.len());
+        assert_eq!(file.sha256, media::sha256_hex(&stored));
+        assert!(!stored.windows(7).any(|w| w == b"SECRET-"));
+        // The corpus photo is 16x8 with EXIF orientation 6.
+        assert_eq!((file.width, file.height), (8, 16));
+    }
+
+    #[test]
+    fn identical_uploads_share_a_file_until_the_last_post_goes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+
+        let first = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("a.png", TINY_PNG)]).unwrap();
+        let second = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("b.png", TINY_PNG)]).unwrap();
+        assert_eq!(store.list(Bucket::Originals).unwrap().len(), 1);
+        assert_eq!(store.list(Bucket::Thumbnails).unwrap().len(), 1);
+        backdate(&store);
+        let delete = |post: &Post| services.delete_post(&admin, g.id, post.post_number, String::new());
+
+        delete(&first).unwrap();
+        assert_eq!(store.list(Bucket::Originals).unwrap().len(), 1);
+
+        delete(&second).unwrap();
+        assert!(store.list(Bucket::Originals).unwrap().is_empty());
+        assert!(store.list(Bucket::Thumbnails).unwrap().is_empty());
+        assert!(matches!(delete(&second), Err(ServiceError::NotFound(_))));
+    }
+
+    #[test]
+    fn recently_saved_files_survive_release_and_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let g = services.create_board(&admin(&services), "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        let post = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("a.png", TINY_PNG)]).unwrap();
+        store.save(Bucket::Originals, "orphan.png", b"x").unwrap();
+
+        services.delete_post(&admin(&services), g.id, post.post_number, String::new()).unwrap();
+        assert_eq!(services.collect_media_garbage().unwrap().removed, 0);
+        assert_eq!(store.list(Bucket::Originals).unwrap().len(), 2);
+
+        services.create_thread(&poster(), g.id, "kept".into(), vec![upload("a.png", TINY_PNG)]).unwrap();
+        backdate(&store);
+        let report = services.collect_media_garbage().unwrap();
+        assert_eq!(report, GcReport { scanned: 3, removed: 1 });
+        assert!(!store.path(Bucket::Originals, "orphan.png").unwrap().exists());
+    }
+
+    #[test]
+    fn duplicate_policy_is_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, _store) = with_media(&dir);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let first = services.create_thread(&poster(), g.id, "one".into(), vec![upload("a.png", TINY_PNG)]).unwrap();
+
+        // Allowed by default.
+        services.create_post(&poster(), first.id, "".into(), "", vec![upload("a.png", TINY_PNG)]).unwrap();
+
+        services.set_duplicate_policy(&admin, g.id, DuplicatePolicy::Thread).unwrap();
+        let in_thread = services.create_post(&poster(), first.id, "".into(), "", vec![upload("a.png", TINY_PNG)]);
+        let same_post = services.create_thread(&poster(), g.id, "two".into(), vec![upload("a.png", TINY_PNG); 2]);
+        let other_thread = services.create_thread(&poster(), g.id, "two".into(), vec![upload("a.png", TINY_PNG)]);
+        assert!(matches!(in_thread, Err(ServiceError::Validation(m)) if m.contains("in this thread")));
+        assert!(matches!(same_post, Err(ServiceError::Validation(_))));
+        assert!(other_thread.is_ok());
+
+        services.set_duplicate_policy(&admin, g.id, DuplicatePolicy::Board).unwrap();
+        let on_board = services.create_thread(&poster(), g.id, "three".into(), vec![upload("a.png", TINY_PNG)]);
+        assert!(matches!(on_board, Err(ServiceError::Validation(m)) if m.contains("on this board")));
+        assert_eq!(services.list_threads(g.id).unwrap().len(), 2);
+
+        let h = services.create_board(&admin, "h".into(), "".into()).unwrap();
+        services.set_duplicate_policy(&admin, h.id, DuplicatePolicy::Board).unwrap();
+        assert!(services.create_thread(&poster(), h.id, "elsewhere".into(), vec![upload("a.png", TINY_PNG)]).is_ok());
+    }
+
+    /// A `width`x`height` picture with enough structure to fingerprint.
+    fn picture(width: u32, height: u32, seed: u32, jpeg: bool) -> Vec<u8> {
+        let pixels = image::RgbImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x * 256 / width, y * 192 / height);
+            let c = ((x * 3 + y * (2 + seed)) % 256) as u8;
+            image::Rgb([c, c / 2, 255 - c])
+        });
+        let format = if jpeg { image::ImageFormat::Jpeg } else { image::ImageFormat::Png };
+        let mut data = std::io::Cursor::new(Vec::new());
+        pixels.write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn banned_images_are_refused_and_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, _store) = with_media(&dir);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        let user = services.register_user("user".into(), "hunter2hunter2").unwrap();
+        assert!(matches!(
+            services.ban_image(&user, &picture(256, 192, 1, false), "spam".into()),
+            Err(ServiceError::Forbidden(_))
+        ));
+        let ban = services.ban_image(&admin, &picture(256, 192, 1, false), "spam".into()).unwrap();
+
+        let altered = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("b.jpg", &picture(300, 225, 1, true))]);
+        assert!(matches!(altered, Err(ServiceError::Validation(m)) if m == "b.jpg: This image is not allowed"));
+        assert!(services.list_posts(thread.id).unwrap().is_empty());
+
+        let hits = services.list_image_ban_matches(&admin, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].ban_id, hits[0].thread_id, hits[0].file_name.as_str()), (ban.id, Some(thread.id), "b.jpg"));
+
+        let other = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("c.png", &picture(256, 192, 7, false))]);
+        assert!(other.is_ok());
+
+        services.unban_image(&admin, ban.id).unwrap();
+        assert!(services.list_image_bans(&admin).unwrap().is_empty());
+        assert_eq!(services.list_image_ban_matches(&admin, 10).unwrap().len(), 1);
+        assert!(matches!(services.unban_image(&admin, ban.id), Err(ServiceError::NotFound(_))));
+        let logged = ModLogQuery { action: Some(ModActionKind::UnbanImage), ..ModLogQuery::default() };
+        let logged = services.mod_log(&admin, logged).unwrap();
+        assert_eq!((logged.len(), logged[0].board.as_deref()), (1, None));
+        assert!(logged[0].action.after.as_deref().unwrap().contains("lifted_at"));
+        assert!(services.create_thread(&poster(), g.id, "again".into(), vec![upload("b.jpg", &picture(300, 225, 1, true))]).is_ok());
+    }
+
+    /// Stands in for ffmpeg: every video's first frame is black.
+    struct BlackFrames;
+
+    impl PosterFrames for BlackFrames {
+        fn poster_frame(&self, _: &[u8], _: media::MediaKind) -> Result<image::DynamicImage, MediaError> {
+            Ok(image::DynamicImage::new_rgb8(320, 180))
+        }
+    }
+
+    #[test]
+    fn audio_and_video_follow_the_board_policy() {
+        let clip: &[u8] = include_bytes!("../../media/testdata/clip.webm");
+        let tone: &[u8] = include_bytes!("../../media/testdata/tone.ogg");
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let services = services.with_poster_frames(Arc::new(BlackFrames));
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+
+        let thread = services
+            .create_thread(&poster(), g.id, "op".into(), vec![upload("clip.webm", clip), upload("tone.ogg", tone)])
+            .unwrap();
+        let view = services.thread_view(thread.id).unwrap();
+        let (video, audio) = (&view.attachments[0], &view.attachments[1]);
+        assert!(video.is_video() && audio.is_audio());
+        assert_eq!((video.width, video.height, video.duration_ms), (640, 360, Some(12_500)));
+        assert_eq!((video.thumbnail_width, video.duration_label().as_str()), (250, "0:13"));
+        assert!(store.path(Bucket::Thumbnails, &video.thumbnail_key).unwrap().exists());
+        assert!(!audio.has_thumbnail() && !audio.metadata_stripped);
+
+        let opus_only = AvPolicy { max_duration_secs: 60, codecs: vec!["opus".into()] };
+        services.set_av_policy(&admin, g.id, opus_only).unwrap();
+        let refused = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("clip.webm", clip)]);
+        assert!(matches!(refused, Err(ServiceError::Validation(m)) if m.contains("`vp9` is not allowed")));
+        assert!(services.create_post(&poster(), thread.id, "".into(), "", vec![upload("tone.ogg", tone)]).is_ok());
+
+        services.set_av_policy(&admin, g.id, AvPolicy { max_duration_secs: 1, ..AvPolicy::default() }).unwrap();
+        let too_long = services.create_post(&poster(), thread.id, "".into(), "", vec![upload("tone.ogg", tone)]);
+        assert!(matches!(too_long, Err(ServiceError::Validation(m)) if m.contains("2 seconds long")));
+
+        let unknown = AvPolicy { codecs: vec!["theora".into()], ..AvPolicy::default() };
+        assert!(matches!(services.set_av_policy(&admin, g.id, unknown), Err(ServiceError::Validation(_))));
+    }
+
+    #[test]
+    fn bad_uploads_are_rejected_before_posting() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, _store) = with_media(&dir);
+        let g = services.create_board(&admin(&services), "g".into(), "".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+
+        let not_image = services.create_post(&poster(), thread.id, "x".into(), "", vec![upload("x.png", b"<html>")]);
+        let too_many = services.create_post(&poster(), thread.id, "x".into(), "", vec![upload("a.png", TINY_PNG); 5]);
+        let plain = self::services();
+        let h = plain.create_board(&admin(&plain), "h".into(), "".into()).unwrap();
+        let disabled = plain.create_thread(&poster(), h.id, "op".into(), vec![upload("a.png", TINY_PNG)]);
+
+        assert!(matches!(not_image, Err(ServiceError::Validation(_))));
+        assert!(matches!(too_many, Err(ServiceError::Validation(_))));
+        assert!(matches!(disabled, Err(ServiceError::Validation(_))));
+        assert!(services.list_posts(thread.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn accounts_register_log_in_and_out() {
+        let services = services();
+
+        let user = services.register_user(" alice ".into(), "hunter2hunter2").unwrap();
+        assert_eq!((user.username.as_str(), &user.role), ("alice", &Role::User));
+        assert!(matches!(
+            services.register_user("alice".into(), "another password"),
+            Err(ServiceError::Validation(_))
+        ));
+        assert!(services.register_user("bob".into(), "short").is_err());
+        assert!(services.register_user("bob smith".into(), "long enough").is_err());
+
+        assert!(matches!(
+            services.log_in("alice", "wrong password"),
+            Err(ServiceError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            services.log_in("nobody", "hunter2hunter2"),
+            Err(ServiceError::Unauthorized(_))
+        ));
+
+        let token = services.log_in("alice", "hunter2hunter2").unwrap();
+        assert_eq!(services.session_user(&token).unwrap().unwrap().id, user.id);
+        services.log_out(&token).unwrap();
+        assert!(services.session_user(&token).unwrap().is_none());
+        services.log_out(&token).unwrap();
+
+        let closed = ServiceLayer::new(Repositories::memory()).with_registration(false);
+        assert!(closed.register_user("carol".into(), "hunter2hunter2").is_err());
+    }
+
+    #[test]
+    fn sessions_expire_and_are_revoked() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone());
+        let user = services.register_user("alice".into(), "hunter2hunter2").unwrap();
+        let root = admin(&services);
+        let log_in = || services.log_in("alice", "hunter2hunter2").unwrap();
+
+        let token = log_in();
+        let stored = repos.sessions.find(&auth::hash_session_token(&token)).unwrap().unwrap();
+        assert_ne!(stored.token_hash, token);
+
+        assert!(services.session_user(&token).unwrap().is_some());
+
+        let other = log_in();
+        assert_eq!(services.log_out_everywhere(user.id).unwrap(), 2);
+        assert!(services.session_user(&other).unwrap().is_none());
+
+        let promoted = log_in();
+        services.set_user_role(&root, user.id, Role::Admin).unwrap();
+        assert!(services.session_user(&promoted).unwrap().is_none());
+        assert_eq!(services.session_user(&log_in()).unwrap().unwrap().role, Role::Admin);
+        assert!(matches!(
+            services.set_user_role(&root, Uuid::new_v4(), Role::User),
+            Err(ServiceError::NotFound(_))
+        ));
+
+        // Idle for two hours: gone under a one-hour idle timeout, and
+        // refreshed on use under a longer one.
+        let hour = Duration::from_secs(60 * 60);
+        let idle = log_in();
+        let idle_hash = auth::hash_session_token(&idle);
+        repos.sessions.touch(&idle_hash, OffsetDateTime::now_utc() - hour * 2).unwrap();
+        let lenient = ServiceLayer::new(repos.clone()).with_session_lifetime(hour * 24, hour * 3);
+        assert!(lenient.session_user(&idle).unwrap().is_some());
+        let last_seen = repos.sessions.find(&idle_hash).unwrap().unwrap().last_seen_at;
+        assert!(OffsetDateTime::now_utc() - last_seen < time::Duration::minutes(1));
+
+        repos.sessions.touch(&idle_hash, OffsetDateTime::now_utc() - hour * 2).unwrap();
+        let strict = ServiceLayer::new(repos.clone()).with_session_lifetime(hour * 24, hour);
+        assert!(strict.session_user(&idle).unwrap().is_none());
+        assert!(repos.sessions.find(&idle_hash).unwrap().is_none());
+
+        // Limits too large to add to a timestamp never expire anything.
+        let lasting = log_in();
+        let forever = ServiceLayer::new(repos.clone()).with_session_lifetime(Duration::MAX, Duration::MAX);
+        assert!(forever.session_user(&lasting).unwrap().is_some());
+        assert_eq!(forever.purge_expired_sessions().unwrap(), 0);
+
+        let fresh = log_in();
+        let short_lived = ServiceLayer::new(repos.clone()).with_session_lifetime(Duration::ZERO, hour);
+        assert_eq!(short_lived.purge_expired_sessions().unwrap(), 3);
+        assert!(services.session_user(&fresh).unwrap().is_none());
+    }
+
+    #[test]
+    fn staff_act_only_within_their_board_and_role() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone());
+        let user = |name: &str, role: Role| {
+            let user = services.register_user(name.into(), "hunter2hunter2").unwrap();
+            repos.users.set_role(user.id, &role).unwrap();
+            User { role, ..user }
+        };
+        let admin = user("admin", Role::Admin);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let owner = user("owner", Role::User);
+        let janitor = user("janitor", Role::User);
+        let global = user("global", Role::GlobalModerator);
+
+        services.appoint_staff(&admin, g.id, owner.id, BoardRole::BoardOwner).unwrap();
+        let before_appointment = services.log_in("janitor", "hunter2hunter2").unwrap();
+        services.appoint_staff(&owner, g.id, janitor.id, BoardRole::Janitor).unwrap();
+        assert!(services.session_user(&before_appointment).unwrap().is_none());
+        let as_janitor = services.log_in("janitor", "hunter2hunter2").unwrap();
+        services.appoint_staff(&owner, g.id, janitor.id, BoardRole::Janitor).unwrap();
+        assert!(services.session_user(&as_janitor).unwrap().is_some());
+        assert!(matches!(
+            services.appoint_staff(&janitor, g.id, janitor.id, BoardRole::Moderator),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(matches!(
+            services.appoint_staff(&owner, g.id, janitor.id, BoardRole::BoardOwner),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(services.dismiss_staff(&owner, g.id, owner.id).is_err());
+
+        assert!(services.authorize(&janitor, Some(g.id), Permission::DeletePost).is_ok());
+        assert!(services.authorize(&janitor, Some(g.id), Permission::BanUser).is_err());
+        assert!(services.authorize(&janitor, Some(v.id), Permission::DeletePost).is_err());
+        assert!(services.authorize(&owner, Some(g.id), Permission::EditBoard).is_ok());
+        assert!(services.authorize(&owner, Some(g.id), Permission::CreateBoard).is_err());
+        assert!(services.authorize(&global, Some(v.id), Permission::BanUser).is_ok());
+        assert!(services.authorize(&global, Some(v.id), Permission::ManageStaff).is_err());
+
+        services.dismiss_staff(&owner, g.id, janitor.id).unwrap();
+        assert!(services.session_user(&as_janitor).unwrap().is_none());
+        assert!(services.authorize(&janitor, Some(g.id), Permission::DeletePost).is_err());
+        assert_eq!(services.list_staff(g.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bans_stop_posting_until_lifted_on_appeal() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone());
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, janitor.id, BoardRole::Janitor).unwrap();
+
+        let range = |range: &str| NewBan {
+            ip_range: range.parse().unwrap(),
+            board_id: Some(g.id),
+            reason: "spam".into(),
+            staff_note: "bot".into(),
+            duration: Some(Duration::from_secs(3600)),
+        };
+        assert!(matches!(services.ban_ip(&janitor, range("10.0.0.0/8")), Err(ServiceError::Forbidden(_))));
+        assert!(services.ban_ip(&admin, NewBan { reason: " ".into(), ..range("10.0.0.0/8") }).is_err());
+        for duration in [Duration::ZERO, MAX_BAN_DURATION * 2, Duration::MAX] {
+            let ban = services.ban_ip(&admin, NewBan { duration: Some(duration), ..range("10.0.0.0/8") });
+            assert!(matches!(ban, Err(ServiceError::Validation(_))));
+        }
+        let ban = services.ban_ip(&admin, range("10.1.2.3/8")).unwrap();
+        assert_eq!(ban.ip_range.to_string(), "10.0.0.0/8");
+
+        let banned = Poster::new("10.9.9.9".parse().unwrap());
+        assert!(matches!(
+            services.create_post(&banned, thread.id, "hi".into(), "", vec![]),
+            Err(ServiceError::Banned(reason)) if reason == "spam"
+        ));
+        assert!(services.create_thread(&banned, g.id, "new".into(), vec![]).is_err());
+        assert!(services.create_thread(&banned, v.id, "elsewhere".into(), vec![]).is_ok());
+        assert!(services.create_post(&poster(), thread.id, "hi".into(), "", vec![]).is_ok());
+
+        let notices = services.ban_notices(banned.ip).unwrap();
+        assert_eq!((notices.len(), notices[0].board.as_deref()), (1, Some("g")));
+        assert!(services.ban_notices(poster().ip).unwrap().is_empty());
+
+        assert!(matches!(
+            services.appeal_ban(poster().ip, ban.id, "please".into()),
+            Err(ServiceError::NotFound(_))
+        ));
+        let appeal = services.appeal_ban(banned.ip, ban.id, "please".into()).unwrap();
+        assert!(services.appeal_ban(banned.ip, ban.id, "again".into()).is_err());
+        assert!(services.list_appeals(&janitor).unwrap().is_empty());
+        assert_eq!(services.list_appeals(&admin).unwrap().len(), 1);
+
+        services.decide_appeal(&admin, appeal.id, true).unwrap();
+        assert!(services.decide_appeal(&admin, appeal.id, false).is_err());
+        assert!(services.create_post(&banned, thread.id, "back".into(), "", vec![]).is_ok());
+        assert!(services.list_bans(&admin).unwrap().is_empty());
+
+        let global = services
+            .ban_ip(&admin, NewBan { board_id: None, duration: None, ..range("10.0.0.0/8") })
+            .unwrap();
+        assert!(services.create_thread(&banned, v.id, "again".into(), vec![]).is_err());
+        services.lift_ban(&admin, global.id).unwrap();
+        assert!(services.create_thread(&banned, v.id, "again".into(), vec![]).is_ok());
+    }
+
+    #[test]
+    fn posts_are_traced_to_their_poster_for_staff_only() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone())
+            .with_ip_hash_key(b"key".to_vec())
+            .with_ip_retention(Duration::ZERO);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+
+        let troll = Poster::new("198.51.100.7".parse().unwrap())
+            .with_user_agent(Some("curl/8".into()));
+        let thread = services.create_thread(&troll, g.id, "op".into(), vec![]).unwrap();
+        let reply = services.create_post(&troll, thread.id, "first\nsecond".into(), "", vec![]).unwrap();
+        services.create_thread(&troll, v.id, "elsewhere".into(), vec![]).unwrap();
+        services.create_post(&poster(), thread.id, "bystander".into(), "", vec![]).unwrap();
+
+        let recorded = reply.poster.clone().unwrap();
+        assert_eq!(recorded.ip_hash, auth::hash_ip(b"key", troll.ip));
+        assert_eq!(recorded.ip, Some(troll.ip));
+        assert_eq!(recorded.user_agent_hash, Some(auth::hash_user_agent(b"key", "curl/8")));
+
+        let moderator = services.register_user("mod".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, moderator.id, BoardRole::Moderator).unwrap();
+        let anon = services.register_user("anon".into(), "hunter2hunter2").unwrap();
+
+        let everything = services.posts_by_poster(&admin, &recorded.ip_hash).unwrap();
+        assert_eq!(everything.len(), 3);
+        assert_eq!(everything[0].excerpt, "elsewhere");
+
+        let on_g = services.posts_by_poster(&moderator, &recorded.ip_hash).unwrap();
+        assert_eq!(on_g.len(), 2);
+        assert!(on_g.iter().all(|view| view.board == "g" && view.ip == Some(troll.ip)));
+        assert!(on_g.iter().any(|view| view.excerpt == "first" && view.number == reply.post_number));
+        assert!(matches!(
+            services.posts_by_poster(&anon, &recorded.ip_hash),
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        let keep_forever = ServiceLayer::new(repos.clone()).with_ip_retention(Duration::MAX);
+        assert_eq!(keep_forever.purge_poster_ips().unwrap(), 0);
+        assert_eq!(services.purge_poster_ips().unwrap(), 4);
+        let purged = services.posts_by_poster(&admin, &recorded.ip_hash).unwrap();
+        assert!(purged.iter().all(|view| view.ip.is_none()));
+        assert_eq!(purged.len(), 3);
+    }
+
+    #[test]
+    fn posters_wait_out_cooldowns_per_board() {
+        let hour = Duration::from_secs(3600);
+        let services = services().with_rate_limits(RateLimits {
+            thread_cooldown: hour,
+            reply_cooldown: Duration::ZERO,
+            repost_cooldown: hour,
+        });
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let other = Poster::new("198.51.100.7".parse().unwrap());
+
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        match services.create_thread(&poster(), g.id, "again".into(), vec![]) {
+            Err(ServiceError::RateLimited { action, retry_after }) => {
+                assert_eq!(action, "starting another thread");
+                assert!(retry_after > hour - Duration::from_secs(60) && retry_after <= hour);
+            }
+            other => panic!("expected a cooldown, got {other:?}"),
+        }
+        assert!(services.create_thread(&other, g.id, "mine".into(), vec![]).is_ok());
+        assert!(services.create_thread(&poster(), v.id, "op".into(), vec![]).is_ok());
+
+        services.create_post(&poster(), thread.id, "bump".into(), "", vec![]).unwrap();
+        services.create_post(&poster(), thread.id, "different".into(), "", vec![]).unwrap();
+        assert!(matches!(
+            services.create_post(&poster(), thread.id, " bump ".into(), "", vec![]),
+            Err(ServiceError::RateLimited { action: "posting the same text again", .. })
+        ));
+        assert!(services.create_post(&other, thread.id, "bump".into(), "", vec![]).is_ok());
+
+        services
+            .set_cooldowns(&admin, g.id, Cooldowns { reply_secs: Some(60), repost_secs: Some(0), ..Cooldowns::default() })
+            .unwrap();
+        assert!(matches!(
+            services.create_post(&other, thread.id, "bump".into(), "", vec![]),
+            Err(ServiceError::RateLimited { action: "replying again", .. })
+        ));
+        services.set_cooldowns(&admin, g.id, Cooldowns { thread_secs: Some(0), ..Cooldowns::default() }).unwrap();
+        assert!(services.create_thread(&poster(), g.id, "again".into(), vec![]).is_ok());
+    }
+
+    #[test]
+    fn cooldowns_past_representable_time_never_end() {
+        let forever = Duration::from_secs(u64::MAX);
+        let services = services().with_rate_limits(RateLimits {
+            thread_cooldown: forever,
+            reply_cooldown: forever,
+            repost_cooldown: forever,
+        });
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        assert!(matches!(
+            services.create_thread(&poster(), g.id, "again".into(), vec![]),
+            Err(ServiceError::RateLimited { retry_after: MAX_RETRY_AFTER, .. })
+        ));
+        services.create_post(&poster(), thread.id, "first".into(), "", vec![]).unwrap();
+        assert!(matches!(
+            services.create_post(&poster(), thread.id, "second".into(), "", vec![]),
+            Err(ServiceError::RateLimited { action: "replying again", .. })
+        ));
+    }
+
+    #[test]
+    fn captchas_are_single_use_and_follow_the_board_mode() {
+        let services = services();
+        let admin = admin(&services);
+        let board = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let thread = services.create_thread(&poster(), board.id, "op".into(), vec![]).unwrap();
+        assert!(!services.thread_needs_captcha(board.id).unwrap());
+
+        services.set_captcha_mode(&admin, board.id, CaptchaMode::Threads).unwrap();
+        assert!(services.thread_needs_captcha(board.id).unwrap());
+        assert!(!services.reply_needs_captcha(thread.id).unwrap());
+        services.set_captcha_mode(&admin, board.id, CaptchaMode::All).unwrap();
+        assert!(services.reply_needs_captcha(thread.id).unwrap());
+
+        let id = services.new_captcha().unwrap();
+        let answer = services.repos.captchas.get(id).unwrap().unwrap().answer;
+        assert_eq!(answer.len(), CAPTCHA_LENGTH);
+        let png = services.captcha_image(id).unwrap();
+        assert_eq!(png, services.captcha_image(id).unwrap());
+
+        assert!(services.solve_captcha(id, &format!(" {} ", answer.to_lowercase())).unwrap());
+        assert!(!services.solve_captcha(id, &answer).unwrap());
+        assert!(matches!(services.captcha_image(id), Err(ServiceError::NotFound(_))));
+
+        let id = services.new_captcha().unwrap();
+        assert!(!services.solve_captcha(id, "WRONG").unwrap());
+        assert!(services.repos.captchas.get(id).unwrap().is_none());
+
+        let expired = Captcha {
+            id: Uuid::new_v4(),
+            answer: "AC3DE7".into(),
+            expires_at: OffsetDateTime::now_utc() - time::Duration::seconds(1),
+        };
+        services.repos.captchas.insert(&expired).unwrap();
+        assert!(services.captcha_image(expired.id).is_err());
+        assert!(!services.solve_captcha(expired.id, "AC3DE7").unwrap());
+    }
+
+    #[test]
+    fn reports_are_grouped_by_post_and_closed_with_the_action_taken() {
+        let repos = Repositories::memory();
+        let services = ServiceLayer::new(repos.clone());
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        let spam = services.create_post(&poster(), thread.id, "buy now".into(), "", vec![]).unwrap();
+        let rude = services.create_post(&poster(), thread.id, "rude".into(), "", vec![]).unwrap();
+
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, janitor.id, BoardRole::Janitor).unwrap();
+        let anon = services.register_user("anon".into(), "hunter2hunter2").unwrap();
+
+        let other = Poster::new("198.51.100.7".parse().unwrap());
+        for reporter in [&other, &poster(), &other] {
+            services
+                .report_post(reporter, g.id, spam.post_number, ReportCategory::Spam, " ad ".into())
+                .unwrap();
+        }
+        services
+            .report_post(&other, g.id, rude.post_number, ReportCategory::Other, String::new())
+            .unwrap();
+        assert!(matches!(
+            services.report_post(&other, g.id, rude.post_number, ReportCategory::Other, "x".repeat(501)),
+            Err(ServiceError::Validation(_))
+        ));
+        assert!(matches!(
+            services.report_post(&other, g.id, 999, ReportCategory::Spam, String::new()),
+            Err(ServiceError::NotFound(_))
+        ));
+
+        assert!(matches!(services.report_queue(&anon), Err(ServiceError::Forbidden(_))));
+        let queue = services.report_queue(&janitor).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].post_id, spam.id);
+        assert_eq!(queue[0].reports.len(), 2);
+        assert_eq!(queue[0].reports[0].comment, "ad");
+        assert!(!queue[0].bannable);
+        assert!(services.report_queue(&admin).unwrap()[0].bannable);
+
+        services.dismiss_reports(&janitor, rude.id).unwrap();
+        assert!(matches!(services.dismiss_reports(&janitor, rude.id), Err(ServiceError::NotFound(_))));
+        let ban = PosterBan { reason: "spam".into(), duration: None };
+        assert!(matches!(
+            services.delete_reported_post(&janitor, spam.id, Some(ban.clone())),
+            Err(ServiceError::Forbidden(_))
+        ));
+        services.delete_reported_post(&admin, spam.id, Some(ban)).unwrap();
+
+        assert!(services.report_queue(&janitor).unwrap().is_empty());
+        assert!(services.find_post_by_number(g.id, spam.post_number).is_err());
+        assert!(matches!(
+            services.create_post(&poster(), thread.id, "again".into(), "", vec![]),
+            Err(ServiceError::Banned(_))
+        ));
+
+        let handled = services.handled_reports(&janitor).unwrap();
+        let statuses: Vec<_> = handled.iter().map(|view| view.report.status).collect();
+        assert_eq!(statuses.iter().filter(|s| **s == ReportStatus::Banned).count(), 2);
+        assert_eq!(statuses.iter().filter(|s| **s == ReportStatus::Dismissed).count(), 1);
+        let banned = handled.iter().find(|view| view.report.status == ReportStatus::Banned).unwrap();
+        assert_eq!(banned.resolved_by.as_deref(), Some("admin"));
+        assert_eq!(banned.report.post_id, None);
+        assert_eq!(banned.report.post_number, spam.post_number);
+    }
+
+    #[test]
+    fn privileged_actions_are_logged_for_staff_and_optionally_the_public() {
+        let services = services();
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let thread = services.create_thread(&poster(), g.id, "op".into(), vec![]).unwrap();
+        let reply = services.create_post(&poster(), thread.id, "buy now".into(), "", vec![]).unwrap();
+
+        let moderator = services.register_user("mod".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, moderator.id, BoardRole::Moderator).unwrap();
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, janitor.id, BoardRole::Janitor).unwrap();
+        let global = services.register_user("global".into(), "hunter2hunter2").unwrap();
+        services.set_user_role(&admin, global.id, Role::GlobalModerator).unwrap();
+        assert!(matches!(
+            services.set_user_role(&moderator, moderator.id, Role::Admin),
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        services.delete_post(&janitor, g.id, reply.post_number, "spam".into()).unwrap();
+        let ban = NewBan {
+            ip_range: "192.0.2.0/24".parse().unwrap(),
+            board_id: Some(g.id),
+            reason: "flooding".into(),
+            staff_note: "bot".into(),
+            duration: None,
+        };
+        let ban = services.ban_ip(&moderator, ban).unwrap();
+        services.lift_ban(&moderator, ban.id).unwrap();
+        services.set_captcha_mode(&admin, v.id, CaptchaMode::All).unwrap();
+
+        let all = services.mod_log(&admin, ModLogQuery::default()).unwrap();
+        let kinds: Vec<_> = all.iter().map(|view| view.action.action).collect();
+        for kind in [
+            ModActionKind::ChangeRole,
+            ModActionKind::AppointStaff,
+            ModActionKind::DeletePost,
+            ModActionKind::BanIp,
+            ModActionKind::LiftBan,
+            ModActionKind::EditBoard,
+        ] {
+            assert!(kinds.contains(&kind), "{kind} was not logged");
+        }
+        let deleted = all.iter().find(|view| view.action.action == ModActionKind::DeletePost).unwrap();
+        assert_eq!((deleted.board.as_deref(), deleted.action.target.as_str()), (Some("g"), ">>2"));
+        assert_eq!((deleted.action.actor_name.as_str(), deleted.action.reason.as_str()), ("janitor", "spam"));
+        assert!(deleted.action.before.as_deref().unwrap().contains("buy now"));
+        let edited = all.iter().find(|view| view.action.action == ModActionKind::EditBoard).unwrap();
+        assert_eq!((edited.action.actor_id, edited.action.actor_name.as_str()), (Some(admin.id), "admin"));
+        assert!(edited.action.after.as_deref().unwrap().contains(r#""captcha_mode":"all""#));
+
+        let seen_by_mod = services.mod_log(&moderator, ModLogQuery::default()).unwrap();
+        assert!(seen_by_mod.iter().all(|view| view.board.as_deref() == Some("g")));
+        assert!(matches!(
+            services.mod_log(&moderator, ModLogQuery { board_id: Some(v.id), ..ModLogQuery::default() }),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(matches!(
+            services.mod_log(&janitor, ModLogQuery { board_id: Some(g.id), ..ModLogQuery::default() }),
+            Err(ServiceError::Forbidden(_))
+        ));
+        let by_mod = ModLogQuery { actor: Some("mod".into()), ..ModLogQuery::default() };
+        assert_eq!(services.mod_log(&admin, by_mod).unwrap().len(), 2);
+        let bans = ModLogQuery { action: Some(ModActionKind::BanIp), ..ModLogQuery::default() };
+        assert_eq!(services.mod_log(&admin, bans).unwrap().len(), 1);
+
+        assert!(matches!(services.public_mod_log(g.id), Err(ServiceError::NotFound(_))));
+        services.set_public_mod_log(&admin, g.id, true).unwrap();
+        let public = services.public_mod_log(g.id).unwrap();
+        assert_eq!(public.len(), 2);
+        assert_eq!((public[0].action, public[0].target.as_deref()), (ModActionKind::BanIp, None));
+        assert_eq!(public[1].target.as_deref(), Some(">>2"));
+    }
+
+    #[test]
+    fn board_staff_page_through_their_own_log_entries() {
+        let services = services();
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let v = services.create_board(&admin, "v".into(), "games".into()).unwrap();
+        let moderator = services.register_user("mod".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, moderator.id, BoardRole::Moderator).unwrap();
+
+        services.set_bump_limit(&admin, g.id, 10).unwrap();
+        for limit in 1..=MOD_LOG_PAGE_LEN + 1 {
+            services.set_bump_limit(&admin, v.id, limit).unwrap();
+        }
+        let seen_by_mod = services.mod_log(&moderator, ModLogQuery::default()).unwrap();
+        let kinds: Vec<_> = seen_by_mod.iter().map(|view| view.action.action).collect();
+        assert_eq!(kinds, [ModActionKind::EditBoard, ModActionKind::AppointStaff]);
+        assert!(seen_by_mod.iter().all(|view| view.board.as_deref() == Some("g")));
+        assert_eq!(services.mod_log(&admin, ModLogQuery::default()).unwrap().len(), MOD_LOG_PAGE_LEN as usize);
+    }
+
+    #[test]
+    fn staff_flag_threads_and_flags_are_enforced() {
+        let services = services().with_cyclical_reply_limit(2);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let older = services.create_thread(&poster(), g.id, "rules".into(), vec![]).unwrap();
+        let newer = services.create_thread(&poster(), g.id, "chat".into(), vec![]).unwrap();
+
+        let janitor = services.register_user("janitor".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, janitor.id, BoardRole::Janitor).unwrap();
+        let moderator = services.register_user("mod".into(), "hunter2hunter2").unwrap();
+        services.appoint_staff(&admin, g.id, moderator.id, BoardRole::Moderator).unwrap();
+
+        let sticky = ThreadFlags { sticky: Some(0), ..ThreadFlags::default() };
+        assert!(matches!(
+            services.set_thread_flags(&janitor, older.id, sticky, "".into()),
+            Err(ServiceError::Forbidden(_))
+        ));
+        services.set_thread_flags(&moderator, older.id, sticky, "read first".into()).unwrap();
+        let order: Vec<_> = services.list_threads(g.id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(order, [older.id, newer.id]);
+
+        let locked = ThreadFlags { locked: true, ..sticky };
+        services.set_thread_flags(&moderator, older.id, locked, "".into()).unwrap();
+        assert!(matches!(
+            services.create_post(&poster(), older.id, "hi".into(), "", vec![]),
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        for n in 0..3 {
+            services.create_post(&poster(), newer.id, format!("reply {n}"), "", vec![]).unwrap();
+        }
+        let cyclical = ThreadFlags { cyclical: true, ..ThreadFlags::default() };
+        services.set_thread_flags(&moderator, newer.id, cyclical, "".into()).unwrap();
+        let bodies = |services: &ServiceLayer| -> Vec<String> {
+            services.list_posts(newer.id).unwrap().into_iter().map(|p| p.content).collect()
+        };
+        assert_eq!(bodies(&services), ["reply 1", "reply 2"]);
+        services.create_post(&poster(), newer.id, "reply 3".into(), "", vec![]).unwrap();
+        assert_eq!(bodies(&services), ["reply 2", "reply 3"]);
+
+        let changes = ModLogQuery { action: Some(ModActionKind::EditThread), ..ModLogQuery::default() };
+        let logged = services.mod_log(&admin, changes.clone()).unwrap();
+        assert_eq!(logged.len(), 3);
+        assert_eq!((logged[2].action.target.as_str(), logged[2].action.reason.as_str()), (">>1", "read first"));
+        assert!(logged[1].action.after.as_deref().unwrap().contains(r#""locked":true"#));
+
+        services.set_thread_flags(&moderator, newer.id, cyclical, "".into()).unwrap();
+        assert_eq!(services.mod_log(&admin, changes).unwrap().len(), 3);
+        assert!(matches!(
+            services.set_thread_flags(&janitor, newer.id, cyclical, "".into()),
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        // The sticky stays and the new thread pushes `newer` into the archive.
+        let policy = PrunePolicy { max_threads: 2, ..PrunePolicy::default() };
+        services.set_prune_policy(&admin, g.id, policy).unwrap();
+        services.create_thread(&poster(), g.id, "news".into(), vec![]).unwrap();
+        assert_eq!(services.list_archived_threads(g.id).unwrap()[0].id, newer.id);
+        for flags in [cyclical, ThreadFlags::default()] {
+            assert!(matches!(
+                services.set_thread_flags(&moderator, newer.id, flags, "".into()),
+                Err(ServiceError::Forbidden(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn replies_bump_unless_saged_autosaged_or_past_the_limit() {
+        let services = services();
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "technology".into()).unwrap();
+        let first = services.create_thread(&poster(), g.id, "first".into(), vec![]).unwrap();
+        let second = services.create_thread(&poster(), g.id, "second".into(), vec![]).unwrap();
+        let order = || -> Vec<Uuid> { services.list_threads(g.id).unwrap().iter().map(|t| t.id).collect() };
+        assert_eq!(order(), [second.id, first.id]);
+
+        services.create_post(&poster(), first.id, "up".into(), "", vec![]).unwrap();
+        assert_eq!(order(), [first.id, second.id]);
+        services.create_post(&poster(), second.id, "quietly".into(), "noko, SAGE", vec![]).unwrap();
+        assert_eq!(order(), [first.id, second.id]);
+
+        let autosage = ThreadFlags { autosage: true, ..ThreadFlags::default() };
+        services.set_thread_flags(&admin, second.id, autosage, "".into()).unwrap();
+        services.create_post(&poster(), second.id, "up".into(), "", vec![]).unwrap();
+        assert_eq!(order(), [first.id, second.id]);
+
+        services.set_bump_limit(&admin, g.id, 2).unwrap();
+        services.create_post(&poster(), first.id, "two".into(), "", vec![]).unwrap();
+        let third = services.create_thread(&poster(), g.id, "third".into(), vec![]).unwrap();
+        services.create_post(&poster(), first.id, "three".into(), "", vec![]).unwrap();
+        assert_eq!(order(), [third.id, first.id, second.id]);
+        assert!(!is_sage("sagesage") && is_sage(" sage "));
+    }
+
+    #[test]
+    fn new_threads_archive_or_delete_the_least_recently_bumped() {
+        let dir = tempfile::tempdir().unwrap();
+        let (services, store) = with_media(&dir);
+        let admin = admin(&services);
+        let g = services.create_board(&admin, "g".into(), "".into()).unwrap();
+        let policy = |max_threads, mode| PrunePolicy { max_threads, mode };
+        let empty = services.set_prune_policy(&admin, g.id, policy(0, PruneMode::Archive));
+        assert!(matches!(empty, Err(ServiceError::Validation(_))));
+        services.set_prune_policy(&admin, g.id, policy(2, PruneMode::Archive)).unwrap();
+
+        let first = services.create_thread(&poster(), g.id, "first".into(), vec![]).unwrap();
+        let second = services.create_thread(&poster(), g.id, "second".into(), vec![upload("a.png", TINY_PNG)]).unwrap();
+        services.create_thread(&poster(), g.id, "third".into(), vec![]).unwrap();
+        let titles = |threads: Vec<Thread>| -> Vec<String> { threads.into_iter().map(|t| t.title).collect() };
+        assert_eq!(titles(services.list_threads(g.id).unwrap()), ["third", "second"]);
+        assert_eq!(titles(services.list_archived_threads(g.id).unwrap()), ["first"]);
+        let late = services.create_post(&poster(), first.id, "late".into(), "", vec![]);
+        assert!(matches!(late, Err(ServiceError::Forbidden(_))));
+
+        services.set_prune_policy(&admin, g.id, policy(2, PruneMode::Delete)).unwrap();
+        backdate(&store);
+        services.create_thread(&poster(), g.id, "fourth".into(), vec![]).unwrap();
+        assert_eq!(titles(services.list_threads(g.id).unwrap()), ["fourth", "third"]);
+        assert!(matches!(services.get_thread(second.id), Err(ServiceError::NotFound(_))));
+        assert!(store.list(Bucket::Originals).unwrap().is_empty());
+        assert_eq!(titles(services.list_archived_threads(g.id).unwrap()), ["first"]);
+    }
+}
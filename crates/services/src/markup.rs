@@ -0,0 +1,471 @@
+//! Post Markup
+//!
+//! Developer Notes:
+//! - Renders a post body to HTML. This is the ONLY place user text
+//!   becomes markup; templates print the result with `|safe`.
+//! - Block level: `[code]`/`[code=lang]` spanning lines becomes a
+//!   `<pre>` block; a line starting with `>` (and not a quote) is
+//!   greentext.
+//! - Inline: `[spoiler]`, `**bold**`, `''italic''`, single-line
+//!   `[code]`, `>>N` / `>>>/board/N` quotes and `http(s)://` links.
+//! - An opener without a closer is left as literal text, and every tag
+//!   is closed inside the span that opened it, so output is always
+//!   well formed.
+//! - Unclosed openers are remembered per span, keeping rendering linear
+//!   on hostile input.
+//!
+//! End Notes:
+//! Every byte of user text must pass through `escape_html`.
+
+use crate::quotes::{escape_html, read_quote, QuoteRef};
+
+const CODE_CLOSE: &str = "[/code]";
+const SPOILER_OPEN: &str = "[spoiler]";
+const SPOILER_CLOSE: &str = "[/spoiler]";
+const MAX_LANG_LEN: usize = 20;
+
+/// Render a post body as HTML.
+///
+/// `resolve` returns the link target of a quote, or `None` when the quote
+/// did not resolve to a real post (it is then shown as dead text).
+pub fn render(body: &str, resolve: impl Fn(&QuoteRef) -> Option<String>) -> String {
+    let body = body.replace("\r\n", "\n");
+    let mut html = String::with_capacity(body.len() + body.len() / 4);
+
+    let mut text_start = 0;
+    let mut search = 0;
+    let mut next_close: Option<usize> = None;
+
+    while let Some(offset) = body[search..].find("[code") {
+        let at = search + offset;
+        search = at + 1;
+
+        let Some((lang, content_start)) = read_code_open(&body, at) else {
+            continue;
+        };
+
+        if next_close.is_none_or(|close| close < content_start) {
+            next_close = body[content_start..].find(CODE_CLOSE).map(|i| content_start + i);
+        }
+        let Some(close) = next_close else {
+            break;
+        };
+
+        let code = &body[content_start..close];
+        if !code.contains('\n') {
+            continue;
+        }
+
+        render_lines(&body[text_start..at], at_line_start(&body, text_start), &resolve, &mut html);
+        render_code_block(lang, code, &mut html);
+
+        text_start = close + CODE_CLOSE.len();
+        search = text_start;
+    }
+
+    render_lines(&body[text_start..], at_line_start(&body, text_start), &resolve, &mut html);
+    html
+}
+
+fn at_line_start(body: &str, at: usize) -> bool {
+    at == 0 || body[..at].ends_with('\n')
+}
+
+/// Parse `[code]` or `[code=lang]` at `at`.
+///
+/// Returns the language tag and the offset just past the opener.
+fn read_code_open(body: &str, at: usize) -> Option<(Option<&str>, usize)> {
+    let rest = &body[at + "[code".len()..];
+
+    if rest.starts_with(']') {
+        return Some((None, at + "[code]".len()));
+    }
+
+    let lang = rest.strip_prefix('=')?;
+    let end = lang.find(']')?;
+    let lang = &lang[..end];
+    let valid = !lang.is_empty()
+        && lang.len() <= MAX_LANG_LEN
+        && lang
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'+' | b'#'));
+
+    valid.then_some((Some(lang), at + "[code=".len() + end + 1))
+}
+
+fn push_code_open(lang: Option<&str>, html: &mut String) {
+    match lang {
+        Some(lang) => {
+            html.push_str("<code class=\"language-");
+            html.push_str(&escape_html(lang));
+            html.push_str("\">");
+        }
+        None => html.push_str("<code>"),
+    }
+}
+
+fn render_code_block(lang: Option<&str>, code: &str, html: &mut String) {
+    let code = code.strip_prefix('\n').unwrap_or(code);
+    let code = code.strip_suffix('\n').unwrap_or(code);
+
+    html.push_str("<pre class=\"code\">");
+    push_code_open(lang, html);
+    html.push_str(&escape_html(code));
+    html.push_str("</code></pre>");
+}
+
+/// Render text outside code blocks, line by line.
+///
+/// `line_start` tells whether `text` begins at the start of a line.
+fn render_lines(
+    text: &str,
+    mut line_start: bool,
+    resolve: &impl Fn(&QuoteRef) -> Option<String>,
+    html: &mut String,
+) {
+    for line in text.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        if line_start && is_greentext(line) {
+            html.push_str("<span class=\"greentext\">");
+            render_inline(line, resolve, html);
+            html.push_str("</span>");
+        } else {
+            render_inline(line, resolve, html);
+        }
+
+        if newline {
+            html.push('\n');
+        }
+        line_start = true;
+    }
+}
+
+fn is_greentext(line: &str) -> bool {
+    line.starts_with('>') && !(line.starts_with(">>") && read_quote(line, 0).is_some())
+}
+
+/// Openers already known to have no closer in the current span.
+#[derive(Default)]
+struct Unclosed {
+    code: bool,
+    spoiler: bool,
+    bold: bool,
+    italic: bool,
+}
+
+/// Find `close` in `text` after `from`, remembering a miss in `flag`.
+fn find_close(text: &str, from: usize, close: &str, flag: &mut bool) -> Option<usize> {
+    if *flag {
+        return None;
+    }
+    let found = text[from..].find(close).map(|i| from + i);
+    *flag = found.is_none();
+    found
+}
+
+/// Render one line (or part of one) of inline markup.
+fn render_inline(text: &str, resolve: &impl Fn(&QuoteRef) -> Option<String>, html: &mut String) {
+    let bytes = text.as_bytes();
+    let mut unclosed = Unclosed::default();
+    let mut plain = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let rest = &text[i..];
+
+        let token_end = if rest.starts_with("[code") {
+            read_code_open(text, i).and_then(|(lang, start)| {
+                let close = find_close(text, start, CODE_CLOSE, &mut unclosed.code)?;
+                html.push_str(&escape_html(&text[plain..i]));
+                push_code_open(lang, html);
+                html.push_str(&escape_html(&text[start..close]));
+                html.push_str("</code>");
+                Some(close + CODE_CLOSE.len())
+            })
+        } else if rest.starts_with(SPOILER_OPEN) {
+            let start = i + SPOILER_OPEN.len();
+            find_close(text, start, SPOILER_CLOSE, &mut unclosed.spoiler).map(|close| {
+                html.push_str(&escape_html(&text[plain..i]));
+                html.push_str("<span class=\"spoiler\">");
+                render_inline(&text[start..close], resolve, html);
+                html.push_str("</span>");
+                close + SPOILER_CLOSE.len()
+            })
+        } else if rest.starts_with("**") {
+            render_pair(text, i, "**", "strong", &mut unclosed.bold, plain, resolve, html)
+        } else if rest.starts_with("''") {
+            render_pair(text, i, "''", "em", &mut unclosed.italic, plain, resolve, html)
+        } else if rest.starts_with(">>") {
+            read_quote(text, i).map(|(quote, end)| {
+                html.push_str(&escape_html(&text[plain..i]));
+                push_quote(&text[i..end], &quote, resolve, html);
+                end
+            })
+        } else if rest.starts_with("http") {
+            read_url(text, i).inspect(|&end| {
+                html.push_str(&escape_html(&text[plain..i]));
+                let url = escape_html(&text[i..end]);
+                html.push_str("<a class=\"autolink\" href=\"");
+                html.push_str(&url);
+                html.push_str("\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">");
+                html.push_str(&url);
+                html.push_str("</a>");
+            })
+        } else {
+            None
+        };
+
+        match token_end {
+            Some(end) => {
+                i = end;
+                plain = end;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+
+    html.push_str(&escape_html(&text[plain..]));
+}
+
+/// Render a symmetric delimiter pair such as `**bold**`.
+///
+/// Returns `None` (leaving the delimiter literal) when there is no
+/// closer or the enclosed text would be empty.
+#[allow(clippy::too_many_arguments)]
+fn render_pair(
+    text: &str,
+    at: usize,
+    delim: &str,
+    tag: &str,
+    unclosed: &mut bool,
+    plain: usize,
+    resolve: &impl Fn(&QuoteRef) -> Option<String>,
+    html: &mut String,
+) -> Option<usize> {
+    let start = at + delim.len();
+    let close = find_close(text, start, delim, unclosed)?;
+    if close == start {
+        return None;
+    }
+
+    html.push_str(&escape_html(&text[plain..at]));
+    html.push('<');
+    html.push_str(tag);
+    html.push('>');
+    render_inline(&text[start..close], resolve, html);
+    html.push_str("</");
+    html.push_str(tag);
+    html.push('>');
+    Some(close + delim.len())
+}
+
+fn push_quote(
+    raw: &str,
+    quote: &QuoteRef,
+    resolve: &impl Fn(&QuoteRef) -> Option<String>,
+    html: &mut String,
+) {
+    match resolve(quote) {
+        Some(href) => {
+            html.push_str("<a class=\"quote-link\" href=\"");
+            html.push_str(&escape_html(&href));
+            html.push_str("\">");
+            html.push_str(&escape_html(raw));
+            html.push_str("</a>");
+        }
+        None => {
+            html.push_str("<span class=\"quote-dead\">");
+            html.push_str(&escape_html(raw));
+            html.push_str("</span>");
+        }
+    }
+}
+
+/// Read an `http://` or `https://` URL starting at `at`.
+///
+/// Returns the offset just past it. Trailing punctuation is left out so
+/// "see https://example.com." links without the full stop.
+fn read_url(text: &str, at: usize) -> Option<usize> {
+    let rest = &text[at..];
+    let scheme = ["https://", "http://"]
+        .into_iter()
+        .find(|scheme| rest.starts_with(scheme))?;
+
+    let preceded_by_word = text[..at]
+        .chars()
+        .next_back()
+        .is_some_and(char::is_alphanumeric);
+    if preceded_by_word {
+        return None;
+    }
+
+    let len = rest
+        .find(|c: char| c.is_whitespace() || c.is_control() || "<>\"'[]`".contains(c))
+        .unwrap_or(rest.len());
+    let url = rest[..len].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '*']);
+
+    (url.len() > scheme.len()).then_some(at + url.len())
+}
+
+
+/// TESTS
+///
+/// The property tests check the renderer's one hard promise: whatever
+/// the input, the output only contains the tags emitted here, properly
+/// nested, with attribute values that cannot break out of their quotes.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quotes::post_href;
+    use proptest::prelude::*;
+
+    fn plain(body: &str) -> String {
+        render(body, |q| Some(post_href("g", q.number, q.board.is_none())))
+    }
+
+    #[test]
+    fn renders_inline_markup() {
+        assert_eq!(
+            plain("**bold** ''it'' [spoiler]s **b**[/spoiler] [code=rust]a<b[/code]"),
+            "<strong>bold</strong> <em>it</em> <span class=\"spoiler\">s <strong>b</strong></span> \
+             <code class=\"language-rust\">a&lt;b</code>"
+        );
+    }
+
+    #[test]
+    fn greentext_is_per_line_and_quotes_are_not_greentext() {
+        assert_eq!(
+            plain(">be me\n>>1 no\nnot >green"),
+            "<span class=\"greentext\">&gt;be me</span>\n\
+             <a class=\"quote-link\" href=\"#p1\">&gt;&gt;1</a> no\nnot &gt;green"
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_literal() {
+        assert_eq!(
+            plain("[code=rust]\nfn main() {\n    **x** >>1\n}\n[/code]\n>after"),
+            "<pre class=\"code\"><code class=\"language-rust\">fn main() {\n    **x** &gt;&gt;1\n}</code></pre>\n\
+             <span class=\"greentext\">&gt;after</span>"
+        );
+    }
+
+    #[test]
+    fn urls_are_linked_without_trailing_punctuation() {
+        assert_eq!(
+            plain("see https://example.com/a?b=1&c=2. javascript:alert(1)"),
+            "see <a class=\"autolink\" href=\"https://example.com/a?b=1&amp;c=2\" \
+             rel=\"nofollow noopener noreferrer\" target=\"_blank\">https://example.com/a?b=1&amp;c=2</a>. \
+             javascript:alert(1)"
+        );
+    }
+
+    #[test]
+    fn unclosed_markup_stays_literal() {
+        assert_eq!(
+            plain("**a [spoiler]b ''c [code]d <i>"),
+            "**a [spoiler]b &#39;&#39;c [code]d &lt;i&gt;"
+        );
+    }
+
+    #[test]
+    fn dead_quotes_are_escaped() {
+        assert_eq!(
+            render("<b>>>1</b> >>2", |q| (q.number == 1).then(|| "#p1".to_string())),
+            "&lt;b&gt;<a class=\"quote-link\" href=\"#p1\">&gt;&gt;1</a>&lt;/b&gt; \
+             <span class=\"quote-dead\">&gt;&gt;2</span>"
+        );
+    }
+
+    /// Open tags the renderer may emit, as (prefix, suffix) around an
+    /// escaped attribute value; `None` means the tag has no value.
+    const OPEN_TAGS: &[(&str, Option<&str>)] = &[
+        ("<strong>", None),
+        ("<em>", None),
+        ("<code>", None),
+        ("<pre class=\"code\">", None),
+        ("<span class=\"greentext\">", None),
+        ("<span class=\"spoiler\">", None),
+        ("<span class=\"quote-dead\">", None),
+        ("<code class=\"language-", Some("\">")),
+        ("<a class=\"quote-link\" href=\"", Some("\">")),
+        (
+            "<a class=\"autolink\" href=\"",
+            Some("\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">"),
+        ),
+    ];
+
+    fn tag_name(tag: &str) -> &str {
+        tag[1..].split([' ', '>']).next().unwrap()
+    }
+
+    /// Assert `html` uses only known tags, balanced, with safe values.
+    fn assert_safe(html: &str) -> Result<(), TestCaseError> {
+        let mut stack: Vec<&str> = Vec::new();
+        let mut rest = html;
+
+        while let Some(at) = rest.find(['<', '>', '"']) {
+            prop_assert_eq!(&rest[at..at + 1], "<", "bare markup character in {:?}", html);
+            rest = &rest[at..];
+
+            if let Some(after) = rest.strip_prefix("</") {
+                let end = after.find('>').unwrap_or(after.len());
+                let open = stack.pop();
+                prop_assert_eq!(open, Some(&after[..end]), "unbalanced close in {:?}", html);
+                rest = &after[(end + 1).min(after.len())..];
+                continue;
+            }
+
+            let matched = OPEN_TAGS.iter().find(|(prefix, _)| rest.starts_with(prefix));
+            prop_assert!(matched.is_some(), "unknown tag in {:?}", html);
+            let (prefix, suffix) = matched.unwrap();
+            stack.push(tag_name(prefix));
+            rest = &rest[prefix.len()..];
+
+            if let Some(suffix) = suffix {
+                let end = rest.find('"').unwrap_or(rest.len());
+                let value = &rest[..end];
+                prop_assert!(!value.contains(['<', '>', '\'']), "unsafe value in {:?}", html);
+                if prefix.contains("autolink") {
+                    prop_assert!(value.starts_with("http"), "non-http link in {:?}", html);
+                }
+                rest = &rest[end..];
+                prop_assert!(rest.starts_with(suffix), "broken attribute in {:?}", html);
+                rest = &rest[suffix.len()..];
+            }
+        }
+
+        prop_assert!(stack.is_empty(), "unclosed tags {:?} in {:?}", stack, html);
+        Ok(())
+    }
+
+    fn markup_soup() -> impl Strategy<Value = String> {
+        let pieces = prop::sample::select(vec![
+            "[code]", "[code=rust]", "[code=<x>]", "[/code]", "[spoiler]", "[/spoiler]",
+            "**", "''", "'", "\"", "<", ">", "&", ">>", ">>1", ">>>/g/2", "\n", "\r\n", " ",
+            "https://", "http://x.y/<script>", "javascript:", "a", "é", "[", "]",
+        ]);
+        prop::collection::vec(pieces, 0..48).prop_map(|pieces| pieces.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn markup_soup_renders_safely(body in markup_soup()) {
+            assert_safe(&plain(&body))?;
+        }
+
+        #[test]
+        fn arbitrary_text_renders_safely(body in any::<String>()) {
+            assert_safe(&plain(&body))?;
+        }
+
+        #[test]
+        fn hostile_resolver_output_is_escaped(body in markup_soup(), href in any::<String>()) {
+            assert_safe(&render(&body, |_| Some(href.clone())))?;
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Quote Links
+//!
+//! Developer Notes:
+//! - Parses `>>N` (same board) and `>>>/board/N` (cross board) quotes.
+//! - Link rendering itself lives in `markup`; nothing here touches storage.
+//!
+//! End Notes:
+//! Every byte of user text must pass through `escape_html`.
+
+/// A quote as written in a post body.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuoteRef {
+    /// Board name for `>>>/board/N`; `None` for same-board `>>N`.
+    pub board: Option<String>,
+    pub number: i64,
+}
+
+/// A slice of a post body: plain text or a quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Quote { raw: &'a str, quote: QuoteRef },
+}
+
+fn is_board_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-'
+}
+
+/// Try to read a quote starting at `start`, which points at `>>`.
+///
+/// Returns the quote and the byte offset just past it.
+pub(crate) fn read_quote(body: &str, start: usize) -> Option<(QuoteRef, usize)> {
+    let bytes = body.as_bytes();
+    let mut i = start + 2;
+
+    let board = if bytes.get(i) == Some(&b'>') && bytes.get(i + 1) == Some(&b'/') {
+        let name_start = i + 2;
+        let mut j = name_start;
+        while j < bytes.len() && is_board_char(bytes[j]) {
+            j += 1;
+        }
+        if j == name_start || bytes.get(j) != Some(&b'/') {
+            return None;
+        }
+        i = j + 1;
+        Some(body[name_start..j].to_string())
+    } else {
+        None
+    };
+
+    let digits_start = i;
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        i += 1;
+    }
+    if i == digits_start {
+        return None;
+    }
+
+    let number = body[digits_start..i].parse().ok()?;
+    Some((QuoteRef { board, number }, i))
+}
+
+/// Split a post body into text and quote segments.
+pub fn segments(body: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while let Some(offset) = body[i..].find(">>") {
+        let at = i + offset;
+        match read_quote(body, at) {
+            Some((quote, end)) => {
+                if at > text_start {
+                    out.push(Segment::Text(&body[text_start..at]));
+                }
+                out.push(Segment::Quote { raw: &body[at..end], quote });
+                text_start = end;
+                i = end;
+            }
+            None => i = at + 1,
+        }
+    }
+
+    if text_start < body.len() {
+        out.push(Segment::Text(&body[text_start..]));
+    }
+
+    out
+}
+
+/// Every distinct quote in a post body, in order of first appearance.
+pub fn parse_quotes(body: &str) -> Vec<QuoteRef> {
+    let mut quotes: Vec<QuoteRef> = Vec::new();
+
+    for segment in segments(body) {
+        if let Segment::Quote { quote, .. } = segment
+            && !quotes.contains(&quote)
+        {
+            quotes.push(quote);
+        }
+    }
+
+    quotes
+}
+
+/// Escape text for inclusion in HTML element content or attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Link to a post number: an in-page anchor when it lives in the thread
+/// being viewed, otherwise the board's `/res/` route.
+pub fn post_href(board: &str, number: i64, same_thread: bool) -> String {
+    if same_thread {
+        format!("#p{number}")
+    } else {
+        format!("/boards/{board}/res/{number}#p{number}")
+    }
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_same_and_cross_board_quotes() {
+        let quotes = parse_quotes(">>12 agreed, see >>>/g/34 and >>12 again >>x >>>/g/");
+
+        assert_eq!(
+            quotes,
+            vec![
+                QuoteRef { board: None, number: 12 },
+                QuoteRef { board: Some("g".into()), number: 34 },
+            ]
+        );
+    }
+}
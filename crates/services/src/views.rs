@@ -0,0 +1,134 @@
+//! Page Views
+//!
+//! Developer Notes:
+//! - Read models assembled by the service layer for the HTTP layer.
+//! - Bodies are already rendered to safe HTML here; templates must not
+//!   re-escape them, and must never render `Post::content` unescaped.
+//!
+//! End Notes:
+//! Plain data only.
+
+use std::net::IpAddr;
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{
+    AppealStatus, Attachment, Ban, BanAppeal, Board, BoardStaff, ModAction, ModActionKind, Post,
+    Report, Thread,
+};
+
+/// A link to another post, as shown in a "Replies:" list.
+#[derive(Debug, Clone)]
+pub struct ReplyLink {
+    pub href: String,
+    /// `>>N` for same-board replies, `>>>/board/N` otherwise.
+    pub label: String,
+}
+
+/// A post ready for rendering.
+#[derive(Debug, Clone)]
+pub struct PostView {
+    pub post: Post,
+    /// Escaped body with quote links.
+    pub body_html: String,
+    /// Posts that quote this one.
+    pub replies: Vec<ReplyLink>,
+    /// Files attached to this post, in upload order.
+    pub attachments: Vec<Attachment>,
+}
+
+/// A full thread page.
+#[derive(Debug, Clone)]
+pub struct ThreadView {
+    pub board: Board,
+    pub thread: Thread,
+    /// Posts that quote the OP.
+    pub replies: Vec<ReplyLink>,
+    /// Files attached to the opening post.
+    pub attachments: Vec<Attachment>,
+    pub posts: Vec<PostView>,
+}
+
+/// A ban as shown to the poster it applies to; never carries the
+/// staff note.
+#[derive(Debug, Clone)]
+pub struct BanNotice {
+    pub ban_id: Uuid,
+    /// Board name, or `None` for a ban on every board.
+    pub board: Option<String>,
+    pub reason: String,
+    pub created_at: OffsetDateTime,
+    /// `None` for a permanent ban.
+    pub expires_at: Option<OffsetDateTime>,
+    /// `None` until the poster appeals.
+    pub appeal: Option<AppealStatus>,
+}
+
+/// A pending appeal with the ban it is against, for staff.
+#[derive(Debug, Clone)]
+pub struct AppealView {
+    pub appeal: BanAppeal,
+    pub ban: Ban,
+}
+
+/// A thread or post found by its poster's address hash, for staff.
+#[derive(Debug, Clone)]
+pub struct PosterPostView {
+    pub board: String,
+    pub number: i64,
+    pub href: String,
+    /// Start of the thread title or post body, unescaped.
+    pub excerpt: String,
+    /// `None` once the retention period has passed.
+    pub ip: Option<IpAddr>,
+    pub created_at: OffsetDateTime,
+}
+
+/// A reply with its open reports, oldest first, for staff.
+#[derive(Debug, Clone)]
+pub struct ReportedPostView {
+    pub post_id: Uuid,
+    pub board: String,
+    pub number: i64,
+    pub href: String,
+    /// Start of the post body, unescaped.
+    pub excerpt: String,
+    /// Whether the viewer may ban the poster, whose address is still kept.
+    pub bannable: bool,
+    pub reports: Vec<Report>,
+}
+
+/// A handled report, for staff.
+#[derive(Debug, Clone)]
+pub struct HandledReportView {
+    pub report: Report,
+    pub board: String,
+    /// Username of the staff member who handled it, while the account exists.
+    pub resolved_by: Option<String>,
+}
+
+/// A board staff member with their username, for the staff page.
+#[derive(Debug, Clone)]
+pub struct StaffView {
+    pub staff: BoardStaff,
+    pub username: String,
+}
+
+/// A moderation log entry, for staff.
+#[derive(Debug, Clone)]
+pub struct ModActionView {
+    pub action: ModAction,
+    /// Board name, or `None` for a site-wide action.
+    pub board: Option<String>,
+}
+
+/// A moderation log entry as a board's public log shows it.
+#[derive(Debug, Clone)]
+pub struct PublicModActionView {
+    pub action: ModActionKind,
+    /// The post acted on; `None` when the target is not public, as for bans.
+    pub target: Option<String>,
+    pub reason: String,
+    pub created_at: OffsetDateTime,
+}
@@ -0,0 +1,203 @@
+//! Attachment Repository
+//!
+//! Developer Notes:
+//! - Handles persistence for `Attachment` metadata.
+//! - The files themselves live in the media store, not here.
+//! - Storage keys are content hashes, so several rows may share one
+//!   file; the reference queries below let the service layer decide
+//!   when a file is no longer used.
+//! - `AttachmentRepository` is the backend-neutral interface.
+//! - `SqliteAttachmentRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! No business logic here.
+
+use rusqlite::{params, Connection, Row};
+use uuid::Uuid;
+
+use models::Attachment;
+use crate::columns::{format_time, get_opt_uuid, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for attachments.
+pub trait AttachmentRepository: Send + Sync {
+    /// Insert the attachments of one post, all or nothing.
+    fn insert_attachments(&self, attachments: &[Attachment]) -> Result<(), StorageError>;
+
+    /// Every attachment in a thread, oldest post first.
+    fn get_attachments_by_thread(&self, thread_id: Uuid) -> Result<Vec<Attachment>, StorageError>;
+
+    /// The attachments of one reply.
+    fn get_attachments_by_post(&self, post_id: Uuid) -> Result<Vec<Attachment>, StorageError>;
+
+    /// Attachments on a board whose file has the given SHA-256.
+    fn find_by_hash(&self, board_id: Uuid, sha256: &str) -> Result<Vec<Attachment>, StorageError>;
+
+    /// How many attachments use the original stored under `storage_key`.
+    fn count_by_storage_key(&self, storage_key: &str) -> Result<u64, StorageError>;
+
+    /// Every distinct `(storage_key, thumbnail_key)` pair in use.
+    fn get_media_keys(&self) -> Result<Vec<(String, String)>, StorageError>;
+}
+
+/// Columns read by `attachment_from_row`, in order.
+pub(crate) const COLUMNS: &str = "a.id, a.thread_id, a.post_id, a.position, a.file_name, a.content_type,
+    a.size_bytes, a.sha256, a.width, a.height, a.storage_key, a.thumbnail_key,
+    a.thumbnail_width, a.thumbnail_height, a.metadata_stripped, a.duration_ms, a.created_at";
+
+/// SQLite implementation of `AttachmentRepository`.
+#[derive(Clone)]
+pub struct SqliteAttachmentRepository {
+    pool: DbPool,
+}
+
+impl SqliteAttachmentRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<Attachment>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, attachment_from_row)?;
+
+        let mut attachments = Vec::new();
+        for a in rows {
+            attachments.push(a?);
+        }
+
+        Ok(attachments)
+    }
+}
+
+pub(crate) fn attachment_from_row(row: &Row<'_>) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: get_uuid(row, 0)?,
+        thread_id: get_uuid(row, 1)?,
+        post_id: get_opt_uuid(row, 2)?,
+        position: row.get(3)?,
+        file_name: row.get(4)?,
+        content_type: row.get(5)?,
+        size_bytes: row.get(6)?,
+        sha256: row.get(7)?,
+        width: row.get(8)?,
+        height: row.get(9)?,
+        storage_key: row.get(10)?,
+        thumbnail_key: row.get(11)?,
+        thumbnail_width: row.get(12)?,
+        thumbnail_height: row.get(13)?,
+        metadata_stripped: row.get(14)?,
+        duration_ms: row.get(15)?,
+        created_at: get_time(row, 16)?,
+    })
+}
+
+/// Insert attachments inside the caller's transaction.
+pub(crate) fn insert_in(conn: &Connection, attachments: &[Attachment]) -> Result<(), StorageError> {
+    for a in attachments {
+        conn.execute(
+            r#"
+            INSERT INTO attachments (
+                id, thread_id, post_id, position, file_name, content_type,
+                size_bytes, sha256, width, height, storage_key, thumbnail_key,
+                thumbnail_width, thumbnail_height, metadata_stripped, duration_ms, created_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+            "#,
+            params![
+                a.id.to_string(),
+                a.thread_id.to_string(),
+                a.post_id.map(|id| id.to_string()),
+                a.position,
+                a.file_name,
+                a.content_type,
+                a.size_bytes,
+                a.sha256,
+                a.width,
+                a.height,
+                a.storage_key,
+                a.thumbnail_key,
+                a.thumbnail_width,
+                a.thumbnail_height,
+                a.metadata_stripped,
+                a.duration_ms,
+                format_time(&a.created_at)
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+impl AttachmentRepository for SqliteAttachmentRepository {
+    fn insert_attachments(&self, attachments: &[Attachment]) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        insert_in(&tx, attachments)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_attachments_by_thread(&self, thread_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 WHERE a.thread_id = ?1
+                 ORDER BY a.created_at ASC, a.position ASC"
+            ),
+            params![thread_id.to_string()],
+        )
+    }
+
+    fn get_attachments_by_post(&self, post_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 WHERE a.post_id = ?1
+                 ORDER BY a.position ASC"
+            ),
+            params![post_id.to_string()],
+        )
+    }
+
+    fn find_by_hash(&self, board_id: Uuid, sha256: &str) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 JOIN threads t ON t.id = a.thread_id
+                 WHERE t.board_id = ?1 AND a.sha256 = ?2
+                 ORDER BY a.created_at ASC, a.position ASC"
+            ),
+            params![board_id.to_string(), sha256],
+        )
+    }
+
+    fn count_by_storage_key(&self, storage_key: &str) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM attachments WHERE storage_key = ?1",
+            params![storage_key],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    fn get_media_keys(&self) -> Result<Vec<(String, String)>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT DISTINCT storage_key, thumbnail_key FROM attachments")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut keys = Vec::new();
+        for k in rows {
+            keys.push(k?);
+        }
+
+        Ok(keys)
+    }
+}
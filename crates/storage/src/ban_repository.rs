@@ -0,0 +1,281 @@
+//! Ban Repository
+//!
+//! Developer Notes:
+//! - Persists IP bans (`bans`) and appeals against them (`ban_appeals`).
+//! - A ban's range is stored as text for display, plus its first and
+//!   last address as 16-byte keys (IPv4 as IPv4-mapped IPv6), so that
+//!   finding the bans covering an address is one indexed range query;
+//!   BLOB and BYTEA both compare bytewise.
+//! - `BanRepository` is the backend-neutral interface.
+//! - `SqliteBanRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! Whether a ban applies to a board, and who may lift it, is decided by
+//! services.
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{AppealStatus, Ban, BanAppeal};
+use crate::columns::{format_time, get_opt_time, get_opt_uuid, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for bans and appeals.
+pub trait BanRepository: Send + Sync {
+    /// Store a new ban.
+    fn insert_ban(&self, ban: &Ban) -> Result<(), StorageError>;
+
+    /// Look up a ban by id.
+    fn get_ban(&self, id: Uuid) -> Result<Option<Ban>, StorageError>;
+
+    /// Bans in force at `now` whose range covers `ip`, on any board.
+    fn find_covering(&self, ip: IpAddr, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError>;
+
+    /// Every ban in force at `now`, newest first.
+    fn get_active(&self, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError>;
+
+    /// Change when a ban ends; returns whether it exists.
+    fn set_expiry(
+        &self,
+        id: Uuid,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<bool, StorageError>;
+
+    /// Store a new appeal. Fails if the ban already has one.
+    fn insert_appeal(&self, appeal: &BanAppeal) -> Result<(), StorageError>;
+
+    /// Look up an appeal by id.
+    fn get_appeal(&self, id: Uuid) -> Result<Option<BanAppeal>, StorageError>;
+
+    /// The appeal against a ban, if one was made.
+    fn get_appeal_by_ban(&self, ban_id: Uuid) -> Result<Option<BanAppeal>, StorageError>;
+
+    /// Appeals awaiting a decision, oldest first.
+    fn get_pending_appeals(&self) -> Result<Vec<BanAppeal>, StorageError>;
+
+    /// Record the decision on a pending appeal; returns false if the
+    /// appeal does not exist or was already decided.
+    fn decide_appeal(
+        &self,
+        id: Uuid,
+        status: AppealStatus,
+        decided_by: Uuid,
+        decided_at: OffsetDateTime,
+    ) -> Result<bool, StorageError>;
+}
+
+/// 16-byte sort key of an address; IPv4 is mapped into IPv6.
+pub(crate) fn ip_key(ip: IpAddr) -> Vec<u8> {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped().octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+/// Keys of the first and last address in a range.
+pub(crate) fn range_keys(range: &IpNet) -> (Vec<u8>, Vec<u8>) {
+    (ip_key(range.network()), ip_key(range.broadcast()))
+}
+
+/// SQLite implementation of `BanRepository`.
+#[derive(Clone)]
+pub struct SqliteBanRepository {
+    pool: DbPool,
+}
+
+impl SqliteBanRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+const BAN_COLUMNS: &str =
+    "id, ip_range, board_id, reason, staff_note, created_by, created_at, expires_at";
+
+const APPEAL_COLUMNS: &str = "id, ban_id, message, status, created_at, decided_by, decided_at";
+
+fn ban_from_row(row: &Row<'_>) -> rusqlite::Result<Ban> {
+    let range: String = row.get(1)?;
+    let ip_range = range.parse().map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+
+    Ok(Ban {
+        id: get_uuid(row, 0)?,
+        ip_range,
+        board_id: get_opt_uuid(row, 2)?,
+        reason: row.get(3)?,
+        staff_note: row.get(4)?,
+        created_by: get_opt_uuid(row, 5)?,
+        created_at: get_time(row, 6)?,
+        expires_at: get_opt_time(row, 7)?,
+    })
+}
+
+fn appeal_from_row(row: &Row<'_>) -> rusqlite::Result<BanAppeal> {
+    let status_str: String = row.get(3)?;
+    let status = status_str.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("unknown appeal status `{status_str}`").into(),
+        )
+    })?;
+
+    Ok(BanAppeal {
+        id: get_uuid(row, 0)?,
+        ban_id: get_uuid(row, 1)?,
+        message: row.get(2)?,
+        status,
+        created_at: get_time(row, 4)?,
+        decided_by: get_opt_uuid(row, 5)?,
+        decided_at: get_opt_time(row, 6)?,
+    })
+}
+
+impl BanRepository for SqliteBanRepository {
+    fn insert_ban(&self, ban: &Ban) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        let (range_start, range_end) = range_keys(&ban.ip_range);
+        conn.execute(
+            "INSERT INTO bans (id, ip_range, range_start, range_end, board_id, reason, staff_note,
+                               created_by, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                ban.id.to_string(),
+                ban.ip_range.to_string(),
+                range_start,
+                range_end,
+                ban.board_id.map(|id| id.to_string()),
+                ban.reason,
+                ban.staff_note,
+                ban.created_by.map(|id| id.to_string()),
+                format_time(&ban.created_at),
+                ban.expires_at.as_ref().map(format_time)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<Ban>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {BAN_COLUMNS} FROM bans WHERE id = ?1"),
+                params![id.to_string()],
+                ban_from_row,
+            )
+            .optional()?)
+    }
+
+    fn find_covering(&self, ip: IpAddr, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BAN_COLUMNS} FROM bans
+             WHERE range_start <= ?1 AND range_end >= ?1
+               AND (expires_at IS NULL OR expires_at > ?2)
+             ORDER BY created_at DESC"
+        ))?;
+        let rows = stmt.query_map(params![ip_key(ip), format_time(&now)], ban_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_active(&self, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {BAN_COLUMNS} FROM bans
+             WHERE expires_at IS NULL OR expires_at > ?1
+             ORDER BY created_at DESC"
+        ))?;
+        let rows = stmt.query_map(params![format_time(&now)], ban_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn set_expiry(
+        &self,
+        id: Uuid,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE bans SET expires_at = ?2 WHERE id = ?1",
+            params![id.to_string(), expires_at.as_ref().map(format_time)],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn insert_appeal(&self, appeal: &BanAppeal) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO ban_appeals (id, ban_id, message, status, created_at, decided_by, decided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                appeal.id.to_string(),
+                appeal.ban_id.to_string(),
+                appeal.message,
+                appeal.status.to_string(),
+                format_time(&appeal.created_at),
+                appeal.decided_by.map(|id| id.to_string()),
+                appeal.decided_at.as_ref().map(format_time)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_appeal(&self, id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE id = ?1"),
+                params![id.to_string()],
+                appeal_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_appeal_by_ban(&self, ban_id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE ban_id = ?1"),
+                params![ban_id.to_string()],
+                appeal_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_pending_appeals(&self) -> Result<Vec<BanAppeal>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE status = ?1 ORDER BY created_at"
+        ))?;
+        let rows = stmt.query_map(params![AppealStatus::Pending.to_string()], appeal_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn decide_appeal(
+        &self,
+        id: Uuid,
+        status: AppealStatus,
+        decided_by: Uuid,
+        decided_at: OffsetDateTime,
+    ) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE ban_appeals SET status = ?2, decided_by = ?3, decided_at = ?4
+             WHERE id = ?1 AND status = ?5",
+            params![
+                id.to_string(),
+                status.to_string(),
+                decided_by.to_string(),
+                format_time(&decided_at),
+                AppealStatus::Pending.to_string()
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+}
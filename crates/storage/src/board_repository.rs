@@ -2,6 +2,8 @@
 //!
 //! Developer Notes:
 //! - Handles persistence for `Board` data.
+//! - `BoardRepository` is the backend-neutral interface.
+//! - `SqliteBoardRepository` is the SQLite implementation.
 //! - All operations return `StorageError`, not raw rusqlite errors.
 //!
 //! TODO:
@@ -10,55 +12,277 @@
 //! End Notes:
 //! Keeps service layer free of DB error details.
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use uuid::Uuid;
-use time::OffsetDateTime;
 
-use models::Board;
-use crate::StorageError;
+use models::{AvPolicy, Board, CaptchaMode, Cooldowns, DuplicatePolicy, PrunePolicy};
+use crate::columns::{format_time, get_time, get_uuid};
+use crate::{DbPool, StorageError};
 
-pub fn insert_board(
+/// Persistence operations for boards.
+pub trait BoardRepository: Send + Sync {
+    /// Insert a new board.
+    fn insert_board(&self, board: &Board) -> Result<(), StorageError>;
+
+    /// List every board.
+    fn get_all(&self) -> Result<Vec<Board>, StorageError>;
+
+    /// Find a board by id.
+    fn get_board(&self, id: Uuid) -> Result<Option<Board>, StorageError>;
+
+    /// Find a board by its unique name.
+    fn get_board_by_name(&self, name: &str) -> Result<Option<Board>, StorageError>;
+
+    /// Change how a board treats duplicate uploads.
+    fn set_duplicate_policy(
+        &self,
+        board_id: Uuid,
+        policy: DuplicatePolicy,
+    ) -> Result<(), StorageError>;
+
+    /// Change a board's audio and video limits.
+    fn set_av_policy(&self, board_id: Uuid, policy: &AvPolicy) -> Result<(), StorageError>;
+
+    /// Change a board's posting cooldowns.
+    fn set_cooldowns(&self, board_id: Uuid, cooldowns: &Cooldowns) -> Result<(), StorageError>;
+
+    /// Change which posts on a board need a CAPTCHA.
+    fn set_captcha_mode(&self, board_id: Uuid, mode: CaptchaMode) -> Result<(), StorageError>;
+
+    /// Open or close a board's public moderation log.
+    fn set_public_mod_log(&self, board_id: Uuid, public: bool) -> Result<(), StorageError>;
+
+    /// Change how many replies bump a thread.
+    fn set_bump_limit(&self, board_id: Uuid, limit: u32) -> Result<(), StorageError>;
+
+    /// Change how many live threads a board keeps and what becomes of the rest.
+    fn set_prune_policy(&self, board_id: Uuid, policy: &PrunePolicy) -> Result<(), StorageError>;
+}
+
+/// Columns read by `board_from_row`, in order.
+const COLUMNS: &str = "id, name, description, duplicate_policy, av_max_duration, av_codecs, \
+                       created_at, thread_cooldown, reply_cooldown, repost_cooldown, captcha_mode, \
+                       public_mod_log, bump_limit, max_threads, prune_mode";
+
+/// Allocate the next post number of a board.
+///
+/// Must run inside the transaction that inserts the numbered row, so the
+/// counter and the row commit (or roll back) together.
+pub(crate) fn next_post_number(
     conn: &Connection,
-    board: &Board,
-) -> Result<(), StorageError> {
-    conn.execute(
-        "INSERT INTO boards (id, name, description, created_at) VALUES (?1, ?2, ?3, ?4)",
-        params![
-            board.id.to_string(),
-            board.name,
-            board.description,
-            board.created_at.format(&time::format_description::well_known::Rfc3339).unwrap()
-        ],
-    )?;
-    Ok(())
+    board_id: Uuid,
+) -> Result<i64, StorageError> {
+    conn.query_row(
+        "UPDATE boards SET last_post_number = last_post_number + 1
+         WHERE id = ?1
+         RETURNING last_post_number",
+        params![board_id.to_string()],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| StorageError::Constraint("FOREIGN KEY constraint failed: board".into()))
+}
+
+/// SQLite implementation of `BoardRepository`.
+#[derive(Clone)]
+pub struct SqliteBoardRepository {
+    pool: DbPool,
+}
+
+impl SqliteBoardRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
 }
 
-pub fn get_all(conn: &Connection) -> Result<Vec<Board>, StorageError> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, description, created_at FROM boards",
-    )?;
-
-    let rows = stmt.query_map([], |row| {
-        // Parse timestamp from stored string
-        let created_str: String = row.get(3)?;
-        let created_at = time::OffsetDateTime::parse(
-            &created_str,
-            &time::format_description::well_known::Rfc3339,
+fn board_from_row(row: &Row<'_>) -> rusqlite::Result<Board> {
+    let policy: String = row.get(3)?;
+    let duplicate_policy = policy.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("unknown duplicate policy `{policy}`").into(),
         )
-        .unwrap();
-
-        Ok(Board {
-            id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-            name: row.get(1)?,
-            description: row.get(2)?,
-            created_at,
-        })
     })?;
+    let mode: String = row.get(10)?;
+    let captcha_mode = mode.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            10,
+            rusqlite::types::Type::Text,
+            format!("unknown captcha mode `{mode}`").into(),
+        )
+    })?;
+    let prune_mode: String = row.get(14)?;
+    let prune_mode = prune_mode.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            14,
+            rusqlite::types::Type::Text,
+            format!("unknown prune mode `{prune_mode}`").into(),
+        )
+    })?;
+
+    Ok(Board {
+        id: get_uuid(row, 0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        duplicate_policy,
+        av_policy: AvPolicy {
+            max_duration_secs: row.get(4)?,
+            codecs: AvPolicy::parse_codec_list(&row.get::<_, String>(5)?),
+        },
+        cooldowns: Cooldowns {
+            thread_secs: row.get(7)?,
+            reply_secs: row.get(8)?,
+            repost_secs: row.get(9)?,
+        },
+        captcha_mode,
+        public_mod_log: row.get(11)?,
+        bump_limit: row.get(12)?,
+        prune_policy: PrunePolicy {
+            max_threads: row.get(13)?,
+            mode: prune_mode,
+        },
+        created_at: get_time(row, 6)?,
+    })
+}
 
-    let mut result = Vec::new();
-    for r in rows {
-        result.push(r?);
+impl BoardRepository for SqliteBoardRepository {
+    fn insert_board(&self, board: &Board) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO boards (
+                 id, name, description, duplicate_policy, av_max_duration, av_codecs, created_at,
+                 thread_cooldown, reply_cooldown, repost_cooldown, captcha_mode, public_mod_log,
+                 bump_limit, max_threads, prune_mode
+             )
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![
+                board.id.to_string(),
+                board.name,
+                board.description,
+                board.duplicate_policy.to_string(),
+                board.av_policy.max_duration_secs,
+                board.av_policy.codec_list(),
+                format_time(&board.created_at),
+                board.cooldowns.thread_secs,
+                board.cooldowns.reply_secs,
+                board.cooldowns.repost_secs,
+                board.captcha_mode.to_string(),
+                board.public_mod_log,
+                board.bump_limit,
+                board.prune_policy.max_threads,
+                board.prune_policy.mode.to_string()
+            ],
+        )?;
+        Ok(())
     }
 
-    Ok(result)
-}
\ No newline at end of file
+    fn get_all(&self) -> Result<Vec<Board>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!("SELECT {COLUMNS} FROM boards ORDER BY name"))?;
+
+        let rows = stmt.query_map([], board_from_row)?;
+
+        let mut result = Vec::new();
+        for r in rows {
+            result.push(r?);
+        }
+
+        Ok(result)
+    }
+
+    fn get_board(&self, id: Uuid) -> Result<Option<Board>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM boards WHERE id = ?1"),
+                params![id.to_string()],
+                board_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_board_by_name(&self, name: &str) -> Result<Option<Board>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM boards WHERE name = ?1"),
+                params![name],
+                board_from_row,
+            )
+            .optional()?)
+    }
+
+    fn set_duplicate_policy(
+        &self,
+        board_id: Uuid,
+        policy: DuplicatePolicy,
+    ) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET duplicate_policy = ?2 WHERE id = ?1",
+            params![board_id.to_string(), policy.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn set_av_policy(&self, board_id: Uuid, policy: &AvPolicy) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET av_max_duration = ?2, av_codecs = ?3 WHERE id = ?1",
+            params![board_id.to_string(), policy.max_duration_secs, policy.codec_list()],
+        )?;
+        Ok(())
+    }
+
+    fn set_cooldowns(&self, board_id: Uuid, cooldowns: &Cooldowns) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET thread_cooldown = ?2, reply_cooldown = ?3, repost_cooldown = ?4
+             WHERE id = ?1",
+            params![
+                board_id.to_string(),
+                cooldowns.thread_secs,
+                cooldowns.reply_secs,
+                cooldowns.repost_secs
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn set_captcha_mode(&self, board_id: Uuid, mode: CaptchaMode) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET captcha_mode = ?2 WHERE id = ?1",
+            params![board_id.to_string(), mode.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn set_public_mod_log(&self, board_id: Uuid, public: bool) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET public_mod_log = ?2 WHERE id = ?1",
+            params![board_id.to_string(), public],
+        )?;
+        Ok(())
+    }
+
+    fn set_bump_limit(&self, board_id: Uuid, limit: u32) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET bump_limit = ?2 WHERE id = ?1",
+            params![board_id.to_string(), limit],
+        )?;
+        Ok(())
+    }
+
+    fn set_prune_policy(&self, board_id: Uuid, policy: &PrunePolicy) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET max_threads = ?2, prune_mode = ?3 WHERE id = ?1",
+            params![board_id.to_string(), policy.max_threads, policy.mode.to_string()],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! CAPTCHA Repository
+//!
+//! Developer Notes:
+//! - Handles persistence for issued CAPTCHA challenges.
+//! - `CaptchaRepository` is the backend-neutral interface.
+//! - `SqliteCaptchaRepository` is the SQLite implementation.
+//! - `take` deletes the row it returns, so a challenge can be checked
+//!   exactly once even when two requests race for it.
+//!
+//! End Notes:
+//! Expiry is decided by the service layer; rows only carry the deadline.
+
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::Captcha;
+use crate::columns::{format_time, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for CAPTCHA challenges.
+pub trait CaptchaRepository: Send + Sync {
+    /// Store a new challenge.
+    fn insert(&self, captcha: &Captcha) -> Result<(), StorageError>;
+
+    /// Look up a challenge without using it up.
+    fn get(&self, id: Uuid) -> Result<Option<Captcha>, StorageError>;
+
+    /// Remove a challenge and return it, if it existed.
+    fn take(&self, id: Uuid) -> Result<Option<Captcha>, StorageError>;
+
+    /// Delete challenges that expired before `now`; returns how many
+    /// there were.
+    fn delete_expired(&self, now: OffsetDateTime) -> Result<u64, StorageError>;
+}
+
+/// SQLite implementation of `CaptchaRepository`.
+#[derive(Clone)]
+pub struct SqliteCaptchaRepository {
+    pool: DbPool,
+}
+
+impl SqliteCaptchaRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn captcha_from_row(row: &Row<'_>) -> rusqlite::Result<Captcha> {
+    Ok(Captcha {
+        id: get_uuid(row, 0)?,
+        answer: row.get(1)?,
+        expires_at: get_time(row, 2)?,
+    })
+}
+
+impl CaptchaRepository for SqliteCaptchaRepository {
+    fn insert(&self, captcha: &Captcha) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO captchas (id, answer, expires_at) VALUES (?1, ?2, ?3)",
+            params![
+                captcha.id.to_string(),
+                captcha.answer,
+                format_time(&captcha.expires_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT id, answer, expires_at FROM captchas WHERE id = ?1",
+                params![id.to_string()],
+                captcha_from_row,
+            )
+            .optional()?)
+    }
+
+    fn take(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "DELETE FROM captchas WHERE id = ?1 RETURNING id, answer, expires_at",
+                params![id.to_string()],
+                captcha_from_row,
+            )
+            .optional()?)
+    }
+
+    fn delete_expired(&self, now: OffsetDateTime) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM captchas WHERE expires_at < ?1",
+            params![format_time(&now)],
+        )?;
+        Ok(deleted as u64)
+    }
+}
@@ -0,0 +1,72 @@
+//! Column Conversions
+//!
+//! Developer Notes:
+//! - Shared helpers for mapping domain types to SQLite columns.
+//! - UUIDs are stored as TEXT, timestamps as RFC 3339 TEXT.
+//! - Malformed rows surface as conversion errors instead of panics.
+//!
+//! End Notes:
+//! SQLite backend only.
+
+use models::PosterInfo;
+use rusqlite::types::Type;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub(crate) fn format_time(value: &OffsetDateTime) -> String {
+    value.format(&Rfc3339).expect("RFC 3339 formatting cannot fail for UTC times")
+}
+
+pub(crate) fn parse_time(idx: usize, value: &str) -> rusqlite::Result<OffsetDateTime> {
+    OffsetDateTime::parse(value, &Rfc3339).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e))
+    })
+}
+
+pub(crate) fn parse_uuid(idx: usize, value: &str) -> rusqlite::Result<Uuid> {
+    Uuid::parse_str(value).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e))
+    })
+}
+
+pub(crate) fn get_time(row: &rusqlite::Row<'_>, idx: usize) -> rusqlite::Result<OffsetDateTime> {
+    parse_time(idx, &row.get::<_, String>(idx)?)
+}
+
+pub(crate) fn get_uuid(row: &rusqlite::Row<'_>, idx: usize) -> rusqlite::Result<Uuid> {
+    parse_uuid(idx, &row.get::<_, String>(idx)?)
+}
+
+pub(crate) fn get_opt_time(
+    row: &rusqlite::Row<'_>,
+    idx: usize,
+) -> rusqlite::Result<Option<OffsetDateTime>> {
+    row.get::<_, Option<String>>(idx)?
+        .map(|value| parse_time(idx, &value))
+        .transpose()
+}
+
+pub(crate) fn get_opt_uuid(row: &rusqlite::Row<'_>, idx: usize) -> rusqlite::Result<Option<Uuid>> {
+    row.get::<_, Option<String>>(idx)?
+        .map(|value| parse_uuid(idx, &value))
+        .transpose()
+}
+
+/// The `ip_hash`, `ip_address` and `user_agent_hash` columns starting
+/// at `idx`; `None` when no hash was recorded.
+pub(crate) fn get_poster(row: &rusqlite::Row<'_>, idx: usize) -> rusqlite::Result<Option<PosterInfo>> {
+    let Some(ip_hash) = row.get::<_, Option<String>>(idx)? else {
+        return Ok(None);
+    };
+    let ip = row
+        .get::<_, Option<String>>(idx + 1)?
+        .map(|value| {
+            value.parse().map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(idx + 1, Type::Text, Box::new(e))
+            })
+        })
+        .transpose()?;
+
+    Ok(Some(PosterInfo { ip_hash, ip, user_agent_hash: row.get(idx + 2)? }))
+}
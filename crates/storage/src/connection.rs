@@ -3,14 +3,108 @@
 //! Developer Notes:
 //! - Creates and configures SQLite connections.
 //! - Enables foreign keys.
+//! - Enables WAL mode and a busy timeout so readers and the writer
+//!   do not block each other.
+//! - Provides an r2d2 connection pool shared by the HTTP handlers.
 //!
 //! End Notes:
 //! Centralized DB setup only.
 
+use std::time::Duration;
+
 use rusqlite::{Connection, Result};
 
+use crate::StorageError;
+
+/// How long a connection waits on a locked database before failing.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pool of configured SQLite connections.
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Connection checked out from a `DbPool`.
+pub type PooledConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
 pub fn create_connection(path: &str) -> Result<Connection> {
     let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
     conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    conn.query_row("PRAGMA journal_mode = WAL;", [], |_| Ok(()))?;
+    conn.execute_batch("PRAGMA synchronous = NORMAL;")?;
     Ok(conn)
-}
\ No newline at end of file
+}
+
+/// r2d2 manager opening connections through `create_connection`.
+#[derive(Debug, Clone)]
+pub struct SqliteConnectionManager {
+    path: String,
+}
+
+impl SqliteConnectionManager {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl r2d2::ManageConnection for SqliteConnectionManager {
+    type Connection = Connection;
+    type Error = rusqlite::Error;
+
+    fn connect(&self) -> Result<Connection> {
+        create_connection(&self.path)
+    }
+
+    fn is_valid(&self, conn: &mut Connection) -> Result<()> {
+        conn.execute_batch("SELECT 1;")
+    }
+
+    fn has_broken(&self, _conn: &mut Connection) -> bool {
+        false
+    }
+}
+
+/// Build a connection pool for the database at `path`.
+///
+/// An in-memory database only exists inside one connection, so
+/// `":memory:"` always gets a single connection that is never recycled.
+pub fn create_pool(path: &str, max_size: u32) -> Result<DbPool, StorageError> {
+    let builder = r2d2::Pool::builder().connection_timeout(BUSY_TIMEOUT);
+
+    let builder = if path == ":memory:" {
+        builder
+            .max_size(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        builder.max_size(max_size.max(1))
+    };
+
+    Ok(builder.build(SqliteConnectionManager::new(path))?)
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_pool_shares_one_database() {
+        let pool = create_pool(":memory:", 8).unwrap();
+
+        pool.get()
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x INTEGER);")
+            .unwrap();
+
+        let count: i64 = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,183 @@
+//! Image Ban Repository
+//!
+//! Developer Notes:
+//! - Persists banned perceptual image hashes and the uploads that were
+//!   refused because of them.
+//! - Hashes are `u64` in the models and stored as signed 64-bit
+//!   integers; the bit pattern is preserved.
+//! - `ImageBanRepository` is the backend-neutral interface.
+//! - `SqliteImageBanRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! Matching by Hamming distance happens in `services`, not here.
+
+use rusqlite::{params, OptionalExtension, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{BannedImageHash, BannedImageMatch};
+use crate::columns::{format_time, get_opt_time, get_opt_uuid, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for image bans.
+pub trait ImageBanRepository: Send + Sync {
+    /// Add a banned hash.
+    fn insert_ban(&self, ban: &BannedImageHash) -> Result<(), StorageError>;
+
+    /// Look up a ban, lifted or not.
+    fn get_ban(&self, id: Uuid) -> Result<Option<BannedImageHash>, StorageError>;
+
+    /// Every ban not lifted, oldest first.
+    fn get_bans(&self) -> Result<Vec<BannedImageHash>, StorageError>;
+
+    /// Lift a ban, keeping its recorded matches.
+    ///
+    /// Returns false if the ban does not exist or was already lifted.
+    fn lift_ban(&self, id: Uuid, lifted_at: OffsetDateTime) -> Result<bool, StorageError>;
+
+    /// Record a refused upload.
+    fn insert_match(&self, hit: &BannedImageMatch) -> Result<(), StorageError>;
+
+    /// The most recent refused uploads, newest first.
+    fn get_matches(&self, limit: u32) -> Result<Vec<BannedImageMatch>, StorageError>;
+}
+
+/// SQLite implementation of `ImageBanRepository`.
+#[derive(Clone)]
+pub struct SqliteImageBanRepository {
+    pool: DbPool,
+}
+
+impl SqliteImageBanRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn ban_from_row(row: &Row<'_>) -> rusqlite::Result<BannedImageHash> {
+    Ok(BannedImageHash {
+        id: get_uuid(row, 0)?,
+        phash: row.get::<_, i64>(1)? as u64,
+        dhash: row.get::<_, i64>(2)? as u64,
+        reason: row.get(3)?,
+        created_at: get_time(row, 4)?,
+        lifted_at: get_opt_time(row, 5)?,
+    })
+}
+
+fn match_from_row(row: &Row<'_>) -> rusqlite::Result<BannedImageMatch> {
+    Ok(BannedImageMatch {
+        id: get_uuid(row, 0)?,
+        ban_id: get_uuid(row, 1)?,
+        board_id: get_uuid(row, 2)?,
+        thread_id: get_opt_uuid(row, 3)?,
+        file_name: row.get(4)?,
+        sha256: row.get(5)?,
+        distance: row.get(6)?,
+        created_at: get_time(row, 7)?,
+    })
+}
+
+impl ImageBanRepository for SqliteImageBanRepository {
+    fn insert_ban(&self, ban: &BannedImageHash) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO banned_image_hashes (id, phash, dhash, reason, created_at, lifted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                ban.id.to_string(),
+                ban.phash as i64,
+                ban.dhash as i64,
+                ban.reason,
+                format_time(&ban.created_at),
+                ban.lifted_at.as_ref().map(format_time)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<BannedImageHash>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT id, phash, dhash, reason, created_at, lifted_at
+                 FROM banned_image_hashes
+                 WHERE id = ?1",
+                params![id.to_string()],
+                ban_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_bans(&self) -> Result<Vec<BannedImageHash>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, phash, dhash, reason, created_at, lifted_at
+             FROM banned_image_hashes
+             WHERE lifted_at IS NULL
+             ORDER BY created_at ASC",
+        )?;
+
+        let rows = stmt.query_map([], ban_from_row)?;
+
+        let mut bans = Vec::new();
+        for b in rows {
+            bans.push(b?);
+        }
+
+        Ok(bans)
+    }
+
+    fn lift_ban(&self, id: Uuid, lifted_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let lifted = conn.execute(
+            "UPDATE banned_image_hashes SET lifted_at = ?2 WHERE id = ?1 AND lifted_at IS NULL",
+            params![id.to_string(), format_time(&lifted_at)],
+        )?;
+        Ok(lifted > 0)
+    }
+
+    fn insert_match(&self, hit: &BannedImageMatch) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            r#"
+            INSERT INTO banned_image_matches (
+                id, ban_id, board_id, thread_id, file_name, sha256, distance, created_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                hit.id.to_string(),
+                hit.ban_id.to_string(),
+                hit.board_id.to_string(),
+                hit.thread_id.map(|id| id.to_string()),
+                hit.file_name,
+                hit.sha256,
+                hit.distance,
+                format_time(&hit.created_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_matches(&self, limit: u32) -> Result<Vec<BannedImageMatch>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, ban_id, board_id, thread_id, file_name, sha256, distance, created_at
+            FROM banned_image_matches
+            ORDER BY created_at DESC
+            LIMIT ?1
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![limit], match_from_row)?;
+
+        let mut matches = Vec::new();
+        for m in rows {
+            matches.push(m?);
+        }
+
+        Ok(matches)
+    }
+}
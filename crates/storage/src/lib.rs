@@ -4,17 +4,37 @@
 //! - This crate is responsible for all database interaction.
 //! - It must NOT contain business logic.
 //! - It must ONLY perform persistence operations.
+//! - Each `*_repository` module defines a backend-neutral trait plus
+//!   its SQLite implementation; `memory` implements them all for tests.
+//! - The `postgres` feature adds a PostgreSQL implementation of every
+//!   repository in the `postgres` module.
 //!
 //! End Notes:
 //! Keep this layer thin and predictable.
 
 pub mod connection;
 pub mod schema;
+pub mod migrations;
 pub mod board_repository;
 pub mod thread_repository;
 pub mod post_repository;
 pub mod user_repository;
 pub mod session_repository;
+pub mod quote_repository;
+pub mod attachment_repository;
+pub mod image_ban_repository;
+pub mod staff_repository;
+pub mod ban_repository;
+pub mod captcha_repository;
+pub mod report_repository;
+pub mod mod_action_repository;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+mod columns;
+
+use std::sync::Arc;
 
 use thiserror::Error;
 
@@ -22,24 +42,627 @@ use thiserror::Error;
 pub enum StorageError {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("PostgreSQL error: {0}")]
+    Postgres(#[from] ::postgres::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("Constraint violation: {0}")]
+    Constraint(String),
+
+    #[error("Corrupt row: {0}")]
+    Corrupt(String),
+
+    #[error("Database schema version {database} is newer than supported version {supported}")]
+    SchemaTooNew { database: u32, supported: u32 },
 }
 
 pub use rusqlite::Connection as DbConnection;
+pub use connection::{create_connection, create_pool, DbPool, PooledConnection};
+pub use migrations::init_database;
+pub use board_repository::{BoardRepository, SqliteBoardRepository};
+pub use thread_repository::{ThreadRepository, SqliteThreadRepository};
+pub use post_repository::{PostRepository, SqlitePostRepository};
+pub use user_repository::{UserRepository, SqliteUserRepository};
+pub use session_repository::{SessionRepository, SqliteSessionRepository};
+pub use quote_repository::{QuoteRepository, SqliteQuoteRepository};
+pub use attachment_repository::{AttachmentRepository, SqliteAttachmentRepository};
+pub use image_ban_repository::{ImageBanRepository, SqliteImageBanRepository};
+pub use staff_repository::{StaffRepository, SqliteStaffRepository};
+pub use ban_repository::{BanRepository, SqliteBanRepository};
+pub use captcha_repository::{CaptchaRepository, SqliteCaptchaRepository};
+pub use report_repository::{ReportRepository, SqliteReportRepository};
+pub use mod_action_repository::{ModActionFilter, ModActionRepository, SqliteModActionRepository};
+pub use memory::MemoryStorage;
 
+/// One handle to every repository, as trait objects.
+///
+/// This is what the service layer is built from; the backend is chosen
+/// once, at construction.
+#[derive(Clone)]
+pub struct Repositories {
+    pub boards: Arc<dyn BoardRepository>,
+    pub threads: Arc<dyn ThreadRepository>,
+    pub posts: Arc<dyn PostRepository>,
+    pub users: Arc<dyn UserRepository>,
+    pub sessions: Arc<dyn SessionRepository>,
+    pub quotes: Arc<dyn QuoteRepository>,
+    pub attachments: Arc<dyn AttachmentRepository>,
+    pub image_bans: Arc<dyn ImageBanRepository>,
+    pub staff: Arc<dyn StaffRepository>,
+    pub bans: Arc<dyn BanRepository>,
+    pub captchas: Arc<dyn CaptchaRepository>,
+    pub reports: Arc<dyn ReportRepository>,
+    pub mod_actions: Arc<dyn ModActionRepository>,
+}
 
-/// TESTS
-/// 
-/// 
+impl Repositories {
+    /// SQLite-backed repositories sharing one connection pool.
+    pub fn sqlite(pool: DbPool) -> Self {
+        Self {
+            boards: Arc::new(SqliteBoardRepository::new(pool.clone())),
+            threads: Arc::new(SqliteThreadRepository::new(pool.clone())),
+            posts: Arc::new(SqlitePostRepository::new(pool.clone())),
+            users: Arc::new(SqliteUserRepository::new(pool.clone())),
+            sessions: Arc::new(SqliteSessionRepository::new(pool.clone())),
+            quotes: Arc::new(SqliteQuoteRepository::new(pool.clone())),
+            attachments: Arc::new(SqliteAttachmentRepository::new(pool.clone())),
+            image_bans: Arc::new(SqliteImageBanRepository::new(pool.clone())),
+            staff: Arc::new(SqliteStaffRepository::new(pool.clone())),
+            bans: Arc::new(SqliteBanRepository::new(pool.clone())),
+            captchas: Arc::new(SqliteCaptchaRepository::new(pool.clone())),
+            reports: Arc::new(SqliteReportRepository::new(pool.clone())),
+            mod_actions: Arc::new(SqliteModActionRepository::new(pool)),
+        }
+    }
 
+    /// In-memory repositories sharing one `MemoryStorage`.
+    pub fn memory() -> Self {
+        let store = MemoryStorage::new();
+        Self {
+            boards: Arc::new(store.clone()),
+            threads: Arc::new(store.clone()),
+            posts: Arc::new(store.clone()),
+            users: Arc::new(store.clone()),
+            sessions: Arc::new(store.clone()),
+            quotes: Arc::new(store.clone()),
+            attachments: Arc::new(store.clone()),
+            image_bans: Arc::new(store.clone()),
+            staff: Arc::new(store.clone()),
+            bans: Arc::new(store.clone()),
+            captchas: Arc::new(store.clone()),
+            reports: Arc::new(store.clone()),
+            mod_actions: Arc::new(store),
+        }
+    }
+}
+
+
+/// TESTS
+///
+///
 #[cfg(test)]
 mod tests {
     use super::*;
     use connection::create_connection;
     use schema::initialize_schema;
+    use models::{
+        AppealStatus, Attachment, AvPolicy, Ban, BanAppeal, BannedImageHash, BannedImageMatch,
+        Board, BoardRole, BoardStaff, Captcha, CaptchaMode, Cooldowns, DuplicatePolicy, ModAction,
+        ModActionKind, Post, PosterInfo, PruneMode, PrunePolicy, QuoteTarget, Report, ReportCategory,
+        ReportStatus, Role, Session, Thread, ThreadFlags, User, DEFAULT_BUMP_LIMIT,
+    };
+    use time::OffsetDateTime;
+    use uuid::Uuid;
 
     #[test]
     fn database_initializes() {
         let conn = create_connection(":memory:").unwrap();
         initialize_schema(&conn).unwrap();
     }
-}
\ No newline at end of file
+
+    fn round_trip(repos: Repositories) {
+        let board = Board {
+            id: Uuid::new_v4(),
+            name: "g".into(),
+            description: "technology".into(),
+            duplicate_policy: DuplicatePolicy::Thread,
+            av_policy: AvPolicy::default(),
+            cooldowns: Cooldowns::default(),
+            captcha_mode: CaptchaMode::Threads,
+            public_mod_log: false,
+            bump_limit: DEFAULT_BUMP_LIMIT,
+            prune_policy: PrunePolicy::default(),
+            created_at: OffsetDateTime::now_utc(),
+        };
+        repos.boards.insert_board(&board).unwrap();
+
+        let opened_at = OffsetDateTime::now_utc();
+        let thread = Thread {
+            id: Uuid::new_v4(),
+            board_id: board.id,
+            op_number: 0,
+            title: "hello".into(),
+            created_at: opened_at,
+            bumped_at: opened_at,
+            poster: None,
+            flags: ThreadFlags::default(),
+            archived_at: None,
+        };
+        assert_eq!(repos.threads.insert_thread(&thread).unwrap(), 1);
+
+        let post = Post {
+            id: Uuid::new_v4(),
+            thread_id: thread.id,
+            post_number: 0,
+            content: "reply".into(),
+            content_html: "reply".into(),
+            created_at: OffsetDateTime::now_utc(),
+            poster: Some(PosterInfo {
+                ip_hash: "ab".repeat(32),
+                ip: Some("2001:db8::7".parse().unwrap()),
+                user_agent_hash: None,
+            }),
+        };
+        assert_eq!(repos.posts.insert_post(&post).unwrap(), 2);
+
+        assert_eq!(repos.threads.get_thread(thread.id).unwrap().unwrap().poster, None);
+        let by_poster = repos.posts.get_posts_by_ip_hash(&"ab".repeat(32)).unwrap();
+        assert_eq!(by_poster[0].poster, post.poster);
+        assert!(repos.threads.get_threads_by_ip_hash(&"ab".repeat(32)).unwrap().is_empty());
+        let later = OffsetDateTime::now_utc() + time::Duration::seconds(1);
+        assert_eq!(repos.threads.clear_ip_addresses(later).unwrap(), 0);
+        assert_eq!(repos.posts.clear_ip_addresses(post.created_at).unwrap(), 0);
+        assert_eq!(repos.posts.clear_ip_addresses(later).unwrap(), 1);
+        let since = post.created_at - time::Duration::seconds(1);
+        assert_eq!(repos.posts.get_recent_by_ip_hash(board.id, &"ab".repeat(32), since).unwrap().len(), 1);
+        assert!(repos.posts.get_recent_by_ip_hash(board.id, &"ab".repeat(32), later).unwrap().is_empty());
+        assert!(repos.threads.get_recent_by_ip_hash(board.id, &"ab".repeat(32), since).unwrap().is_empty());
+        let cleared = repos.posts.get_post_by_number(board.id, 2).unwrap().unwrap().poster.unwrap();
+        assert_eq!((cleared.ip, cleared.ip_hash), (None, "ab".repeat(32)));
+
+        repos.posts.set_content_html(post.id, "<em>reply</em>").unwrap();
+        assert_eq!(
+            repos.posts.get_posts_by_thread(thread.id).unwrap()[0].content_html,
+            "<em>reply</em>"
+        );
+
+        assert_eq!(repos.boards.get_board(board.id).unwrap().unwrap().name, "g");
+        assert_eq!(
+            repos.boards.get_board(board.id).unwrap().unwrap().duplicate_policy,
+            DuplicatePolicy::Thread
+        );
+        repos.boards.set_duplicate_policy(board.id, DuplicatePolicy::Board).unwrap();
+        assert_eq!(
+            repos.boards.get_board_by_name("g").unwrap().unwrap().duplicate_policy,
+            DuplicatePolicy::Board
+        );
+        assert_eq!(repos.boards.get_board_by_name("g").unwrap().unwrap().id, board.id);
+        assert_eq!(repos.boards.get_board(board.id).unwrap().unwrap().av_policy, AvPolicy::default());
+        let audio_only = AvPolicy { max_duration_secs: 60, codecs: vec!["opus".into(), "flac".into()] };
+        repos.boards.set_av_policy(board.id, &audio_only).unwrap();
+        assert_eq!(repos.boards.get_all().unwrap()[0].av_policy, audio_only);
+        assert_eq!(repos.boards.get_board(board.id).unwrap().unwrap().cooldowns, Cooldowns::default());
+        let cooldowns = Cooldowns { thread_secs: Some(600), reply_secs: None, repost_secs: Some(0) };
+        repos.boards.set_cooldowns(board.id, &cooldowns).unwrap();
+        assert_eq!(repos.boards.get_board_by_name("g").unwrap().unwrap().cooldowns, cooldowns);
+        assert_eq!(repos.boards.get_board(board.id).unwrap().unwrap().captcha_mode, CaptchaMode::Threads);
+        repos.boards.set_captcha_mode(board.id, CaptchaMode::All).unwrap();
+        assert_eq!(repos.boards.get_all().unwrap()[0].captcha_mode, CaptchaMode::All);
+        repos.boards.set_public_mod_log(board.id, true).unwrap();
+        assert!(repos.boards.get_board(board.id).unwrap().unwrap().public_mod_log);
+        assert_eq!(repos.threads.get_threads_by_board(board.id).unwrap().len(), 1);
+        assert_eq!(
+            repos.threads.get_thread_by_number(board.id, 1).unwrap().unwrap().id,
+            thread.id
+        );
+        assert_eq!(
+            repos.posts.get_post_by_number(board.id, 2).unwrap().unwrap().id,
+            post.id
+        );
+        assert!(repos.posts.get_post_by_number(board.id, 1).unwrap().is_none());
+
+        let newer = Thread {
+            id: Uuid::new_v4(),
+            created_at: opened_at + time::Duration::seconds(1),
+            bumped_at: opened_at + time::Duration::seconds(1),
+            ..thread.clone()
+        };
+        repos.threads.insert_thread(&newer).unwrap();
+        let order = |repos: &Repositories| -> Vec<Uuid> {
+            repos.threads.get_threads_by_board(board.id).unwrap().iter().map(|t| t.id).collect()
+        };
+        assert_eq!(order(&repos), [newer.id, thread.id]);
+        let flags = ThreadFlags { sticky: Some(3), locked: true, cyclical: false, autosage: true };
+        repos.threads.set_flags(thread.id, &flags).unwrap();
+        assert_eq!(repos.threads.get_thread(thread.id).unwrap().unwrap().flags, flags);
+        assert_eq!(order(&repos), [thread.id, newer.id]);
+        repos.threads.set_flags(newer.id, &ThreadFlags { sticky: Some(1), ..ThreadFlags::default() }).unwrap();
+        assert_eq!(order(&repos), [newer.id, thread.id]);
+        repos.threads.set_flags(thread.id, &ThreadFlags::default()).unwrap();
+        repos.threads.set_flags(newer.id, &ThreadFlags::default()).unwrap();
+        repos.threads.bump_thread(thread.id, opened_at + time::Duration::seconds(2)).unwrap();
+        assert_eq!(order(&repos), [thread.id, newer.id]);
+        repos.threads.bump_thread(thread.id, opened_at).unwrap();
+        let bumped = repos.threads.get_thread(thread.id).unwrap().unwrap().bumped_at;
+        assert_eq!(bumped.unix_timestamp(), (opened_at + time::Duration::seconds(2)).unix_timestamp());
+        assert_eq!(repos.posts.count_posts_by_thread(thread.id).unwrap(), 1);
+        assert_eq!(repos.posts.count_posts_by_thread(newer.id).unwrap(), 0);
+        repos.boards.set_bump_limit(board.id, 150).unwrap();
+        assert_eq!(repos.boards.get_board(board.id).unwrap().unwrap().bump_limit, 150);
+
+        let target = QuoteTarget { board_id: board.id, number: 1, thread_id: thread.id };
+        repos.quotes.insert_quotes(post.id, &[target.clone(), target]).unwrap();
+        let quotes = repos.quotes.get_quotes_by_thread(thread.id).unwrap();
+        assert_eq!(quotes.len(), 1);
+        assert_eq!((quotes[0].from_number, quotes[0].to_number), (2, 1));
+        assert!(repos.boards.insert_board(&board).is_err());
+
+        let attachment = |post_id, position| Attachment {
+            id: Uuid::new_v4(),
+            thread_id: thread.id,
+            post_id,
+            position,
+            file_name: "cat.png".into(),
+            content_type: "image/png".into(),
+            size_bytes: 1234,
+            sha256: "ab".repeat(32),
+            width: 800,
+            height: 600,
+            storage_key: format!("{}.png", "ab".repeat(32)),
+            thumbnail_key: format!("{}.jpg", "ab".repeat(32)),
+            thumbnail_width: 250,
+            thumbnail_height: 188,
+            metadata_stripped: position == 1,
+            duration_ms: (position == 1).then_some(61_500),
+            created_at: OffsetDateTime::now_utc(),
+        };
+        repos
+            .attachments
+            .insert_attachments(&[attachment(None, 0), attachment(Some(post.id), 1)])
+            .unwrap();
+        let attachments = repos.attachments.get_attachments_by_thread(thread.id).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].post_id, None);
+        assert_eq!(attachments[1].post_id, Some(post.id));
+        assert_eq!((attachments[1].width, attachments[1].thumbnail_height), (800, 188));
+        assert!(!attachments[0].metadata_stripped && attachments[1].metadata_stripped);
+        assert_eq!((attachments[0].duration_ms, attachments[1].duration_ms), (None, Some(61_500)));
+        assert!(repos
+            .attachments
+            .insert_attachments(&[attachment(Some(Uuid::new_v4()), 0)])
+            .is_err());
+
+        let storage_key = attachments[0].storage_key.clone();
+        assert_eq!(repos.attachments.find_by_hash(board.id, &"ab".repeat(32)).unwrap().len(), 2);
+        assert!(repos.attachments.find_by_hash(Uuid::new_v4(), &"ab".repeat(32)).unwrap().is_empty());
+        assert_eq!(repos.attachments.count_by_storage_key(&storage_key).unwrap(), 2);
+        assert_eq!(repos.attachments.get_media_keys().unwrap().len(), 1);
+        assert_eq!(repos.attachments.get_attachments_by_post(post.id).unwrap().len(), 1);
+
+        let report = |category, created_at| Report {
+            id: Uuid::new_v4(),
+            post_id: Some(post.id),
+            board_id: board.id,
+            post_number: 2,
+            category,
+            comment: "buy now".into(),
+            reporter_ip_hash: "cd".repeat(32),
+            created_at,
+            status: ReportStatus::Open,
+            resolved_by: None,
+            resolved_at: None,
+        };
+        let reported_at = OffsetDateTime::now_utc();
+        let user_id = Uuid::new_v4();
+        repos
+            .users
+            .create(&User { id: user_id, username: "janitor".into(), password_hash: String::new(), role: Role::User })
+            .unwrap();
+        repos.reports.insert_report(&report(ReportCategory::Spam, reported_at)).unwrap();
+        repos.reports.insert_report(&report(ReportCategory::Other, reported_at + time::Duration::seconds(1))).unwrap();
+        let open = repos.reports.get_open_reports().unwrap();
+        assert_eq!(open.len(), 2);
+        assert_eq!((open[0].category, open[0].post_number), (ReportCategory::Spam, 2));
+        assert_eq!(repos.reports.get_open_by_post(post.id).unwrap().len(), 2);
+        assert!(repos.reports.get_resolved_reports(10).unwrap().is_empty());
+        assert_eq!(repos.reports.resolve_reports(post.id, ReportStatus::Dismissed, user_id, reported_at).unwrap(), 2);
+        assert_eq!(repos.reports.resolve_reports(post.id, ReportStatus::Deleted, user_id, reported_at).unwrap(), 0);
+        let resolved = repos.reports.get_resolved_reports(1).unwrap();
+        assert_eq!((resolved.len(), resolved[0].status, resolved[0].resolved_by), (1, ReportStatus::Dismissed, Some(user_id)));
+        repos.reports.insert_report(&report(ReportCategory::Illegal, reported_at)).unwrap();
+
+        assert!(repos.posts.delete_post(post.id).unwrap());
+        assert!(!repos.posts.delete_post(post.id).unwrap());
+        let orphaned = repos.reports.get_open_reports().unwrap();
+        assert_eq!((orphaned.len(), orphaned[0].post_id, orphaned[0].post_number), (1, None, 2));
+        assert!(repos.posts.get_posts_by_thread(thread.id).unwrap().is_empty());
+        assert!(repos.quotes.get_quotes_by_thread(thread.id).unwrap().is_empty());
+        assert_eq!(repos.attachments.count_by_storage_key(&storage_key).unwrap(), 1);
+
+        let ban = BannedImageHash {
+            id: Uuid::new_v4(),
+            phash: u64::MAX - 1,
+            dhash: 0x8000_0000_0000_0001,
+            reason: "spam".into(),
+            created_at: OffsetDateTime::now_utc(),
+            lifted_at: None,
+        };
+        repos.image_bans.insert_ban(&ban).unwrap();
+        let bans = repos.image_bans.get_bans().unwrap();
+        assert_eq!((bans[0].phash, bans[0].dhash), (ban.phash, ban.dhash));
+
+        let hit = |thread_id| BannedImageMatch {
+            id: Uuid::new_v4(),
+            ban_id: ban.id,
+            board_id: board.id,
+            thread_id,
+            file_name: "spam.png".into(),
+            sha256: "cd".repeat(32),
+            distance: 3,
+            created_at: OffsetDateTime::now_utc(),
+        };
+        repos.image_bans.insert_match(&hit(None)).unwrap();
+        repos.image_bans.insert_match(&hit(Some(thread.id))).unwrap();
+        let matches = repos.image_bans.get_matches(1).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].thread_id, matches[0].distance), (Some(thread.id), 3));
+
+        let lifted_at = OffsetDateTime::now_utc();
+        assert!(repos.image_bans.lift_ban(ban.id, lifted_at).unwrap());
+        assert!(!repos.image_bans.lift_ban(ban.id, lifted_at).unwrap());
+        assert!(!repos.image_bans.lift_ban(Uuid::new_v4(), lifted_at).unwrap());
+        assert!(repos.image_bans.get_bans().unwrap().is_empty());
+        assert!(repos.image_bans.get_ban(ban.id).unwrap().unwrap().lifted_at.is_some());
+        assert_eq!(repos.image_bans.get_matches(10).unwrap().len(), 2);
+
+        let user = User {
+            id: Uuid::new_v4(),
+            username: "admin".into(),
+            password_hash: "$argon2id$hash".into(),
+            role: Role::Admin,
+        };
+        repos.users.create(&user).unwrap();
+        assert!(repos.users.create(&User { id: Uuid::new_v4(), ..user.clone() }).is_err());
+        assert_eq!(repos.users.find_by_username("admin").unwrap().unwrap().id, user.id);
+        assert_eq!(repos.users.find_by_id(user.id).unwrap().unwrap().role, Role::Admin);
+        assert!(repos.users.set_role(user.id, &Role::User).unwrap());
+        assert_eq!(repos.users.find_by_id(user.id).unwrap().unwrap().role, Role::User);
+        assert!(!repos.users.set_role(Uuid::new_v4(), &Role::User).unwrap());
+
+        let now = OffsetDateTime::now_utc();
+        let hour = time::Duration::hours(1);
+        let session = |token_hash: &str, created_at: OffsetDateTime| Session {
+            token_hash: token_hash.into(),
+            user_id: user.id,
+            created_at,
+            last_seen_at: created_at,
+        };
+        repos.sessions.create(&session("a", now)).unwrap();
+        repos.sessions.create(&session("b", now - hour * 2)).unwrap();
+        repos.sessions.create(&session("c", now - hour * 3)).unwrap();
+        assert!(repos.sessions.create(&session("a", now)).is_err());
+        assert_eq!(repos.sessions.find("a").unwrap().unwrap().user_id, user.id);
+
+        assert!(repos.sessions.touch("c", now).unwrap());
+        assert!(!repos.sessions.touch("z", now).unwrap());
+        assert_eq!(repos.sessions.find("a").unwrap().unwrap().created_at.unix_timestamp(), now.unix_timestamp());
+
+        // "b" is idle too long; "c" is too old despite being seen just now.
+        assert_eq!(repos.sessions.delete_expired(now - hour * 2 - hour / 2, now - hour).unwrap(), 2);
+        assert!(repos.sessions.find("a").unwrap().is_some());
+
+        repos.sessions.create(&session("d", now)).unwrap();
+        assert!(repos.sessions.delete("d").unwrap());
+        assert!(!repos.sessions.delete("d").unwrap());
+        repos.sessions.create(&session("e", now)).unwrap();
+        assert_eq!(repos.sessions.delete_by_user(user.id).unwrap(), 2);
+        assert!(repos.sessions.find("a").unwrap().is_none());
+
+        let appointment = BoardStaff {
+            board_id: board.id,
+            user_id: user.id,
+            role: BoardRole::Janitor,
+            created_at: now,
+        };
+        repos.staff.upsert(&appointment).unwrap();
+        repos.staff.upsert(&BoardStaff { role: BoardRole::Moderator, ..appointment.clone() }).unwrap();
+        assert_eq!(repos.staff.find(board.id, user.id).unwrap().unwrap().role, BoardRole::Moderator);
+        assert_eq!(repos.staff.get_by_board(board.id).unwrap().len(), 1);
+        assert_eq!(repos.staff.get_by_user(user.id).unwrap()[0].board_id, board.id);
+        assert!(repos.staff.upsert(&BoardStaff { user_id: Uuid::new_v4(), ..appointment.clone() }).is_err());
+        assert!(repos.staff.delete(board.id, user.id).unwrap());
+        assert!(!repos.staff.delete(board.id, user.id).unwrap());
+        assert!(repos.staff.find(board.id, user.id).unwrap().is_none());
+
+        let ban = Ban {
+            id: Uuid::new_v4(),
+            ip_range: "2001:db8::/32".parse().unwrap(),
+            board_id: Some(board.id),
+            reason: "spam".into(),
+            staff_note: "same bot as last week".into(),
+            created_by: Some(user.id),
+            created_at: now,
+            expires_at: Some(now + hour),
+        };
+        repos.bans.insert_ban(&ban).unwrap();
+        let single = Ban {
+            id: Uuid::new_v4(),
+            ip_range: "192.0.2.7/32".parse().unwrap(),
+            board_id: None,
+            expires_at: None,
+            ..ban.clone()
+        };
+        repos.bans.insert_ban(&single).unwrap();
+        let covering = |ip: &str, at| repos.bans.find_covering(ip.parse().unwrap(), at).unwrap();
+        assert_eq!(covering("2001:db8:ffff::1", now)[0].id, ban.id);
+        assert!(covering("2001:db9::1", now).is_empty());
+        assert!(covering("2001:db8::1", now + hour * 2).is_empty());
+        assert_eq!(covering("192.0.2.7", now)[0].board_id, None);
+        assert_eq!(covering("::ffff:192.0.2.7", now)[0].id, single.id);
+        assert!(covering("192.0.2.8", now).is_empty());
+        assert_eq!(repos.bans.get_active(now).unwrap().len(), 2);
+        assert_eq!(repos.bans.get_ban(ban.id).unwrap().unwrap().staff_note, ban.staff_note);
+
+        let appeal = BanAppeal {
+            id: Uuid::new_v4(),
+            ban_id: single.id,
+            message: "it was my brother".into(),
+            status: AppealStatus::Pending,
+            created_at: now,
+            decided_by: None,
+            decided_at: None,
+        };
+        repos.bans.insert_appeal(&appeal).unwrap();
+        assert!(repos.bans.insert_appeal(&BanAppeal { id: Uuid::new_v4(), ..appeal.clone() }).is_err());
+        assert_eq!(repos.bans.get_pending_appeals().unwrap().len(), 1);
+        assert!(repos.bans.decide_appeal(appeal.id, AppealStatus::Accepted, user.id, now).unwrap());
+        assert!(!repos.bans.decide_appeal(appeal.id, AppealStatus::Denied, user.id, now).unwrap());
+        assert_eq!(repos.bans.get_appeal_by_ban(single.id).unwrap().unwrap().status, AppealStatus::Accepted);
+        assert!(repos.bans.get_pending_appeals().unwrap().is_empty());
+
+        assert!(repos.bans.set_expiry(single.id, Some(now - hour)).unwrap());
+        assert!(covering("192.0.2.7", now).is_empty());
+        assert!(!repos.bans.set_expiry(Uuid::new_v4(), None).unwrap());
+
+        let captcha = |answer: &str, expires_at| Captcha {
+            id: Uuid::new_v4(),
+            answer: answer.into(),
+            expires_at,
+        };
+        let fresh = captcha("AC3DE7", now + hour);
+        let stale = captcha("XY9", now - hour);
+        repos.captchas.insert(&fresh).unwrap();
+        repos.captchas.insert(&stale).unwrap();
+        assert!(repos.captchas.insert(&fresh).is_err());
+        assert_eq!(repos.captchas.get(fresh.id).unwrap().unwrap().answer, "AC3DE7");
+        assert_eq!(repos.captchas.delete_expired(now).unwrap(), 1);
+        assert!(repos.captchas.get(stale.id).unwrap().is_none());
+        let taken = repos.captchas.take(fresh.id).unwrap().unwrap();
+        assert_eq!(taken.expires_at.unix_timestamp(), fresh.expires_at.unix_timestamp());
+        assert!(repos.captchas.take(fresh.id).unwrap().is_none());
+
+        let action = |kind, board_id, created_at| ModAction {
+            id: Uuid::new_v4(),
+            actor_id: Some(user_id),
+            actor_name: "janitor".into(),
+            action: kind,
+            board_id,
+            target: ">>2".into(),
+            reason: "spam".into(),
+            before: Some(r#"{"content":"buy now"}"#.into()),
+            after: None,
+            created_at,
+        };
+        let deleted = action(ModActionKind::DeletePost, Some(board.id), now - hour);
+        repos.mod_actions.insert_action(&deleted).unwrap();
+        repos.mod_actions.insert_action(&action(ModActionKind::BanIp, Some(board.id), now)).unwrap();
+        repos.mod_actions.insert_action(&action(ModActionKind::ChangeRole, None, now)).unwrap();
+        assert!(repos.mod_actions.insert_action(&deleted).is_err());
+        let filter = |filter: ModActionFilter| repos.mod_actions.get_actions(&ModActionFilter { limit: 10, ..filter }).unwrap();
+        assert_eq!(filter(ModActionFilter::default()).len(), 3);
+        let on_board = filter(ModActionFilter { board_id: Some(board.id), ..Default::default() });
+        assert_eq!(on_board.iter().map(|a| a.action).collect::<Vec<_>>(), [ModActionKind::BanIp, ModActionKind::DeletePost]);
+        let found = filter(ModActionFilter {
+            actor_id: Some(user_id),
+            actions: vec![ModActionKind::DeletePost, ModActionKind::ChangeRole],
+            before: Some(now),
+            ..Default::default()
+        });
+        assert_eq!((found.len(), found[0].before.as_deref()), (1, deleted.before.as_deref()));
+        assert_eq!(found[0].created_at.unix_timestamp(), deleted.created_at.unix_timestamp());
+        assert!(filter(ModActionFilter { actor_id: Some(Uuid::new_v4()), ..Default::default() }).is_empty());
+        assert_eq!(filter(ModActionFilter { board_ids: Some(vec![board.id]), ..Default::default() }).len(), 2);
+        assert!(filter(ModActionFilter { board_ids: Some(vec![Uuid::new_v4()]), ..Default::default() }).is_empty());
+        assert!(filter(ModActionFilter { board_ids: Some(Vec::new()), ..Default::default() }).is_empty());
+        assert_eq!(repos.mod_actions.get_actions(&ModActionFilter { limit: 1, ..Default::default() }).unwrap().len(), 1);
+
+        let small = Board {
+            id: Uuid::new_v4(),
+            name: "p".into(),
+            prune_policy: PrunePolicy { max_threads: 2, mode: PruneMode::Archive },
+            ..board.clone()
+        };
+        repos.boards.insert_board(&small).unwrap();
+        let policy = repos.boards.get_board(small.id).unwrap().unwrap().prune_policy;
+        assert_eq!(policy, small.prune_policy);
+        let open = |seconds| {
+            let at = opened_at + time::Duration::seconds(seconds);
+            let new = Thread { id: Uuid::new_v4(), board_id: small.id, created_at: at, bumped_at: at, ..thread.clone() };
+            let (_, pruned) = repos.threads.insert_thread_and_prune(&new, &[], &policy).unwrap();
+            (new.id, pruned)
+        };
+        let (sticky, _) = open(0);
+        repos.threads.set_flags(sticky, &ThreadFlags { sticky: Some(1), ..ThreadFlags::default() }).unwrap();
+        let (first, pruned) = open(1);
+        assert!(pruned.threads.is_empty());
+        let (second, pruned) = open(2);
+        assert_eq!(pruned.threads.iter().map(|t| t.id).collect::<Vec<_>>(), [first]);
+        assert!(pruned.threads[0].archived_at.is_some() && pruned.attachments.is_empty());
+        let live = |repos: &Repositories| -> Vec<Uuid> {
+            repos.threads.get_threads_by_board(small.id).unwrap().iter().map(|t| t.id).collect()
+        };
+        assert_eq!(live(&repos), [sticky, second]);
+        let archived = repos.threads.get_archived_threads(small.id).unwrap();
+        assert_eq!((archived.len(), archived[0].id), (1, first));
+        assert!(repos.threads.get_thread(first).unwrap().unwrap().archived_at.is_some());
+
+        let policy = PrunePolicy { max_threads: 2, mode: PruneMode::Delete };
+        repos.boards.set_prune_policy(small.id, &policy).unwrap();
+        assert_eq!(repos.boards.get_board(small.id).unwrap().unwrap().prune_policy, policy);
+        let kept = Attachment { thread_id: second, ..attachment(None, 0) };
+        repos.attachments.insert_attachments(std::slice::from_ref(&kept)).unwrap();
+        let at = opened_at + time::Duration::seconds(3);
+        let third = Thread { id: Uuid::new_v4(), board_id: small.id, created_at: at, bumped_at: at, ..thread.clone() };
+        // A failing attachment undoes the whole insert, pruning included.
+        let clash = Attachment { thread_id: third.id, ..kept.clone() };
+        assert!(repos.threads.insert_thread_and_prune(&third, &[clash], &policy).is_err());
+        assert!(repos.threads.get_thread(third.id).unwrap().is_none());
+        assert_eq!(live(&repos), [sticky, second]);
+        let op_file = Attachment { thread_id: third.id, ..attachment(None, 0) };
+        let (op_number, pruned) = repos.threads.insert_thread_and_prune(&third, &[op_file], &policy).unwrap();
+        assert_eq!(repos.threads.get_thread(third.id).unwrap().unwrap().op_number, op_number);
+        assert_eq!(repos.attachments.get_attachments_by_thread(third.id).unwrap().len(), 1);
+        assert_eq!(pruned.threads.iter().map(|t| t.id).collect::<Vec<_>>(), [second]);
+        assert_eq!(pruned.attachments.len(), 1);
+        assert!(repos.threads.get_thread(second).unwrap().is_none());
+        assert!(repos.attachments.get_attachments_by_thread(second).unwrap().is_empty());
+        assert_eq!(live(&repos), [sticky, third.id]);
+        assert_eq!(repos.threads.get_archived_threads(small.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sqlite_and_memory_repositories_agree() {
+        let pool = create_pool(":memory:", 1).unwrap();
+        init_database(&pool.get().unwrap()).unwrap();
+
+        round_trip(Repositories::sqlite(pool));
+        round_trip(Repositories::memory());
+    }
+
+    #[test]
+    fn mod_log_is_append_only() {
+        let conn = create_connection(":memory:").unwrap();
+        initialize_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO mod_actions (id, actor_name, action, target, reason, created_at)
+             VALUES ('a', 'system', 'change_role', 'alice', '', '2024-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+
+        assert!(conn.execute("UPDATE mod_actions SET reason = 'edited'", []).is_err());
+        assert!(conn.execute("DELETE FROM mod_actions", []).is_err());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn postgres_repositories_agree() {
+        if let Some((_guard, pool)) = postgres::tests::test_pool() {
+            round_trip(Repositories::postgres(pool));
+        }
+    }
+}
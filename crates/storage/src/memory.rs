@@ -0,0 +1,1025 @@
+//! In-Memory Storage
+//!
+//! Developer Notes:
+//! - Implements every repository trait on plain collections.
+//! - Intended for unit tests of the service layer; nothing is persisted.
+//! - Mirrors the SQLite constraints that business rules rely on
+//!   (unique names, foreign keys) so tests behave like production.
+//!
+//! End Notes:
+//! Keep semantics identical to the SQLite implementations.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{
+    AppealStatus, Attachment, AvPolicy, Ban, BanAppeal, BannedImageHash, BannedImageMatch, Board,
+    BoardStaff, Captcha, CaptchaMode, Cooldowns, DuplicatePolicy, ModAction, Post, PosterInfo,
+    PruneMode, PrunePolicy, Quote, QuoteTarget, Report, ReportStatus, Role, Session, Thread,
+    ThreadFlags, User,
+};
+use crate::attachment_repository::AttachmentRepository;
+use crate::ban_repository::{ip_key, range_keys, BanRepository};
+use crate::board_repository::BoardRepository;
+use crate::captcha_repository::CaptchaRepository;
+use crate::image_ban_repository::ImageBanRepository;
+use crate::mod_action_repository::{ModActionFilter, ModActionRepository};
+use crate::post_repository::PostRepository;
+use crate::quote_repository::QuoteRepository;
+use crate::report_repository::ReportRepository;
+use crate::session_repository::SessionRepository;
+use crate::staff_repository::StaffRepository;
+use crate::thread_repository::{PrunedThreads, ThreadRepository};
+use crate::user_repository::UserRepository;
+use crate::StorageError;
+
+#[derive(Default)]
+struct Tables {
+    boards: Vec<Board>,
+    threads: Vec<Thread>,
+    posts: Vec<Post>,
+    users: Vec<User>,
+    sessions: Vec<Session>,
+    quotes: Vec<(Uuid, QuoteTarget)>,
+    attachments: Vec<Attachment>,
+    image_bans: Vec<BannedImageHash>,
+    image_ban_matches: Vec<BannedImageMatch>,
+    staff: Vec<BoardStaff>,
+    bans: Vec<Ban>,
+    ban_appeals: Vec<BanAppeal>,
+    captchas: Vec<Captcha>,
+    reports: Vec<Report>,
+    mod_actions: Vec<ModAction>,
+    last_post_number: HashMap<Uuid, i64>,
+}
+
+impl Tables {
+    fn next_post_number(&mut self, board_id: Uuid) -> i64 {
+        let counter = self.last_post_number.entry(board_id).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    fn board_name(&self, board_id: Uuid) -> String {
+        self.boards
+            .iter()
+            .find(|b| b.id == board_id)
+            .map(|b| b.name.clone())
+            .unwrap_or_default()
+    }
+
+    fn insert_thread(&mut self, thread: &Thread) -> Result<i64, StorageError> {
+        if !self.boards.iter().any(|b| b.id == thread.board_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: threads.board_id"));
+        }
+        if self.threads.iter().any(|x| x.id == thread.id) {
+            return Err(constraint("UNIQUE constraint failed: threads.id"));
+        }
+        let op_number = self.next_post_number(thread.board_id);
+        self.threads.push(Thread { op_number, ..thread.clone() });
+        Ok(op_number)
+    }
+
+    /// Insert all of `attachments`, or none if any would fail.
+    fn insert_attachments(&mut self, attachments: &[Attachment]) -> Result<(), StorageError> {
+        for a in attachments {
+            if !self.threads.iter().any(|x| x.id == a.thread_id) {
+                return Err(constraint("FOREIGN KEY constraint failed: attachments.thread_id"));
+            }
+            if let Some(post_id) = a.post_id
+                && !self.posts.iter().any(|p| p.id == post_id)
+            {
+                return Err(constraint("FOREIGN KEY constraint failed: attachments.post_id"));
+            }
+            if self.attachments.iter().any(|x| x.id == a.id) {
+                return Err(constraint("UNIQUE constraint failed: attachments.id"));
+            }
+        }
+        self.attachments.extend_from_slice(attachments);
+        Ok(())
+    }
+
+    /// Live threads of a board in listing order.
+    fn live_threads(&self, board_id: Uuid) -> Vec<Thread> {
+        let mut threads: Vec<Thread> = self
+            .threads
+            .iter()
+            .filter(|x| x.board_id == board_id && x.archived_at.is_none())
+            .cloned()
+            .collect();
+        threads.sort_by_key(|x| (x.flags.sticky.is_none(), x.flags.sticky, std::cmp::Reverse(x.bumped_at)));
+        threads
+    }
+
+    /// Delete a thread with everything that cascades from it, returning
+    /// its attachments.
+    fn delete_thread(&mut self, thread_id: Uuid) -> Vec<Attachment> {
+        let posts: Vec<Uuid> = self
+            .posts
+            .iter()
+            .filter(|p| p.thread_id == thread_id)
+            .map(|p| p.id)
+            .collect();
+        let (removed, kept) = std::mem::take(&mut self.attachments)
+            .into_iter()
+            .partition(|a| a.thread_id == thread_id);
+        self.attachments = kept;
+
+        self.threads.retain(|x| x.id != thread_id);
+        self.posts.retain(|p| p.thread_id != thread_id);
+        self.quotes.retain(|(id, target)| {
+            !posts.contains(id) && target.thread_id != thread_id
+        });
+        for report in self.reports.iter_mut().filter(|r| r.post_id.is_some_and(|id| posts.contains(&id))) {
+            report.post_id = None;
+        }
+        for m in self.image_ban_matches.iter_mut().filter(|m| m.thread_id == Some(thread_id)) {
+            m.thread_id = None;
+        }
+        removed
+    }
+
+    fn board_of_thread(&self, thread_id: Uuid) -> Option<Uuid> {
+        self.threads
+            .iter()
+            .find(|x| x.id == thread_id)
+            .map(|x| x.board_id)
+    }
+}
+
+/// Shared in-memory store implementing all repositories.
+///
+/// Clones share the same underlying tables.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn constraint(message: &str) -> StorageError {
+    StorageError::Constraint(message.into())
+}
+
+impl BoardRepository for MemoryStorage {
+    fn insert_board(&self, board: &Board) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if t.boards.iter().any(|b| b.id == board.id || b.name == board.name) {
+            return Err(constraint("UNIQUE constraint failed: boards"));
+        }
+        t.boards.push(board.clone());
+        Ok(())
+    }
+
+    fn get_all(&self) -> Result<Vec<Board>, StorageError> {
+        let mut boards = self.lock().boards.clone();
+        boards.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(boards)
+    }
+
+    fn get_board(&self, id: Uuid) -> Result<Option<Board>, StorageError> {
+        Ok(self.lock().boards.iter().find(|b| b.id == id).cloned())
+    }
+
+    fn get_board_by_name(&self, name: &str) -> Result<Option<Board>, StorageError> {
+        Ok(self.lock().boards.iter().find(|b| b.name == name).cloned())
+    }
+
+    fn set_duplicate_policy(
+        &self,
+        board_id: Uuid,
+        policy: DuplicatePolicy,
+    ) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.duplicate_policy = policy;
+        }
+        Ok(())
+    }
+    fn set_av_policy(&self, board_id: Uuid, policy: &AvPolicy) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.av_policy = policy.clone();
+        }
+        Ok(())
+    }
+
+    fn set_cooldowns(&self, board_id: Uuid, cooldowns: &Cooldowns) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.cooldowns = *cooldowns;
+        }
+        Ok(())
+    }
+
+    fn set_captcha_mode(&self, board_id: Uuid, mode: CaptchaMode) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.captcha_mode = mode;
+        }
+        Ok(())
+    }
+
+    fn set_public_mod_log(&self, board_id: Uuid, public: bool) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.public_mod_log = public;
+        }
+        Ok(())
+    }
+
+    fn set_bump_limit(&self, board_id: Uuid, limit: u32) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.bump_limit = limit;
+        }
+        Ok(())
+    }
+
+    fn set_prune_policy(&self, board_id: Uuid, policy: &PrunePolicy) -> Result<(), StorageError> {
+        if let Some(board) = self.lock().boards.iter_mut().find(|b| b.id == board_id) {
+            board.prune_policy = *policy;
+        }
+        Ok(())
+    }
+}
+
+impl ThreadRepository for MemoryStorage {
+    fn insert_thread(&self, thread: &Thread) -> Result<i64, StorageError> {
+        self.lock().insert_thread(thread)
+    }
+
+    fn insert_thread_and_prune(
+        &self,
+        thread: &Thread,
+        attachments: &[Attachment],
+        policy: &PrunePolicy,
+    ) -> Result<(i64, PrunedThreads), StorageError> {
+        let mut t = self.lock();
+        let op_number = t.insert_thread(thread)?;
+        if let Err(err) = t.insert_attachments(attachments) {
+            // Undo the insert, as a rolled back transaction would.
+            t.threads.retain(|x| x.id != thread.id);
+            t.last_post_number.insert(thread.board_id, op_number - 1);
+            return Err(err);
+        }
+
+        let mut pruned = PrunedThreads::default();
+        let overflow = t.live_threads(thread.board_id).into_iter().skip(policy.max_threads as usize);
+        for mut old in overflow {
+            if old.flags.sticky.is_some() || old.id == thread.id {
+                continue;
+            }
+
+            match policy.mode {
+                PruneMode::Archive => {
+                    if let Some(x) = t.threads.iter_mut().find(|x| x.id == old.id) {
+                        x.archived_at = Some(thread.created_at);
+                    }
+                    old.archived_at = Some(thread.created_at);
+                }
+                PruneMode::Delete => {
+                    let attachments = t.delete_thread(old.id);
+                    pruned.attachments.extend(attachments);
+                }
+            }
+            pruned.threads.push(old);
+        }
+
+        Ok((op_number, pruned))
+    }
+
+    fn get_thread(&self, id: Uuid) -> Result<Option<Thread>, StorageError> {
+        Ok(self.lock().threads.iter().find(|x| x.id == id).cloned())
+    }
+
+    fn get_thread_by_number(
+        &self,
+        board_id: Uuid,
+        op_number: i64,
+    ) -> Result<Option<Thread>, StorageError> {
+        Ok(self
+            .lock()
+            .threads
+            .iter()
+            .find(|x| x.board_id == board_id && x.op_number == op_number)
+            .cloned())
+    }
+
+    fn get_threads_by_board(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        Ok(self.lock().live_threads(board_id))
+    }
+
+    fn get_archived_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        let mut threads: Vec<Thread> = self
+            .lock()
+            .threads
+            .iter()
+            .filter(|x| x.board_id == board_id && x.archived_at.is_some())
+            .cloned()
+            .collect();
+        threads.sort_by_key(|x| std::cmp::Reverse((x.archived_at, x.bumped_at)));
+        Ok(threads)
+    }
+
+    fn get_threads_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Thread>, StorageError> {
+        let mut threads: Vec<Thread> = self
+            .lock()
+            .threads
+            .iter()
+            .filter(|x| x.poster.as_ref().is_some_and(|p| p.ip_hash == ip_hash))
+            .cloned()
+            .collect();
+        threads.sort_by_key(|x| std::cmp::Reverse(x.created_at));
+        Ok(threads)
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Thread>, StorageError> {
+        let threads: Vec<Thread> = self
+            .get_threads_by_ip_hash(ip_hash)?
+            .into_iter()
+            .filter(|x| x.board_id == board_id && x.created_at >= since)
+            .collect();
+        Ok(threads)
+    }
+
+    fn set_flags(&self, thread_id: Uuid, flags: &ThreadFlags) -> Result<(), StorageError> {
+        if let Some(thread) = self.lock().threads.iter_mut().find(|x| x.id == thread_id) {
+            thread.flags = *flags;
+        }
+        Ok(())
+    }
+
+    fn bump_thread(&self, thread_id: Uuid, at: OffsetDateTime) -> Result<(), StorageError> {
+        if let Some(thread) = self.lock().threads.iter_mut().find(|x| x.id == thread_id) {
+            thread.bumped_at = thread.bumped_at.max(at);
+        }
+        Ok(())
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let old = t.threads.iter_mut().filter(|x| x.created_at < created_before);
+        Ok(clear_ips(old.map(|x| &mut x.poster)))
+    }
+}
+
+impl PostRepository for MemoryStorage {
+    fn insert_post(&self, post: &Post) -> Result<i64, StorageError> {
+        let mut t = self.lock();
+        let Some(board_id) = t.board_of_thread(post.thread_id) else {
+            return Err(constraint("FOREIGN KEY constraint failed: posts.thread_id"));
+        };
+        if t.posts.iter().any(|p| p.id == post.id) {
+            return Err(constraint("UNIQUE constraint failed: posts.id"));
+        }
+        let post_number = t.next_post_number(board_id);
+        t.posts.push(Post { post_number, ..post.clone() });
+        Ok(post_number)
+    }
+
+    fn get_post_by_number(
+        &self,
+        board_id: Uuid,
+        post_number: i64,
+    ) -> Result<Option<Post>, StorageError> {
+        let t = self.lock();
+        Ok(t.posts
+            .iter()
+            .find(|p| {
+                p.post_number == post_number
+                    && t.board_of_thread(p.thread_id) == Some(board_id)
+            })
+            .cloned())
+    }
+
+    fn get_posts_by_thread(&self, thread_id: Uuid) -> Result<Vec<Post>, StorageError> {
+        let mut posts: Vec<Post> = self
+            .lock()
+            .posts
+            .iter()
+            .filter(|p| p.thread_id == thread_id)
+            .cloned()
+            .collect();
+        posts.sort_by_key(|p| p.created_at);
+        Ok(posts)
+    }
+
+    fn count_posts_by_thread(&self, thread_id: Uuid) -> Result<u64, StorageError> {
+        Ok(self.lock().posts.iter().filter(|p| p.thread_id == thread_id).count() as u64)
+    }
+
+    fn set_content_html(&self, post_id: Uuid, html: &str) -> Result<(), StorageError> {
+        if let Some(post) = self.lock().posts.iter_mut().find(|p| p.id == post_id) {
+            post.content_html = html.to_string();
+        }
+        Ok(())
+    }
+
+    fn delete_post(&self, post_id: Uuid) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        let before = t.posts.len();
+        t.posts.retain(|p| p.id != post_id);
+        t.attachments.retain(|a| a.post_id != Some(post_id));
+        t.quotes.retain(|(id, _)| *id != post_id);
+        for report in t.reports.iter_mut().filter(|r| r.post_id == Some(post_id)) {
+            report.post_id = None;
+        }
+        Ok(t.posts.len() < before)
+    }
+
+    fn get_posts_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Post>, StorageError> {
+        let mut posts: Vec<Post> = self
+            .lock()
+            .posts
+            .iter()
+            .filter(|p| p.poster.as_ref().is_some_and(|p| p.ip_hash == ip_hash))
+            .cloned()
+            .collect();
+        posts.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(posts)
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Post>, StorageError> {
+        let posts = self.get_posts_by_ip_hash(ip_hash)?;
+        let t = self.lock();
+        Ok(posts
+            .into_iter()
+            .filter(|p| p.created_at >= since && t.board_of_thread(p.thread_id) == Some(board_id))
+            .collect())
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let old = t.posts.iter_mut().filter(|p| p.created_at < created_before);
+        Ok(clear_ips(old.map(|p| &mut p.poster)))
+    }
+}
+
+/// Forget the raw addresses among `posters`; returns how many were set.
+fn clear_ips<'a>(posters: impl Iterator<Item = &'a mut Option<PosterInfo>>) -> u64 {
+    posters
+        .filter_map(|poster| poster.as_mut()?.ip.take())
+        .count() as u64
+}
+
+impl UserRepository for MemoryStorage {
+    fn create(&self, user: &User) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if t.users.iter().any(|u| u.id == user.id || u.username == user.username) {
+            return Err(constraint("UNIQUE constraint failed: users"));
+        }
+        t.users.push(user.clone());
+        Ok(())
+    }
+
+    fn find_by_username(&self, username: &str) -> Result<Option<User>, StorageError> {
+        Ok(self.lock().users.iter().find(|u| u.username == username).cloned())
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        Ok(self.lock().users.iter().find(|u| u.id == id).cloned())
+    }
+
+    fn set_role(&self, id: Uuid, role: &Role) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        match t.users.iter_mut().find(|u| u.id == id) {
+            Some(user) => {
+                user.role = *role;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl SessionRepository for MemoryStorage {
+    fn create(&self, session: &Session) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.users.iter().any(|u| u.id == session.user_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: sessions.user_id"));
+        }
+        if t.sessions.iter().any(|s| s.token_hash == session.token_hash) {
+            return Err(constraint("UNIQUE constraint failed: sessions.token_hash"));
+        }
+        t.sessions.push(session.clone());
+        Ok(())
+    }
+
+    fn find(&self, token_hash: &str) -> Result<Option<Session>, StorageError> {
+        Ok(self.lock().sessions.iter().find(|s| s.token_hash == token_hash).cloned())
+    }
+
+    fn touch(&self, token_hash: &str, last_seen_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        match t.sessions.iter_mut().find(|s| s.token_hash == token_hash) {
+            Some(session) => {
+                session.last_seen_at = last_seen_at;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn delete(&self, token_hash: &str) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        let before = t.sessions.len();
+        t.sessions.retain(|s| s.token_hash != token_hash);
+        Ok(t.sessions.len() < before)
+    }
+
+    fn delete_by_user(&self, user_id: Uuid) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let before = t.sessions.len();
+        t.sessions.retain(|s| s.user_id != user_id);
+        Ok((before - t.sessions.len()) as u64)
+    }
+
+    fn delete_expired(
+        &self,
+        created_before: OffsetDateTime,
+        seen_before: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let before = t.sessions.len();
+        t.sessions
+            .retain(|s| s.created_at >= created_before && s.last_seen_at >= seen_before);
+        Ok((before - t.sessions.len()) as u64)
+    }
+}
+
+impl QuoteRepository for MemoryStorage {
+    fn insert_quotes(&self, post_id: Uuid, targets: &[QuoteTarget]) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.posts.iter().any(|p| p.id == post_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: post_quotes.post_id"));
+        }
+        for target in targets {
+            let exists = t.quotes.iter().any(|(id, q)| {
+                *id == post_id && q.board_id == target.board_id && q.number == target.number
+            });
+            if !exists {
+                t.quotes.push((post_id, target.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_quotes_by_thread(&self, thread_id: Uuid) -> Result<Vec<Quote>, StorageError> {
+        let t = self.lock();
+        let mut quotes = Vec::new();
+
+        for (post_id, target) in &t.quotes {
+            let Some(post) = t.posts.iter().find(|p| p.id == *post_id) else {
+                continue;
+            };
+            if post.thread_id != thread_id && target.thread_id != thread_id {
+                continue;
+            }
+            let from_board = t.board_of_thread(post.thread_id).unwrap_or_default();
+            quotes.push(Quote {
+                from_post_id: post.id,
+                from_thread_id: post.thread_id,
+                from_board: t.board_name(from_board),
+                from_number: post.post_number,
+                to_thread_id: target.thread_id,
+                to_board: t.board_name(target.board_id),
+                to_number: target.number,
+            });
+        }
+
+        quotes.sort_by_key(|q| q.from_number);
+        Ok(quotes)
+    }
+}
+
+impl AttachmentRepository for MemoryStorage {
+    fn insert_attachments(&self, attachments: &[Attachment]) -> Result<(), StorageError> {
+        self.lock().insert_attachments(attachments)
+    }
+
+    fn get_attachments_by_thread(&self, thread_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        let mut attachments: Vec<Attachment> = self
+            .lock()
+            .attachments
+            .iter()
+            .filter(|a| a.thread_id == thread_id)
+            .cloned()
+            .collect();
+        attachments.sort_by_key(|a| (a.created_at, a.position));
+        Ok(attachments)
+    }
+
+    fn get_attachments_by_post(&self, post_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        let mut attachments: Vec<Attachment> = self
+            .lock()
+            .attachments
+            .iter()
+            .filter(|a| a.post_id == Some(post_id))
+            .cloned()
+            .collect();
+        attachments.sort_by_key(|a| a.position);
+        Ok(attachments)
+    }
+
+    fn find_by_hash(&self, board_id: Uuid, sha256: &str) -> Result<Vec<Attachment>, StorageError> {
+        let t = self.lock();
+        let mut attachments: Vec<Attachment> = t
+            .attachments
+            .iter()
+            .filter(|a| a.sha256 == sha256 && t.board_of_thread(a.thread_id) == Some(board_id))
+            .cloned()
+            .collect();
+        attachments.sort_by_key(|a| (a.created_at, a.position));
+        Ok(attachments)
+    }
+
+    fn count_by_storage_key(&self, storage_key: &str) -> Result<u64, StorageError> {
+        Ok(self
+            .lock()
+            .attachments
+            .iter()
+            .filter(|a| a.storage_key == storage_key)
+            .count() as u64)
+    }
+
+    fn get_media_keys(&self) -> Result<Vec<(String, String)>, StorageError> {
+        let mut keys: Vec<(String, String)> = self
+            .lock()
+            .attachments
+            .iter()
+            .map(|a| (a.storage_key.clone(), a.thumbnail_key.clone()))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+}
+
+impl ImageBanRepository for MemoryStorage {
+    fn insert_ban(&self, ban: &BannedImageHash) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if t.image_bans.iter().any(|b| b.id == ban.id) {
+            return Err(constraint("UNIQUE constraint failed: banned_image_hashes.id"));
+        }
+        t.image_bans.push(ban.clone());
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<BannedImageHash>, StorageError> {
+        Ok(self.lock().image_bans.iter().find(|b| b.id == id).cloned())
+    }
+
+    fn get_bans(&self) -> Result<Vec<BannedImageHash>, StorageError> {
+        let mut bans: Vec<_> = self
+            .lock()
+            .image_bans
+            .iter()
+            .filter(|b| b.lifted_at.is_none())
+            .cloned()
+            .collect();
+        bans.sort_by_key(|b| b.created_at);
+        Ok(bans)
+    }
+
+    fn lift_ban(&self, id: Uuid, lifted_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        match t.image_bans.iter_mut().find(|b| b.id == id && b.lifted_at.is_none()) {
+            Some(ban) => {
+                ban.lifted_at = Some(lifted_at);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn insert_match(&self, hit: &BannedImageMatch) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.image_bans.iter().any(|b| b.id == hit.ban_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: banned_image_matches.ban_id"));
+        }
+        if !t.boards.iter().any(|b| b.id == hit.board_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: banned_image_matches.board_id"));
+        }
+        t.image_ban_matches.push(hit.clone());
+        Ok(())
+    }
+
+    fn get_matches(&self, limit: u32) -> Result<Vec<BannedImageMatch>, StorageError> {
+        let mut matches = self.lock().image_ban_matches.clone();
+        matches.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        matches.truncate(limit as usize);
+        Ok(matches)
+    }
+}
+
+impl StaffRepository for MemoryStorage {
+    fn upsert(&self, staff: &BoardStaff) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.boards.iter().any(|b| b.id == staff.board_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: board_staff.board_id"));
+        }
+        if !t.users.iter().any(|u| u.id == staff.user_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: board_staff.user_id"));
+        }
+        match t
+            .staff
+            .iter_mut()
+            .find(|s| s.board_id == staff.board_id && s.user_id == staff.user_id)
+        {
+            Some(existing) => existing.role = staff.role,
+            None => t.staff.push(staff.clone()),
+        }
+        Ok(())
+    }
+
+    fn find(&self, board_id: Uuid, user_id: Uuid) -> Result<Option<BoardStaff>, StorageError> {
+        Ok(self
+            .lock()
+            .staff
+            .iter()
+            .find(|s| s.board_id == board_id && s.user_id == user_id)
+            .cloned())
+    }
+
+    fn get_by_board(&self, board_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let mut staff: Vec<BoardStaff> =
+            self.lock().staff.iter().filter(|s| s.board_id == board_id).cloned().collect();
+        staff.sort_by_key(|s| s.created_at);
+        Ok(staff)
+    }
+
+    fn get_by_user(&self, user_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let mut staff: Vec<BoardStaff> =
+            self.lock().staff.iter().filter(|s| s.user_id == user_id).cloned().collect();
+        staff.sort_by_key(|s| s.created_at);
+        Ok(staff)
+    }
+
+    fn delete(&self, board_id: Uuid, user_id: Uuid) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        let before = t.staff.len();
+        t.staff.retain(|s| !(s.board_id == board_id && s.user_id == user_id));
+        Ok(t.staff.len() < before)
+    }
+}
+
+impl BanRepository for MemoryStorage {
+    fn insert_ban(&self, ban: &Ban) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if let Some(board_id) = ban.board_id
+            && !t.boards.iter().any(|b| b.id == board_id)
+        {
+            return Err(constraint("FOREIGN KEY constraint failed: bans.board_id"));
+        }
+        if t.bans.iter().any(|b| b.id == ban.id) {
+            return Err(constraint("UNIQUE constraint failed: bans.id"));
+        }
+        t.bans.push(ban.clone());
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<Ban>, StorageError> {
+        Ok(self.lock().bans.iter().find(|b| b.id == id).cloned())
+    }
+
+    fn find_covering(&self, ip: IpAddr, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let key = ip_key(ip);
+        let mut bans: Vec<Ban> = self
+            .lock()
+            .bans
+            .iter()
+            .filter(|b| {
+                let (start, end) = range_keys(&b.ip_range);
+                start <= key && key <= end && b.is_active(now)
+            })
+            .cloned()
+            .collect();
+        bans.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(bans)
+    }
+
+    fn get_active(&self, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let mut bans: Vec<Ban> =
+            self.lock().bans.iter().filter(|b| b.is_active(now)).cloned().collect();
+        bans.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(bans)
+    }
+
+    fn set_expiry(
+        &self,
+        id: Uuid,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<bool, StorageError> {
+        match self.lock().bans.iter_mut().find(|b| b.id == id) {
+            Some(ban) => {
+                ban.expires_at = expires_at;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn insert_appeal(&self, appeal: &BanAppeal) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.bans.iter().any(|b| b.id == appeal.ban_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: ban_appeals.ban_id"));
+        }
+        if t.ban_appeals.iter().any(|a| a.id == appeal.id || a.ban_id == appeal.ban_id) {
+            return Err(constraint("UNIQUE constraint failed: ban_appeals.ban_id"));
+        }
+        t.ban_appeals.push(appeal.clone());
+        Ok(())
+    }
+
+    fn get_appeal(&self, id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        Ok(self.lock().ban_appeals.iter().find(|a| a.id == id).cloned())
+    }
+
+    fn get_appeal_by_ban(&self, ban_id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        Ok(self.lock().ban_appeals.iter().find(|a| a.ban_id == ban_id).cloned())
+    }
+
+    fn get_pending_appeals(&self) -> Result<Vec<BanAppeal>, StorageError> {
+        let mut appeals: Vec<BanAppeal> = self
+            .lock()
+            .ban_appeals
+            .iter()
+            .filter(|a| a.status == AppealStatus::Pending)
+            .cloned()
+            .collect();
+        appeals.sort_by_key(|a| a.created_at);
+        Ok(appeals)
+    }
+
+    fn decide_appeal(
+        &self,
+        id: Uuid,
+        status: AppealStatus,
+        decided_by: Uuid,
+        decided_at: OffsetDateTime,
+    ) -> Result<bool, StorageError> {
+        let mut t = self.lock();
+        match t.ban_appeals.iter_mut().find(|a| a.id == id && a.status == AppealStatus::Pending) {
+            Some(appeal) => {
+                appeal.status = status;
+                appeal.decided_by = Some(decided_by);
+                appeal.decided_at = Some(decided_at);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl CaptchaRepository for MemoryStorage {
+    fn insert(&self, captcha: &Captcha) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if t.captchas.iter().any(|c| c.id == captcha.id) {
+            return Err(constraint("UNIQUE constraint failed: captchas.id"));
+        }
+        t.captchas.push(captcha.clone());
+        Ok(())
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        Ok(self.lock().captchas.iter().find(|c| c.id == id).cloned())
+    }
+
+    fn take(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        let mut t = self.lock();
+        let index = t.captchas.iter().position(|c| c.id == id);
+        Ok(index.map(|index| t.captchas.remove(index)))
+    }
+
+    fn delete_expired(&self, now: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let before = t.captchas.len();
+        t.captchas.retain(|c| c.expires_at >= now);
+        Ok((before - t.captchas.len()) as u64)
+    }
+}
+
+impl ReportRepository for MemoryStorage {
+    fn insert_report(&self, report: &Report) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if !t.boards.iter().any(|b| b.id == report.board_id) {
+            return Err(constraint("FOREIGN KEY constraint failed: reports.board_id"));
+        }
+        if let Some(post_id) = report.post_id
+            && !t.posts.iter().any(|p| p.id == post_id)
+        {
+            return Err(constraint("FOREIGN KEY constraint failed: reports.post_id"));
+        }
+        if t.reports.iter().any(|r| r.id == report.id) {
+            return Err(constraint("UNIQUE constraint failed: reports.id"));
+        }
+        t.reports.push(report.clone());
+        Ok(())
+    }
+
+    fn get_open_reports(&self) -> Result<Vec<Report>, StorageError> {
+        let mut reports: Vec<Report> = self
+            .lock()
+            .reports
+            .iter()
+            .filter(|r| r.status == ReportStatus::Open)
+            .cloned()
+            .collect();
+        reports.sort_by_key(|r| r.created_at);
+        Ok(reports)
+    }
+
+    fn get_open_by_post(&self, post_id: Uuid) -> Result<Vec<Report>, StorageError> {
+        let mut reports: Vec<Report> = self
+            .lock()
+            .reports
+            .iter()
+            .filter(|r| r.post_id == Some(post_id) && r.status == ReportStatus::Open)
+            .cloned()
+            .collect();
+        reports.sort_by_key(|r| r.created_at);
+        Ok(reports)
+    }
+
+    fn resolve_reports(
+        &self,
+        post_id: Uuid,
+        status: ReportStatus,
+        resolved_by: Uuid,
+        resolved_at: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let mut t = self.lock();
+        let mut resolved = 0;
+        for report in t
+            .reports
+            .iter_mut()
+            .filter(|r| r.post_id == Some(post_id) && r.status == ReportStatus::Open)
+        {
+            report.status = status;
+            report.resolved_by = Some(resolved_by);
+            report.resolved_at = Some(resolved_at);
+            resolved += 1;
+        }
+        Ok(resolved)
+    }
+
+    fn get_resolved_reports(&self, limit: u32) -> Result<Vec<Report>, StorageError> {
+        let mut reports: Vec<Report> = self
+            .lock()
+            .reports
+            .iter()
+            .filter(|r| r.status != ReportStatus::Open)
+            .cloned()
+            .collect();
+        reports.sort_by_key(|r| std::cmp::Reverse((r.resolved_at, r.created_at)));
+        reports.truncate(limit as usize);
+        Ok(reports)
+    }
+}
+
+impl ModActionRepository for MemoryStorage {
+    fn insert_action(&self, action: &ModAction) -> Result<(), StorageError> {
+        let mut t = self.lock();
+        if t.mod_actions.iter().any(|a| a.id == action.id) {
+            return Err(constraint("UNIQUE constraint failed: mod_actions.id"));
+        }
+        t.mod_actions.push(action.clone());
+        Ok(())
+    }
+
+    fn get_actions(&self, filter: &ModActionFilter) -> Result<Vec<ModAction>, StorageError> {
+        let mut actions: Vec<ModAction> = self
+            .lock()
+            .mod_actions
+            .iter()
+            .filter(|a| filter.board_id.is_none_or(|id| a.board_id == Some(id)))
+            .filter(|a| {
+                filter.board_ids.as_ref().is_none_or(|ids| a.board_id.is_some_and(|id| ids.contains(&id)))
+            })
+            .filter(|a| filter.actor_id.is_none_or(|id| a.actor_id == Some(id)))
+            .filter(|a| filter.actions.is_empty() || filter.actions.contains(&a.action))
+            .filter(|a| filter.before.is_none_or(|before| a.created_at < before))
+            .cloned()
+            .collect();
+        actions.sort_by_key(|a| std::cmp::Reverse(a.created_at));
+        actions.truncate(filter.limit as usize);
+        Ok(actions)
+    }
+}
@@ -0,0 +1,182 @@
+//! Schema Migrations
+//!
+//! Developer Notes:
+//! - Applies the ordered migrations declared in `schema::MIGRATIONS`.
+//! - The applied version is tracked in the `schema_version` table.
+//! - All pending migrations run inside a single transaction.
+//! - A database newer than this binary is refused, never downgraded.
+//!
+//! TODO:
+//! - Add a dry-run mode that prints the SQL of pending migrations.
+//!
+//! End Notes:
+//! Never edit a released migration; append a new one instead.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use time::OffsetDateTime;
+
+use crate::schema::MIGRATIONS;
+use crate::StorageError;
+
+/// A single numbered schema change.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Strictly increasing version number, starting at 1.
+    pub version: u32,
+    /// Short human readable description.
+    pub name: &'static str,
+    /// DDL executed as one batch.
+    pub sql: &'static str,
+}
+
+/// Highest schema version this binary knows how to produce.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn ensure_version_table(conn: &Connection) -> Result<(), StorageError> {
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Current schema version of the database (0 if nothing was applied yet).
+pub fn current_version(conn: &Connection) -> Result<u32, StorageError> {
+    ensure_version_table(conn)?;
+
+    let version: Option<u32> = conn
+        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .flatten();
+
+    Ok(version.unwrap_or(0))
+}
+
+/// List migrations that have not been applied to this database yet.
+pub fn pending_migrations(
+    conn: &Connection,
+) -> Result<Vec<&'static Migration>, StorageError> {
+    let current = current_version(conn)?;
+    check_not_newer(current)?;
+
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+fn check_not_newer(current: u32) -> Result<(), StorageError> {
+    let supported = latest_version();
+    if current > supported {
+        return Err(StorageError::SchemaTooNew {
+            database: current,
+            supported,
+        });
+    }
+    Ok(())
+}
+
+/// Apply every pending migration in one transaction.
+///
+/// Returns the number of migrations applied.
+pub fn run_migrations(conn: &Connection) -> Result<usize, StorageError> {
+    let pending = pending_migrations(conn)?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    for migration in &pending {
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at)
+             VALUES (?1, ?2, ?3)",
+            params![
+                migration.version,
+                migration.name,
+                OffsetDateTime::now_utc()
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .unwrap()
+            ],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+/// Bring the database up to the latest schema at startup.
+pub fn init_database(conn: &Connection) -> Result<usize, StorageError> {
+    run_migrations(conn)
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::create_connection;
+
+    #[test]
+    fn migrations_apply_once() {
+        let conn = create_connection(":memory:").unwrap();
+
+        assert_eq!(run_migrations(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(pending_migrations(&conn).unwrap().is_empty());
+        assert_eq!(run_migrations(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let conn = create_connection(":memory:").unwrap();
+        run_migrations(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO schema_version (version, name, applied_at)
+             VALUES (?1, 'from the future', '')",
+            params![latest_version() + 1],
+        )
+        .unwrap();
+
+        assert!(matches!(
+            run_migrations(&conn),
+            Err(StorageError::SchemaTooNew { .. })
+        ));
+    }
+
+    #[test]
+    fn legacy_database_is_adopted() {
+        let conn = create_connection(":memory:").unwrap();
+        conn.execute_batch(MIGRATIONS[0].sql).unwrap();
+        conn.execute_batch(
+            "INSERT INTO boards VALUES ('b', 'g', '', '2024-01-01T00:00:00Z');
+             INSERT INTO threads VALUES ('t', 'b', 'op', '2024-01-01T00:00:01Z');
+             INSERT INTO posts VALUES ('p', 't', 'reply', '2024-01-01T00:00:02Z');",
+        )
+        .unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        run_migrations(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+
+        let numbers: (i64, i64, i64) = conn
+            .query_row(
+                "SELECT t.op_number, p.post_number, b.last_post_number
+                 FROM threads t, posts p, boards b",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(numbers, (1, 2, 2));
+    }
+}
@@ -0,0 +1,150 @@
+//! Mod Action Repository
+//!
+//! Developer Notes:
+//! - Persists the moderation log.
+//! - The log is append-only: the trait has no update or delete, and the
+//!   schema refuses both with triggers. Rows carry no foreign keys, so
+//!   deleting a user or board never rewrites them; the actor's name is
+//!   copied in for the same reason.
+//! - `ModActionRepository` is the backend-neutral interface.
+//! - `SqliteModActionRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! What gets recorded, and who may read it, is decided in `services`.
+
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{ModAction, ModActionKind};
+use crate::columns::{format_time, get_opt_uuid, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Which log entries to read; unset fields match every entry.
+#[derive(Debug, Clone, Default)]
+pub struct ModActionFilter {
+    pub board_id: Option<Uuid>,
+    /// Only entries on these boards, leaving out site-wide ones.
+    pub board_ids: Option<Vec<Uuid>>,
+    pub actor_id: Option<Uuid>,
+    /// Empty matches every kind.
+    pub actions: Vec<ModActionKind>,
+    /// Only entries older than this, for paging.
+    pub before: Option<OffsetDateTime>,
+    pub limit: u32,
+}
+
+/// Persistence operations for the moderation log.
+pub trait ModActionRepository: Send + Sync {
+    /// Append an entry.
+    fn insert_action(&self, action: &ModAction) -> Result<(), StorageError>;
+
+    /// Entries matching `filter`, newest first.
+    fn get_actions(&self, filter: &ModActionFilter) -> Result<Vec<ModAction>, StorageError>;
+}
+
+/// SQLite implementation of `ModActionRepository`.
+#[derive(Clone)]
+pub struct SqliteModActionRepository {
+    pool: DbPool,
+}
+
+impl SqliteModActionRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `action_from_row`, in order.
+const COLUMNS: &str = "id, actor_id, actor_name, action, board_id, target, reason, \
+                       before_state, after_state, created_at";
+
+fn action_from_row(row: &Row<'_>) -> rusqlite::Result<ModAction> {
+    let action: String = row.get(3)?;
+
+    Ok(ModAction {
+        id: get_uuid(row, 0)?,
+        actor_id: get_opt_uuid(row, 1)?,
+        actor_name: row.get(2)?,
+        action: action.parse().map_err(|_| {
+            rusqlite::Error::FromSqlConversionFailure(
+                3,
+                rusqlite::types::Type::Text,
+                format!("unknown mod action `{action}`").into(),
+            )
+        })?,
+        board_id: get_opt_uuid(row, 4)?,
+        target: row.get(5)?,
+        reason: row.get(6)?,
+        before: row.get(7)?,
+        after: row.get(8)?,
+        created_at: get_time(row, 9)?,
+    })
+}
+
+impl ModActionRepository for SqliteModActionRepository {
+    fn insert_action(&self, action: &ModAction) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            &format!("INSERT INTO mod_actions ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"),
+            params![
+                action.id.to_string(),
+                action.actor_id.map(|id| id.to_string()),
+                action.actor_name,
+                action.action.to_string(),
+                action.board_id.map(|id| id.to_string()),
+                action.target,
+                action.reason,
+                action.before,
+                action.after,
+                format_time(&action.created_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_actions(&self, filter: &ModActionFilter) -> Result<Vec<ModAction>, StorageError> {
+        let mut sql = format!("SELECT {COLUMNS} FROM mod_actions WHERE 1 = 1");
+        let mut args: Vec<Value> = Vec::new();
+        let mut arg = |sql: &mut String, clause: &str, value: Value| {
+            args.push(value);
+            sql.push_str(&clause.replace('?', &format!("?{}", args.len())));
+        };
+
+        if let Some(board_id) = filter.board_id {
+            arg(&mut sql, " AND board_id = ?", Value::Text(board_id.to_string()));
+        }
+        if let Some(board_ids) = &filter.board_ids {
+            if board_ids.is_empty() {
+                sql.push_str(" AND 0 = 1");
+            }
+            for (i, board_id) in board_ids.iter().enumerate() {
+                let clause = if i == 0 { " AND board_id IN (?" } else { ", ?" };
+                arg(&mut sql, clause, Value::Text(board_id.to_string()));
+            }
+            if !board_ids.is_empty() {
+                sql.push(')');
+            }
+        }
+        if let Some(actor_id) = filter.actor_id {
+            arg(&mut sql, " AND actor_id = ?", Value::Text(actor_id.to_string()));
+        }
+        if let Some(before) = filter.before {
+            arg(&mut sql, " AND created_at < ?", Value::Text(format_time(&before)));
+        }
+        for (i, action) in filter.actions.iter().enumerate() {
+            let clause = if i == 0 { " AND action IN (?" } else { ", ?" };
+            arg(&mut sql, clause, Value::Text(action.to_string()));
+        }
+        if !filter.actions.is_empty() {
+            sql.push(')');
+        }
+        arg(&mut sql, " ORDER BY created_at DESC LIMIT ?", Value::Integer(filter.limit.into()));
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args), action_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
@@ -1,57 +1,252 @@
-use rusqlite::{params, Connection, Result};
-use uuid::Uuid;
+//! Post Repository
+//!
+//! Developer Notes:
+//! - Handles persistence for `Post` data.
+//! - `PostRepository` is the backend-neutral interface.
+//! - `SqlitePostRepository` is the SQLite implementation.
+//! - Inserting a post allocates its number from the board counter, so
+//!   replies and thread OPs share one "No." sequence per board.
+//!
+//! End Notes:
+//! No business logic here.
+
+use rusqlite::{params, OptionalExtension, Row, TransactionBehavior};
 use time::OffsetDateTime;
+use uuid::Uuid;
 
 use models::Post;
+use crate::board_repository::next_post_number;
+use crate::columns::{format_time, get_poster, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for posts.
+pub trait PostRepository: Send + Sync {
+    /// Insert post, returning its newly allocated post number.
+    ///
+    /// `post.post_number` is ignored.
+    fn insert_post(&self, post: &Post) -> Result<i64, StorageError>;
+
+    /// Find a post by its number within a board.
+    fn get_post_by_number(
+        &self,
+        board_id: Uuid,
+        post_number: i64,
+    ) -> Result<Option<Post>, StorageError>;
+
+    /// Get posts by thread, oldest first.
+    fn get_posts_by_thread(&self, thread_id: Uuid) -> Result<Vec<Post>, StorageError>;
+
+    /// How many replies a thread has.
+    fn count_posts_by_thread(&self, thread_id: Uuid) -> Result<u64, StorageError>;
+
+    /// Replace the cached HTML rendering of a post.
+    fn set_content_html(&self, post_id: Uuid, html: &str) -> Result<(), StorageError>;
+
+    /// Delete a post together with its attachments and outgoing quotes.
+    ///
+    /// Returns whether the post existed.
+    fn delete_post(&self, post_id: Uuid) -> Result<bool, StorageError>;
+
+    /// Posts made from the address with this hash, newest first.
+    fn get_posts_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Post>, StorageError>;
+
+    /// Posts made on a board from the address with this hash since
+    /// `since`, newest first.
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Post>, StorageError>;
 
-/// Insert post.
-pub fn insert_post(conn: &Connection, post: &Post) -> Result<()> {
-    conn.execute(
-        r#"
-        INSERT INTO posts (id, thread_id, content, created_at)
-        VALUES (?1, ?2, ?3, ?4)
-        "#,
-        params![
-            post.id.to_string(),
-            post.thread_id.to_string(),
-            post.content,
-            post.created_at.to_string()
-        ],
-    )?;
-    Ok(())
+    /// Forget the raw address of posts created before `created_before`,
+    /// keeping its hash; returns how many were cleared.
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError>;
 }
 
-/// Get posts by thread.
-pub fn get_posts_by_thread(
-    conn: &Connection,
-    thread_id: Uuid,
-) -> Result<Vec<Post>> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT id, thread_id, content, created_at
-        FROM posts
-        WHERE thread_id = ?1
-        ORDER BY created_at ASC
-        "#,
-    )?;
-
-    let rows = stmt.query_map(params![thread_id.to_string()], |row| {
-        Ok(Post {
-            id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-            thread_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-            content: row.get(2)?,
-            created_at: OffsetDateTime::parse(
-                &row.get::<_, String>(3)?,
-                &time::format_description::well_known::Rfc3339,
+/// SQLite implementation of `PostRepository`.
+#[derive(Clone)]
+pub struct SqlitePostRepository {
+    pool: DbPool,
+}
+
+impl SqlitePostRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn post_from_row(row: &Row<'_>) -> rusqlite::Result<Post> {
+    Ok(Post {
+        id: get_uuid(row, 0)?,
+        thread_id: get_uuid(row, 1)?,
+        post_number: row.get(2)?,
+        content: row.get(3)?,
+        content_html: row.get(4)?,
+        created_at: get_time(row, 5)?,
+        poster: get_poster(row, 6)?,
+    })
+}
+
+impl PostRepository for SqlitePostRepository {
+    fn insert_post(&self, post: &Post) -> Result<i64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let board_id = tx
+            .query_row(
+                "SELECT board_id FROM threads WHERE id = ?1",
+                params![post.thread_id.to_string()],
+                |row| get_uuid(row, 0),
             )
-            .unwrap(),
-        })
-    })?;
+            .optional()?
+            .ok_or_else(|| {
+                StorageError::Constraint("FOREIGN KEY constraint failed: posts.thread_id".into())
+            })?;
+
+        let post_number = next_post_number(&tx, board_id)?;
 
-    let mut posts = Vec::new();
-    for p in rows {
-        posts.push(p?);
+        tx.execute(
+            r#"
+            INSERT INTO posts (id, thread_id, board_id, post_number, content, content_html, created_at,
+                               ip_hash, ip_address, user_agent_hash)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            params![
+                post.id.to_string(),
+                post.thread_id.to_string(),
+                board_id.to_string(),
+                post_number,
+                post.content,
+                post.content_html,
+                format_time(&post.created_at),
+                post.poster.as_ref().map(|p| &p.ip_hash),
+                post.poster.as_ref().and_then(|p| p.ip).map(|ip| ip.to_string()),
+                post.poster.as_ref().and_then(|p| p.user_agent_hash.as_ref())
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(post_number)
     }
 
-    Ok(posts)
-}
\ No newline at end of file
+    fn get_post_by_number(
+        &self,
+        board_id: Uuid,
+        post_number: i64,
+    ) -> Result<Option<Post>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                r#"
+                SELECT id, thread_id, post_number, content, content_html, created_at,
+                       ip_hash, ip_address, user_agent_hash
+                FROM posts
+                WHERE board_id = ?1 AND post_number = ?2
+                "#,
+                params![board_id.to_string(), post_number],
+                post_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_posts_by_thread(&self, thread_id: Uuid) -> Result<Vec<Post>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE thread_id = ?1
+            ORDER BY created_at ASC
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![thread_id.to_string()], post_from_row)?;
+
+        let mut posts = Vec::new();
+        for p in rows {
+            posts.push(p?);
+        }
+
+        Ok(posts)
+    }
+
+    fn count_posts_by_thread(&self, thread_id: Uuid) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM posts WHERE thread_id = ?1",
+            params![thread_id.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(count as u64)
+    }
+
+    fn set_content_html(&self, post_id: Uuid, html: &str) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE posts SET content_html = ?2 WHERE id = ?1",
+            params![post_id.to_string(), html],
+        )?;
+        Ok(())
+    }
+
+    fn delete_post(&self, post_id: Uuid) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM posts WHERE id = ?1",
+            params![post_id.to_string()],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn get_posts_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Post>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE ip_hash = ?1
+            ORDER BY created_at DESC
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![ip_hash], post_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Post>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE board_id = ?1 AND ip_hash = ?2 AND created_at >= ?3
+            ORDER BY created_at DESC
+            "#,
+        )?;
+
+        let rows = stmt.query_map(
+            params![board_id.to_string(), ip_hash, format_time(&since)],
+            post_from_row,
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let cleared = conn.execute(
+            "UPDATE posts SET ip_address = NULL
+             WHERE ip_address IS NOT NULL AND created_at < ?1",
+            params![format_time(&created_before)],
+        )?;
+        Ok(cleared as u64)
+    }
+}
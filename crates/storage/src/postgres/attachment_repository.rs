@@ -0,0 +1,167 @@
+//! PostgreSQL Attachment Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `AttachmentRepository`.
+//! - Pixel sizes are `INTEGER` columns, converted to and from `u32`.
+//!
+//! End Notes:
+//! Mirrors `crate::attachment_repository`.
+
+use postgres::types::ToSql;
+use postgres::{GenericClient, Row};
+use uuid::Uuid;
+
+use models::Attachment;
+use crate::attachment_repository::AttachmentRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// Columns read by `attachment_from_row`, in order.
+pub(super) const COLUMNS: &str = "a.id, a.thread_id, a.post_id, a.position, a.file_name, a.content_type,
+    a.size_bytes, a.sha256, a.width, a.height, a.storage_key, a.thumbnail_key,
+    a.thumbnail_width, a.thumbnail_height, a.metadata_stripped, a.duration_ms, a.created_at";
+
+/// PostgreSQL implementation of `AttachmentRepository`.
+#[derive(Clone)]
+pub struct PgAttachmentRepository {
+    pool: PgPool,
+}
+
+impl PgAttachmentRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    fn query(
+        &self,
+        sql: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Attachment>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(sql, params)?;
+        Ok(rows.iter().map(attachment_from_row).collect())
+    }
+}
+
+fn get_u32(row: &Row, idx: usize) -> u32 {
+    row.get::<_, i32>(idx) as u32
+}
+
+pub(super) fn attachment_from_row(row: &Row) -> Attachment {
+    Attachment {
+        id: row.get(0),
+        thread_id: row.get(1),
+        post_id: row.get(2),
+        position: get_u32(row, 3),
+        file_name: row.get(4),
+        content_type: row.get(5),
+        size_bytes: row.get(6),
+        sha256: row.get(7),
+        width: get_u32(row, 8),
+        height: get_u32(row, 9),
+        storage_key: row.get(10),
+        thumbnail_key: row.get(11),
+        thumbnail_width: get_u32(row, 12),
+        thumbnail_height: get_u32(row, 13),
+        metadata_stripped: row.get(14),
+        duration_ms: row.get::<_, Option<i32>>(15).map(|ms| ms as u32),
+        created_at: row.get(16),
+    }
+}
+
+/// Insert attachments inside the caller's transaction.
+pub(super) fn insert_in(client: &mut impl GenericClient, attachments: &[Attachment]) -> Result<(), StorageError> {
+    for a in attachments {
+        client.execute(
+            r#"
+            INSERT INTO attachments (
+                id, thread_id, post_id, position, file_name, content_type,
+                size_bytes, sha256, width, height, storage_key, thumbnail_key,
+                thumbnail_width, thumbnail_height, metadata_stripped, duration_ms, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+            "#,
+            &[
+                &a.id,
+                &a.thread_id,
+                &a.post_id,
+                &(a.position as i32),
+                &a.file_name,
+                &a.content_type,
+                &a.size_bytes,
+                &a.sha256,
+                &(a.width as i32),
+                &(a.height as i32),
+                &a.storage_key,
+                &a.thumbnail_key,
+                &(a.thumbnail_width as i32),
+                &(a.thumbnail_height as i32),
+                &a.metadata_stripped,
+                &a.duration_ms.map(|ms| ms as i32),
+                &a.created_at,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+impl AttachmentRepository for PgAttachmentRepository {
+    fn insert_attachments(&self, attachments: &[Attachment]) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        insert_in(&mut tx, attachments)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_attachments_by_thread(&self, thread_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 WHERE a.thread_id = $1
+                 ORDER BY a.created_at ASC, a.position ASC"
+            ),
+            &[&thread_id],
+        )
+    }
+
+    fn get_attachments_by_post(&self, post_id: Uuid) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 WHERE a.post_id = $1
+                 ORDER BY a.position ASC"
+            ),
+            &[&post_id],
+        )
+    }
+
+    fn find_by_hash(&self, board_id: Uuid, sha256: &str) -> Result<Vec<Attachment>, StorageError> {
+        self.query(
+            &format!(
+                "SELECT {COLUMNS} FROM attachments a
+                 JOIN threads t ON t.id = a.thread_id
+                 WHERE t.board_id = $1 AND a.sha256 = $2
+                 ORDER BY a.created_at ASC, a.position ASC"
+            ),
+            &[&board_id, &sha256],
+        )
+    }
+
+    fn count_by_storage_key(&self, storage_key: &str) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_one(
+            "SELECT COUNT(*) FROM attachments WHERE storage_key = $1",
+            &[&storage_key],
+        )?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    fn get_media_keys(&self) -> Result<Vec<(String, String)>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query("SELECT DISTINCT storage_key, thumbnail_key FROM attachments", &[])?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+}
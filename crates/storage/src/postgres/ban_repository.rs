@@ -0,0 +1,216 @@
+//! PostgreSQL Ban Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `BanRepository`.
+//! - Range keys are BYTEA, which compares bytewise like SQLite BLOBs.
+//!
+//! End Notes:
+//! Mirrors `crate::ban_repository`.
+
+use std::net::IpAddr;
+
+use postgres::Row;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{AppealStatus, Ban, BanAppeal};
+use crate::ban_repository::{ip_key, range_keys, BanRepository};
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `BanRepository`.
+#[derive(Clone)]
+pub struct PgBanRepository {
+    pool: PgPool,
+}
+
+impl PgBanRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+const BAN_COLUMNS: &str =
+    "id, ip_range, board_id, reason, staff_note, created_by, created_at, expires_at";
+
+const APPEAL_COLUMNS: &str = "id, ban_id, message, status, created_at, decided_by, decided_at";
+
+fn ban_from_row(row: &Row) -> Result<Ban, StorageError> {
+    let range: String = row.get(1);
+    let ip_range = range
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("bad ip range `{range}`")))?;
+
+    Ok(Ban {
+        id: row.get(0),
+        ip_range,
+        board_id: row.get(2),
+        reason: row.get(3),
+        staff_note: row.get(4),
+        created_by: row.get(5),
+        created_at: row.get(6),
+        expires_at: row.get(7),
+    })
+}
+
+fn appeal_from_row(row: &Row) -> Result<BanAppeal, StorageError> {
+    let status_str: String = row.get(3);
+    let status = status_str
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown appeal status `{status_str}`")))?;
+
+    Ok(BanAppeal {
+        id: row.get(0),
+        ban_id: row.get(1),
+        message: row.get(2),
+        status,
+        created_at: row.get(4),
+        decided_by: row.get(5),
+        decided_at: row.get(6),
+    })
+}
+
+impl BanRepository for PgBanRepository {
+    fn insert_ban(&self, ban: &Ban) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        let (range_start, range_end) = range_keys(&ban.ip_range);
+        conn.execute(
+            "INSERT INTO bans (id, ip_range, range_start, range_end, board_id, reason, staff_note,
+                               created_by, created_at, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+            &[
+                &ban.id,
+                &ban.ip_range.to_string(),
+                &range_start,
+                &range_end,
+                &ban.board_id,
+                &ban.reason,
+                &ban.staff_note,
+                &ban.created_by,
+                &ban.created_at,
+                &ban.expires_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<Ban>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(&format!("SELECT {BAN_COLUMNS} FROM bans WHERE id = $1"), &[&id])?
+            .as_ref()
+            .map(ban_from_row)
+            .transpose()
+    }
+
+    fn find_covering(&self, ip: IpAddr, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!(
+                "SELECT {BAN_COLUMNS} FROM bans
+                 WHERE range_start <= $1 AND range_end >= $1
+                   AND (expires_at IS NULL OR expires_at > $2)
+                 ORDER BY created_at DESC"
+            ),
+            &[&ip_key(ip), &now],
+        )?
+        .iter()
+        .map(ban_from_row)
+        .collect()
+    }
+
+    fn get_active(&self, now: OffsetDateTime) -> Result<Vec<Ban>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!(
+                "SELECT {BAN_COLUMNS} FROM bans
+                 WHERE expires_at IS NULL OR expires_at > $1
+                 ORDER BY created_at DESC"
+            ),
+            &[&now],
+        )?
+        .iter()
+        .map(ban_from_row)
+        .collect()
+    }
+
+    fn set_expiry(
+        &self,
+        id: Uuid,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let updated =
+            conn.execute("UPDATE bans SET expires_at = $2 WHERE id = $1", &[&id, &expires_at])?;
+        Ok(updated > 0)
+    }
+
+    fn insert_appeal(&self, appeal: &BanAppeal) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO ban_appeals (id, ban_id, message, status, created_at, decided_by, decided_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &appeal.id,
+                &appeal.ban_id,
+                &appeal.message,
+                &appeal.status.to_string(),
+                &appeal.created_at,
+                &appeal.decided_by,
+                &appeal.decided_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_appeal(&self, id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(&format!("SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE id = $1"), &[&id])?
+            .as_ref()
+            .map(appeal_from_row)
+            .transpose()
+    }
+
+    fn get_appeal_by_ban(&self, ban_id: Uuid) -> Result<Option<BanAppeal>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            &format!("SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE ban_id = $1"),
+            &[&ban_id],
+        )?
+        .as_ref()
+        .map(appeal_from_row)
+        .transpose()
+    }
+
+    fn get_pending_appeals(&self) -> Result<Vec<BanAppeal>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!("SELECT {APPEAL_COLUMNS} FROM ban_appeals WHERE status = $1 ORDER BY created_at"),
+            &[&AppealStatus::Pending.to_string()],
+        )?
+        .iter()
+        .map(appeal_from_row)
+        .collect()
+    }
+
+    fn decide_appeal(
+        &self,
+        id: Uuid,
+        status: AppealStatus,
+        decided_by: Uuid,
+        decided_at: OffsetDateTime,
+    ) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE ban_appeals SET status = $2, decided_by = $3, decided_at = $4
+             WHERE id = $1 AND status = $5",
+            &[
+                &id,
+                &status.to_string(),
+                &decided_by,
+                &decided_at,
+                &AppealStatus::Pending.to_string(),
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+}
@@ -0,0 +1,235 @@
+//! PostgreSQL Board Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `BoardRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::board_repository`.
+
+use postgres::{GenericClient, Row};
+use uuid::Uuid;
+
+use models::{AvPolicy, Board, CaptchaMode, Cooldowns, DuplicatePolicy, PrunePolicy};
+use crate::board_repository::BoardRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `BoardRepository`.
+#[derive(Clone)]
+pub struct PgBoardRepository {
+    pool: PgPool,
+}
+
+impl PgBoardRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Allocate the next post number of a board inside the caller's transaction.
+///
+/// The `UPDATE` row lock serializes concurrent allocations, including
+/// those from other processes.
+pub(super) fn next_post_number(
+    client: &mut impl GenericClient,
+    board_id: Uuid,
+) -> Result<i64, StorageError> {
+    client
+        .query_opt(
+            "UPDATE boards SET last_post_number = last_post_number + 1
+             WHERE id = $1
+             RETURNING last_post_number",
+            &[&board_id],
+        )?
+        .map(|row| row.get(0))
+        .ok_or_else(|| StorageError::Constraint("FOREIGN KEY constraint failed: board".into()))
+}
+
+/// Columns read by `board_from_row`, in order.
+const COLUMNS: &str = "id, name, description, duplicate_policy, av_max_duration, av_codecs, \
+                       created_at, thread_cooldown, reply_cooldown, repost_cooldown, captcha_mode, \
+                       public_mod_log, bump_limit, max_threads, prune_mode";
+
+/// A cooldown column: `INTEGER` in PostgreSQL is `i32`.
+fn get_secs(row: &Row, idx: usize) -> Option<u32> {
+    row.get::<_, Option<i32>>(idx).map(|secs| secs as u32)
+}
+
+fn secs_param(secs: Option<u32>) -> Option<i32> {
+    secs.map(|secs| secs.min(i32::MAX as u32) as i32)
+}
+
+fn board_from_row(row: &Row) -> Result<Board, StorageError> {
+    let policy: String = row.get(3);
+    let duplicate_policy = policy
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown duplicate policy `{policy}`")))?;
+    let mode: String = row.get(10);
+    let captcha_mode = mode
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown captcha mode `{mode}`")))?;
+    let prune_mode: String = row.get(14);
+    let prune_mode = prune_mode
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown prune mode `{prune_mode}`")))?;
+
+    Ok(Board {
+        id: row.get(0),
+        name: row.get(1),
+        description: row.get(2),
+        duplicate_policy,
+        av_policy: AvPolicy {
+            max_duration_secs: row.get::<_, i32>(4) as u32,
+            codecs: AvPolicy::parse_codec_list(row.get(5)),
+        },
+        cooldowns: Cooldowns {
+            thread_secs: get_secs(row, 7),
+            reply_secs: get_secs(row, 8),
+            repost_secs: get_secs(row, 9),
+        },
+        captcha_mode,
+        public_mod_log: row.get(11),
+        bump_limit: row.get::<_, i32>(12) as u32,
+        prune_policy: PrunePolicy {
+            max_threads: row.get::<_, i32>(13) as u32,
+            mode: prune_mode,
+        },
+        created_at: row.get(6),
+    })
+}
+
+impl BoardRepository for PgBoardRepository {
+    fn insert_board(&self, board: &Board) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO boards (
+                 id, name, description, duplicate_policy, av_max_duration, av_codecs, created_at,
+                 thread_cooldown, reply_cooldown, repost_cooldown, captcha_mode, public_mod_log,
+                 bump_limit, max_threads, prune_mode
+             )
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+            &[
+                &board.id,
+                &board.name,
+                &board.description,
+                &board.duplicate_policy.to_string(),
+                &(board.av_policy.max_duration_secs as i32),
+                &board.av_policy.codec_list(),
+                &board.created_at,
+                &secs_param(board.cooldowns.thread_secs),
+                &secs_param(board.cooldowns.reply_secs),
+                &secs_param(board.cooldowns.repost_secs),
+                &board.captcha_mode.to_string(),
+                &board.public_mod_log,
+                &(board.bump_limit.min(i32::MAX as u32) as i32),
+                &(board.prune_policy.max_threads.min(i32::MAX as u32) as i32),
+                &board.prune_policy.mode.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_all(&self) -> Result<Vec<Board>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            &format!("SELECT {COLUMNS} FROM boards ORDER BY name"),
+            &[],
+        )?;
+        rows.iter().map(board_from_row).collect()
+    }
+
+    fn get_board(&self, id: Uuid) -> Result<Option<Board>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            &format!("SELECT {COLUMNS} FROM boards WHERE id = $1"),
+            &[&id],
+        )?;
+        row.as_ref().map(board_from_row).transpose()
+    }
+
+    fn get_board_by_name(&self, name: &str) -> Result<Option<Board>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            &format!("SELECT {COLUMNS} FROM boards WHERE name = $1"),
+            &[&name],
+        )?;
+        row.as_ref().map(board_from_row).transpose()
+    }
+
+    fn set_duplicate_policy(
+        &self,
+        board_id: Uuid,
+        policy: DuplicatePolicy,
+    ) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET duplicate_policy = $2 WHERE id = $1",
+            &[&board_id, &policy.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn set_av_policy(&self, board_id: Uuid, policy: &AvPolicy) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET av_max_duration = $2, av_codecs = $3 WHERE id = $1",
+            &[&board_id, &(policy.max_duration_secs as i32), &policy.codec_list()],
+        )?;
+        Ok(())
+    }
+
+    fn set_cooldowns(&self, board_id: Uuid, cooldowns: &Cooldowns) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET thread_cooldown = $2, reply_cooldown = $3, repost_cooldown = $4
+             WHERE id = $1",
+            &[
+                &board_id,
+                &secs_param(cooldowns.thread_secs),
+                &secs_param(cooldowns.reply_secs),
+                &secs_param(cooldowns.repost_secs),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn set_captcha_mode(&self, board_id: Uuid, mode: CaptchaMode) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET captcha_mode = $2 WHERE id = $1",
+            &[&board_id, &mode.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn set_public_mod_log(&self, board_id: Uuid, public: bool) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET public_mod_log = $2 WHERE id = $1",
+            &[&board_id, &public],
+        )?;
+        Ok(())
+    }
+
+    fn set_bump_limit(&self, board_id: Uuid, limit: u32) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET bump_limit = $2 WHERE id = $1",
+            &[&board_id, &(limit.min(i32::MAX as u32) as i32)],
+        )?;
+        Ok(())
+    }
+
+    fn set_prune_policy(&self, board_id: Uuid, policy: &PrunePolicy) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE boards SET max_threads = $2, prune_mode = $3 WHERE id = $1",
+            &[
+                &board_id,
+                &(policy.max_threads.min(i32::MAX as u32) as i32),
+                &policy.mode.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+}
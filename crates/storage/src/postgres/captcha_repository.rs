@@ -0,0 +1,70 @@
+//! PostgreSQL CAPTCHA Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `CaptchaRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::captcha_repository`.
+
+use postgres::Row;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::Captcha;
+use crate::captcha_repository::CaptchaRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `CaptchaRepository`.
+#[derive(Clone)]
+pub struct PgCaptchaRepository {
+    pool: PgPool,
+}
+
+impl PgCaptchaRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn captcha_from_row(row: &Row) -> Captcha {
+    Captcha {
+        id: row.get(0),
+        answer: row.get(1),
+        expires_at: row.get(2),
+    }
+}
+
+impl CaptchaRepository for PgCaptchaRepository {
+    fn insert(&self, captcha: &Captcha) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO captchas (id, answer, expires_at) VALUES ($1, $2, $3)",
+            &[&captcha.id, &captcha.answer, &captcha.expires_at],
+        )?;
+        Ok(())
+    }
+
+    fn get(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            "SELECT id, answer, expires_at FROM captchas WHERE id = $1",
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(captcha_from_row))
+    }
+
+    fn take(&self, id: Uuid) -> Result<Option<Captcha>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            "DELETE FROM captchas WHERE id = $1 RETURNING id, answer, expires_at",
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(captcha_from_row))
+    }
+
+    fn delete_expired(&self, now: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute("DELETE FROM captchas WHERE expires_at < $1", &[&now])?)
+    }
+}
@@ -0,0 +1,141 @@
+//! PostgreSQL Image Ban Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `ImageBanRepository`.
+//! - Hashes are `BIGINT` columns holding the `u64` bit pattern.
+//!
+//! End Notes:
+//! Mirrors `crate::image_ban_repository`.
+
+use postgres::Row;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{BannedImageHash, BannedImageMatch};
+use crate::image_ban_repository::ImageBanRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `ImageBanRepository`.
+#[derive(Clone)]
+pub struct PgImageBanRepository {
+    pool: PgPool,
+}
+
+impl PgImageBanRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn ban_from_row(row: &Row) -> BannedImageHash {
+    BannedImageHash {
+        id: row.get(0),
+        phash: row.get::<_, i64>(1) as u64,
+        dhash: row.get::<_, i64>(2) as u64,
+        reason: row.get(3),
+        created_at: row.get(4),
+        lifted_at: row.get(5),
+    }
+}
+
+fn match_from_row(row: &Row) -> BannedImageMatch {
+    BannedImageMatch {
+        id: row.get(0),
+        ban_id: row.get(1),
+        board_id: row.get(2),
+        thread_id: row.get(3),
+        file_name: row.get(4),
+        sha256: row.get(5),
+        distance: row.get::<_, i32>(6) as u32,
+        created_at: row.get(7),
+    }
+}
+
+impl ImageBanRepository for PgImageBanRepository {
+    fn insert_ban(&self, ban: &BannedImageHash) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO banned_image_hashes (id, phash, dhash, reason, created_at, lifted_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &ban.id,
+                &(ban.phash as i64),
+                &(ban.dhash as i64),
+                &ban.reason,
+                &ban.created_at,
+                &ban.lifted_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_ban(&self, id: Uuid) -> Result<Option<BannedImageHash>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            "SELECT id, phash, dhash, reason, created_at, lifted_at
+             FROM banned_image_hashes
+             WHERE id = $1",
+            &[&id],
+        )?;
+        Ok(row.as_ref().map(ban_from_row))
+    }
+
+    fn get_bans(&self) -> Result<Vec<BannedImageHash>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            "SELECT id, phash, dhash, reason, created_at, lifted_at
+             FROM banned_image_hashes
+             WHERE lifted_at IS NULL
+             ORDER BY created_at ASC",
+            &[],
+        )?;
+        Ok(rows.iter().map(ban_from_row).collect())
+    }
+
+    fn lift_ban(&self, id: Uuid, lifted_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let lifted = conn.execute(
+            "UPDATE banned_image_hashes SET lifted_at = $2 WHERE id = $1 AND lifted_at IS NULL",
+            &[&id, &lifted_at],
+        )?;
+        Ok(lifted > 0)
+    }
+
+    fn insert_match(&self, hit: &BannedImageMatch) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            r#"
+            INSERT INTO banned_image_matches (
+                id, ban_id, board_id, thread_id, file_name, sha256, distance, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            &[
+                &hit.id,
+                &hit.ban_id,
+                &hit.board_id,
+                &hit.thread_id,
+                &hit.file_name,
+                &hit.sha256,
+                &(hit.distance as i32),
+                &hit.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_matches(&self, limit: u32) -> Result<Vec<BannedImageMatch>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            r#"
+            SELECT id, ban_id, board_id, thread_id, file_name, sha256, distance, created_at
+            FROM banned_image_matches
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#,
+            &[&(limit as i64)],
+        )?;
+        Ok(rows.iter().map(match_from_row).collect())
+    }
+}
@@ -0,0 +1,502 @@
+//! PostgreSQL Schema Migrations
+//!
+//! Developer Notes:
+//! - PostgreSQL flavour of `crate::schema::MIGRATIONS`.
+//! - Versions and names MUST match the SQLite list one-to-one.
+//! - Applied under `pg_advisory_xact_lock` so concurrent processes
+//!   starting together do not race.
+//!
+//! End Notes:
+//! Never edit a released migration; append a new one instead.
+
+use postgres::Client;
+use time::OffsetDateTime;
+
+use crate::migrations::Migration;
+use crate::StorageError;
+
+/// Arbitrary key identifying the migration advisory lock.
+const MIGRATION_LOCK_KEY: i64 = 0x7275_7374_7962_6264;
+
+/// Every PostgreSQL schema migration, in application order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS boards (
+            id UUID PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            description TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS threads (
+            id UUID PRIMARY KEY,
+            board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+            title TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS posts (
+            id UUID PRIMARY KEY,
+            thread_id UUID NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+            content TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS users (
+            id UUID PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS sessions (
+            token UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+        "#,
+    },
+    Migration {
+        version: 2,
+        name: "per-board post numbers",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN last_post_number BIGINT NOT NULL DEFAULT 0;
+        ALTER TABLE threads ADD COLUMN op_number BIGINT NOT NULL DEFAULT 0;
+        ALTER TABLE posts ADD COLUMN board_id UUID REFERENCES boards(id) ON DELETE CASCADE;
+        ALTER TABLE posts ADD COLUMN post_number BIGINT NOT NULL DEFAULT 0;
+
+        UPDATE posts SET board_id = threads.board_id
+        FROM threads WHERE threads.id = posts.thread_id;
+
+        CREATE TEMP TABLE post_numbering ON COMMIT DROP AS
+        SELECT kind, id, board_id,
+               ROW_NUMBER() OVER (
+                   PARTITION BY board_id ORDER BY created_at, kind DESC, id
+               ) AS number
+        FROM (
+            SELECT 't' AS kind, id, board_id, created_at FROM threads
+            UNION ALL
+            SELECT 'p' AS kind, id, board_id, created_at FROM posts
+        ) AS numbered;
+
+        UPDATE threads SET op_number = n.number
+        FROM post_numbering n WHERE n.kind = 't' AND n.id = threads.id;
+        UPDATE posts SET post_number = n.number
+        FROM post_numbering n WHERE n.kind = 'p' AND n.id = posts.id;
+        UPDATE boards SET last_post_number = COALESCE((
+            SELECT MAX(number) FROM post_numbering n WHERE n.board_id = boards.id
+        ), 0);
+
+        ALTER TABLE posts ALTER COLUMN board_id SET NOT NULL;
+
+        CREATE UNIQUE INDEX idx_threads_board_op_number ON threads(board_id, op_number);
+        CREATE UNIQUE INDEX idx_posts_board_post_number ON posts(board_id, post_number);
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "post quote graph",
+        sql: r#"
+        CREATE TABLE post_quotes (
+            post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+            target_board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+            target_number BIGINT NOT NULL,
+            target_thread_id UUID NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+            PRIMARY KEY (post_id, target_board_id, target_number)
+        );
+
+        CREATE INDEX idx_post_quotes_target_thread ON post_quotes(target_thread_id);
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "cached post markup",
+        sql: r#"
+        ALTER TABLE posts ADD COLUMN content_html TEXT NOT NULL DEFAULT '';
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "attachments",
+        sql: r#"
+        CREATE TABLE attachments (
+            id UUID PRIMARY KEY,
+            thread_id UUID NOT NULL REFERENCES threads(id) ON DELETE CASCADE,
+            post_id UUID REFERENCES posts(id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            file_name TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size_bytes BIGINT NOT NULL,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            storage_key TEXT NOT NULL,
+            thumbnail_key TEXT NOT NULL,
+            thumbnail_width INTEGER NOT NULL,
+            thumbnail_height INTEGER NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX idx_attachments_thread ON attachments(thread_id);
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "attachment metadata flag",
+        sql: r#"
+        ALTER TABLE attachments ADD COLUMN metadata_stripped BOOLEAN NOT NULL DEFAULT FALSE;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "content addressed media",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN duplicate_policy TEXT NOT NULL DEFAULT 'allow';
+        ALTER TABLE attachments ADD COLUMN sha256 TEXT NOT NULL DEFAULT '';
+
+        CREATE INDEX idx_attachments_sha256 ON attachments(sha256);
+        CREATE INDEX idx_attachments_post ON attachments(post_id);
+        CREATE INDEX idx_attachments_storage_key ON attachments(storage_key);
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "image hash bans",
+        sql: r#"
+        CREATE TABLE banned_image_hashes (
+            id UUID PRIMARY KEY,
+            phash BIGINT NOT NULL,
+            dhash BIGINT NOT NULL,
+            reason TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE TABLE banned_image_matches (
+            id UUID PRIMARY KEY,
+            ban_id UUID NOT NULL REFERENCES banned_image_hashes(id) ON DELETE CASCADE,
+            board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+            thread_id UUID REFERENCES threads(id) ON DELETE SET NULL,
+            file_name TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            distance INTEGER NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX idx_banned_image_matches_created ON banned_image_matches(created_at);
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "audio and video",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN av_max_duration INTEGER NOT NULL DEFAULT 300;
+        ALTER TABLE boards ADD COLUMN av_codecs TEXT NOT NULL
+            DEFAULT 'vp8,vp9,av1,h264,opus,vorbis,aac,mp3,flac';
+        ALTER TABLE attachments ADD COLUMN duration_ms INTEGER;
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "hashed expiring sessions",
+        sql: r#"
+        DROP TABLE sessions;
+
+        CREATE TABLE sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL,
+            last_seen_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX idx_sessions_user ON sessions(user_id);
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "board staff",
+        sql: r#"
+        CREATE TABLE board_staff (
+            board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            role TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (board_id, user_id)
+        );
+
+        CREATE INDEX idx_board_staff_user ON board_staff(user_id);
+        "#,
+    },
+    Migration {
+        version: 12,
+        name: "ip bans and appeals",
+        sql: r#"
+        CREATE TABLE bans (
+            id UUID PRIMARY KEY,
+            ip_range TEXT NOT NULL,
+            range_start BYTEA NOT NULL,
+            range_end BYTEA NOT NULL,
+            board_id UUID REFERENCES boards(id) ON DELETE CASCADE,
+            reason TEXT NOT NULL,
+            staff_note TEXT NOT NULL,
+            created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            expires_at TIMESTAMPTZ
+        );
+
+        CREATE INDEX idx_bans_range ON bans(range_start, range_end);
+
+        CREATE TABLE ban_appeals (
+            id UUID PRIMARY KEY,
+            ban_id UUID NOT NULL UNIQUE REFERENCES bans(id) ON DELETE CASCADE,
+            message TEXT NOT NULL,
+            status TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            decided_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            decided_at TIMESTAMPTZ
+        );
+
+        CREATE INDEX idx_ban_appeals_status ON ban_appeals(status);
+        "#,
+    },
+    Migration {
+        version: 13,
+        name: "poster records",
+        sql: r#"
+        ALTER TABLE threads ADD COLUMN ip_hash TEXT;
+        ALTER TABLE threads ADD COLUMN ip_address TEXT;
+        ALTER TABLE threads ADD COLUMN user_agent_hash TEXT;
+        ALTER TABLE posts ADD COLUMN ip_hash TEXT;
+        ALTER TABLE posts ADD COLUMN ip_address TEXT;
+        ALTER TABLE posts ADD COLUMN user_agent_hash TEXT;
+
+        CREATE INDEX idx_threads_ip_hash ON threads(ip_hash);
+        CREATE INDEX idx_posts_ip_hash ON posts(ip_hash);
+        "#,
+    },
+    Migration {
+        version: 14,
+        name: "board cooldowns",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN thread_cooldown INTEGER;
+        ALTER TABLE boards ADD COLUMN reply_cooldown INTEGER;
+        ALTER TABLE boards ADD COLUMN repost_cooldown INTEGER;
+        "#,
+    },
+    Migration {
+        version: 15,
+        name: "captchas",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN captcha_mode TEXT NOT NULL DEFAULT 'off';
+
+        CREATE TABLE captchas (
+            id UUID PRIMARY KEY,
+            answer TEXT NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX idx_captchas_expires_at ON captchas(expires_at);
+        "#,
+    },
+    Migration {
+        version: 16,
+        name: "reports",
+        sql: r#"
+        CREATE TABLE reports (
+            id UUID PRIMARY KEY,
+            post_id UUID REFERENCES posts(id) ON DELETE SET NULL,
+            board_id UUID NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+            post_number BIGINT NOT NULL,
+            category TEXT NOT NULL,
+            comment TEXT NOT NULL,
+            reporter_ip_hash TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL,
+            status TEXT NOT NULL,
+            resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            resolved_at TIMESTAMPTZ
+        );
+
+        CREATE INDEX idx_reports_status ON reports(status, created_at);
+        CREATE INDEX idx_reports_post ON reports(post_id);
+        "#,
+    },
+    Migration {
+        version: 17,
+        name: "moderation log",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN public_mod_log BOOLEAN NOT NULL DEFAULT FALSE;
+
+        CREATE TABLE mod_actions (
+            id UUID PRIMARY KEY,
+            actor_id UUID,
+            actor_name TEXT NOT NULL,
+            action TEXT NOT NULL,
+            board_id UUID,
+            target TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            before_state TEXT,
+            after_state TEXT,
+            created_at TIMESTAMPTZ NOT NULL
+        );
+
+        CREATE INDEX idx_mod_actions_created_at ON mod_actions(created_at);
+        CREATE INDEX idx_mod_actions_board ON mod_actions(board_id, created_at);
+
+        CREATE FUNCTION mod_actions_append_only() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'mod_actions is append-only';
+        END;
+        $$ LANGUAGE plpgsql;
+
+        CREATE TRIGGER mod_actions_append_only BEFORE UPDATE OR DELETE ON mod_actions
+            FOR EACH ROW EXECUTE FUNCTION mod_actions_append_only();
+        "#,
+    },
+    Migration {
+        version: 18,
+        name: "thread flags",
+        sql: r#"
+        ALTER TABLE threads ADD COLUMN sticky INTEGER;
+        ALTER TABLE threads ADD COLUMN locked BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE threads ADD COLUMN cyclical BOOLEAN NOT NULL DEFAULT FALSE;
+        ALTER TABLE threads ADD COLUMN autosage BOOLEAN NOT NULL DEFAULT FALSE;
+        "#,
+    },
+    Migration {
+        version: 19,
+        name: "bump order",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN bump_limit INTEGER NOT NULL DEFAULT 300;
+
+        ALTER TABLE threads ADD COLUMN bumped_at TIMESTAMPTZ;
+        UPDATE threads SET bumped_at = COALESCE(
+            (SELECT MAX(created_at) FROM posts WHERE posts.thread_id = threads.id),
+            created_at
+        );
+        ALTER TABLE threads ALTER COLUMN bumped_at SET NOT NULL;
+
+        CREATE INDEX idx_threads_board_bumped_at ON threads(board_id, bumped_at);
+        "#,
+    },
+    Migration {
+        version: 20,
+        name: "thread pruning",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN max_threads INTEGER NOT NULL DEFAULT 150;
+        ALTER TABLE boards ADD COLUMN prune_mode TEXT NOT NULL DEFAULT 'archive';
+
+        ALTER TABLE threads ADD COLUMN archived_at TIMESTAMPTZ;
+        "#,
+    },
+    Migration {
+        version: 21,
+        name: "image ban history",
+        sql: r#"
+        ALTER TABLE banned_image_hashes ADD COLUMN lifted_at TIMESTAMPTZ;
+        "#,
+    },
+];
+/// Highest schema version this binary knows how to produce.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn ensure_version_table(client: &mut Client) -> Result<(), StorageError> {
+    client.batch_execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL
+        );
+        "#,
+    )?;
+    Ok(())
+}
+
+fn read_version(client: &mut impl postgres::GenericClient) -> Result<u32, StorageError> {
+    let row = client.query_one("SELECT MAX(version) FROM schema_version", &[])?;
+    let version: Option<i32> = row.get(0);
+    Ok(version.unwrap_or(0) as u32)
+}
+
+fn check_not_newer(current: u32) -> Result<(), StorageError> {
+    let supported = latest_version();
+    if current > supported {
+        return Err(StorageError::SchemaTooNew {
+            database: current,
+            supported,
+        });
+    }
+    Ok(())
+}
+
+/// Current schema version of the database (0 if nothing was applied yet).
+pub fn current_version(client: &mut Client) -> Result<u32, StorageError> {
+    ensure_version_table(client)?;
+    read_version(client)
+}
+
+/// List migrations that have not been applied to this database yet.
+pub fn pending_migrations(
+    client: &mut Client,
+) -> Result<Vec<&'static Migration>, StorageError> {
+    let current = current_version(client)?;
+    check_not_newer(current)?;
+
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+/// Apply every pending migration in one transaction.
+///
+/// Returns the number of migrations applied.
+pub fn run_migrations(client: &mut Client) -> Result<usize, StorageError> {
+    ensure_version_table(client)?;
+
+    let mut tx = client.transaction()?;
+    tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK_KEY])?;
+
+    // Re-read under the lock: another process may have just migrated.
+    let current = read_version(&mut tx)?;
+    check_not_newer(current)?;
+
+    let pending: Vec<&Migration> =
+        MIGRATIONS.iter().filter(|m| m.version > current).collect();
+
+    for migration in &pending {
+        tx.batch_execute(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at)
+             VALUES ($1, $2, $3)",
+            &[
+                &(migration.version as i32),
+                &migration.name,
+                &OffsetDateTime::now_utc(),
+            ],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(pending.len())
+}
+
+
+/// TESTS
+///
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_match_sqlite() {
+        let sqlite: Vec<_> = crate::schema::MIGRATIONS
+            .iter()
+            .map(|m| (m.version, m.name))
+            .collect();
+        let postgres: Vec<_> = MIGRATIONS.iter().map(|m| (m.version, m.name)).collect();
+
+        assert_eq!(sqlite, postgres);
+    }
+}
@@ -0,0 +1,117 @@
+//! PostgreSQL Backend
+//!
+//! Developer Notes:
+//! - Compiled only with the `postgres` cargo feature.
+//! - Implements every repository trait on top of an r2d2 pool of
+//!   synchronous `postgres` clients; callers stay on blocking threads,
+//!   exactly like the SQLite backend.
+//! - Uses native UUID and TIMESTAMPTZ columns.
+//! - Several `rusty-board` processes may share one database; migrations
+//!   serialize on an advisory lock.
+//!
+//! End Notes:
+//! Keep behaviour identical to the SQLite repositories.
+
+pub mod migrations;
+pub mod board_repository;
+pub mod thread_repository;
+pub mod post_repository;
+pub mod user_repository;
+pub mod session_repository;
+pub mod quote_repository;
+pub mod attachment_repository;
+pub mod image_ban_repository;
+pub mod staff_repository;
+pub mod ban_repository;
+pub mod captcha_repository;
+pub mod report_repository;
+pub mod mod_action_repository;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use r2d2_postgres::postgres::NoTls;
+use r2d2_postgres::PostgresConnectionManager;
+
+use crate::{Repositories, StorageError};
+
+pub use board_repository::PgBoardRepository;
+pub use thread_repository::PgThreadRepository;
+pub use post_repository::PgPostRepository;
+pub use user_repository::PgUserRepository;
+pub use session_repository::PgSessionRepository;
+pub use quote_repository::PgQuoteRepository;
+pub use attachment_repository::PgAttachmentRepository;
+pub use image_ban_repository::PgImageBanRepository;
+pub use staff_repository::PgStaffRepository;
+pub use ban_repository::PgBanRepository;
+pub use captcha_repository::PgCaptchaRepository;
+pub use report_repository::PgReportRepository;
+pub use mod_action_repository::PgModActionRepository;
+
+/// Pool of PostgreSQL clients.
+pub type PgPool = r2d2::Pool<PostgresConnectionManager<NoTls>>;
+
+/// Build a connection pool from a `postgres://` URL.
+pub fn create_pg_pool(url: &str, max_size: u32) -> Result<PgPool, StorageError> {
+    let config = url.parse()?;
+    let manager = PostgresConnectionManager::new(config, NoTls);
+
+    Ok(r2d2::Pool::builder()
+        .max_size(max_size.max(1))
+        .connection_timeout(Duration::from_secs(5))
+        .build(manager)?)
+}
+
+impl Repositories {
+    /// PostgreSQL-backed repositories sharing one connection pool.
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            boards: Arc::new(PgBoardRepository::new(pool.clone())),
+            threads: Arc::new(PgThreadRepository::new(pool.clone())),
+            posts: Arc::new(PgPostRepository::new(pool.clone())),
+            users: Arc::new(PgUserRepository::new(pool.clone())),
+            sessions: Arc::new(PgSessionRepository::new(pool.clone())),
+            quotes: Arc::new(PgQuoteRepository::new(pool.clone())),
+            attachments: Arc::new(PgAttachmentRepository::new(pool.clone())),
+            image_bans: Arc::new(PgImageBanRepository::new(pool.clone())),
+            staff: Arc::new(PgStaffRepository::new(pool.clone())),
+            bans: Arc::new(PgBanRepository::new(pool.clone())),
+            captchas: Arc::new(PgCaptchaRepository::new(pool.clone())),
+            reports: Arc::new(PgReportRepository::new(pool.clone())),
+            mod_actions: Arc::new(PgModActionRepository::new(pool)),
+        }
+    }
+}
+
+
+/// TESTS
+///
+/// These run against a throwaway database named by `TEST_POSTGRES_URL`
+/// and are skipped when it is unset.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::sync::{Mutex, MutexGuard};
+
+    static DATABASE: Mutex<()> = Mutex::new(());
+
+    /// Fresh, migrated pool, or `None` when no test database is configured.
+    ///
+    /// Every call drops and recreates the `public` schema; the returned
+    /// guard keeps other tests off the database until it is dropped.
+    pub(crate) fn test_pool() -> Option<(MutexGuard<'static, ()>, PgPool)> {
+        let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+        let guard = DATABASE.lock().unwrap_or_else(|e| e.into_inner());
+        let pool = create_pg_pool(&url, 2).unwrap();
+
+        pool.get()
+            .unwrap()
+            .batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public;")
+            .unwrap();
+        migrations::run_migrations(&mut pool.get().unwrap()).unwrap();
+
+        Some((guard, pool))
+    }
+}
@@ -0,0 +1,103 @@
+//! PostgreSQL Mod Action Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `ModActionRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::mod_action_repository`.
+
+use postgres::types::ToSql;
+use postgres::Row;
+
+use models::ModAction;
+use crate::mod_action_repository::{ModActionFilter, ModActionRepository};
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `ModActionRepository`.
+#[derive(Clone)]
+pub struct PgModActionRepository {
+    pool: PgPool,
+}
+
+impl PgModActionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `action_from_row`, in order.
+const COLUMNS: &str = "id, actor_id, actor_name, action, board_id, target, reason, \
+                       before_state, after_state, created_at";
+
+fn action_from_row(row: &Row) -> Result<ModAction, StorageError> {
+    let action: String = row.get(3);
+
+    Ok(ModAction {
+        id: row.get(0),
+        actor_id: row.get(1),
+        actor_name: row.get(2),
+        action: action
+            .parse()
+            .map_err(|_| StorageError::Corrupt(format!("unknown mod action `{action}`")))?,
+        board_id: row.get(4),
+        target: row.get(5),
+        reason: row.get(6),
+        before: row.get(7),
+        after: row.get(8),
+        created_at: row.get(9),
+    })
+}
+
+impl ModActionRepository for PgModActionRepository {
+    fn insert_action(&self, action: &ModAction) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            &format!("INSERT INTO mod_actions ({COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"),
+            &[
+                &action.id,
+                &action.actor_id,
+                &action.actor_name,
+                &action.action.to_string(),
+                &action.board_id,
+                &action.target,
+                &action.reason,
+                &action.before,
+                &action.after,
+                &action.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_actions(&self, filter: &ModActionFilter) -> Result<Vec<ModAction>, StorageError> {
+        let mut sql = format!("SELECT {COLUMNS} FROM mod_actions WHERE TRUE");
+        let mut args: Vec<Box<dyn ToSql + Sync>> = Vec::new();
+        let mut arg = |sql: &mut String, clause: &str, value: Box<dyn ToSql + Sync>| {
+            args.push(value);
+            sql.push_str(&clause.replace('$', &format!("${}", args.len())));
+        };
+
+        if let Some(board_id) = filter.board_id {
+            arg(&mut sql, " AND board_id = $", Box::new(board_id));
+        }
+        if let Some(board_ids) = &filter.board_ids {
+            arg(&mut sql, " AND board_id = ANY($)", Box::new(board_ids.clone()));
+        }
+        if let Some(actor_id) = filter.actor_id {
+            arg(&mut sql, " AND actor_id = $", Box::new(actor_id));
+        }
+        if let Some(before) = filter.before {
+            arg(&mut sql, " AND created_at < $", Box::new(before));
+        }
+        if !filter.actions.is_empty() {
+            let actions: Vec<String> = filter.actions.iter().map(ToString::to_string).collect();
+            arg(&mut sql, " AND action = ANY($)", Box::new(actions));
+        }
+        arg(&mut sql, " ORDER BY created_at DESC LIMIT $", Box::new(i64::from(filter.limit)));
+
+        let mut conn = self.pool.get()?;
+        let params: Vec<&(dyn ToSql + Sync)> = args.iter().map(|arg| arg.as_ref()).collect();
+        conn.query(&sql, &params)?.iter().map(action_from_row).collect()
+    }
+}
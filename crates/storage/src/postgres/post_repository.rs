@@ -0,0 +1,179 @@
+//! PostgreSQL Post Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `PostRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::post_repository`.
+
+use postgres::Row;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::Post;
+use crate::post_repository::PostRepository;
+use crate::StorageError;
+use super::board_repository::next_post_number;
+use super::thread_repository::poster_from_row;
+use super::PgPool;
+
+/// PostgreSQL implementation of `PostRepository`.
+#[derive(Clone)]
+pub struct PgPostRepository {
+    pool: PgPool,
+}
+
+impl PgPostRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn post_from_row(row: &Row) -> Result<Post, StorageError> {
+    Ok(Post {
+        id: row.get(0),
+        thread_id: row.get(1),
+        post_number: row.get(2),
+        content: row.get(3),
+        content_html: row.get(4),
+        created_at: row.get(5),
+        poster: poster_from_row(row, 6)?,
+    })
+}
+
+impl PostRepository for PgPostRepository {
+    fn insert_post(&self, post: &Post) -> Result<i64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        let board_id: Uuid = tx
+            .query_opt("SELECT board_id FROM threads WHERE id = $1", &[&post.thread_id])?
+            .map(|row| row.get(0))
+            .ok_or_else(|| {
+                StorageError::Constraint("FOREIGN KEY constraint failed: posts.thread_id".into())
+            })?;
+
+        let post_number = next_post_number(&mut tx, board_id)?;
+
+        tx.execute(
+            r#"
+            INSERT INTO posts (id, thread_id, board_id, post_number, content, content_html, created_at,
+                               ip_hash, ip_address, user_agent_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            &[
+                &post.id,
+                &post.thread_id,
+                &board_id,
+                &post_number,
+                &post.content,
+                &post.content_html,
+                &post.created_at,
+                &post.poster.as_ref().map(|p| &p.ip_hash),
+                &post.poster.as_ref().and_then(|p| p.ip).map(|ip| ip.to_string()),
+                &post.poster.as_ref().and_then(|p| p.user_agent_hash.as_ref()),
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(post_number)
+    }
+
+    fn get_post_by_number(
+        &self,
+        board_id: Uuid,
+        post_number: i64,
+    ) -> Result<Option<Post>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE board_id = $1 AND post_number = $2
+            "#,
+            &[&board_id, &post_number],
+        )?;
+        row.as_ref().map(post_from_row).transpose()
+    }
+
+    fn get_posts_by_thread(&self, thread_id: Uuid) -> Result<Vec<Post>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE thread_id = $1
+            ORDER BY created_at ASC
+            "#,
+            &[&thread_id],
+        )?;
+        rows.iter().map(post_from_row).collect()
+    }
+
+    fn count_posts_by_thread(&self, thread_id: Uuid) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_one("SELECT COUNT(*) FROM posts WHERE thread_id = $1", &[&thread_id])?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    fn set_content_html(&self, post_id: Uuid, html: &str) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE posts SET content_html = $2 WHERE id = $1",
+            &[&post_id, &html],
+        )?;
+        Ok(())
+    }
+
+    fn delete_post(&self, post_id: Uuid) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let deleted = conn.execute("DELETE FROM posts WHERE id = $1", &[&post_id])?;
+        Ok(deleted > 0)
+    }
+
+    fn get_posts_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Post>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE ip_hash = $1
+            ORDER BY created_at DESC
+            "#,
+            &[&ip_hash],
+        )?;
+        rows.iter().map(post_from_row).collect()
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Post>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            r#"
+            SELECT id, thread_id, post_number, content, content_html, created_at,
+                   ip_hash, ip_address, user_agent_hash
+            FROM posts
+            WHERE board_id = $1 AND ip_hash = $2 AND created_at >= $3
+            ORDER BY created_at DESC
+            "#,
+            &[&board_id, &ip_hash, &since],
+        )?;
+        rows.iter().map(post_from_row).collect()
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute(
+            "UPDATE posts SET ip_address = NULL
+             WHERE ip_address IS NOT NULL AND created_at < $1",
+            &[&created_before],
+        )?)
+    }
+}
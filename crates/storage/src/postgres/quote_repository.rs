@@ -0,0 +1,78 @@
+//! PostgreSQL Quote Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `QuoteRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::quote_repository`.
+
+use uuid::Uuid;
+
+use models::{Quote, QuoteTarget};
+use crate::quote_repository::QuoteRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `QuoteRepository`.
+#[derive(Clone)]
+pub struct PgQuoteRepository {
+    pool: PgPool,
+}
+
+impl PgQuoteRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl QuoteRepository for PgQuoteRepository {
+    fn insert_quotes(&self, post_id: Uuid, targets: &[QuoteTarget]) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        for target in targets {
+            tx.execute(
+                r#"
+                INSERT INTO post_quotes
+                    (post_id, target_board_id, target_number, target_thread_id)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                "#,
+                &[&post_id, &target.board_id, &target.number, &target.thread_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_quotes_by_thread(&self, thread_id: Uuid) -> Result<Vec<Quote>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            r#"
+            SELECT p.id, p.thread_id, pb.name, p.post_number,
+                   q.target_thread_id, tb.name, q.target_number
+            FROM post_quotes q
+            JOIN posts p ON p.id = q.post_id
+            JOIN boards pb ON pb.id = p.board_id
+            JOIN boards tb ON tb.id = q.target_board_id
+            WHERE p.thread_id = $1 OR q.target_thread_id = $1
+            ORDER BY p.post_number
+            "#,
+            &[&thread_id],
+        )?;
+
+        Ok(rows
+            .iter()
+            .map(|row| Quote {
+                from_post_id: row.get(0),
+                from_thread_id: row.get(1),
+                from_board: row.get(2),
+                from_number: row.get(3),
+                to_thread_id: row.get(4),
+                to_board: row.get(5),
+                to_number: row.get(6),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,137 @@
+//! PostgreSQL Report Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `ReportRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::report_repository`.
+
+use postgres::Row;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{Report, ReportStatus};
+use crate::report_repository::ReportRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `ReportRepository`.
+#[derive(Clone)]
+pub struct PgReportRepository {
+    pool: PgPool,
+}
+
+impl PgReportRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `report_from_row`, in order.
+const COLUMNS: &str = "id, post_id, board_id, post_number, category, comment, reporter_ip_hash, \
+                       created_at, status, resolved_by, resolved_at";
+
+fn report_from_row(row: &Row) -> Result<Report, StorageError> {
+    let category: String = row.get(4);
+    let status: String = row.get(8);
+
+    Ok(Report {
+        id: row.get(0),
+        post_id: row.get(1),
+        board_id: row.get(2),
+        post_number: row.get(3),
+        category: category
+            .parse()
+            .map_err(|_| StorageError::Corrupt(format!("unknown report category `{category}`")))?,
+        comment: row.get(5),
+        reporter_ip_hash: row.get(6),
+        created_at: row.get(7),
+        status: status
+            .parse()
+            .map_err(|_| StorageError::Corrupt(format!("unknown report status `{status}`")))?,
+        resolved_by: row.get(9),
+        resolved_at: row.get(10),
+    })
+}
+
+impl ReportRepository for PgReportRepository {
+    fn insert_report(&self, report: &Report) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            &format!("INSERT INTO reports ({COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"),
+            &[
+                &report.id,
+                &report.post_id,
+                &report.board_id,
+                &report.post_number,
+                &report.category.to_string(),
+                &report.comment,
+                &report.reporter_ip_hash,
+                &report.created_at,
+                &report.status.to_string(),
+                &report.resolved_by,
+                &report.resolved_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_open_reports(&self) -> Result<Vec<Report>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!("SELECT {COLUMNS} FROM reports WHERE status = $1 ORDER BY created_at"),
+            &[&ReportStatus::Open.to_string()],
+        )?
+        .iter()
+        .map(report_from_row)
+        .collect()
+    }
+
+    fn get_open_by_post(&self, post_id: Uuid) -> Result<Vec<Report>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!(
+                "SELECT {COLUMNS} FROM reports WHERE post_id = $1 AND status = $2 ORDER BY created_at"
+            ),
+            &[&post_id, &ReportStatus::Open.to_string()],
+        )?
+        .iter()
+        .map(report_from_row)
+        .collect()
+    }
+
+    fn resolve_reports(
+        &self,
+        post_id: Uuid,
+        status: ReportStatus,
+        resolved_by: Uuid,
+        resolved_at: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute(
+            "UPDATE reports SET status = $2, resolved_by = $3, resolved_at = $4
+             WHERE post_id = $1 AND status = $5",
+            &[
+                &post_id,
+                &status.to_string(),
+                &resolved_by,
+                &resolved_at,
+                &ReportStatus::Open.to_string(),
+            ],
+        )?)
+    }
+
+    fn get_resolved_reports(&self, limit: u32) -> Result<Vec<Report>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!(
+                "SELECT {COLUMNS} FROM reports WHERE status <> $1
+                 ORDER BY resolved_at DESC, created_at DESC LIMIT $2"
+            ),
+            &[&ReportStatus::Open.to_string(), &i64::from(limit)],
+        )?
+        .iter()
+        .map(report_from_row)
+        .collect()
+    }
+}
@@ -0,0 +1,93 @@
+//! PostgreSQL Session Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `SessionRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::session_repository`.
+
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::Session;
+use crate::session_repository::SessionRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `SessionRepository`.
+#[derive(Clone)]
+pub struct PgSessionRepository {
+    pool: PgPool,
+}
+
+impl PgSessionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SessionRepository for PgSessionRepository {
+    fn create(&self, session: &Session) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO sessions (token_hash, user_id, created_at, last_seen_at)
+             VALUES ($1, $2, $3, $4)",
+            &[
+                &session.token_hash,
+                &session.user_id,
+                &session.created_at,
+                &session.last_seen_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find(&self, token_hash: &str) -> Result<Option<Session>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            "SELECT token_hash, user_id, created_at, last_seen_at
+             FROM sessions
+             WHERE token_hash = $1",
+            &[&token_hash],
+        )?;
+
+        Ok(row.map(|row| Session {
+            token_hash: row.get(0),
+            user_id: row.get(1),
+            created_at: row.get(2),
+            last_seen_at: row.get(3),
+        }))
+    }
+
+    fn touch(&self, token_hash: &str, last_seen_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE sessions SET last_seen_at = $2 WHERE token_hash = $1",
+            &[&token_hash, &last_seen_at],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn delete(&self, token_hash: &str) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let deleted = conn.execute("DELETE FROM sessions WHERE token_hash = $1", &[&token_hash])?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_by_user(&self, user_id: Uuid) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute("DELETE FROM sessions WHERE user_id = $1", &[&user_id])?)
+    }
+
+    fn delete_expired(
+        &self,
+        created_before: OffsetDateTime,
+        seen_before: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute(
+            "DELETE FROM sessions WHERE created_at < $1 OR last_seen_at < $2",
+            &[&created_before, &seen_before],
+        )?)
+    }
+}
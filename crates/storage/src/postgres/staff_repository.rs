@@ -0,0 +1,98 @@
+//! PostgreSQL Board Staff Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `StaffRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::staff_repository`.
+
+use postgres::Row;
+use uuid::Uuid;
+
+use models::BoardStaff;
+use crate::staff_repository::StaffRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `StaffRepository`.
+#[derive(Clone)]
+pub struct PgStaffRepository {
+    pool: PgPool,
+}
+
+impl PgStaffRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+const COLUMNS: &str = "board_id, user_id, role, created_at";
+
+fn staff_from_row(row: &Row) -> Result<BoardStaff, StorageError> {
+    let role_str: String = row.get(2);
+    let role = role_str
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown board role `{role_str}`")))?;
+
+    Ok(BoardStaff {
+        board_id: row.get(0),
+        user_id: row.get(1),
+        role,
+        created_at: row.get(3),
+    })
+}
+
+impl StaffRepository for PgStaffRepository {
+    fn upsert(&self, staff: &BoardStaff) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO board_staff (board_id, user_id, role, created_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (board_id, user_id) DO UPDATE SET role = excluded.role",
+            &[&staff.board_id, &staff.user_id, &staff.role.to_string(), &staff.created_at],
+        )?;
+        Ok(())
+    }
+
+    fn find(&self, board_id: Uuid, user_id: Uuid) -> Result<Option<BoardStaff>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            &format!("SELECT {COLUMNS} FROM board_staff WHERE board_id = $1 AND user_id = $2"),
+            &[&board_id, &user_id],
+        )?
+        .as_ref()
+        .map(staff_from_row)
+        .transpose()
+    }
+
+    fn get_by_board(&self, board_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!("SELECT {COLUMNS} FROM board_staff WHERE board_id = $1 ORDER BY created_at"),
+            &[&board_id],
+        )?
+        .iter()
+        .map(staff_from_row)
+        .collect()
+    }
+
+    fn get_by_user(&self, user_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            &format!("SELECT {COLUMNS} FROM board_staff WHERE user_id = $1 ORDER BY created_at"),
+            &[&user_id],
+        )?
+        .iter()
+        .map(staff_from_row)
+        .collect()
+    }
+
+    fn delete(&self, board_id: Uuid, user_id: Uuid) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM board_staff WHERE board_id = $1 AND user_id = $2",
+            &[&board_id, &user_id],
+        )?;
+        Ok(deleted > 0)
+    }
+}
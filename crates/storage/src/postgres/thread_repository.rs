@@ -0,0 +1,293 @@
+//! PostgreSQL Thread Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `ThreadRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::thread_repository`.
+
+use postgres::{GenericClient, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{Attachment, PosterInfo, PruneMode, PrunePolicy, Thread, ThreadFlags};
+use crate::thread_repository::{PrunedThreads, ThreadRepository};
+use crate::StorageError;
+use super::attachment_repository::{
+    attachment_from_row, insert_in as insert_attachments_in, COLUMNS as ATTACHMENT_COLUMNS,
+};
+use super::board_repository::next_post_number;
+use super::PgPool;
+
+/// PostgreSQL implementation of `ThreadRepository`.
+#[derive(Clone)]
+pub struct PgThreadRepository {
+    pool: PgPool,
+}
+
+impl PgThreadRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `thread_from_row`, in order.
+const COLUMNS: &str = "id, board_id, op_number, title, created_at, ip_hash, ip_address, \
+                       user_agent_hash, sticky, locked, cyclical, autosage, bumped_at, archived_at";
+
+/// Stickies by position, then the rest most recently bumped first.
+const BOARD_ORDER: &str = "sticky IS NULL, sticky, bumped_at DESC";
+
+fn thread_from_row(row: &Row) -> Result<Thread, StorageError> {
+    Ok(Thread {
+        id: row.get(0),
+        board_id: row.get(1),
+        op_number: row.get(2),
+        title: row.get(3),
+        created_at: row.get(4),
+        poster: poster_from_row(row, 5)?,
+        flags: ThreadFlags {
+            // `INTEGER` in PostgreSQL is `i32`.
+            sticky: row.get::<_, Option<i32>>(8).map(|position| position as u32),
+            locked: row.get(9),
+            cyclical: row.get(10),
+            autosage: row.get(11),
+        },
+        bumped_at: row.get(12),
+        archived_at: row.get(13),
+    })
+}
+
+fn sticky_param(position: Option<u32>) -> Option<i32> {
+    position.map(|position| position.min(i32::MAX as u32) as i32)
+}
+
+/// The `ip_hash`, `ip_address` and `user_agent_hash` columns starting
+/// at `idx`; `None` when no hash was recorded.
+pub(super) fn poster_from_row(row: &Row, idx: usize) -> Result<Option<PosterInfo>, StorageError> {
+    let Some(ip_hash) = row.get::<_, Option<String>>(idx) else {
+        return Ok(None);
+    };
+    let ip = row
+        .get::<_, Option<String>>(idx + 1)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| StorageError::Corrupt(format!("bad ip address `{value}`")))
+        })
+        .transpose()?;
+
+    Ok(Some(PosterInfo { ip_hash, ip, user_agent_hash: row.get(idx + 2) }))
+}
+
+/// Insert thread inside the caller's transaction, returning its OP number.
+fn insert_in(client: &mut impl GenericClient, thread: &Thread) -> Result<i64, StorageError> {
+    let op_number = next_post_number(client, thread.board_id)?;
+
+    client.execute(
+        &format!(
+            "INSERT INTO threads ({COLUMNS})
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"
+        ),
+        &[
+            &thread.id,
+            &thread.board_id,
+            &op_number,
+            &thread.title,
+            &thread.created_at,
+            &thread.poster.as_ref().map(|p| &p.ip_hash),
+            &thread.poster.as_ref().and_then(|p| p.ip).map(|ip| ip.to_string()),
+            &thread.poster.as_ref().and_then(|p| p.user_agent_hash.as_ref()),
+            &sticky_param(thread.flags.sticky),
+            &thread.flags.locked,
+            &thread.flags.cyclical,
+            &thread.flags.autosage,
+            &thread.bumped_at,
+            &thread.archived_at,
+        ],
+    )?;
+
+    Ok(op_number)
+}
+
+/// Prune the board of `new` down to `policy.max_threads` live threads
+/// inside the caller's transaction.
+fn prune_in(
+    client: &mut impl GenericClient,
+    new: &Thread,
+    policy: &PrunePolicy,
+) -> Result<PrunedThreads, StorageError> {
+    let rows = client.query(
+        &format!(
+            "SELECT {COLUMNS} FROM threads
+             WHERE board_id = $1 AND archived_at IS NULL
+             ORDER BY {BOARD_ORDER}
+             OFFSET $2"
+        ),
+        &[&new.board_id, &i64::from(policy.max_threads)],
+    )?;
+
+    let mut pruned = PrunedThreads::default();
+    for row in &rows {
+        let mut thread = thread_from_row(row)?;
+        if thread.flags.sticky.is_some() || thread.id == new.id {
+            continue;
+        }
+
+        match policy.mode {
+            PruneMode::Archive => {
+                client.execute(
+                    "UPDATE threads SET archived_at = $2 WHERE id = $1",
+                    &[&thread.id, &new.created_at],
+                )?;
+                thread.archived_at = Some(new.created_at);
+            }
+            PruneMode::Delete => {
+                let attachments = client.query(
+                    &format!("SELECT {ATTACHMENT_COLUMNS} FROM attachments a WHERE a.thread_id = $1"),
+                    &[&thread.id],
+                )?;
+                pruned.attachments.extend(attachments.iter().map(attachment_from_row));
+                // Posts, attachments and quotes go with it (ON DELETE CASCADE).
+                client.execute("DELETE FROM threads WHERE id = $1", &[&thread.id])?;
+            }
+        }
+        pruned.threads.push(thread);
+    }
+
+    Ok(pruned)
+}
+
+impl ThreadRepository for PgThreadRepository {
+    fn insert_thread(&self, thread: &Thread) -> Result<i64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        let op_number = insert_in(&mut tx, thread)?;
+
+        tx.commit()?;
+        Ok(op_number)
+    }
+
+    fn insert_thread_and_prune(
+        &self,
+        thread: &Thread,
+        attachments: &[Attachment],
+        policy: &PrunePolicy,
+    ) -> Result<(i64, PrunedThreads), StorageError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        let op_number = insert_in(&mut tx, thread)?;
+        insert_attachments_in(&mut tx, attachments)?;
+        let pruned = prune_in(&mut tx, thread, policy)?;
+
+        tx.commit()?;
+        Ok((op_number, pruned))
+    }
+
+    fn get_thread(&self, id: Uuid) -> Result<Option<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(&format!("SELECT {COLUMNS} FROM threads WHERE id = $1"), &[&id])?;
+        row.as_ref().map(thread_from_row).transpose()
+    }
+
+    fn get_thread_by_number(
+        &self,
+        board_id: Uuid,
+        op_number: i64,
+    ) -> Result<Option<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let row = conn.query_opt(
+            &format!("SELECT {COLUMNS} FROM threads WHERE board_id = $1 AND op_number = $2"),
+            &[&board_id, &op_number],
+        )?;
+        row.as_ref().map(thread_from_row).transpose()
+    }
+
+    fn get_threads_by_board(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {COLUMNS} FROM threads
+                 WHERE board_id = $1 AND archived_at IS NULL
+                 ORDER BY {BOARD_ORDER}"
+            ),
+            &[&board_id],
+        )?;
+        rows.iter().map(thread_from_row).collect()
+    }
+
+    fn get_archived_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {COLUMNS} FROM threads
+                 WHERE board_id = $1 AND archived_at IS NOT NULL
+                 ORDER BY archived_at DESC, bumped_at DESC"
+            ),
+            &[&board_id],
+        )?;
+        rows.iter().map(thread_from_row).collect()
+    }
+
+    fn get_threads_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            &format!("SELECT {COLUMNS} FROM threads WHERE ip_hash = $1 ORDER BY created_at DESC"),
+            &[&ip_hash],
+        )?;
+        rows.iter().map(thread_from_row).collect()
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Thread>, StorageError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            &format!(
+                "SELECT {COLUMNS} FROM threads
+                 WHERE board_id = $1 AND ip_hash = $2 AND created_at >= $3
+                 ORDER BY created_at DESC"
+            ),
+            &[&board_id, &ip_hash, &since],
+        )?;
+        rows.iter().map(thread_from_row).collect()
+    }
+
+    fn set_flags(&self, thread_id: Uuid, flags: &ThreadFlags) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE threads SET sticky = $2, locked = $3, cyclical = $4, autosage = $5 WHERE id = $1",
+            &[
+                &thread_id,
+                &sticky_param(flags.sticky),
+                &flags.locked,
+                &flags.cyclical,
+                &flags.autosage,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn bump_thread(&self, thread_id: Uuid, at: OffsetDateTime) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE threads SET bumped_at = $2 WHERE id = $1 AND bumped_at < $2",
+            &[&thread_id, &at],
+        )?;
+        Ok(())
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let mut conn = self.pool.get()?;
+        Ok(conn.execute(
+            "UPDATE threads SET ip_address = NULL
+             WHERE ip_address IS NOT NULL AND created_at < $1",
+            &[&created_before],
+        )?)
+    }
+}
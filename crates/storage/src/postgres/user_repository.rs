@@ -0,0 +1,88 @@
+//! PostgreSQL User Repository
+//!
+//! Developer Notes:
+//! - PostgreSQL implementation of `UserRepository`.
+//!
+//! End Notes:
+//! Mirrors `crate::user_repository`.
+
+use postgres::Row;
+use uuid::Uuid;
+
+use models::{Role, User};
+use crate::user_repository::UserRepository;
+use crate::StorageError;
+use super::PgPool;
+
+/// PostgreSQL implementation of `UserRepository`.
+#[derive(Clone)]
+pub struct PgUserRepository {
+    pool: PgPool,
+}
+
+impl PgUserRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn user_from_row(row: &Row) -> Result<User, StorageError> {
+    let role_str: String = row.get(3);
+    let role = role_str
+        .parse()
+        .map_err(|_| StorageError::Corrupt(format!("unknown role `{role_str}`")))?;
+
+    Ok(User {
+        id: row.get(0),
+        username: row.get(1),
+        password_hash: row.get(2),
+        role,
+    })
+}
+
+impl UserRepository for PgUserRepository {
+    fn create(&self, user: &User) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO users (id, username, password_hash, role)
+             VALUES ($1, $2, $3, $4)",
+            &[&user.id, &user.username, &user.password_hash, &user.role.to_string()],
+        )?;
+        Ok(())
+    }
+
+    fn find_by_username(&self, username: &str) -> Result<Option<User>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT id, username, password_hash, role
+             FROM users
+             WHERE username = $1",
+            &[&username],
+        )?
+        .as_ref()
+        .map(user_from_row)
+        .transpose()
+    }
+
+    fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT id, username, password_hash, role
+             FROM users
+             WHERE id = $1",
+            &[&id],
+        )?
+        .as_ref()
+        .map(user_from_row)
+        .transpose()
+    }
+
+    fn set_role(&self, id: Uuid, role: &Role) -> Result<bool, StorageError> {
+        let mut conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE users SET role = $2 WHERE id = $1",
+            &[&id, &role.to_string()],
+        )?;
+        Ok(updated > 0)
+    }
+}
@@ -0,0 +1,101 @@
+//! Quote Repository
+//!
+//! Developer Notes:
+//! - Persists the reply graph built from `>>N` / `>>>/board/N` quotes.
+//! - Edges are written once, when the quoting post is created.
+//! - `QuoteRepository` is the backend-neutral interface.
+//! - `SqliteQuoteRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! Parsing and resolution live in `services`, not here.
+
+use rusqlite::{params, Row};
+use uuid::Uuid;
+
+use models::{Quote, QuoteTarget};
+use crate::columns::get_uuid;
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for the reply graph.
+pub trait QuoteRepository: Send + Sync {
+    /// Record the quotes made by a post.
+    fn insert_quotes(&self, post_id: Uuid, targets: &[QuoteTarget]) -> Result<(), StorageError>;
+
+    /// Every edge that starts or ends in a thread.
+    fn get_quotes_by_thread(&self, thread_id: Uuid) -> Result<Vec<Quote>, StorageError>;
+}
+
+/// SQLite implementation of `QuoteRepository`.
+#[derive(Clone)]
+pub struct SqliteQuoteRepository {
+    pool: DbPool,
+}
+
+impl SqliteQuoteRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn quote_from_row(row: &Row<'_>) -> rusqlite::Result<Quote> {
+    Ok(Quote {
+        from_post_id: get_uuid(row, 0)?,
+        from_thread_id: get_uuid(row, 1)?,
+        from_board: row.get(2)?,
+        from_number: row.get(3)?,
+        to_thread_id: get_uuid(row, 4)?,
+        to_board: row.get(5)?,
+        to_number: row.get(6)?,
+    })
+}
+
+impl QuoteRepository for SqliteQuoteRepository {
+    fn insert_quotes(&self, post_id: Uuid, targets: &[QuoteTarget]) -> Result<(), StorageError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        for target in targets {
+            tx.execute(
+                r#"
+                INSERT OR IGNORE INTO post_quotes
+                    (post_id, target_board_id, target_number, target_thread_id)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+                params![
+                    post_id.to_string(),
+                    target.board_id.to_string(),
+                    target.number,
+                    target.thread_id.to_string()
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_quotes_by_thread(&self, thread_id: Uuid) -> Result<Vec<Quote>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT p.id, p.thread_id, pb.name, p.post_number,
+                   q.target_thread_id, tb.name, q.target_number
+            FROM post_quotes q
+            JOIN posts p ON p.id = q.post_id
+            JOIN boards pb ON pb.id = p.board_id
+            JOIN boards tb ON tb.id = q.target_board_id
+            WHERE p.thread_id = ?1 OR q.target_thread_id = ?1
+            ORDER BY p.post_number
+            "#,
+        )?;
+
+        let rows = stmt.query_map(params![thread_id.to_string()], quote_from_row)?;
+
+        let mut quotes = Vec::new();
+        for q in rows {
+            quotes.push(q?);
+        }
+
+        Ok(quotes)
+    }
+}
@@ -0,0 +1,163 @@
+//! Report Repository
+//!
+//! Developer Notes:
+//! - Persists visitors' reports of posts and what staff did about them.
+//! - Reports outlive the post they are about: deleting the post clears
+//!   `post_id` (`ON DELETE SET NULL`) and keeps the row, with the board
+//!   and post number, as a record of the action.
+//! - `ReportRepository` is the backend-neutral interface.
+//! - `SqliteReportRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! Grouping by post and permission checks happen in `services`.
+
+use rusqlite::{params, Row};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{Report, ReportStatus};
+use crate::columns::{format_time, get_opt_time, get_opt_uuid, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for post reports.
+pub trait ReportRepository: Send + Sync {
+    /// Record a new report.
+    fn insert_report(&self, report: &Report) -> Result<(), StorageError>;
+
+    /// Every open report, oldest first.
+    fn get_open_reports(&self) -> Result<Vec<Report>, StorageError>;
+
+    /// The open reports on one post, oldest first.
+    fn get_open_by_post(&self, post_id: Uuid) -> Result<Vec<Report>, StorageError>;
+
+    /// Close every open report on a post with `status`; returns how
+    /// many there were.
+    fn resolve_reports(
+        &self,
+        post_id: Uuid,
+        status: ReportStatus,
+        resolved_by: Uuid,
+        resolved_at: OffsetDateTime,
+    ) -> Result<u64, StorageError>;
+
+    /// The most recently handled reports, newest first.
+    fn get_resolved_reports(&self, limit: u32) -> Result<Vec<Report>, StorageError>;
+}
+
+/// SQLite implementation of `ReportRepository`.
+#[derive(Clone)]
+pub struct SqliteReportRepository {
+    pool: DbPool,
+}
+
+impl SqliteReportRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `report_from_row`, in order.
+const COLUMNS: &str = "id, post_id, board_id, post_number, category, comment, reporter_ip_hash, \
+                       created_at, status, resolved_by, resolved_at";
+
+fn report_from_row(row: &Row<'_>) -> rusqlite::Result<Report> {
+    let parse_failure = |idx: usize, what: &str, value: &str| {
+        rusqlite::Error::FromSqlConversionFailure(
+            idx,
+            rusqlite::types::Type::Text,
+            format!("unknown report {what} `{value}`").into(),
+        )
+    };
+    let category: String = row.get(4)?;
+    let status: String = row.get(8)?;
+
+    Ok(Report {
+        id: get_uuid(row, 0)?,
+        post_id: get_opt_uuid(row, 1)?,
+        board_id: get_uuid(row, 2)?,
+        post_number: row.get(3)?,
+        category: category.parse().map_err(|_| parse_failure(4, "category", &category))?,
+        comment: row.get(5)?,
+        reporter_ip_hash: row.get(6)?,
+        created_at: get_time(row, 7)?,
+        status: status.parse().map_err(|_| parse_failure(8, "status", &status))?,
+        resolved_by: get_opt_uuid(row, 9)?,
+        resolved_at: get_opt_time(row, 10)?,
+    })
+}
+
+impl ReportRepository for SqliteReportRepository {
+    fn insert_report(&self, report: &Report) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            &format!("INSERT INTO reports ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"),
+            params![
+                report.id.to_string(),
+                report.post_id.map(|id| id.to_string()),
+                report.board_id.to_string(),
+                report.post_number,
+                report.category.to_string(),
+                report.comment,
+                report.reporter_ip_hash,
+                format_time(&report.created_at),
+                report.status.to_string(),
+                report.resolved_by.map(|id| id.to_string()),
+                report.resolved_at.as_ref().map(format_time)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_open_reports(&self) -> Result<Vec<Report>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM reports WHERE status = ?1 ORDER BY created_at"
+        ))?;
+        let rows = stmt.query_map(params![ReportStatus::Open.to_string()], report_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_open_by_post(&self, post_id: Uuid) -> Result<Vec<Report>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM reports WHERE post_id = ?1 AND status = ?2 ORDER BY created_at"
+        ))?;
+        let rows = stmt.query_map(
+            params![post_id.to_string(), ReportStatus::Open.to_string()],
+            report_from_row,
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn resolve_reports(
+        &self,
+        post_id: Uuid,
+        status: ReportStatus,
+        resolved_by: Uuid,
+        resolved_at: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE reports SET status = ?2, resolved_by = ?3, resolved_at = ?4
+             WHERE post_id = ?1 AND status = ?5",
+            params![
+                post_id.to_string(),
+                status.to_string(),
+                resolved_by.to_string(),
+                format_time(&resolved_at),
+                ReportStatus::Open.to_string()
+            ],
+        )?;
+        Ok(updated as u64)
+    }
+
+    fn get_resolved_reports(&self, limit: u32) -> Result<Vec<Report>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM reports WHERE status != ?1
+             ORDER BY resolved_at DESC, created_at DESC LIMIT ?2"
+        ))?;
+        let rows = stmt.query_map(params![ReportStatus::Open.to_string(), limit], report_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
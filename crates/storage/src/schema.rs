@@ -3,15 +3,22 @@
 //! Developer Notes:
 //! - Contains ONLY DDL statements.
 //! - No logic.
+//! - Schema is expressed as ordered, numbered migrations.
+//! - Migration 1 uses `IF NOT EXISTS` so databases created before
+//!   versioning was introduced are adopted as version 1.
 //!
 //! End Notes:
-//! All schema changes must be reflected here.
+//! All schema changes must be reflected here, as a NEW migration.
 
-use rusqlite::{Connection, Result};
+use crate::migrations::{run_migrations, Migration};
+use crate::StorageError;
 
-pub fn initialize_schema(conn: &Connection) -> Result<()> {
-    conn.execute_batch(
-        r#"
+/// Every schema migration, in application order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        sql: r#"
         CREATE TABLE IF NOT EXISTS boards (
             id TEXT PRIMARY KEY,
             name TEXT NOT NULL UNIQUE,
@@ -49,7 +56,373 @@ pub fn initialize_schema(conn: &Connection) -> Result<()> {
             FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
         );
         "#,
-    )?;
+    },
+    Migration {
+        version: 2,
+        name: "per-board post numbers",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN last_post_number INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE threads ADD COLUMN op_number INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE posts ADD COLUMN board_id TEXT REFERENCES boards(id) ON DELETE CASCADE;
+        ALTER TABLE posts ADD COLUMN post_number INTEGER NOT NULL DEFAULT 0;
 
+        UPDATE posts SET board_id = (
+            SELECT board_id FROM threads WHERE threads.id = posts.thread_id
+        );
+
+        CREATE TEMP TABLE post_numbering AS
+        SELECT kind, id, board_id,
+               ROW_NUMBER() OVER (
+                   PARTITION BY board_id ORDER BY created_at, kind DESC, id
+               ) AS number
+        FROM (
+            SELECT 't' AS kind, id, board_id, created_at FROM threads
+            UNION ALL
+            SELECT 'p' AS kind, id, board_id, created_at FROM posts
+        );
+
+        UPDATE threads SET op_number = (
+            SELECT number FROM post_numbering
+            WHERE kind = 't' AND post_numbering.id = threads.id
+        );
+        UPDATE posts SET post_number = (
+            SELECT number FROM post_numbering
+            WHERE kind = 'p' AND post_numbering.id = posts.id
+        );
+        UPDATE boards SET last_post_number = COALESCE((
+            SELECT MAX(number) FROM post_numbering
+            WHERE post_numbering.board_id = boards.id
+        ), 0);
+
+        DROP TABLE post_numbering;
+
+        CREATE UNIQUE INDEX idx_threads_board_op_number ON threads(board_id, op_number);
+        CREATE UNIQUE INDEX idx_posts_board_post_number ON posts(board_id, post_number);
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "post quote graph",
+        sql: r#"
+        CREATE TABLE post_quotes (
+            post_id TEXT NOT NULL,
+            target_board_id TEXT NOT NULL,
+            target_number INTEGER NOT NULL,
+            target_thread_id TEXT NOT NULL,
+            PRIMARY KEY (post_id, target_board_id, target_number),
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE,
+            FOREIGN KEY(target_board_id) REFERENCES boards(id) ON DELETE CASCADE,
+            FOREIGN KEY(target_thread_id) REFERENCES threads(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_post_quotes_target_thread ON post_quotes(target_thread_id);
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "cached post markup",
+        sql: r#"
+        ALTER TABLE posts ADD COLUMN content_html TEXT NOT NULL DEFAULT '';
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "attachments",
+        sql: r#"
+        CREATE TABLE attachments (
+            id TEXT PRIMARY KEY,
+            thread_id TEXT NOT NULL,
+            post_id TEXT,
+            position INTEGER NOT NULL,
+            file_name TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            storage_key TEXT NOT NULL,
+            thumbnail_key TEXT NOT NULL,
+            thumbnail_width INTEGER NOT NULL,
+            thumbnail_height INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(thread_id) REFERENCES threads(id) ON DELETE CASCADE,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_attachments_thread ON attachments(thread_id);
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "attachment metadata flag",
+        sql: r#"
+        ALTER TABLE attachments ADD COLUMN metadata_stripped INTEGER NOT NULL DEFAULT 0;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "content addressed media",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN duplicate_policy TEXT NOT NULL DEFAULT 'allow';
+        ALTER TABLE attachments ADD COLUMN sha256 TEXT NOT NULL DEFAULT '';
+
+        CREATE INDEX idx_attachments_sha256 ON attachments(sha256);
+        CREATE INDEX idx_attachments_post ON attachments(post_id);
+        CREATE INDEX idx_attachments_storage_key ON attachments(storage_key);
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "image hash bans",
+        sql: r#"
+        CREATE TABLE banned_image_hashes (
+            id TEXT PRIMARY KEY,
+            phash INTEGER NOT NULL,
+            dhash INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE banned_image_matches (
+            id TEXT PRIMARY KEY,
+            ban_id TEXT NOT NULL,
+            board_id TEXT NOT NULL,
+            thread_id TEXT,
+            file_name TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            distance INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY(ban_id) REFERENCES banned_image_hashes(id) ON DELETE CASCADE,
+            FOREIGN KEY(board_id) REFERENCES boards(id) ON DELETE CASCADE,
+            FOREIGN KEY(thread_id) REFERENCES threads(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX idx_banned_image_matches_created ON banned_image_matches(created_at);
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "audio and video",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN av_max_duration INTEGER NOT NULL DEFAULT 300;
+        ALTER TABLE boards ADD COLUMN av_codecs TEXT NOT NULL
+            DEFAULT 'vp8,vp9,av1,h264,opus,vorbis,aac,mp3,flac';
+        ALTER TABLE attachments ADD COLUMN duration_ms INTEGER;
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "hashed expiring sessions",
+        sql: r#"
+        DROP TABLE sessions;
+
+        CREATE TABLE sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            last_seen_at TEXT NOT NULL,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_sessions_user ON sessions(user_id);
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "board staff",
+        sql: r#"
+        CREATE TABLE board_staff (
+            board_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (board_id, user_id),
+            FOREIGN KEY(board_id) REFERENCES boards(id) ON DELETE CASCADE,
+            FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_board_staff_user ON board_staff(user_id);
+        "#,
+    },
+    Migration {
+        version: 12,
+        name: "ip bans and appeals",
+        sql: r#"
+        CREATE TABLE bans (
+            id TEXT PRIMARY KEY,
+            ip_range TEXT NOT NULL,
+            range_start BLOB NOT NULL,
+            range_end BLOB NOT NULL,
+            board_id TEXT,
+            reason TEXT NOT NULL,
+            staff_note TEXT NOT NULL,
+            created_by TEXT,
+            created_at TEXT NOT NULL,
+            expires_at TEXT,
+            FOREIGN KEY(board_id) REFERENCES boards(id) ON DELETE CASCADE,
+            FOREIGN KEY(created_by) REFERENCES users(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX idx_bans_range ON bans(range_start, range_end);
+
+        CREATE TABLE ban_appeals (
+            id TEXT PRIMARY KEY,
+            ban_id TEXT NOT NULL UNIQUE,
+            message TEXT NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            decided_by TEXT,
+            decided_at TEXT,
+            FOREIGN KEY(ban_id) REFERENCES bans(id) ON DELETE CASCADE,
+            FOREIGN KEY(decided_by) REFERENCES users(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX idx_ban_appeals_status ON ban_appeals(status);
+        "#,
+    },
+    Migration {
+        version: 13,
+        name: "poster records",
+        sql: r#"
+        ALTER TABLE threads ADD COLUMN ip_hash TEXT;
+        ALTER TABLE threads ADD COLUMN ip_address TEXT;
+        ALTER TABLE threads ADD COLUMN user_agent_hash TEXT;
+        ALTER TABLE posts ADD COLUMN ip_hash TEXT;
+        ALTER TABLE posts ADD COLUMN ip_address TEXT;
+        ALTER TABLE posts ADD COLUMN user_agent_hash TEXT;
+
+        CREATE INDEX idx_threads_ip_hash ON threads(ip_hash);
+        CREATE INDEX idx_posts_ip_hash ON posts(ip_hash);
+        "#,
+    },
+    Migration {
+        version: 14,
+        name: "board cooldowns",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN thread_cooldown INTEGER;
+        ALTER TABLE boards ADD COLUMN reply_cooldown INTEGER;
+        ALTER TABLE boards ADD COLUMN repost_cooldown INTEGER;
+        "#,
+    },
+    Migration {
+        version: 15,
+        name: "captchas",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN captcha_mode TEXT NOT NULL DEFAULT 'off';
+
+        CREATE TABLE captchas (
+            id TEXT PRIMARY KEY,
+            answer TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_captchas_expires_at ON captchas(expires_at);
+        "#,
+    },
+    Migration {
+        version: 16,
+        name: "reports",
+        sql: r#"
+        CREATE TABLE reports (
+            id TEXT PRIMARY KEY,
+            post_id TEXT,
+            board_id TEXT NOT NULL,
+            post_number INTEGER NOT NULL,
+            category TEXT NOT NULL,
+            comment TEXT NOT NULL,
+            reporter_ip_hash TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            status TEXT NOT NULL,
+            resolved_by TEXT,
+            resolved_at TEXT,
+            FOREIGN KEY(post_id) REFERENCES posts(id) ON DELETE SET NULL,
+            FOREIGN KEY(board_id) REFERENCES boards(id) ON DELETE CASCADE,
+            FOREIGN KEY(resolved_by) REFERENCES users(id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX idx_reports_status ON reports(status, created_at);
+        CREATE INDEX idx_reports_post ON reports(post_id);
+        "#,
+    },
+    Migration {
+        version: 17,
+        name: "moderation log",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN public_mod_log INTEGER NOT NULL DEFAULT 0;
+
+        CREATE TABLE mod_actions (
+            id TEXT PRIMARY KEY,
+            actor_id TEXT,
+            actor_name TEXT NOT NULL,
+            action TEXT NOT NULL,
+            board_id TEXT,
+            target TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            before_state TEXT,
+            after_state TEXT,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_mod_actions_created_at ON mod_actions(created_at);
+        CREATE INDEX idx_mod_actions_board ON mod_actions(board_id, created_at);
+
+        CREATE TRIGGER mod_actions_no_update BEFORE UPDATE ON mod_actions
+        BEGIN
+            SELECT RAISE(ABORT, 'mod_actions is append-only');
+        END;
+
+        CREATE TRIGGER mod_actions_no_delete BEFORE DELETE ON mod_actions
+        BEGIN
+            SELECT RAISE(ABORT, 'mod_actions is append-only');
+        END;
+        "#,
+    },
+    Migration {
+        version: 18,
+        name: "thread flags",
+        sql: r#"
+        ALTER TABLE threads ADD COLUMN sticky INTEGER;
+        ALTER TABLE threads ADD COLUMN locked INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE threads ADD COLUMN cyclical INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE threads ADD COLUMN autosage INTEGER NOT NULL DEFAULT 0;
+        "#,
+    },
+    Migration {
+        version: 19,
+        name: "bump order",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN bump_limit INTEGER NOT NULL DEFAULT 300;
+
+        ALTER TABLE threads ADD COLUMN bumped_at TEXT NOT NULL DEFAULT '';
+        UPDATE threads SET bumped_at = COALESCE(
+            (SELECT MAX(created_at) FROM posts WHERE posts.thread_id = threads.id),
+            created_at
+        );
+
+        CREATE INDEX idx_threads_board_bumped_at ON threads(board_id, bumped_at);
+        "#,
+    },
+    Migration {
+        version: 20,
+        name: "thread pruning",
+        sql: r#"
+        ALTER TABLE boards ADD COLUMN max_threads INTEGER NOT NULL DEFAULT 150;
+        ALTER TABLE boards ADD COLUMN prune_mode TEXT NOT NULL DEFAULT 'archive';
+
+        ALTER TABLE threads ADD COLUMN archived_at TEXT;
+        "#,
+    },
+    Migration {
+        version: 21,
+        name: "image ban history",
+        sql: r#"
+        ALTER TABLE banned_image_hashes ADD COLUMN lifted_at TEXT;
+        "#,
+    },
+];
+/// Bring the schema up to date.
+///
+/// Kept for callers that only need a ready database (tests, tools).
+pub fn initialize_schema(conn: &rusqlite::Connection) -> Result<(), StorageError> {
+    run_migrations(conn)?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -2,70 +2,136 @@
 //!
 //! Developer Notes:
 //! - Handles session persistence.
-//! - UUID tokens.
-//!
-//! TODO:
-//! - Add expiration cleanup job.
+//! - Sessions are keyed by the hash of their token; raw tokens never
+//!   reach the database.
+//! - `SessionRepository` is the backend-neutral interface.
+//! - `SqliteSessionRepository` is the SQLite implementation.
+//! - Timestamps are RFC 3339 TEXT, so `delete_expired` compares them as
+//!   strings; that is exact to the second, which is all expiry needs.
 //!
 //! End Notes:
-//! No auth logic here.
+//! No auth logic here; services decide when a session has expired.
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, OptionalExtension};
+use time::OffsetDateTime;
 use uuid::Uuid;
 use models::Session;
 
-use crate::StorageError;
+use crate::columns::{format_time, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for login sessions.
+pub trait SessionRepository: Send + Sync {
+    /// Store a new session.
+    fn create(&self, session: &Session) -> Result<(), StorageError>;
+
+    /// Look up a session by token hash.
+    fn find(&self, token_hash: &str) -> Result<Option<Session>, StorageError>;
+
+    /// Record activity on a session; returns whether it exists.
+    fn touch(&self, token_hash: &str, last_seen_at: OffsetDateTime) -> Result<bool, StorageError>;
+
+    /// Delete a session; returns whether it existed.
+    fn delete(&self, token_hash: &str) -> Result<bool, StorageError>;
+
+    /// Delete every session of a user; returns how many there were.
+    fn delete_by_user(&self, user_id: Uuid) -> Result<u64, StorageError>;
 
-pub struct SessionRepository<'a> {
-    pub conn: &'a Connection,
+    /// Delete sessions created before `created_before` or last seen
+    /// before `seen_before`; returns how many there were.
+    fn delete_expired(
+        &self,
+        created_before: OffsetDateTime,
+        seen_before: OffsetDateTime,
+    ) -> Result<u64, StorageError>;
 }
 
-impl<'a> SessionRepository<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
-    }
+/// SQLite implementation of `SessionRepository`.
+#[derive(Clone)]
+pub struct SqliteSessionRepository {
+    pool: DbPool,
+}
 
-    pub fn create(&self, session: &Session) -> Result<(), StorageError> {
-        let created_str = session
-            .created_at
-            .format(&time::format_description::well_known::Rfc3339)
-            .unwrap();
+impl SqliteSessionRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
 
-        self.conn.execute(
-            "INSERT INTO sessions (token, user_id, created_at)
-             VALUES (?1, ?2, ?3)",
+impl SessionRepository for SqliteSessionRepository {
+    fn create(&self, session: &Session) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO sessions (token_hash, user_id, created_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?4)",
             params![
-                session.token.to_string(),
+                session.token_hash,
                 session.user_id.to_string(),
-                created_str
+                format_time(&session.created_at),
+                format_time(&session.last_seen_at)
             ],
         )?;
 
         Ok(())
     }
 
-    pub fn find(&self, token: &Uuid) -> Result<Option<Session>, StorageError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT token, user_id, created_at FROM sessions WHERE token = ?1",
+    fn find(&self, token_hash: &str) -> Result<Option<Session>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT token_hash, user_id, created_at, last_seen_at
+                 FROM sessions
+                 WHERE token_hash = ?1",
+                params![token_hash],
+                |row| {
+                    Ok(Session {
+                        token_hash: row.get(0)?,
+                        user_id: get_uuid(row, 1)?,
+                        created_at: get_time(row, 2)?,
+                        last_seen_at: get_time(row, 3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    fn touch(&self, token_hash: &str, last_seen_at: OffsetDateTime) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE sessions SET last_seen_at = ?2 WHERE token_hash = ?1",
+            params![token_hash, format_time(&last_seen_at)],
         )?;
+        Ok(updated > 0)
+    }
 
-        let mut rows = stmt.query(params![token.to_string()])?;
+    fn delete(&self, token_hash: &str) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM sessions WHERE token_hash = ?1",
+            params![token_hash],
+        )?;
+        Ok(deleted > 0)
+    }
 
-        if let Some(row) = rows.next()? {
-            let created_str: String = row.get(2)?;
-            let created_at = time::OffsetDateTime::parse(
-                &created_str,
-                &time::format_description::well_known::Rfc3339,
-            )
-            .unwrap();
-
-            Ok(Some(Session {
-                token: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-                user_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-                created_at,
-            }))
-        } else {
-            Ok(None)
-        }
+    fn delete_by_user(&self, user_id: Uuid) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM sessions WHERE user_id = ?1",
+            params![user_id.to_string()],
+        )?;
+        Ok(deleted as u64)
     }
-}
\ No newline at end of file
+
+    fn delete_expired(
+        &self,
+        created_before: OffsetDateTime,
+        seen_before: OffsetDateTime,
+    ) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM sessions WHERE created_at < ?1 OR last_seen_at < ?2",
+            params![format_time(&created_before), format_time(&seen_before)],
+        )?;
+        Ok(deleted as u64)
+    }
+}
@@ -0,0 +1,124 @@
+//! Board Staff Repository
+//!
+//! Developer Notes:
+//! - Persists per-board staff appointments (`board_staff` rows).
+//! - A user holds at most one role per board; appointing again
+//!   replaces the role and keeps the original `created_at`.
+//! - `StaffRepository` is the backend-neutral interface.
+//! - `SqliteStaffRepository` is the SQLite implementation.
+//!
+//! End Notes:
+//! What a role may do is decided by `auth::authorize`, not here.
+
+use rusqlite::{params, OptionalExtension, Row};
+use uuid::Uuid;
+
+use models::BoardStaff;
+use crate::columns::{format_time, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Persistence operations for board staff.
+pub trait StaffRepository: Send + Sync {
+    /// Appoint a user, or change their role if already appointed.
+    fn upsert(&self, staff: &BoardStaff) -> Result<(), StorageError>;
+
+    /// A user's appointment on a board.
+    fn find(&self, board_id: Uuid, user_id: Uuid) -> Result<Option<BoardStaff>, StorageError>;
+
+    /// Every appointment on a board, oldest first.
+    fn get_by_board(&self, board_id: Uuid) -> Result<Vec<BoardStaff>, StorageError>;
+
+    /// Every appointment a user holds, oldest first.
+    fn get_by_user(&self, user_id: Uuid) -> Result<Vec<BoardStaff>, StorageError>;
+
+    /// Remove an appointment; returns whether it existed.
+    fn delete(&self, board_id: Uuid, user_id: Uuid) -> Result<bool, StorageError>;
+}
+
+/// SQLite implementation of `StaffRepository`.
+#[derive(Clone)]
+pub struct SqliteStaffRepository {
+    pool: DbPool,
+}
+
+impl SqliteStaffRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+const COLUMNS: &str = "board_id, user_id, role, created_at";
+
+fn staff_from_row(row: &Row<'_>) -> rusqlite::Result<BoardStaff> {
+    let role_str: String = row.get(2)?;
+    let role = role_str.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("unknown board role `{role_str}`").into(),
+        )
+    })?;
+
+    Ok(BoardStaff {
+        board_id: get_uuid(row, 0)?,
+        user_id: get_uuid(row, 1)?,
+        role,
+        created_at: get_time(row, 3)?,
+    })
+}
+
+impl StaffRepository for SqliteStaffRepository {
+    fn upsert(&self, staff: &BoardStaff) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO board_staff (board_id, user_id, role, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (board_id, user_id) DO UPDATE SET role = excluded.role",
+            params![
+                staff.board_id.to_string(),
+                staff.user_id.to_string(),
+                staff.role.to_string(),
+                format_time(&staff.created_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn find(&self, board_id: Uuid, user_id: Uuid) -> Result<Option<BoardStaff>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM board_staff WHERE board_id = ?1 AND user_id = ?2"),
+                params![board_id.to_string(), user_id.to_string()],
+                staff_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_by_board(&self, board_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM board_staff WHERE board_id = ?1 ORDER BY created_at"
+        ))?;
+        let rows = stmt.query_map(params![board_id.to_string()], staff_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_by_user(&self, user_id: Uuid) -> Result<Vec<BoardStaff>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM board_staff WHERE user_id = ?1 ORDER BY created_at"
+        ))?;
+        let rows = stmt.query_map(params![user_id.to_string()], staff_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn delete(&self, board_id: Uuid, user_id: Uuid) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let deleted = conn.execute(
+            "DELETE FROM board_staff WHERE board_id = ?1 AND user_id = ?2",
+            params![board_id.to_string(), user_id.to_string()],
+        )?;
+        Ok(deleted > 0)
+    }
+}
@@ -1,57 +1,361 @@
-use rusqlite::{params, Connection, Result};
-use uuid::Uuid;
+//! Thread Repository
+//!
+//! Developer Notes:
+//! - Handles persistence for `Thread` data.
+//! - `ThreadRepository` is the backend-neutral interface.
+//! - `SqliteThreadRepository` is the SQLite implementation.
+//! - Inserting a thread allocates its OP number from the board counter.
+//! - A board lists its stickies first, by position, then the rest by
+//!   when they were last bumped.
+//! - Archived threads are left out of the board listing. Pruning runs in
+//!   the transaction that inserts the new thread, so concurrent inserts
+//!   cannot both see room for one more thread.
+//!
+//! End Notes:
+//! No business logic here.
+
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
 use time::OffsetDateTime;
+use uuid::Uuid;
+
+use models::{Attachment, PruneMode, PrunePolicy, Thread, ThreadFlags};
+use crate::attachment_repository::{
+    attachment_from_row, insert_in as insert_attachments_in, COLUMNS as ATTACHMENT_COLUMNS,
+};
+use crate::board_repository::next_post_number;
+use crate::columns::{format_time, get_opt_time, get_poster, get_time, get_uuid};
+use crate::{DbPool, StorageError};
+
+/// Threads pushed off a board by a new one.
+#[derive(Debug, Default)]
+pub struct PrunedThreads {
+    /// The pruned threads; archived ones carry their `archived_at`.
+    pub threads: Vec<Thread>,
+    /// Attachments of deleted threads, whose files may now be unused.
+    pub attachments: Vec<Attachment>,
+}
+
+/// Persistence operations for threads.
+pub trait ThreadRepository: Send + Sync {
+    /// Insert thread, returning its newly allocated OP number.
+    ///
+    /// `thread.op_number` is ignored.
+    fn insert_thread(&self, thread: &Thread) -> Result<i64, StorageError>;
+
+    /// Insert thread like `insert_thread` with its opening post's
+    /// `attachments`, then prune its board down to `policy.max_threads`
+    /// live threads in the same transaction.
+    ///
+    /// The least recently bumped threads go first; stickies and `thread`
+    /// itself are never pruned.
+    fn insert_thread_and_prune(
+        &self,
+        thread: &Thread,
+        attachments: &[Attachment],
+        policy: &PrunePolicy,
+    ) -> Result<(i64, PrunedThreads), StorageError>;
+
+    /// Find a thread by id.
+    fn get_thread(&self, id: Uuid) -> Result<Option<Thread>, StorageError>;
+
+    /// Find a thread by the OP number within its board.
+    fn get_thread_by_number(
+        &self,
+        board_id: Uuid,
+        op_number: i64,
+    ) -> Result<Option<Thread>, StorageError>;
+
+    /// Get the live threads of a board: stickies by position, then the
+    /// rest most recently bumped first.
+    fn get_threads_by_board(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError>;
+
+    /// Archived threads of a board, most recently archived first.
+    fn get_archived_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError>;
+
+    /// Threads opened from the address with this hash, newest first.
+    fn get_threads_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Thread>, StorageError>;
+
+    /// Threads opened on a board from the address with this hash since
+    /// `since`, newest first.
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Thread>, StorageError>;
+
+    /// Replace a thread's flags.
+    fn set_flags(&self, thread_id: Uuid, flags: &ThreadFlags) -> Result<(), StorageError>;
+
+    /// Move a thread's `bumped_at` forward to `at`; never moves it back.
+    fn bump_thread(&self, thread_id: Uuid, at: OffsetDateTime) -> Result<(), StorageError>;
 
-use models::Thread;
+    /// Forget the raw address of threads created before `created_before`,
+    /// keeping its hash; returns how many were cleared.
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError>;
+}
+
+/// SQLite implementation of `ThreadRepository`.
+#[derive(Clone)]
+pub struct SqliteThreadRepository {
+    pool: DbPool,
+}
+
+impl SqliteThreadRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Columns read by `thread_from_row`, in order.
+const COLUMNS: &str = "id, board_id, op_number, title, created_at, ip_hash, ip_address, \
+                       user_agent_hash, sticky, locked, cyclical, autosage, bumped_at, archived_at";
+
+/// Stickies by position, then the rest most recently bumped first.
+const BOARD_ORDER: &str = "sticky IS NULL, sticky, bumped_at DESC";
+
+fn thread_from_row(row: &Row<'_>) -> rusqlite::Result<Thread> {
+    Ok(Thread {
+        id: get_uuid(row, 0)?,
+        board_id: get_uuid(row, 1)?,
+        op_number: row.get(2)?,
+        title: row.get(3)?,
+        created_at: get_time(row, 4)?,
+        poster: get_poster(row, 5)?,
+        flags: ThreadFlags {
+            sticky: row.get(8)?,
+            locked: row.get(9)?,
+            cyclical: row.get(10)?,
+            autosage: row.get(11)?,
+        },
+        bumped_at: get_time(row, 12)?,
+        archived_at: get_opt_time(row, 13)?,
+    })
+}
+
+/// Insert thread inside the caller's transaction, returning its OP number.
+fn insert_in(conn: &Connection, thread: &Thread) -> Result<i64, StorageError> {
+    let op_number = next_post_number(conn, thread.board_id)?;
 
-/// Insert thread.
-pub fn insert_thread(conn: &Connection, thread: &Thread) -> Result<()> {
     conn.execute(
-        r#"
-        INSERT INTO threads (id, board_id, title, created_at)
-        VALUES (?1, ?2, ?3, ?4)
-        "#,
+        &format!(
+            "INSERT INTO threads ({COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)"
+        ),
         params![
             thread.id.to_string(),
             thread.board_id.to_string(),
+            op_number,
             thread.title,
-            thread.created_at.to_string()
+            format_time(&thread.created_at),
+            thread.poster.as_ref().map(|p| &p.ip_hash),
+            thread.poster.as_ref().and_then(|p| p.ip).map(|ip| ip.to_string()),
+            thread.poster.as_ref().and_then(|p| p.user_agent_hash.as_ref()),
+            thread.flags.sticky,
+            thread.flags.locked,
+            thread.flags.cyclical,
+            thread.flags.autosage,
+            format_time(&thread.bumped_at),
+            thread.archived_at.as_ref().map(format_time)
         ],
     )?;
-    Ok(())
+
+    Ok(op_number)
 }
 
-/// Get threads by board.
-pub fn get_threads_by_board(
+/// Prune the board of `new` down to `policy.max_threads` live threads
+/// inside the caller's transaction.
+fn prune_in(
     conn: &Connection,
-    board_id: Uuid,
-) -> Result<Vec<Thread>> {
-    let mut stmt = conn.prepare(
-        r#"
-        SELECT id, board_id, title, created_at
-        FROM threads
-        WHERE board_id = ?1
-        ORDER BY created_at DESC
-        "#,
-    )?;
+    new: &Thread,
+    policy: &PrunePolicy,
+) -> Result<PrunedThreads, StorageError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM threads
+         WHERE board_id = ?1 AND archived_at IS NULL
+         ORDER BY {BOARD_ORDER}
+         LIMIT -1 OFFSET ?2"
+    ))?;
+    let overflow = stmt
+        .query_map(params![new.board_id.to_string(), policy.max_threads], thread_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pruned = PrunedThreads::default();
+    for mut thread in overflow {
+        if thread.flags.sticky.is_some() || thread.id == new.id {
+            continue;
+        }
+
+        match policy.mode {
+            PruneMode::Archive => {
+                conn.execute(
+                    "UPDATE threads SET archived_at = ?2 WHERE id = ?1",
+                    params![thread.id.to_string(), format_time(&new.created_at)],
+                )?;
+                thread.archived_at = Some(new.created_at);
+            }
+            PruneMode::Delete => {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {ATTACHMENT_COLUMNS} FROM attachments a WHERE a.thread_id = ?1"
+                ))?;
+                for a in stmt.query_map(params![thread.id.to_string()], attachment_from_row)? {
+                    pruned.attachments.push(a?);
+                }
+                // Posts, attachments and quotes go with it (ON DELETE CASCADE).
+                conn.execute("DELETE FROM threads WHERE id = ?1", params![thread.id.to_string()])?;
+            }
+        }
+        pruned.threads.push(thread);
+    }
 
-    let rows = stmt.query_map(params![board_id.to_string()], |row| {
-        Ok(Thread {
-            id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-            board_id: Uuid::parse_str(&row.get::<_, String>(1)?).unwrap(),
-            title: row.get(2)?,
-            created_at: OffsetDateTime::parse(
-                &row.get::<_, String>(3)?,
-                &time::format_description::well_known::Rfc3339,
+    Ok(pruned)
+}
+
+impl ThreadRepository for SqliteThreadRepository {
+    fn insert_thread(&self, thread: &Thread) -> Result<i64, StorageError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let op_number = insert_in(&tx, thread)?;
+
+        tx.commit()?;
+        Ok(op_number)
+    }
+
+    fn insert_thread_and_prune(
+        &self,
+        thread: &Thread,
+        attachments: &[Attachment],
+        policy: &PrunePolicy,
+    ) -> Result<(i64, PrunedThreads), StorageError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let op_number = insert_in(&tx, thread)?;
+        insert_attachments_in(&tx, attachments)?;
+        let pruned = prune_in(&tx, thread, policy)?;
+
+        tx.commit()?;
+        Ok((op_number, pruned))
+    }
+
+    fn get_thread(&self, id: Uuid) -> Result<Option<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM threads WHERE id = ?1"),
+                params![id.to_string()],
+                thread_from_row,
+            )
+            .optional()?)
+    }
+
+    fn get_thread_by_number(
+        &self,
+        board_id: Uuid,
+        op_number: i64,
+    ) -> Result<Option<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM threads WHERE board_id = ?1 AND op_number = ?2"),
+                params![board_id.to_string(), op_number],
+                thread_from_row,
             )
-            .unwrap(),
-        })
-    })?;
+            .optional()?)
+    }
+
+    fn get_threads_by_board(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM threads
+             WHERE board_id = ?1 AND archived_at IS NULL
+             ORDER BY {BOARD_ORDER}"
+        ))?;
+
+        let rows = stmt.query_map(params![board_id.to_string()], thread_from_row)?;
 
-    let mut threads = Vec::new();
-    for t in rows {
-        threads.push(t?);
+        let mut threads = Vec::new();
+        for t in rows {
+            threads.push(t?);
+        }
+
+        Ok(threads)
+    }
+
+    fn get_archived_threads(&self, board_id: Uuid) -> Result<Vec<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM threads
+             WHERE board_id = ?1 AND archived_at IS NOT NULL
+             ORDER BY archived_at DESC, bumped_at DESC"
+        ))?;
+
+        let rows = stmt.query_map(params![board_id.to_string()], thread_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_threads_by_ip_hash(&self, ip_hash: &str) -> Result<Vec<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM threads WHERE ip_hash = ?1 ORDER BY created_at DESC"
+        ))?;
+
+        let rows = stmt.query_map(params![ip_hash], thread_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn get_recent_by_ip_hash(
+        &self,
+        board_id: Uuid,
+        ip_hash: &str,
+        since: OffsetDateTime,
+    ) -> Result<Vec<Thread>, StorageError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM threads
+             WHERE board_id = ?1 AND ip_hash = ?2 AND created_at >= ?3
+             ORDER BY created_at DESC"
+        ))?;
+
+        let rows = stmt.query_map(
+            params![board_id.to_string(), ip_hash, format_time(&since)],
+            thread_from_row,
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn set_flags(&self, thread_id: Uuid, flags: &ThreadFlags) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE threads SET sticky = ?2, locked = ?3, cyclical = ?4, autosage = ?5 WHERE id = ?1",
+            params![
+                thread_id.to_string(),
+                flags.sticky,
+                flags.locked,
+                flags.cyclical,
+                flags.autosage
+            ],
+        )?;
+        Ok(())
     }
 
-    Ok(threads)
-}
\ No newline at end of file
+    fn bump_thread(&self, thread_id: Uuid, at: OffsetDateTime) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE threads SET bumped_at = ?2 WHERE id = ?1 AND bumped_at < ?2",
+            params![thread_id.to_string(), format_time(&at)],
+        )?;
+        Ok(())
+    }
+
+    fn clear_ip_addresses(&self, created_before: OffsetDateTime) -> Result<u64, StorageError> {
+        let conn = self.pool.get()?;
+        let cleared = conn.execute(
+            "UPDATE threads SET ip_address = NULL
+             WHERE ip_address IS NOT NULL AND created_at < ?1",
+            params![format_time(&created_before)],
+        )?;
+        Ok(cleared as u64)
+    }
+}
@@ -2,31 +2,72 @@
 //!
 //! Developer Notes:
 //! - Handles persistence for Users.
+//! - `UserRepository` is the backend-neutral interface.
+//! - `SqliteUserRepository` is the SQLite implementation.
 //! - No business logic allowed.
 //!
 //! TODO:
 //! - Add pagination for admin listing.
 //!
 //! End Notes:
-//! Pure SQLite repository.
+//! Pure persistence.
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, OptionalExtension, Row};
 use uuid::Uuid;
 
-use models::User;
-use crate::StorageError;
+use models::{Role, User};
+use crate::columns::get_uuid;
+use crate::{DbPool, StorageError};
 
-pub struct UserRepository<'a> {
-    pub conn: &'a Connection,
+/// Persistence operations for users.
+pub trait UserRepository: Send + Sync {
+    /// Insert a new user.
+    fn create(&self, user: &User) -> Result<(), StorageError>;
+
+    /// Find a user by unique username.
+    fn find_by_username(&self, username: &str) -> Result<Option<User>, StorageError>;
+
+    /// Find a user by id.
+    fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StorageError>;
+
+    /// Change a user's role; returns whether the user exists.
+    fn set_role(&self, id: Uuid, role: &Role) -> Result<bool, StorageError>;
+}
+
+/// SQLite implementation of `UserRepository`.
+#[derive(Clone)]
+pub struct SqliteUserRepository {
+    pool: DbPool,
 }
 
-impl<'a> UserRepository<'a> {
-    pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+impl SqliteUserRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
     }
+}
 
-    pub fn create(&self, user: &User) -> Result<(), StorageError> {
-        self.conn.execute(
+fn user_from_row(row: &Row<'_>) -> rusqlite::Result<User> {
+    let role_str: String = row.get(3)?;
+    let role = role_str.parse().map_err(|_| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("unknown role `{role_str}`").into(),
+        )
+    })?;
+
+    Ok(User {
+        id: get_uuid(row, 0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        role,
+    })
+}
+
+impl UserRepository for SqliteUserRepository {
+    fn create(&self, user: &User) -> Result<(), StorageError> {
+        let conn = self.pool.get()?;
+        conn.execute(
             "INSERT INTO users (id, username, password_hash, role)
              VALUES (?1, ?2, ?3, ?4)",
             params![
@@ -39,30 +80,38 @@ impl<'a> UserRepository<'a> {
         Ok(())
     }
 
-    pub fn find_by_username(
-        &self,
-        username: &str,
-    ) -> Result<Option<User>, StorageError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, username, password_hash, role
-             FROM users
-             WHERE username = ?1",
-        )?;
-
-        let mut rows = stmt.query(params![username])?;
+    fn find_by_username(&self, username: &str) -> Result<Option<User>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT id, username, password_hash, role
+                 FROM users
+                 WHERE username = ?1",
+                params![username],
+                user_from_row,
+            )
+            .optional()?)
+    }
 
-        if let Some(row) = rows.next()? {
-            let role_str: String = row.get(3)?;
-            let role = role_str.parse().unwrap();
+    fn find_by_id(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT id, username, password_hash, role
+                 FROM users
+                 WHERE id = ?1",
+                params![id.to_string()],
+                user_from_row,
+            )
+            .optional()?)
+    }
 
-            Ok(Some(User {
-                id: Uuid::parse_str(&row.get::<_, String>(0)?).unwrap(),
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-                role,
-            }))
-        } else {
-            Ok(None)
-        }
+    fn set_role(&self, id: Uuid, role: &Role) -> Result<bool, StorageError> {
+        let conn = self.pool.get()?;
+        let updated = conn.execute(
+            "UPDATE users SET role = ?2 WHERE id = ?1",
+            params![id.to_string(), role.to_string()],
+        )?;
+        Ok(updated > 0)
     }
-}
\ No newline at end of file
+}